@@ -0,0 +1,451 @@
+use regex::Regex;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use crate::html::strip_markup;
+use crate::kind::{true_false_choices, truth_value, QuestionKind};
+use crate::similarity::normalize;
+use crate::{Bank, OutputError, Question};
+
+// Anki flashcards. `to_cloze` turns every answered question into a note of Anki's Cloze type, the correct answer text
+// hidden as `{{c1::...}}`, which drills definitions better than picking from choices. A stem with a gap (`____`,
+// `...`) gets the answer in the gap and a stem already naming the answer gets it hidden in place; other stems are
+// followed by the answer. Multiple-select answers share one deletion, the steps of an ordering question and the
+// pairs of a matching question get one deletion each. The file is Anki's tab-separated import format, its header
+// lines selecting the note type, the columns and the tags column, so File > Import needs no settings.
+//
+// The other way round, `read_deck` reads the notes of a deck exported from Anki, either as a deck package (an archive
+// holding the collection as an SQLite database) or as notes in plain text, and `note_question` makes a question of a
+// note whose front has the stem followed by lettered choices and whose back names the correct ones, by letter or by
+// text. Packages of recent Anki versions compress the collection in a format only Anki reads, they have to be
+// exported with "Support older Anki versions".
+
+const HEADER: &str = "#separator:tab\n#html:true\n#notetype:Cloze\n#columns:Text\tBack Extra\tTags\n#tags column:3\n";
+
+lazy_static! {
+    /// Gap left for the answer in a fill-in-the-blank stem
+    static ref GAP: Regex = Regex::new(r"_{3,}|\.{3,}|…").unwrap();
+    static ref CLOZE: Regex = Regex::new(r"\{\{c\d+::").unwrap();
+    static ref LINE_BREAK: Regex = Regex::new(r"(?i)<br\s*/?>|</?(div|p|li)\b[^>]*>").unwrap();
+    static ref MEDIA: Regex = Regex::new(r"\[sound:[^\]]*\]").unwrap();
+    static ref CHOICE: Regex = Regex::new(r"^\(?([A-Ha-h])[.):]\s+(.+)$").unwrap();
+    static ref ANSWER_LABEL: Regex =
+        Regex::new(r"(?i)^\s*(correct\s+)?(answers?|antworten?|réponses?)\s*[:\-]?\s*").unwrap();
+    /// Correct letters at the start of the back: `B`, `A, C`, `B and D`, `BD.`
+    static ref LETTERS: Regex = Regex::new(r"^([A-H](?:\s*(?:[,;&/]|and)?\s*[A-H])*)\s*(?:[.):]|$)").unwrap();
+}
+
+/// Collections in a deck package, newest first
+const COLLECTIONS: &[&str] = &["collection.anki21", "collection.anki2"];
+const COMPRESSED_COLLECTION: &str = "collection.anki21b";
+
+/// Text of a field: HTML-escaped, with line breaks and tabs that would end the field or the note replaced.
+fn field(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\t', " ")
+        .replace('\n', "<br>")
+}
+
+/// Cloze deletion of `text`; `::` and `}}` inside it would end the deletion early.
+fn cloze(number: usize, text: &str) -> String {
+    format!("{{{{c{}::{}}}}}", number, field(text).replace("::", ": :").replace("}}", "} }"))
+}
+
+fn choice_text<'a>(question: &'a Question, letter: &'a str) -> &'a str {
+    question.choices.get(letter).map_or(letter, String::as_str)
+}
+
+/// The stem with the answer hidden in its gap or where it already appears, else followed by it.
+fn blank_stem(stem: &str, answer: &str) -> String {
+    if let Some(gap) = GAP.find(stem) {
+        return format!("{}{}{}", field(&stem[..gap.start()]), cloze(1, answer), field(&stem[gap.end()..]));
+    }
+    let lower = stem.to_lowercase();
+    // Only where lowercasing kept the byte offsets, so they can be used on the stem itself
+    if answer.chars().count() >= 3 && lower.len() == stem.len() {
+        if let Some(start) = lower.find(&answer.to_lowercase()) {
+            let end = start + answer.len();
+            if stem.is_char_boundary(start) && stem.is_char_boundary(end) {
+                return format!("{}{}{}", field(&stem[..start]), cloze(1, &stem[start..end]), field(&stem[end..]));
+            }
+        }
+    }
+    format!("{}<br><br>{}", field(stem), cloze(1, answer))
+}
+
+/// Text field of the note, or none when the question has no answer to hide.
+fn cloze_text(question: &Question) -> Option<String> {
+    let mut stem = question.text.clone();
+    for statement in &question.statements {
+        let _ = write!(stem, "\n{}. {}", statement.label, statement.text);
+    }
+    match question.kind {
+        QuestionKind::TrueFalse => {
+            let truth = if question.truth? { "True" } else { "False" };
+            Some(format!("{}<br><br>{}", field(&stem), cloze(1, truth)))
+        }
+        QuestionKind::Ordering if !question.order.is_empty() => {
+            let steps: Vec<String> = question
+                .order
+                .iter()
+                .enumerate()
+                .map(|(i, letter)| format!("{}. {}", i + 1, cloze(i + 1, choice_text(question, letter))))
+                .collect();
+            Some(format!("{}<br><br>{}", field(&stem), steps.join("<br>")))
+        }
+        QuestionKind::Matching if !question.pairs.is_empty() => {
+            let pairs: Vec<String> = question
+                .pairs
+                .iter()
+                .enumerate()
+                .map(|(i, pair)| {
+                    let premise = question.premises.iter().find(|premise| premise.label == pair.premise);
+                    let premise = premise.map_or(pair.premise.as_str(), |premise| premise.text.as_str());
+                    format!("{} → {}", field(premise), cloze(i + 1, choice_text(question, &pair.choice)))
+                })
+                .collect();
+            Some(format!("{}<br><br>{}", field(&stem), pairs.join("<br>")))
+        }
+        QuestionKind::Ordering | QuestionKind::Matching => None,
+        QuestionKind::MultipleChoice | QuestionKind::MultipleSelect => {
+            let answers: Vec<&str> = question.answers.iter().map(|letter| choice_text(question, letter)).collect();
+            match answers.as_slice() {
+                [] => None,
+                [answer] => Some(blank_stem(&stem, answer)),
+                answers => Some(format!("{}<br><br>{}", field(&stem), cloze(1, &answers.join("\n")))),
+            }
+        }
+    }
+}
+
+/// Anki tag: no spaces, which separate tags.
+fn tag(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+pub(crate) fn to_cloze(bank: &Bank) -> String {
+    let mut out = String::from(HEADER);
+    for question in &bank.questions {
+        let Some(text) = cloze_text(question) else {
+            continue;
+        };
+        let mut extra = format!("Question {}", question.number);
+        if let Some(page) = question.page {
+            let _ = write!(extra, ", page {}", page);
+        }
+        let mut tags = vec![tag(&serde_json::to_string(&question.kind).unwrap_or_default().replace('"', ""))];
+        tags.extend(bank.meta.exam_code.as_deref().map(tag));
+        tags.extend(question.id.as_deref().map(|id| format!("id::{}", id)));
+        let _ = writeln!(out, "{}\t{}\t{}", text, field(&extra), tags.join(" "));
+    }
+    out
+}
+
+/// A note of an imported deck, its fields in plain text in the order of its note type
+pub(crate) struct Note {
+    pub(crate) fields: Vec<String>,
+}
+
+/// Text of a field with the markup dropped, one line per line break or block, without blank lines.
+fn plain_text(field: &str, html: bool) -> String {
+    let field = MEDIA.replace_all(field, "");
+    let text = if html {
+        strip_markup(&LINE_BREAK.replace_all(&field, "\n")).into_owned()
+    } else {
+        field.into_owned()
+    };
+    text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+/// Notes of an Anki export: a deck package (`.apkg`, `.colpkg`) or notes in plain text.
+pub(crate) fn read_deck(path: &Path) -> Result<Vec<Note>, Box<dyn Error>> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    match extension.to_lowercase().as_str() {
+        "apkg" | "colpkg" => read_package(path),
+        _ => read_text(path),
+    }
+}
+
+fn read_package(path: &Path) -> Result<Vec<Note>, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let compressed = archive.index_for_name(COMPRESSED_COLLECTION).is_some();
+    let collection = COLLECTIONS.iter().copied().find(|name| archive.index_for_name(name).is_some());
+    let collection = match collection {
+        // Packages of recent Anki versions hold a placeholder `collection.anki2` next to the compressed collection
+        Some(name) if !compressed || name == COLLECTIONS[0] => name,
+        _ if compressed => {
+            let message = format!(
+                "{} was exported in the format of recent Anki versions, export it again with \"Support older Anki \
+                 versions\" checked or as notes in plain text",
+                path.display()
+            );
+            return Err(OutputError { message }.into());
+        }
+        _ => {
+            let message = format!("{} is not an Anki deck package", path.display());
+            return Err(OutputError { message }.into());
+        }
+    };
+
+    // SQLite opens files only, so the collection is copied out of the archive first
+    let database = std::env::temp_dir().join(format!("s4wm-anki-{}.sqlite", std::process::id()));
+    io::copy(&mut archive.by_name(collection)?, &mut File::create(&database)?)?;
+    let notes = read_collection(&database);
+    let _ = fs::remove_file(&database);
+    notes
+}
+
+fn read_collection(path: &Path) -> Result<Vec<Note>, Box<dyn Error>> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut statement = connection.prepare("SELECT flds FROM notes ORDER BY id")?;
+    let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+    let mut notes = Vec::new();
+    for fields in rows {
+        notes.push(Note {
+            fields: fields?.split('\x1f').map(|field| plain_text(field, true)).collect(),
+        });
+    }
+    Ok(notes)
+}
+
+/// Notes in plain text, with the header lines Anki writes (`#separator:tab`, `#html:true`, `#tags column:3`, ...).
+fn read_text(path: &Path) -> Result<Vec<Note>, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let mut separator = b'\t';
+    // Exports without a header kept the markup
+    let mut html = true;
+    // Columns that are not fields of the note
+    let mut other_columns = Vec::new();
+    for line in text.lines().take_while(|line| line.starts_with('#')) {
+        let Some((key, value)) = line[1..].split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "separator" => {
+                separator = match value.to_lowercase().as_str() {
+                    "tab" => b'\t',
+                    "comma" => b',',
+                    "semicolon" => b';',
+                    "space" => b' ',
+                    "pipe" => b'|',
+                    "colon" => b':',
+                    _ => value.bytes().next().unwrap_or(b'\t'),
+                }
+            }
+            "html" => html = value == "true",
+            "tags column" | "guid column" | "notetype column" | "deck column" => {
+                other_columns.extend(value.parse::<usize>().ok())
+            }
+            _ => {}
+        }
+    }
+
+    let body: Vec<&str> = text.lines().skip_while(|line| line.starts_with('#')).collect();
+    let body = body.join("\n");
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(separator)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(body.as_bytes());
+    let mut notes = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        // Columns are numbered from 1 in the header
+        let fields = (1..).zip(record.iter()).filter(|(column, _)| !other_columns.contains(column));
+        notes.push(Note {
+            fields: fields.map(|(_, value)| plain_text(value, html)).collect(),
+        });
+    }
+    Ok(notes)
+}
+
+/// The stem and the choices of a front: the lines before choice A, and the choices lettered in order, a line that
+/// does not start the next choice continuing the previous one.
+fn split_choices(front: &str) -> (String, HashMap<String, String>) {
+    let mut stem = Vec::new();
+    let mut choices: Vec<(String, String)> = Vec::new();
+    for line in front.lines() {
+        let next = char::from(b'A' + choices.len() as u8).to_string();
+        match CHOICE.captures(line) {
+            Some(caps) if caps[1].eq_ignore_ascii_case(&next) => choices.push((next, caps[2].to_string())),
+            _ => match choices.last_mut() {
+                Some((_, text)) => {
+                    text.push(' ');
+                    text.push_str(line);
+                }
+                None => stem.push(line),
+            },
+        }
+    }
+    (stem.join(" "), choices.into_iter().collect())
+}
+
+/// Correct letters named at the start of the back, else the letters of the choices whose text the back repeats.
+pub(crate) fn answer_letters(back: &str, choices: &HashMap<String, String>) -> Vec<String> {
+    let first = ANSWER_LABEL.replace(back.lines().next().unwrap_or_default(), "");
+    if let Some(caps) = LETTERS.captures(&first) {
+        let mut letters: Vec<String> = Vec::new();
+        for letter in caps[1].chars().filter(char::is_ascii_uppercase).map(String::from) {
+            if choices.contains_key(&letter) && !letters.contains(&letter) {
+                letters.push(letter);
+            }
+        }
+        if !letters.is_empty() {
+            return letters;
+        }
+    }
+    let back = format!(" {} ", normalize(back));
+    let mut letters: Vec<String> = choices
+        .iter()
+        .filter(|(_, text)| !normalize(text).is_empty() && back.contains(&format!(" {} ", normalize(text))))
+        .map(|(letter, _)| letter.clone())
+        .collect();
+    letters.sort();
+    letters
+}
+
+/// The question a note asks, or why it is not one. The front holds the stem and the lettered choices, the back the
+/// answer; a front without choices is a true/false question when the back is true or false. A question whose answer
+/// is not recognized is kept without one, for validation to report.
+pub(crate) fn note_question(note: &Note) -> Result<Question, &'static str> {
+    let front = note.fields.first().map_or("", String::as_str);
+    let back = note.fields.get(1).map_or("", String::as_str);
+    if CLOZE.is_match(front) {
+        return Err("cloze deletions");
+    }
+    let (text, choices) = split_choices(front);
+    if text.is_empty() {
+        return Err("empty front");
+    }
+    let mut question = Question {
+        text,
+        choices,
+        ..Question::default()
+    };
+    if question.choices.is_empty() {
+        let truth = ANSWER_LABEL.replace(back.lines().next().unwrap_or_default(), "");
+        let Some(truth) = truth_value(&truth) else {
+            return Err("no lettered choices");
+        };
+        true_false_choices(&mut question, truth);
+    } else {
+        question.answers = answer_letters(back, &question.choices);
+    }
+    Ok(question)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+
+    const DECK: &str = "#separator:tab\n#html:true\n#tags column:3\n\
+        Which transaction shows the warehouse monitor?<br>A. /SCWM/MON<br>B. /SCWM/PRDI\tAnswer: A\tewm\n\
+        Which objects can a wave hold?<div>a) Warehouse requests</div><div>b) Warehouse tasks</div>\
+        <div>c) Warehouse request items</div>\tWarehouse requests<br>Warehouse request items\tewm\n\
+        Putaway strategies are set per storage type.\tTrue\tewm\n\
+        {{c1::Waves}} group warehouse request items.\t\tewm\n";
+
+    fn questions(notes: &[Note]) -> Vec<Result<Question, &'static str>> {
+        notes.iter().map(note_question).collect()
+    }
+
+    fn choices(choices: &[(&str, &str)]) -> HashMap<String, String> {
+        choices.iter().map(|(letter, text)| (letter.to_string(), text.to_string())).collect()
+    }
+
+    #[test]
+    fn text_decks() {
+        let path = std::env::temp_dir().join(format!("s4wm-anki-deck-{}.txt", std::process::id()));
+        fs::write(&path, DECK).unwrap();
+        let notes = read_deck(&path);
+        let _ = fs::remove_file(&path);
+        let notes = notes.unwrap();
+        assert_eq!(notes.len(), 4);
+        assert_eq!(notes[0].fields.len(), 2);
+
+        let questions = questions(&notes);
+        let monitor = questions[0].as_ref().unwrap();
+        assert_eq!(monitor.text, "Which transaction shows the warehouse monitor?");
+        assert_eq!(monitor.choices, choices(&[("A", "/SCWM/MON"), ("B", "/SCWM/PRDI")]));
+        assert_eq!(monitor.answers, ["A"]);
+
+        let wave = questions[1].as_ref().unwrap();
+        assert_eq!(wave.text, "Which objects can a wave hold?");
+        let expected = [("A", "Warehouse requests"), ("B", "Warehouse tasks"), ("C", "Warehouse request items")];
+        assert_eq!(wave.choices, choices(&expected));
+        assert_eq!(wave.answers, ["A", "C"]);
+
+        let putaway = questions[2].as_ref().unwrap();
+        assert_eq!(putaway.text, "Putaway strategies are set per storage type.");
+        assert_eq!(putaway.choices, choices(&[("A", "True"), ("B", "False")]));
+        assert_eq!(putaway.answers, ["A"]);
+
+        assert_eq!(questions[3].as_ref().err(), Some(&"cloze deletions"));
+    }
+
+    #[test]
+    fn notes_that_are_not_questions() {
+        let note = |front: &str, back: &str| Note {
+            fields: vec![front.to_string(), back.to_string()],
+        };
+        assert_eq!(note_question(&note("", "A")).unwrap_err(), "empty front");
+        assert_eq!(note_question(&note("What is a wave?", "A group of requests")).unwrap_err(), "no lettered choices");
+        assert_eq!(note_question(&Note { fields: Vec::new() }).unwrap_err(), "empty front");
+        // An answer naming no choice keeps the question, for validation to report
+        let unanswered = note_question(&note("Pick one\nA. Bin\nB. Lane", "Answer: D")).unwrap();
+        assert!(unanswered.answers.is_empty());
+    }
+
+    #[test]
+    fn packages() {
+        let dir = std::env::temp_dir().join(format!("s4wm-anki-packages-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let database = dir.join("collection.anki2");
+        let connection = Connection::open(&database).unwrap();
+        connection.execute_batch("CREATE TABLE notes (id INTEGER PRIMARY KEY, flds TEXT NOT NULL)").unwrap();
+        let notes = [(2, "Which bin type fits pallets?<br>A. P1<br>B. B1\x1fA"), (1, "Waves are released.\x1fFalse")];
+        for (id, fields) in notes {
+            connection.execute("INSERT INTO notes (id, flds) VALUES (?1, ?2)", (id, fields)).unwrap();
+        }
+        drop(connection);
+
+        let package = dir.join("deck.apkg");
+        let mut writer = zip::ZipWriter::new(File::create(&package).unwrap());
+        writer.start_file("collection.anki2", zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(&fs::read(&database).unwrap()).unwrap();
+        writer.finish().unwrap();
+        let empty = dir.join("empty.apkg");
+        let mut writer = zip::ZipWriter::new(File::create(&empty).unwrap());
+        writer.start_file("media", zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(b"{}").unwrap();
+        writer.finish().unwrap();
+        let broken = dir.join("broken.apkg");
+        fs::write(&broken, DECK).unwrap();
+
+        let deck = read_deck(&package);
+        let empty = read_deck(&empty);
+        let broken = read_deck(&broken);
+        let _ = fs::remove_dir_all(&dir);
+
+        let questions = questions(&deck.unwrap());
+        let waves = questions[0].as_ref().unwrap();
+        assert_eq!(waves.text, "Waves are released.");
+        assert_eq!(waves.answers, ["B"]);
+        let bins = questions[1].as_ref().unwrap();
+        assert_eq!(bins.text, "Which bin type fits pallets?");
+        assert_eq!(bins.choices, choices(&[("A", "P1"), ("B", "B1")]));
+        assert_eq!(bins.answers, ["A"]);
+
+        assert!(empty.err().unwrap().to_string().ends_with("is not an Anki deck package"));
+        assert!(broken.is_err());
+    }
+}
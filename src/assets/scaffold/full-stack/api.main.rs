@@ -0,0 +1,41 @@
+// API of the quiz, generated by `S4WM-extract scaffold --full-stack`. It serves the bank extracted by S4WM-extract
+// read-only with the routes of `S4WM-extract serve`, so the frontend can use either. The bank is passed through as
+// JSON: its schema is the TypeScript declarations in `src/types/question.ts`, generated from S4WM-extract's own types.
+//
+// BANK names the bank file (default `../src/data/questions.json`), PORT the port (default 3000).
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn questions_of(bank: &Value) -> &[Value] {
+    bank["questions"].as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+async fn bank(State(bank): State<Arc<Value>>) -> Json<Value> {
+    Json(bank.as_ref().clone())
+}
+
+/// Every question, as `serve` lists them with `?all=true`
+async fn questions(State(bank): State<Arc<Value>>) -> Json<Value> {
+    let questions = questions_of(&bank);
+    Json(json!({ "review": false, "total": questions.len(), "questions": questions }))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::var("BANK").unwrap_or_else(|_| "../src/data/questions.json".to_string());
+    let port: u16 = std::env::var("PORT").ok().and_then(|port| port.parse().ok()).unwrap_or(3000);
+    let bank: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+
+    let app = Router::new()
+        .route("/api/bank", get(bank))
+        .route("/api/questions", get(questions))
+        .with_state(Arc::new(bank));
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+    println!("Serving {} on port {}", path, port);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
@@ -0,0 +1,30 @@
+import type { Bank, Listing, Question } from "../types/question";
+
+// Loading of the bank from the API generated by `S4WM-extract scaffold --full-stack` (or `S4WM-extract serve`),
+// reached through the /api proxy of the Vite dev server. Shared by the quiz pages of every template.
+
+export interface LoadedBank {
+  bank: Bank;
+  /** Questions in use: retired and superseded ones are left out */
+  questions: Question[];
+}
+
+async function get<T>(path: string): Promise<T> {
+  const response = await fetch(path);
+  if (!response.ok) {
+    throw new Error(`${path}: ${response.status} ${response.statusText}`);
+  }
+  return (await response.json()) as T;
+}
+
+export async function loadQuestions(): Promise<LoadedBank> {
+  const [bank, listing] = await Promise.all([get<Bank>("/api/bank"), get<Listing>("/api/questions?all=true")]);
+  const questions = listing.questions.filter(
+    q => q.lifecycle !== "retired" && !(typeof q.lifecycle === "object" && q.lifecycle.superseded_by),
+  );
+  return { bank, questions };
+}
+
+export function sameSet(a: string[], b: string[]): boolean {
+  return a.length === b.length && [...a].sort().join() === [...b].sort().join();
+}
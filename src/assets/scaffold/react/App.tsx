@@ -0,0 +1,5 @@
+import QuizPage from "./pages/QuizPage";
+
+export default function App() {
+  return <QuizPage />;
+}
@@ -0,0 +1,95 @@
+import { useState } from "react";
+import { useQuestions } from "../hooks/useQuestions";
+import { sameSet } from "../lib/questions";
+import type { Question } from "../types/question";
+
+function QuestionCard({ question, onAnswered }: { question: Question; onAnswered: (correct: boolean) => void }) {
+  const answers = question.answers ?? [];
+  const multiple = answers.length > 1;
+  const [selected, setSelected] = useState<string[]>([]);
+  const [checked, setChecked] = useState(false);
+  const letters = Object.keys(question.choices).sort();
+
+  const toggle = (letter: string) => {
+    if (checked) return;
+    setSelected(current =>
+      multiple
+        ? current.includes(letter)
+          ? current.filter(l => l !== letter)
+          : [...current, letter]
+        : [letter],
+    );
+  };
+
+  const check = () => {
+    setChecked(true);
+    onAnswered(sameSet(selected, answers));
+  };
+
+  return (
+    <section>
+      <h2>Question {question.number}</h2>
+      <p style={{ whiteSpace: "pre-wrap" }}>{question.text}</p>
+      {letters.map(letter => {
+        const correct = checked && answers.includes(letter);
+        const wrong = checked && selected.includes(letter) && !answers.includes(letter);
+        return (
+          <label key={letter} style={{ display: "block", color: correct ? "green" : wrong ? "crimson" : undefined }}>
+            <input
+              type={multiple ? "checkbox" : "radio"}
+              checked={selected.includes(letter)}
+              onChange={() => toggle(letter)}
+            />{" "}
+            {letter}. {question.choices[letter]}
+          </label>
+        );
+      })}
+      {answers.length > 0 ? (
+        <button onClick={check} disabled={checked || selected.length === 0}>
+          Check
+        </button>
+      ) : (
+        <p>No answer in the bank for this question.</p>
+      )}
+    </section>
+  );
+}
+
+export default function QuizPage() {
+  const { bank, questions, loading, error } = useQuestions();
+  const [position, setPosition] = useState(0);
+  const [results, setResults] = useState<Record<string, boolean>>({});
+
+  if (loading) return <p>Loading questions…</p>;
+  if (error || !bank) return <p>Could not load the questions: {error}</p>;
+  if (questions.length === 0) return <p>The bank has no questions.</p>;
+
+  const question = questions[position];
+  const answered = Object.values(results);
+  const correct = answered.filter(Boolean).length;
+
+  return (
+    <main style={{ maxWidth: "48rem", margin: "0 auto", padding: "1rem" }}>
+      <h1>{bank.meta.title ?? bank.meta.exam_code ?? "Practice quiz"}</h1>
+      <p>
+        {position + 1} / {questions.length} · {correct} of {answered.length} correct
+      </p>
+      <QuestionCard
+        key={question.number}
+        question={question}
+        onAnswered={ok => setResults(current => ({ ...current, [question.number]: ok }))}
+      />
+      <nav style={{ display: "flex", justifyContent: "space-between", marginTop: "1rem" }}>
+        <button onClick={() => setPosition(p => Math.max(0, p - 1))} disabled={position === 0}>
+          Previous
+        </button>
+        <button
+          onClick={() => setPosition(p => Math.min(questions.length - 1, p + 1))}
+          disabled={position === questions.length - 1}
+        >
+          Next
+        </button>
+      </nav>
+    </main>
+  );
+}
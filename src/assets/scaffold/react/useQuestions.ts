@@ -0,0 +1,23 @@
+import { useEffect, useState } from "react";
+import { loadQuestions } from "../lib/questions";
+import type { Bank, Question } from "../types/question";
+
+export interface QuestionsState {
+  bank: Bank | null;
+  /** Questions in use: retired and superseded ones are left out */
+  questions: Question[];
+  loading: boolean;
+  error: string | null;
+}
+
+export function useQuestions(): QuestionsState {
+  const [state, setState] = useState<QuestionsState>({ bank: null, questions: [], loading: true, error: null });
+
+  useEffect(() => {
+    loadQuestions()
+      .then(({ bank, questions }) => setState({ bank, questions, loading: false, error: null }))
+      .catch((error: unknown) => setState({ bank: null, questions: [], loading: false, error: String(error) }));
+  }, []);
+
+  return state;
+}
@@ -0,0 +1,18 @@
+import path from "node:path";
+import react from "@vitejs/plugin-react";
+import { defineConfig } from "vite";
+
+export default defineConfig({
+  plugins: [react()],
+  resolve: {
+    alias: {
+      "@": path.resolve(__dirname, "./src"),
+    },
+  },
+  server: {
+    // The API of `scaffold --full-stack`: `cargo run` in api/ locally, the api service under docker compose
+    proxy: {
+      "/api": process.env.API_URL ?? "http://localhost:3000",
+    },
+  },
+});
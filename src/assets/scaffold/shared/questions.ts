@@ -0,0 +1,27 @@
+import type { Bank, Question } from "../types/question";
+
+// Loading of the bank copied by `S4WM-extract scaffold --with-questions`, shared by the quiz pages of every template.
+// The bank is loaded lazily so that it stays out of the main bundle.
+const banks = import.meta.glob<Bank>("../data/questions.json", { import: "default" });
+
+export interface LoadedBank {
+  bank: Bank;
+  /** Questions in use: retired and superseded ones are left out */
+  questions: Question[];
+}
+
+export async function loadQuestions(): Promise<LoadedBank> {
+  const load = banks["../data/questions.json"];
+  if (!load) {
+    throw new Error("src/data/questions.json is missing");
+  }
+  const bank = await load();
+  const questions = bank.questions.filter(
+    q => q.lifecycle !== "retired" && !(typeof q.lifecycle === "object" && q.lifecycle.superseded_by),
+  );
+  return { bank, questions };
+}
+
+export function sameSet(a: string[], b: string[]): boolean {
+  return a.length === b.length && [...a].sort().join() === [...b].sort().join();
+}
@@ -0,0 +1,8 @@
+/** @type {import("tailwindcss").Config} */
+export default {
+  content: ["./index.html", "./src/**/*.{ts,tsx,vue,svelte}"],
+  theme: {
+    extend: {},
+  },
+  plugins: [],
+};
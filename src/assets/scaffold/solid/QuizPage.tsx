@@ -0,0 +1,95 @@
+import { createResource, createSignal, For, Match, Show, Switch } from "solid-js";
+import { loadQuestions, sameSet } from "../lib/questions";
+
+export default function QuizPage() {
+  const [loaded] = createResource(loadQuestions);
+  const [position, setPosition] = createSignal(0);
+  const [results, setResults] = createSignal<Record<string, boolean>>({});
+  const [selected, setSelected] = createSignal<string[]>([]);
+  const [checked, setChecked] = createSignal(false);
+
+  const questions = () => loaded()?.questions ?? [];
+  const question = () => questions()[position()];
+  const answers = () => question()?.answers ?? [];
+  const multiple = () => answers().length > 1;
+  const letters = () => (question() ? Object.keys(question().choices).sort() : []);
+  const answered = () => Object.values(results());
+  const correct = () => answered().filter(Boolean).length;
+
+  const go = (to: number) => {
+    setPosition(to);
+    setSelected([]);
+    setChecked(false);
+  };
+
+  const toggle = (letter: string) => {
+    if (checked()) return;
+    setSelected(current =>
+      !multiple() ? [letter] : current.includes(letter) ? current.filter(l => l !== letter) : [...current, letter],
+    );
+  };
+
+  const check = () => {
+    setChecked(true);
+    setResults(current => ({ ...current, [question().number]: sameSet(selected(), answers()) }));
+  };
+
+  const color = (letter: string) => {
+    if (!checked()) return undefined;
+    if (answers().includes(letter)) return "green";
+    return selected().includes(letter) ? "crimson" : undefined;
+  };
+
+  return (
+    <Switch>
+      <Match when={loaded.loading}>
+        <p>Loading questions…</p>
+      </Match>
+      <Match when={loaded.error}>
+        <p>Could not load the questions: {String(loaded.error)}</p>
+      </Match>
+      <Match when={questions().length === 0}>
+        <p>The bank has no questions.</p>
+      </Match>
+      <Match when={loaded()}>
+        {data => (
+          <main style={{ "max-width": "48rem", margin: "0 auto", padding: "1rem" }}>
+            <h1>{data().bank.meta.title ?? data().bank.meta.exam_code ?? "Practice quiz"}</h1>
+            <p>
+              {position() + 1} / {questions().length} · {correct()} of {answered().length} correct
+            </p>
+            <section>
+              <h2>Question {question().number}</h2>
+              <p style={{ "white-space": "pre-wrap" }}>{question().text}</p>
+              <For each={letters()}>
+                {letter => (
+                  <label style={{ display: "block", color: color(letter) }}>
+                    <input
+                      type={multiple() ? "checkbox" : "radio"}
+                      checked={selected().includes(letter)}
+                      onChange={() => toggle(letter)}
+                    />{" "}
+                    {letter}. {question().choices[letter]}
+                  </label>
+                )}
+              </For>
+              <Show when={answers().length > 0} fallback={<p>No answer in the bank for this question.</p>}>
+                <button onClick={check} disabled={checked() || selected().length === 0}>
+                  Check
+                </button>
+              </Show>
+            </section>
+            <nav style={{ display: "flex", "justify-content": "space-between", "margin-top": "1rem" }}>
+              <button onClick={() => go(position() - 1)} disabled={position() === 0}>
+                Previous
+              </button>
+              <button onClick={() => go(position() + 1)} disabled={position() === questions().length - 1}>
+                Next
+              </button>
+            </nav>
+          </main>
+        )}
+      </Match>
+    </Switch>
+  );
+}
@@ -0,0 +1,12 @@
+import solid from "vite-plugin-solid";
+import { defineConfig } from "vite";
+
+export default defineConfig({
+  plugins: [solid()],
+  server: {
+    // The API of `scaffold --full-stack`: `cargo run` in api/ locally, the api service under docker compose
+    proxy: {
+      "/api": process.env.API_URL ?? "http://localhost:3000",
+    },
+  },
+});
@@ -0,0 +1,12 @@
+import { svelte } from "@sveltejs/vite-plugin-svelte";
+import { defineConfig } from "vite";
+
+export default defineConfig({
+  plugins: [svelte()],
+  server: {
+    // The API of `scaffold --full-stack`: `cargo run` in api/ locally, the api service under docker compose
+    proxy: {
+      "/api": process.env.API_URL ?? "http://localhost:3000",
+    },
+  },
+});
@@ -0,0 +1,32 @@
+import { useState } from "react";
+import { extractPdf } from "./lib/questions";
+import QuizPage from "./pages/QuizPage";
+
+export default function App() {
+  // Remounts the quiz page, reloading the questions, after an extraction
+  const [generation, setGeneration] = useState(0);
+  const [status, setStatus] = useState<string | null>(null);
+
+  async function extract() {
+    setStatus("Extracting…");
+    try {
+      const bank = await extractPdf();
+      setStatus(bank ? `Extracted ${bank.questions.length} questions` : null);
+      if (bank) setGeneration(generation => generation + 1);
+    } catch (error) {
+      setStatus(`Extraction failed: ${String(error)}`);
+    }
+  }
+
+  return (
+    <>
+      <header style={{ display: "flex", gap: "1rem", alignItems: "center", padding: "1rem" }}>
+        <button type="button" onClick={extract}>
+          Extract a PDF
+        </button>
+        {status && <span>{status}</span>}
+      </header>
+      <QuizPage key={generation} />
+    </>
+  );
+}
@@ -0,0 +1,62 @@
+// Commands of the quiz desktop app, generated by `S4WM-extract scaffold --template tauri`. Extraction is done by the
+// S4WM-extract binary bundled as a resource of the app (`binaries/`), copied there by the scaffold, so the app works
+// offline and needs nothing installed. The last extracted bank is kept in the app data directory.
+
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Manager};
+
+fn extractor(app: &AppHandle) -> Result<PathBuf, String> {
+    let name = if cfg!(windows) { "binaries/S4WM-extract.exe" } else { "binaries/S4WM-extract" };
+    app.path().resolve(name, BaseDirectory::Resource).map_err(|e| e.to_string())
+}
+
+fn bank_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join("bank"))
+}
+
+fn read_bank(path: &PathBuf) -> Result<Value, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Extracts the questions of a PDF, replacing the last extracted bank.
+#[tauri::command]
+async fn extract_pdf(app: AppHandle, path: String) -> Result<Value, String> {
+    let output_dir = bank_dir(&app)?;
+    fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
+    let output = Command::new(extractor(&app)?)
+        .arg("extract")
+        .arg("--input")
+        .arg(&path)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .output()
+        .map_err(|e| format!("Cannot run S4WM-extract: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    read_bank(&output_dir.join("questions.json"))
+}
+
+/// The last extracted bank, if any
+#[tauri::command]
+fn load_bank(app: AppHandle) -> Result<Option<Value>, String> {
+    let path = bank_dir(&app)?.join("questions.json");
+    if !path.exists() {
+        return Ok(None);
+    }
+    read_bank(&path).map(Some)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .invoke_handler(tauri::generate_handler![extract_pdf, load_bank])
+        .run(tauri::generate_context!())
+        .expect("error while running the quiz app");
+}
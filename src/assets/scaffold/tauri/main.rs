@@ -0,0 +1,6 @@
+// Prevents an additional console window on Windows in release
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+fn main() {
+    quiz_desktop_lib::run()
+}
@@ -0,0 +1,46 @@
+import { invoke } from "@tauri-apps/api/core";
+import { open } from "@tauri-apps/plugin-dialog";
+import type { Bank, Question } from "../types/question";
+
+// Loading of the questions in the desktop app generated by `S4WM-extract scaffold --template tauri`: the bank last
+// extracted in the app, else the one copied by `--with-questions`. Extraction runs in the Rust commands of
+// `src-tauri`, without any server.
+const banks = import.meta.glob<Bank>("../data/questions.json", { import: "default" });
+
+export interface LoadedBank {
+  bank: Bank;
+  /** Questions in use: retired and superseded ones are left out */
+  questions: Question[];
+}
+
+function loaded(bank: Bank): LoadedBank {
+  const questions = bank.questions.filter(
+    q => q.lifecycle !== "retired" && !(typeof q.lifecycle === "object" && q.lifecycle.superseded_by),
+  );
+  return { bank, questions };
+}
+
+export async function loadQuestions(): Promise<LoadedBank> {
+  const extracted = await invoke<Bank | null>("load_bank");
+  if (extracted) {
+    return loaded(extracted);
+  }
+  const load = banks["../data/questions.json"];
+  if (!load) {
+    throw new Error("No bank yet: extract a PDF");
+  }
+  return loaded(await load());
+}
+
+/** Asks for a PDF and extracts it; null when the dialog is cancelled */
+export async function extractPdf(): Promise<Bank | null> {
+  const path = await open({ multiple: false, directory: false, filters: [{ name: "PDF", extensions: ["pdf"] }] });
+  if (!path) {
+    return null;
+  }
+  return invoke<Bank>("extract_pdf", { path });
+}
+
+export function sameSet(a: string[], b: string[]): boolean {
+  return a.length === b.length && [...a].sort().join() === [...b].sort().join();
+}
@@ -0,0 +1,27 @@
+import { onMounted, ref } from "vue";
+import { loadQuestions } from "../lib/questions";
+import type { Bank, Question } from "../types/question";
+
+export function useQuestions() {
+  const bank = ref<Bank | null>(null);
+  /** Questions in use: retired and superseded ones are left out */
+  const questions = ref<Question[]>([]);
+  const loading = ref(true);
+  const error = ref<string | null>(null);
+
+  onMounted(() => {
+    loadQuestions()
+      .then(loaded => {
+        bank.value = loaded.bank;
+        questions.value = loaded.questions;
+      })
+      .catch((e: unknown) => {
+        error.value = String(e);
+      })
+      .finally(() => {
+        loading.value = false;
+      });
+  });
+
+  return { bank, questions, loading, error };
+}
@@ -0,0 +1,12 @@
+import vue from "@vitejs/plugin-vue";
+import { defineConfig } from "vite";
+
+export default defineConfig({
+  plugins: [vue()],
+  server: {
+    // The API of `scaffold --full-stack`: `cargo run` in api/ locally, the api service under docker compose
+    proxy: {
+      "/api": process.env.API_URL ?? "http://localhost:3000",
+    },
+  },
+});
@@ -0,0 +1,29 @@
+import type { Bank, Question } from "../types/question";
+
+// Loading of the bank copied by `S4WM-extract scaffold --workspace --with-questions` into the `data/` directory at
+// the root of the workspace, shared by the quiz pages of every template. The bank is loaded lazily so that it stays
+// out of the main bundle; Vite serves it as the workspace root is allowed by default.
+const BANK = "../../../../data/questions.json";
+const banks = import.meta.glob<Bank>("../../../../data/questions.json", { import: "default" });
+
+export interface LoadedBank {
+  bank: Bank;
+  /** Questions in use: retired and superseded ones are left out */
+  questions: Question[];
+}
+
+export async function loadQuestions(): Promise<LoadedBank> {
+  const load = banks[BANK];
+  if (!load) {
+    throw new Error("data/questions.json is missing at the root of the workspace");
+  }
+  const bank = await load();
+  const questions = bank.questions.filter(
+    q => q.lifecycle !== "retired" && !(typeof q.lifecycle === "object" && q.lifecycle.superseded_by),
+  );
+  return { bank, questions };
+}
+
+export function sameSet(a: string[], b: string[]): boolean {
+  return a.length === b.length && [...a].sort().join() === [...b].sort().join();
+}
@@ -0,0 +1,312 @@
+"use strict";
+// Quiz page of a site generated by `S4WM-extract site`. The questions come from questions.js (window.BANK); results
+// are kept in the browser's local storage so a study session survives a reload. Bookmarks and notes are kept there
+// too, by stable question id rather than number so they survive the bank being extracted again, and "Export notes"
+// downloads them for `S4WM-extract notes export`. Questions answered wrong stay in the drill pool, also by id and
+// across sessions, until answered right `bank.streak` times in a row; "Missed only" shows just the pool.
+//
+// With profiles (window.PROFILES from profiles.js, edited by `S4WM-extract profile`) all of that is kept per profile,
+// under keys ending in "@<profile>", and the profile is picked in the header or with `?profile=` in the address.
+// Whatever was kept for a profile no longer listed is removed.
+
+const bank = window.BANK;
+const profiles = window.PROFILES || [];
+const lastProfileKey = "s4wm-profile:" + bank.title;
+const profile = [new URLSearchParams(location.search).get("profile"), localStorage.getItem(lastProfileKey),
+  bank.profile, profiles[0]].find(name => profiles.includes(name)) || null;
+const suffix = profile ? "@" + profile : "";
+const storageKey = "s4wm-quiz:" + bank.title + suffix;
+const notesKey = "s4wm-notes:" + bank.title + suffix;
+const results = JSON.parse(localStorage.getItem(storageKey) || "{}");
+/** Question id to { bookmarked, note } */
+const notes = JSON.parse(localStorage.getItem(notesKey) || "{}");
+const drillKey = "s4wm-drill:" + bank.title + suffix;
+/** Id of every missed question still in the drill to the number of right answers in a row since */
+const drill = JSON.parse(localStorage.getItem(drillKey) || "{}");
+const $ = id => document.getElementById(id);
+let visible = [];
+let position = 0;
+let checkAnswer = null;
+
+function escapeHtml(text) {
+  return text.replace(/[&<>"']/g, c => ({ "&": "&amp;", "<": "&lt;", ">": "&gt;", '"': "&quot;", "'": "&#39;" })[c]);
+}
+
+/** Stem text with the keywords the extractor marked as **bold** */
+function formatted(text) {
+  return escapeHtml(text).replace(/\*\*(.+?)\*\*/g, "<strong>$1</strong>");
+}
+
+function sameSet(a, b) {
+  return a.length === b.length && [...a].sort().join() === [...b].sort().join();
+}
+
+function save() {
+  localStorage.setItem(storageKey, JSON.stringify(results));
+}
+
+/** Takes a checked answer into the drill pool: a wrong one (re)enters it, enough right ones in a row leave it */
+function drillResult(question, correct) {
+  if (!correct) drill[question.id] = 0;
+  else if (question.id in drill && ++drill[question.id] >= bank.streak) delete drill[question.id];
+  localStorage.setItem(drillKey, JSON.stringify(drill));
+}
+
+function annotation(question) {
+  return notes[question.id] || { bookmarked: false, note: "" };
+}
+
+function annotate(question, change) {
+  const updated = Object.assign(annotation(question), change);
+  if (updated.bookmarked || updated.note) notes[question.id] = updated;
+  else delete notes[question.id];
+  localStorage.setItem(notesKey, JSON.stringify(notes));
+}
+
+/** Downloads the bookmarks and notes with the number and stem of their question, for `notes export` */
+function exportNotes() {
+  const byId = new Map(bank.questions.map(question => [question.id, question]));
+  const entries = Object.entries(notes).map(([id, { bookmarked, note }]) => {
+    const question = byId.get(id);
+    return { id, number: question ? question.number : null, text: question ? question.text : null, bookmarked, note };
+  });
+  const file = { title: bank.title, exported: new Date().toISOString(), notes: entries };
+  const link = document.createElement("a");
+  link.href = URL.createObjectURL(new Blob([JSON.stringify(file, null, 2)], { type: "application/json" }));
+  link.download = "notes.json";
+  link.click();
+  URL.revokeObjectURL(link.href);
+}
+
+function updateScore() {
+  const answered = visible.filter(i => bank.questions[i].number in results);
+  const correct = answered.filter(i => results[bank.questions[i].number]).length;
+  $("score").textContent = visible.length + " question(s), " + answered.length + " answered, " + correct + " correct"
+    + (answered.length ? " (" + Math.round((100 * correct) / answered.length) + "%)" : "");
+}
+
+function renderNavigation() {
+  const buttons = visible.map((index, i) => {
+    const number = bank.questions[index].number;
+    const button = document.createElement("button");
+    button.type = "button";
+    button.textContent = number;
+    if (i === position) button.classList.add("current");
+    if (annotation(bank.questions[index]).bookmarked) button.classList.add("bookmarked");
+    if (number in results) button.classList.add(results[number] ? "correct" : "wrong");
+    button.addEventListener("click", () => show(i));
+    return button;
+  });
+  $("navigation").replaceChildren(...buttons);
+}
+
+function choiceInput(type, name, value, label) {
+  const row = document.createElement("label");
+  row.className = "choice";
+  row.innerHTML = '<input type="' + type + '" name="' + name + '" value="' + escapeHtml(value) + '"> <span>'
+    + label + "</span>";
+  return row;
+}
+
+function selected(container) {
+  return [...container.querySelectorAll("input:checked")].map(input => input.value);
+}
+
+/** Builds the answer controls of a question and returns the function scoring them, or null when the bank has no answer */
+function renderAnswer(question, container) {
+  const name = "q" + question.number;
+  switch (question.kind) {
+    case "true_false": {
+      for (const [value, label] of [["true", "True"], ["false", "False"]]) {
+        container.append(choiceInput("radio", name, value, label));
+      }
+      if (question.truth === undefined) return null;
+      return () => selected(container)[0] === String(question.truth);
+    }
+    case "ordering": {
+      const list = document.createElement("div");
+      for (const [letter, text] of question.choices) {
+        const row = document.createElement("label");
+        row.className = "choice";
+        const options = question.choices.map((_, i) => "<option>" + (i + 1) + "</option>").join("");
+        row.innerHTML = '<select data-letter="' + escapeHtml(letter) + '"><option></option>' + options + "</select> "
+          + escapeHtml(letter) + ". " + escapeHtml(text);
+        list.append(row);
+      }
+      container.append(list);
+      if (!question.order || !question.order.length) return null;
+      return () => {
+        const placed = [...list.querySelectorAll("select")].map(s => [Number(s.value), s.dataset.letter]);
+        return placed.sort((a, b) => a[0] - b[0]).map(p => p[1]).join() === question.order.join();
+      };
+    }
+    case "matching": {
+      const letters = question.choices.map(([letter]) => letter);
+      for (const premise of question.premises || []) {
+        const row = document.createElement("label");
+        row.className = "choice";
+        const options = letters.map(l => "<option>" + escapeHtml(l) + "</option>").join("");
+        row.innerHTML = escapeHtml(premise.label) + ") " + escapeHtml(premise.text) + ' <select data-premise="'
+          + escapeHtml(premise.label) + '"><option></option>' + options + "</select>";
+        container.append(row);
+      }
+      const legend = document.createElement("div");
+      legend.className = "meta";
+      legend.innerHTML = question.choices.map(([l, t]) => escapeHtml(l) + ". " + escapeHtml(t)).join("<br>");
+      container.append(legend);
+      if (!question.pairs || !question.pairs.length) return null;
+      return () => question.pairs.every(pair =>
+        container.querySelector('select[data-premise="' + CSS.escape(pair.premise) + '"]')?.value === pair.choice);
+    }
+    default: {
+      const type = question.answers.length > 1 ? "checkbox" : "radio";
+      for (const [letter, text] of question.choices) {
+        container.append(choiceInput(type, name, letter, escapeHtml(letter) + ". " + escapeHtml(text)));
+      }
+      if (!question.answers.length) return null;
+      return () => {
+        for (const row of container.querySelectorAll(".choice")) {
+          const input = row.querySelector("input");
+          const right = question.answers.includes(input.value);
+          if (right || input.checked) row.classList.add(right ? "correct" : "wrong");
+        }
+        return sameSet(selected(container), question.answers);
+      };
+    }
+  }
+}
+
+function show(i) {
+  position = Math.max(0, Math.min(i, visible.length - 1));
+  const main = $("question");
+  main.replaceChildren();
+  checkAnswer = null;
+  if (!visible.length) {
+    main.textContent = "No question matches the filters.";
+    renderNavigation();
+    return;
+  }
+  const question = bank.questions[visible[position]];
+  const meta = document.createElement("p");
+  meta.className = "meta";
+  meta.textContent = "Question " + question.number + " · section " + bank.sections[question.section] + " · "
+    + question.level + (question.id in drill ? " · missed, " + drill[question.id] + "/" + bank.streak + " right since"
+    : "");
+  const bookmark = document.createElement("button");
+  bookmark.type = "button";
+  bookmark.className = "bookmark";
+  const showBookmark = () => {
+    bookmark.textContent = annotation(question).bookmarked ? "★ Bookmarked" : "☆ Bookmark";
+  };
+  showBookmark();
+  bookmark.addEventListener("click", () => {
+    annotate(question, { bookmarked: !annotation(question).bookmarked });
+    showBookmark();
+    renderNavigation();
+  });
+  meta.append(bookmark);
+  const stem = document.createElement("p");
+  stem.className = "stem";
+  stem.innerHTML = formatted(question.text);
+  main.append(meta, stem);
+  if (question.statements && question.statements.length) {
+    const statements = document.createElement("ol");
+    statements.className = "statements";
+    statements.type = "I";
+    for (const statement of question.statements) {
+      const item = document.createElement("li");
+      item.textContent = statement.text;
+      statements.append(item);
+    }
+    main.append(statements);
+  }
+  const answer = document.createElement("div");
+  main.append(answer);
+  const feedback = document.createElement("p");
+  feedback.className = "feedback";
+  main.append(feedback);
+  const note = document.createElement("textarea");
+  note.className = "note";
+  note.placeholder = "Your notes on this question";
+  note.value = annotation(question).note;
+  note.addEventListener("input", () => annotate(question, { note: note.value }));
+  main.append(note);
+
+  const score = renderAnswer(question, answer);
+  checkAnswer = () => {
+    if (!score) {
+      feedback.textContent = "The bank has no answer for this question.";
+      return;
+    }
+    const correct = score();
+    results[question.number] = correct;
+    save();
+    drillResult(question, correct);
+    feedback.className = "feedback " + (correct ? "correct" : "wrong");
+    feedback.textContent = correct ? "Correct" : "Wrong" + (question.answers.length ? ", the answer is "
+      + question.answers.join(", ") : question.order && question.order.length ? ", the order is "
+      + question.order.join(" → ") : "");
+    updateScore();
+    renderNavigation();
+  };
+  renderNavigation();
+}
+
+function applyFilters() {
+  const section = $("section").value;
+  const level = $("level").value;
+  const kind = $("kind").value;
+  const bookmarked = $("bookmarked").checked;
+  const missed = $("missed").checked;
+  visible = bank.questions
+    .map((question, index) => [question, index])
+    .filter(([q]) => (section === "" || String(q.section) === section) && (!level || q.level === level)
+      && (!kind || q.kind === kind) && (!bookmarked || annotation(q).bookmarked) && (!missed || q.id in drill))
+    .map(([, index]) => index);
+  updateScore();
+  show(0);
+}
+
+document.title = bank.title;
+$("title").textContent = bank.title;
+for (const [index, label] of bank.sections.entries()) {
+  const option = document.createElement("option");
+  option.value = String(index);
+  option.textContent = label;
+  $("section").append(option);
+}
+/** Offers the profiles in the header and drops the state of deleted ones */
+function setUpProfiles() {
+  for (const key of Object.keys(localStorage)) {
+    const owner = ["s4wm-quiz:", "s4wm-notes:", "s4wm-drill:"]
+      .map(prefix => prefix + bank.title + "@").find(prefix => key.startsWith(prefix));
+    if (owner && !profiles.includes(key.slice(owner.length))) localStorage.removeItem(key);
+  }
+  if (!profile) return;
+  localStorage.setItem(lastProfileKey, profile);
+  for (const name of profiles) {
+    const option = document.createElement("option");
+    option.textContent = name;
+    option.selected = name === profile;
+    $("profile").append(option);
+  }
+  $("profile").addEventListener("change", () => {
+    location.search = "?profile=" + encodeURIComponent($("profile").value);
+  });
+  $("profile-picker").hidden = false;
+}
+
+setUpProfiles();
+$("missed").checked = bank.missed;
+for (const id of ["section", "level", "kind", "bookmarked", "missed"]) $(id).addEventListener("change", applyFilters);
+$("export-notes").addEventListener("click", exportNotes);
+$("previous").addEventListener("click", () => show(position - 1));
+$("next").addEventListener("click", () => show(position + 1));
+$("check").addEventListener("click", () => checkAnswer && checkAnswer());
+$("reset").addEventListener("click", () => {
+  for (const number of Object.keys(results)) delete results[number];
+  save();
+  updateScore();
+  renderNavigation();
+});
+applyFilters();
@@ -0,0 +1,433 @@
+use clap::Args;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::crypt::Crypto;
+use crate::dashboard;
+use crate::meta;
+use crate::pages;
+use crate::progress::{Channel, Event};
+use crate::queue::Queue;
+use crate::stream;
+use crate::timings::Timings;
+use crate::{bank_path, extract_file, load_from_json, ExtractOptions, OutputError, DEFAULT_OUTPUT_DIR};
+
+// Batch extraction: `--input <dir>` extracts every PDF of the directory, each into `<output dir>/<file stem>/` with
+// its own manifest. A pool of `--jobs` workers takes the files in order. Files are isolated from each other: one that
+// fails, panics or runs longer than `--file-timeout` is reported and the batch continues. An in-process extraction
+// cannot be stopped, so a timed-out one is abandoned and keeps running in the background; with `--isolate` every
+// file runs in a child process of this binary instead, which is killed on timeout or when its resident memory
+// exceeds `--max-memory`. Progress goes to the terminal line by line, or to the dashboard with `--dashboard`.
+//
+// With `--by-exam` a mixed folder of dumps sorts itself: the exam code is read from the title and first pages of each
+// file before it is extracted, its outputs go to `<output dir>/<exam code>/<file stem>/`, named after the code
+// (`--exam-code`), and the code is the one in the metadata of the bank. Files without a code stay directly in the
+// output directory, under the usual names.
+//
+// With `--queue` the state of every file is kept in a job queue on disk (see `queue`), so a batch interrupted or
+// failing in places is run again for the files left rather than from the start.
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Options that only concern the batch or are set per file, not forwarded to child processes
+const BATCH_OPTIONS: &[&str] = &[
+    "input",
+    "output_dir",
+    "dashboard",
+    "jobs",
+    "file_timeout",
+    "by_exam",
+    "exam_code",
+    "queue",
+    "isolate",
+    "max_memory",
+    "record",
+    "replay",
+];
+
+/// PDF files directly inside `dir`, sorted by name
+fn pdf_files(dir: &Path) -> Result<Vec<PathBuf>, OutputError> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// The options of the extraction of a file and its output directory, routed by exam code with `--by-exam`.
+fn file_options(options: &ExtractOptions, pdf: &Path, channel: &Channel) -> (ExtractOptions, PathBuf) {
+    let mut options = options.clone();
+    let root = Path::new(options.output_dir.as_deref().unwrap_or(DEFAULT_OUTPUT_DIR));
+    let stem = pdf.file_stem().unwrap_or(pdf.as_os_str());
+    if !options.by_exam {
+        let output = root.join(stem);
+        return (options, output);
+    }
+    let output = match meta::detect_exam_code(&pdf.to_string_lossy()) {
+        Some(code) => {
+            channel.send(Event::Info(format!("exam {}, saved under {}", code, root.join(&code).display())));
+            let output = root.join(&code).join(stem);
+            options.exam_code = Some(code);
+            output
+        }
+        None => {
+            channel.send(Event::Info("no exam code found, saved directly under the output directory".to_string()));
+            root.join(stem)
+        }
+    };
+    (options, output)
+}
+
+fn file_timeout(options: &ExtractOptions) -> Option<Duration> {
+    options.file_timeout.map(Duration::from_secs)
+}
+
+/// Extracts the file on a thread of its own, so that a panic only fails this file.
+fn run_in_process(options: &ExtractOptions, pdf: &Path, channel: &Channel) -> Result<usize, String> {
+    let (result_sender, result_receiver) = mpsc::channel();
+    // the files are the unit of parallelism, their pages are extracted one after the other
+    let pdf = pdf.to_path_buf();
+    let mut progress = Channel {
+        file: channel.file,
+        sender: channel.sender.clone(),
+    };
+    let options_of_batch = options.clone();
+    thread::spawn(move || {
+        let (mut thread_options, output) = file_options(&options_of_batch, &pdf, &progress);
+        thread_options.jobs = Some(1);
+        let pdf_path = pdf.to_string_lossy();
+        let result = extract_file(&thread_options, &pdf_path, &output, None, None, Timings::default(), &mut progress);
+        let _ = result_sender.send(result.map_err(|error| error.to_string()));
+    });
+
+    let received = match file_timeout(options) {
+        Some(timeout) => result_receiver.recv_timeout(timeout),
+        None => result_receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+    match received {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(format!(
+            "timed out after {}s, the extraction was abandoned",
+            options.file_timeout.unwrap_or_default()
+        )),
+        // the thread ended without a result
+        Err(RecvTimeoutError::Disconnected) => Err("extraction panicked".to_string()),
+    }
+}
+
+/// Command-line flags reproducing the extraction options in a child process.
+fn child_args(options: &ExtractOptions) -> Result<Vec<String>, String> {
+    let values = toml::Table::try_from(options).map_err(|e| e.to_string())?;
+    let command = ExtractOptions::augment_args(clap::Command::new(env!("CARGO_PKG_NAME")));
+    let mut args = Vec::new();
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if BATCH_OPTIONS.contains(&id) {
+            continue;
+        }
+        let (Some(long), Some(value)) = (arg.get_long(), values.get(id)) else {
+            continue;
+        };
+        let value = match value {
+            toml::Value::Boolean(true) => None,
+            toml::Value::Boolean(false) => continue,
+            toml::Value::String(value) => Some(value.clone()),
+            toml::Value::Array(values) if values.is_empty() => continue,
+            toml::Value::Array(values) => {
+                let values: Vec<String> =
+                    values.iter().map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string)).collect();
+                Some(values.join(","))
+            }
+            other => Some(other.to_string()),
+        };
+        args.push(format!("--{}", long));
+        args.extend(value);
+    }
+    Ok(args)
+}
+
+/// Resident memory of a process in MiB, where the platform exposes it (Linux)
+fn resident_mib(pid: u32) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib / 1024)
+}
+
+/// Forwards the output lines of a child process, turning `severity[rule] message` lines into diagnostics.
+fn forward_output(output: impl Read, channel: &Channel) {
+    for line in BufReader::new(output).lines().map_while(Result::ok) {
+        let diagnostic = line.split_once('[').and_then(|(severity, rest)| {
+            let severity = ["warning", "error"].into_iter().find(|&s| s == severity)?;
+            let (rule, message) = rest.split_once("] ")?;
+            Some(Event::Diagnostic(severity, rule.to_string(), message.to_string()))
+        });
+        channel.send(diagnostic.unwrap_or(Event::Info(line)));
+    }
+}
+
+/// Extracts the file in a child process of this binary, killed on timeout or when it exceeds the memory limit.
+fn run_isolated(options: &ExtractOptions, pdf: &Path, channel: &Channel) -> Result<usize, String> {
+    let (options, output) = file_options(options, pdf, channel);
+    let options = &options;
+    let exam_code = options.exam_code.iter().flat_map(|code| ["--exam-code", code.as_str()]);
+    let mut child = Command::new(env::current_exe().map_err(|e| e.to_string())?)
+        .args(child_args(options)?)
+        .args(exam_code)
+        .arg("--input")
+        .arg(pdf)
+        .arg("--output-dir")
+        .arg(&output)
+        .args(["--jobs", "1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot start the extraction process: {}", e))?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let started = Instant::now();
+    let status = thread::scope(|scope| {
+        if let Some(stdout) = stdout {
+            scope.spawn(|| forward_output(stdout, channel));
+        }
+        if let Some(stderr) = stderr {
+            scope.spawn(|| forward_output(stderr, channel));
+        }
+        loop {
+            if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+                return Ok(status);
+            }
+            let failure = if file_timeout(options).is_some_and(|timeout| started.elapsed() >= timeout) {
+                Some(format!("timed out after {}s", options.file_timeout.unwrap_or_default()))
+            } else {
+                options
+                    .max_memory
+                    .filter(|&limit| resident_mib(child.id()).is_some_and(|resident| resident > limit))
+                    .map(|limit| format!("exceeded the memory limit of {} MiB", limit))
+            };
+            if let Some(failure) = failure {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(failure);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    })?;
+    if !status.success() {
+        return Err(format!("extraction process failed ({})", status));
+    }
+
+    let bank = bank_path(options, &output);
+    let questions = if options.stream {
+        stream::count(&bank)
+    } else {
+        load_from_json(&bank.to_string_lossy(), &Crypto::new(options)).map(|bank| bank.questions.len())
+    };
+    questions.map_err(|e| e.to_string())
+}
+
+/// Records the state of a job in the queue, if any. A queue that cannot be written is reported, the file goes on.
+fn record(queue: Option<&Mutex<Queue>>, channel: &Channel, update: impl FnOnce(&Queue) -> Result<(), Box<dyn Error>>) {
+    let Some(queue) = queue else {
+        return;
+    };
+    let queue = queue.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Err(error) = update(&queue) {
+        channel.send(Event::Diagnostic("warning", "queue".to_string(), error.to_string()));
+    }
+}
+
+/// Runs the files through a pool of workers, sending their events to `sender` and their state to `queue`.
+fn schedule(options: &ExtractOptions, files: &[PathBuf], sender: Sender<(usize, Event)>, queue: Option<Queue>) {
+    let jobs = pages::jobs(options.jobs).min(files.len().max(1));
+    let next = AtomicUsize::new(0);
+    let queue = queue.map(Mutex::new);
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let file = next.fetch_add(1, Ordering::Relaxed);
+                let Some(pdf) = files.get(file) else {
+                    break;
+                };
+                let channel = Channel {
+                    file,
+                    sender: sender.clone(),
+                };
+                channel.send(Event::Started);
+                record(queue.as_ref(), &channel, |queue| queue.start(pdf));
+                let result = if options.isolate {
+                    run_isolated(options, pdf, &channel)
+                } else {
+                    run_in_process(options, pdf, &channel)
+                };
+                record(queue.as_ref(), &channel, |queue| queue.finish(pdf, &result));
+                channel.send(match result {
+                    Ok(questions) => Event::Finished(questions),
+                    Err(message) => Event::Failed(message),
+                });
+            });
+        }
+    });
+}
+
+/// Prints the events of the batch line by line until every file is finished. Returns the number of failed files.
+fn report_console(files: &[PathBuf], receiver: Receiver<(usize, Event)>) -> usize {
+    let mut finished = vec![false; files.len()];
+    let (mut failed, mut questions) = (0, 0);
+    let mut timings = Timings::default();
+    while finished.iter().any(|&done| !done) {
+        let Ok((file, event)) = receiver.recv() else {
+            break;
+        };
+        // an abandoned extraction may still report after its file was given up
+        if finished[file] {
+            continue;
+        }
+        let name = files[file].display();
+        match event {
+            Event::Started => println!("[{}/{}] {}", file + 1, files.len(), name),
+            Event::Info(message) => println!("{}: {}", name, message),
+            Event::Diagnostic(severity, rule, message) => eprintln!("{}: {}[{}] {}", name, severity, rule, message),
+            Event::Timings(file_timings) => {
+                println!("{}: time: {}", name, file_timings.summary());
+                timings.merge(&file_timings);
+            }
+            Event::Finished(count) => {
+                finished[file] = true;
+                questions += count;
+                println!("{}: {} question(s) saved", name, count);
+            }
+            Event::Failed(message) => {
+                finished[file] = true;
+                failed += 1;
+                eprintln!("error[batch] {}: {}", name, message);
+            }
+            Event::Pages(_) | Event::Extracted(_) | Event::Page(_) | Event::Parsed(_) => {}
+        }
+    }
+    println!("Extracted {} question(s) from {} file(s), {} failed", questions, files.len() - failed, failed);
+    // the files extracted in child processes print their times, which are not added up here
+    if !timings.is_empty() {
+        println!("Time across files: {}", timings.summary());
+    }
+    failed
+}
+
+/// Extracts every PDF in `dir`. Fails when a file failed, after all files were tried.
+pub(crate) fn run(options: &ExtractOptions, dir: &Path) -> Result<(), Box<dyn Error>> {
+    if options.max_memory.is_some() && !options.isolate {
+        return Err(OutputError::from("--max-memory requires --isolate").into());
+    }
+    let mut files = pdf_files(dir)?;
+    if files.is_empty() {
+        return Err(OutputError {
+            message: format!("No PDF files in {}", dir.display()),
+        }
+        .into());
+    }
+    let queue = options.queue.as_deref().map(Queue::open).transpose()?;
+    if let (Some(queue), Some(path)) = (&queue, &options.queue) {
+        let total = files.len();
+        files = queue.prepare(options, dir, &files)?;
+        println!("Job queue {}: {} of {} file(s) left to extract", path, files.len(), total);
+        if files.is_empty() {
+            println!("Nothing left to extract, `jobs retry-failed {}` runs the failed files again", path);
+            return Ok(());
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let scheduler_options = options.clone();
+    let scheduler_files = files.clone();
+    thread::spawn(move || schedule(&scheduler_options, &scheduler_files, sender, queue));
+    let failed = if options.dashboard {
+        dashboard::run(&files, receiver)?
+    } else {
+        report_console(&files, receiver)
+    };
+    if failed > 0 {
+        return Err(OutputError {
+            message: format!("{} of {} file(s) failed", failed, files.len()),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::tests::titled_pdf;
+
+    #[test]
+    fn files_are_routed_and_named_by_exam_code() {
+        let root = std::env::temp_dir().join(format!("s4wm-batch-routing-{}", std::process::id()));
+        let (sender, receiver) = mpsc::channel();
+        let channel = Channel { file: 0, sender };
+        let options = ExtractOptions {
+            output_dir: Some(root.to_string_lossy().into_owned()),
+            by_exam: true,
+            ..ExtractOptions::default()
+        };
+        let pdf = titled_pdf("batch-ewm", "C_S4EWM_2020 - Extended Warehouse Management");
+        let (routed, output) = file_options(&options, &pdf, &channel);
+        let untitled = titled_pdf("batch-untitled", "Practice questions");
+        let (unrouted, unrouted_output) = file_options(&options, &untitled, &channel);
+        let flat = ExtractOptions {
+            by_exam: false,
+            ..options.clone()
+        };
+        let (unsorted, unsorted_output) = file_options(&flat, &pdf, &channel);
+        fs::remove_file(&pdf).unwrap();
+        fs::remove_file(&untitled).unwrap();
+
+        let stem = pdf.file_stem().unwrap();
+        assert_eq!(output, root.join("C_S4EWM_2020").join(stem));
+        assert_eq!(routed.exam_code.as_deref(), Some("C_S4EWM_2020"));
+        assert_eq!(bank_path(&routed, &output), output.join("C_S4EWM_2020-questions.json"));
+        let streamed = ExtractOptions {
+            stream: true,
+            ..routed
+        };
+        assert_eq!(bank_path(&streamed, &output), output.join("C_S4EWM_2020-questions.ndjson"));
+
+        assert_eq!(unrouted_output, root.join(untitled.file_stem().unwrap()));
+        assert_eq!(bank_path(&unrouted, &unrouted_output), unrouted_output.join("questions.json"));
+        assert_eq!((unsorted.exam_code, unsorted_output), (None, root.join(stem)));
+        let messages: Vec<String> = receiver
+            .try_iter()
+            .filter_map(|(_, event)| match event {
+                Event::Info(message) => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("exam C_S4EWM_2020, saved under "));
+    }
+
+    #[test]
+    fn exam_codes_go_to_child_processes_per_file() {
+        let options = ExtractOptions {
+            by_exam: true,
+            exam_code: Some("C_S4EWM_2020".to_string()),
+            drop_duplicates: true,
+            ..ExtractOptions::default()
+        };
+        let args = child_args(&options).unwrap();
+        assert!(args.contains(&"--drop-duplicates".to_string()));
+        assert!(!args.iter().any(|arg| arg == "--exam-code" || arg == "--by-exam"));
+    }
+}
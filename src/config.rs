@@ -0,0 +1,127 @@
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, Subcommand};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{ExtractOptions, OutputError};
+
+// Layered configuration of the extraction options, from lowest to highest precedence: built-in defaults, the user
+// config file (`~/.config/s4wm-extract/config.toml`), the project config file (`./s4wm-extract.toml`), `S4WM_*`
+// environment variables and command-line flags. Config files use the option names as keys (`profile = "de"`,
+// `answer_strategy = ["marker", "bold"]`). Environment variables are handled by clap together with the flags.
+
+pub(crate) const PROJECT_FILE: &str = "s4wm-extract.toml";
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the effective configuration after merging all layers
+    Show,
+}
+
+/// The user configuration directory, `~/.config/s4wm-extract` unless `XDG_CONFIG_HOME` says otherwise
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("s4wm-extract"))
+}
+
+fn user_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// Config files in order of increasing precedence.
+fn config_files() -> Vec<PathBuf> {
+    user_file().into_iter().chain([PathBuf::from(PROJECT_FILE)]).filter(|path| path.is_file()).collect()
+}
+
+/// Whether the option was given on the command line or through its environment variable.
+fn explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id).is_some_and(|source| source != ValueSource::DefaultValue)
+}
+
+/// Fills the options not given on the command line or in the environment from the config files. Returns the files
+/// that were read.
+pub(crate) fn apply_files(options: &mut ExtractOptions, matches: &ArgMatches) -> Result<Vec<PathBuf>, OutputError> {
+    let files = config_files();
+    let mut merged = toml::Table::new();
+    for path in &files {
+        let table: toml::Table = toml::from_str(&fs::read_to_string(path)?).map_err(|e| OutputError {
+            message: format!("Invalid config file {}: {}", path.display(), e),
+        })?;
+        merged.extend(table);
+    }
+    let from_files: ExtractOptions = toml::Value::Table(merged).try_into().map_err(|e| OutputError {
+        message: format!("Invalid configuration: {}", e),
+    })?;
+
+    macro_rules! layer {
+        ($($field:ident),*) => {
+            $(
+                if !explicit(matches, stringify!($field)) {
+                    options.$field = from_files.$field.clone();
+                }
+            )*
+        };
+    }
+    layer!(
+        input,
+        output_dir,
+        profile,
+        answer_strategy,
+        typography,
+        element_order,
+        columns,
+        previous,
+        layout,
+        formats,
+        plugin_dir,
+        hook,
+        dashboard,
+        jobs,
+        file_timeout,
+        by_exam,
+        queue,
+        page_timeout,
+        skip_pages,
+        keep_noise_pages,
+        chapter,
+        isolate,
+        max_memory,
+        stream,
+        strict,
+        drop_duplicates,
+        explain,
+        history,
+        author,
+        dictionary,
+        schema,
+        allow,
+        baseline,
+        encrypt,
+        recipient,
+        identity,
+        sign
+    );
+    Ok(files)
+}
+
+pub(crate) fn run(args: ConfigArgs, options: &ExtractOptions, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    match args.command {
+        ConfigCommand::Show => {
+            for file in files {
+                println!("# from {}", file.display());
+            }
+            print!("{}", toml::to_string_pretty(options)?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,435 @@
+use clap::Args;
+use regex::Regex;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Response, Url};
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::batch;
+use crate::ExtractOptions;
+
+// Crawling a provider's site for its dumps, for providers publishing one PDF per topic across many pages. From the
+// index page the crawler follows the links of every HTML page, breadth first and down to `--max-depth` links away,
+// without leaving the host of the index. Links whose file name matches `--match` are collected rather than followed.
+// It is meant to be polite: robots.txt is read first and its Disallow, Allow and Crawl-delay lines for our user agent
+// (or `*`) are obeyed, requests are spaced by at least `--delay`, and `--max-pages` bounds the pages fetched. The PDFs
+// found are downloaded into `--download-dir`, leaving the files already there alone, and extracted as a batch with
+// the extraction options given before `crawl`. A document served as something else than a PDF (often the HTML of a
+// login or error page) or larger than `--max-size` is skipped with a warning, and pages are read up to `MAX_PAGE`.
+
+const USER_AGENT: &str = concat!("S4WM-extract/", env!("CARGO_PKG_VERSION"));
+/// Name matched against the User-agent lines of robots.txt
+const ROBOTS_AGENT: &str = "s4wm-extract";
+/// Bytes of an HTML page read for its links
+const MAX_PAGE: usize = 10 << 20;
+/// Content types a document may be served with, besides none at all
+const DOCUMENT_TYPES: &[&str] =
+    &["application/pdf", "application/x-pdf", "application/octet-stream", "binary/octet-stream"];
+
+lazy_static! {
+    static ref HREF_REGEX: Regex = Regex::new(r#"(?i)\bhref\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap();
+}
+
+#[derive(Args, Debug)]
+pub struct CrawlArgs {
+    /// Page listing the documents, where the crawl starts
+    url: String,
+    /// File names of the documents to collect, `*` standing for any characters
+    #[arg(long = "match", default_value = "*.pdf", value_name = "PATTERN")]
+    pattern: String,
+    /// Links to follow from the index page
+    #[arg(long, default_value_t = 2)]
+    max_depth: usize,
+    /// Most pages to fetch, documents not included
+    #[arg(long, default_value_t = 200)]
+    max_pages: usize,
+    /// Milliseconds to wait between two requests, raised to the Crawl-delay of robots.txt
+    #[arg(long, default_value_t = 1000)]
+    delay: u64,
+    /// Where to save the documents found
+    #[arg(long, default_value = "crawl")]
+    download_dir: String,
+    /// Largest document to download, in MiB
+    #[arg(long, default_value_t = 100)]
+    max_size: usize,
+    /// Only list the documents found, without downloading them
+    #[arg(long)]
+    list: bool,
+    /// Download the documents without extracting them
+    #[arg(long, conflicts_with = "list")]
+    no_extract: bool,
+}
+
+/// The rules of robots.txt that apply to us
+#[derive(Debug, Default)]
+struct Robots {
+    /// Paths of the Disallow and Allow lines, with whether they allow
+    rules: Vec<(String, bool)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Reads the group of `ROBOTS_AGENT`, or else the group of `*`.
+    fn parse(text: &str) -> Self {
+        let (mut ours, mut any) = (None, None);
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        let mut current = Robots::default();
+        let mut finish = |agents: &[String], current: Robots| {
+            let named = |agent: &String| !agent.is_empty() && agent != "*" && ROBOTS_AGENT.starts_with(agent.as_str());
+            if agents.iter().any(named) {
+                ours.get_or_insert(current);
+            } else if agents.iter().any(|agent| agent == "*") {
+                any.get_or_insert(current);
+            }
+        };
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (field, value) = (field.trim().to_lowercase(), value.trim());
+            match field.as_str() {
+                "user-agent" => {
+                    // a User-agent line after rules starts a new group
+                    if in_rules {
+                        finish(&agents, std::mem::take(&mut current));
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_lowercase());
+                }
+                "disallow" | "allow" => {
+                    in_rules = true;
+                    // an empty Disallow allows everything
+                    if !value.is_empty() {
+                        current.rules.push((value.to_string(), field == "allow"));
+                    }
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    current.crawl_delay = value.parse::<f64>().ok().map(Duration::from_secs_f64);
+                }
+                _ => {}
+            }
+        }
+        finish(&agents, current);
+        ours.or(any).unwrap_or_default()
+    }
+
+    /// Whether `path` may be fetched: the longest matching rule decides, Allow winning a tie.
+    fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(rule, _)| rule_matches(rule, path))
+            .max_by_key(|(prefix, allow)| (prefix.len(), *allow))
+            .is_none_or(|(_, allow)| *allow)
+    }
+}
+
+/// Whether a Disallow or Allow path matches `path`: a prefix, where `*` stands for any characters and a final `$`
+/// anchors the end.
+fn rule_matches(rule: &str, path: &str) -> bool {
+    if !rule.contains(['*', '$']) {
+        return path.starts_with(rule);
+    }
+    let (rule, anchored) = rule.strip_suffix('$').map_or((rule, false), |rule| (rule, true));
+    let parts: Vec<String> = rule.split('*').map(regex::escape).collect();
+    let regex = format!("^{}{}", parts.join(".*"), if anchored { "$" } else { "" });
+    Regex::new(&regex).is_ok_and(|regex| regex.is_match(path))
+}
+
+/// Regex matching the file names of `pattern`, a glob where `*` stands for any characters and `?` for one.
+fn file_name_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut regex = String::from("(?i)^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex)
+}
+
+fn file_name(url: &Url) -> &str {
+    url.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or_default()
+}
+
+/// Absolute URLs of the links of an HTML page, without fragments.
+fn links(base: &Url, html: &str) -> Vec<Url> {
+    HREF_REGEX
+        .captures_iter(html)
+        .filter_map(|caps| caps.iter().skip(1).flatten().next().map(|m| m.as_str().replace("&amp;", "&")))
+        .filter_map(|href| base.join(href.trim()).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(|mut url| {
+            url.set_fragment(None);
+            url
+        })
+        .collect()
+}
+
+/// Spaces the requests of the crawl.
+struct Throttle {
+    delay: Duration,
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    async fn wait(&mut self) {
+        if let Some(last) = self.last {
+            tokio::time::sleep(self.delay.saturating_sub(last.elapsed())).await;
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+async fn robots(client: &Client, index: &Url) -> Robots {
+    let Ok(url) = index.join("/robots.txt") else {
+        return Robots::default();
+    };
+    let response = match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => response,
+        // no robots.txt, everything is allowed
+        _ => return Robots::default(),
+    };
+    Robots::parse(&response.text().await.unwrap_or_default())
+}
+
+/// The documents reachable from the index page, in the order found.
+async fn crawl(
+    client: &Client,
+    args: &CrawlArgs,
+    index: &Url,
+    robots: &Robots,
+    throttle: &mut Throttle,
+) -> Result<Vec<Url>, Box<dyn Error>> {
+    let pattern = file_name_regex(&args.pattern)?;
+    let mut seen: HashSet<Url> = HashSet::from([index.clone()]);
+    let mut queue = VecDeque::from([(index.clone(), 0)]);
+    let (mut documents, mut fetched) = (Vec::new(), 0);
+    while let Some((url, depth)) = queue.pop_front() {
+        if !robots.allows(url.path()) {
+            println!("skipped {} (disallowed by robots.txt)", url);
+            continue;
+        }
+        if fetched == args.max_pages {
+            println!("Stopped after {} pages (--max-pages)", fetched);
+            break;
+        }
+        throttle.wait().await;
+        fetched += 1;
+        let response = match client.get(url.clone()).send().await.and_then(|r| r.error_for_status()) {
+            Ok(response) => response,
+            Err(error) => {
+                println!("warning[crawl] {}: {}", url, error);
+                continue;
+            }
+        };
+        let html = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| content_type.contains("html"));
+        if !html {
+            continue;
+        }
+        let page = match read_body(response, MAX_PAGE).await {
+            Ok(page) => String::from_utf8_lossy(&page).into_owned(),
+            Err(error) => {
+                println!("warning[crawl] {}: {}", url, error);
+                continue;
+            }
+        };
+        for link in links(&url, &page) {
+            if link.host_str() != index.host_str() || !seen.insert(link.clone()) {
+                continue;
+            }
+            if pattern.is_match(file_name(&link)) {
+                if robots.allows(link.path()) {
+                    println!("found {}", link);
+                    documents.push(link);
+                }
+            } else if depth < args.max_depth {
+                queue.push_back((link, depth + 1));
+            }
+        }
+    }
+    println!("Fetched {} page(s), found {} document(s)", fetched, documents.len());
+    Ok(documents)
+}
+
+/// Whether a document served with this content type can be a PDF.
+fn is_document_type(content_type: Option<&str>) -> bool {
+    let Some(content_type) = content_type else {
+        return true;
+    };
+    let media_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+    DOCUMENT_TYPES.contains(&media_type.as_str())
+}
+
+/// The body of a response, refused when it is larger than `limit` bytes, announced or not.
+async fn read_body(mut response: Response, limit: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let too_large = || format!("larger than {} MiB", limit >> 20);
+    if response.content_length().is_some_and(|length| length > limit as u64) {
+        return Err(too_large().into());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large().into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Downloads a document, or says why it was skipped.
+async fn download(client: &Client, url: &Url, limit: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    let content_type = response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    if !is_document_type(content_type) {
+        return Err(format!("served as {}, not as a PDF", content_type.unwrap_or_default()).into());
+    }
+    read_body(response, limit).await
+}
+
+/// File name of a URL path segment, with its percent escapes decoded where they make valid UTF-8. Separators are
+/// replaced, and a name of dots only, such as a decoded `..`, is not used.
+fn decoded_file_name(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = (bytes[index] == b'%')
+            .then(|| segment.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    let name = String::from_utf8(decoded).unwrap_or_else(|_| segment.to_string());
+    if name.trim_matches('.').is_empty() {
+        return "download".to_string();
+    }
+    name.replace(['/', '\\'], "_")
+}
+
+pub(crate) async fn run(args: CrawlArgs, options: &ExtractOptions) -> Result<(), Box<dyn Error>> {
+    let index = Url::parse(&args.url).map_err(|e| format!("Invalid index URL {}: {}", args.url, e))?;
+    let client = Client::builder().user_agent(USER_AGENT).build()?;
+    let robots = robots(&client, &index).await;
+    let mut throttle = Throttle {
+        delay: Duration::from_millis(args.delay).max(robots.crawl_delay.unwrap_or_default()),
+        last: None,
+    };
+    let documents = crawl(&client, &args, &index, &robots, &mut throttle).await?;
+    if args.list {
+        return Ok(());
+    }
+
+    let dir = Path::new(&args.download_dir);
+    fs::create_dir_all(dir)?;
+    for url in &documents {
+        let name = decoded_file_name(file_name(url));
+        let path = dir.join(if name.to_lowercase().ends_with(".pdf") { name } else { format!("{}.pdf", name) });
+        if path.exists() {
+            println!("kept {} (already downloaded)", path.display());
+            continue;
+        }
+        throttle.wait().await;
+        match download(&client, url, args.max_size << 20).await {
+            Ok(bytes) => {
+                fs::write(&path, &bytes)?;
+                println!("downloaded {}", path.display());
+            }
+            Err(error) => println!("warning[crawl] {}: skipped, {}", url, error),
+        }
+    }
+    if args.no_extract || documents.is_empty() {
+        return Ok(());
+    }
+    batch::run(options, dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_robots_rule_decides_and_allow_wins_ties() {
+        let robots = Robots::parse(
+            "User-agent: *\nDisallow: /dumps/\nAllow: /dumps/free/\nDisallow: /dumps/free/premium\n\
+             Allow: /tie\nDisallow: /tie\nDisallow:\n",
+        );
+        assert!(robots.allows("/index.html"));
+        assert!(!robots.allows("/dumps/sap.pdf"));
+        assert!(robots.allows("/dumps/free/sap.pdf"));
+        assert!(!robots.allows("/dumps/free/premium.pdf"));
+        assert!(robots.allows("/tie"));
+    }
+
+    #[test]
+    fn robots_group_of_our_agent_wins_over_the_wildcard() {
+        let text = "User-agent: *\nDisallow: /\n\nUser-agent: other\nUser-agent: S4WM\nDisallow: /private\n\
+                    Crawl-delay: 2.5\n";
+        let robots = Robots::parse(text);
+        assert!(robots.allows("/dumps/sap.pdf"));
+        assert!(!robots.allows("/private/sap.pdf"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(2500)));
+        assert!(!Robots::parse("User-agent: googlebot\nDisallow: /\nUser-agent: *\nDisallow: /\n").allows("/"));
+        assert!(Robots::parse("User-agent: googlebot\nDisallow: /\n").allows("/"));
+    }
+
+    #[test]
+    fn robots_globs_match_prefixes_unless_anchored() {
+        assert!(rule_matches("/dumps", "/dumps-2024/sap.pdf"));
+        assert!(rule_matches("/*.pdf", "/dumps/sap.pdf?download=1"));
+        assert!(rule_matches("/*.pdf$", "/dumps/sap.pdf"));
+        assert!(!rule_matches("/*.pdf$", "/dumps/sap.pdf?download=1"));
+        assert!(!rule_matches("/dumps/*/sap", "/sap/dumps/2024/sap"));
+        assert!(rule_matches("/a+b(c)*", "/a+b(c)/d"));
+    }
+
+    #[test]
+    fn file_name_globs_match_whole_names_ignoring_case() {
+        let regex = file_name_regex("C_S4EWM*.pdf").unwrap();
+        assert!(regex.is_match("c_s4ewm_2020.PDF"));
+        assert!(!regex.is_match("old_C_S4EWM_2020.pdf"));
+        assert!(!regex.is_match("C_S4EWM_2020.pdf.zip"));
+        assert!(file_name_regex("dump?.pdf").unwrap().is_match("dump1.pdf"));
+        assert!(!file_name_regex("dump?.pdf").unwrap().is_match("dump.pdf"));
+    }
+
+    #[test]
+    fn decoded_file_names_stay_in_the_download_directory() {
+        assert_eq!(decoded_file_name("SAP%20EWM%20%C3%A9dition.pdf"), "SAP EWM édition.pdf");
+        assert_eq!(decoded_file_name("%2E%2E%2Fsecret.pdf"), ".._secret.pdf");
+        assert_eq!(decoded_file_name("..%5C..%5Cboot.ini"), ".._.._boot.ini");
+        assert_eq!(decoded_file_name("%2e%2e"), "download");
+        assert_eq!(decoded_file_name("100%.pdf"), "100%.pdf");
+        assert_eq!(decoded_file_name("%FF.pdf"), "%FF.pdf");
+    }
+
+    #[test]
+    fn documents_must_be_served_as_pdf() {
+        assert!(is_document_type(None));
+        assert!(is_document_type(Some("application/pdf")));
+        assert!(is_document_type(Some("Application/PDF; charset=binary")));
+        assert!(is_document_type(Some("application/octet-stream")));
+        assert!(!is_document_type(Some("text/html; charset=utf-8")));
+    }
+}
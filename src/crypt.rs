@@ -0,0 +1,259 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::tree::Layout;
+use crate::{ExtractOptions, OutputError};
+
+// Encryption at rest of the outputs of an extraction, for banks of licensed content kept on shared servers. With
+// `--encrypt` the bank and the exports are encrypted with age (https://age-encryption.org) to every `--recipient`,
+// given as a public key (`age1...`) or as a file of them, one per line. The files keep their names; the manifest
+// lists the checksums of the encrypted files. Every command reading a bank recognizes an encrypted one by the age
+// header and decrypts it with the identities (private keys) of the file named by `--identity`, which like every
+// option can come from a config file or the environment (`S4WM_IDENTITY`). The per-question layout and `--stream`
+// write their outputs piecemeal and are not encrypted.
+//
+// The identity, the recipients and whether to encrypt are carried by a `Crypto` context handed to what reads and
+// writes. Opening an encrypted bank gives the context to write that bank and what is made of it with, one that
+// encrypts, so that editing a bank does not leave it in plaintext: the bytes are encrypted in memory before they reach
+// the disk, to the `--recipient` keys of the run, and nothing is written when there are none. Other files read on the
+// way (a baseline, a previous bank) do not change how the outputs are written. The change log only records which
+// fields changed for encrypted banks.
+
+const AGE_HEADER: &[u8] = b"age-encryption.org/";
+
+/// What reading and writing encrypted files takes: the identity file decrypting them, the `--recipient` keys (public
+/// keys or files of them) encrypting them and whether what is written is encrypted
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Crypto {
+    identity: Option<String>,
+    recipients: Vec<String>,
+    encrypt: bool,
+}
+
+fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(AGE_HEADER)
+}
+
+fn error(message: impl ToString) -> OutputError {
+    OutputError {
+        message: message.to_string(),
+    }
+}
+
+/// Recipients of the outputs when `--encrypt` is given, from public keys or files of public keys.
+pub(crate) fn recipients(options: &ExtractOptions) -> Result<Option<Vec<age::x25519::Recipient>>, OutputError> {
+    if !options.encrypt {
+        return Ok(None);
+    }
+    if options.recipient.is_empty() {
+        return Err(error("--encrypt needs at least one --recipient (or S4WM_RECIPIENT)"));
+    }
+    if options.layout == Layout::PerQuestion {
+        return Err(error("--encrypt cannot be combined with --layout per-question"));
+    }
+    parse_recipients(&options.recipient).map(Some)
+}
+
+fn parse_recipients(recipients: &[String]) -> Result<Vec<age::x25519::Recipient>, OutputError> {
+    let mut keys = Vec::new();
+    for recipient in recipients {
+        if recipient.starts_with("age1") {
+            keys.push(recipient.clone());
+            continue;
+        }
+        let text = fs::read_to_string(recipient)
+            .map_err(|e| error(format!("{} is neither an age public key nor a readable file: {}", recipient, e)))?;
+        let lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        keys.extend(lines.map(str::to_string));
+    }
+    let recipients = keys.iter().map(|key| {
+        age::x25519::Recipient::from_str(key).map_err(|e| error(format!("Invalid recipient {}: {}", key, e)))
+    });
+    recipients.collect()
+}
+
+fn encrypt(bytes: &[u8], recipients: &[age::x25519::Recipient]) -> Result<Vec<u8>, OutputError> {
+    let recipients: Vec<Box<dyn age::Recipient + Send>> =
+        recipients.iter().map(|recipient| Box::new(recipient.clone()) as Box<dyn age::Recipient + Send>).collect();
+    let encryptor = age::Encryptor::with_recipients(recipients).ok_or_else(|| error("No recipient to encrypt to"))?;
+    let mut encrypted = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut encrypted).map_err(|e| error(format!("Cannot encrypt: {}", e)))?;
+    writer.write_all(bytes)?;
+    writer.finish()?;
+    Ok(encrypted)
+}
+
+impl Crypto {
+    /// Takes the identity, the recipients and `--encrypt` from the options of the run.
+    pub(crate) fn new(options: &ExtractOptions) -> Self {
+        Crypto {
+            identity: options.identity.clone(),
+            recipients: options.recipient.clone(),
+            encrypt: options.encrypt,
+        }
+    }
+
+    /// Whether what is written with this context is encrypted.
+    pub(crate) fn is_encrypting(&self) -> bool {
+        self.encrypt
+    }
+
+    /// The bytes to write to disk: encrypted to the recipients when the context encrypts.
+    fn seal(&self, bytes: Vec<u8>) -> Result<Vec<u8>, OutputError> {
+        if !self.encrypt {
+            return Ok(bytes);
+        }
+        if self.recipients.is_empty() {
+            return Err(error(
+                "The run reads or writes encrypted banks, give the keys to encrypt its outputs to with --recipient (or \
+                 S4WM_RECIPIENT); nothing is written in plaintext",
+            ));
+        }
+        encrypt(&bytes, &parse_recipients(&self.recipients)?)
+    }
+
+    /// Writes a file, encrypted when the context encrypts.
+    pub(crate) fn write(&self, path: impl AsRef<Path>, bytes: impl Into<Vec<u8>>) -> Result<(), OutputError> {
+        fs::write(path, self.seal(bytes.into())?)?;
+        Ok(())
+    }
+
+    /// Fails when the context encrypts, for outputs that cannot be encrypted.
+    pub(crate) fn plaintext_allowed(&self, what: &str) -> Result<(), OutputError> {
+        if self.encrypt {
+            return Err(error(format!("{} cannot be encrypted and the run encrypts, nothing was written", what)));
+        }
+        Ok(())
+    }
+
+    fn decrypt(&self, bytes: &[u8], source: &Path) -> Result<Vec<u8>, OutputError> {
+        let Some(identity) = &self.identity else {
+            return Err(error(format!(
+                "{} is encrypted, give the identity file to decrypt it with --identity or S4WM_IDENTITY",
+                source.display()
+            )));
+        };
+        let entries = age::IdentityFile::from_file(identity.clone())
+            .map_err(|e| error(format!("Cannot read the identity file {}: {}", identity, e)))?
+            .into_identities();
+        // without the `plugin` feature of age every entry is a native x25519 identity
+        let identities: Vec<age::x25519::Identity> = entries
+            .into_iter()
+            .map(|entry| match entry {
+                age::IdentityFileEntry::Native(identity) => identity,
+            })
+            .collect();
+        let decryptor = match age::Decryptor::new(bytes).map_err(error)? {
+            age::Decryptor::Recipients(decryptor) => decryptor,
+            _ => return Err(error(format!("{} is encrypted with a passphrase, not to a key", source.display()))),
+        };
+        let mut reader = decryptor
+            .decrypt(identities.iter().map(|identity| identity as &dyn age::Identity))
+            .map_err(|e| error(format!("Cannot decrypt {} with {}: {}", source.display(), identity, e)))?;
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted)?;
+        Ok(decrypted)
+    }
+
+    /// Contents of a file, decrypted when it is encrypted, and the context to write it back and what is made of it
+    /// with: one that encrypts when the file was encrypted.
+    pub(crate) fn open(&self, path: &Path) -> Result<(Vec<u8>, Crypto), OutputError> {
+        let bytes = fs::read(path)?;
+        if !is_encrypted(&bytes) {
+            return Ok((bytes, self.clone()));
+        }
+        let decrypted = self.decrypt(&bytes, path)?;
+        let crypto = Crypto {
+            encrypt: true,
+            ..self.clone()
+        };
+        Ok((decrypted, crypto))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+
+    /// A directory of its own for a test, with an identity file; returns the directory, the context decrypting with
+    /// the identity and its public key.
+    fn setup(name: &str) -> (std::path::PathBuf, Crypto, age::x25519::Recipient) {
+        let dir = std::env::temp_dir().join(format!("s4wm-crypt-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let identity = age::x25519::Identity::generate();
+        let identity_path = dir.join("identity.txt");
+        fs::write(&identity_path, identity.to_string().expose_secret()).unwrap();
+        let crypto = Crypto {
+            identity: Some(identity_path.to_string_lossy().into_owned()),
+            recipients: vec![identity.to_public().to_string()],
+            encrypt: false,
+        };
+        (dir, crypto, identity.to_public())
+    }
+
+    #[test]
+    fn encrypted_bank_reads_back_with_the_identity() {
+        let (dir, crypto, recipient) = setup("read");
+        let bank = dir.join("questions.json");
+        let contents = br#"{"meta":{},"questions":[]}"#;
+        fs::write(&bank, encrypt(contents, &[recipient]).unwrap()).unwrap();
+
+        let encrypted = fs::read(&bank).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_ne!(encrypted, contents);
+
+        let opened = crypto.open(&bank);
+        fs::remove_dir_all(&dir).unwrap();
+        let (decrypted, writing) = opened.unwrap();
+        assert_eq!(decrypted, contents);
+        assert!(writing.is_encrypting());
+        assert!(!crypto.is_encrypting());
+    }
+
+    #[test]
+    fn plaintext_files_keep_the_context() {
+        let (dir, crypto, _) = setup("plaintext");
+        let bank = dir.join("questions.json");
+        fs::write(&bank, b"[]").unwrap();
+        let opened = crypto.open(&bank);
+        fs::remove_dir_all(&dir).unwrap();
+        let (bytes, writing) = opened.unwrap();
+        assert_eq!(bytes, b"[]");
+        assert!(!writing.is_encrypting());
+    }
+
+    #[test]
+    fn encrypting_context_writes_what_it_reads_back() {
+        let (dir, crypto, _) = setup("write");
+        let crypto = Crypto {
+            encrypt: true,
+            ..crypto
+        };
+        let bank = dir.join("questions.json");
+        let written = crypto.write(&bank, b"[]".to_vec());
+        let on_disk = fs::read(&bank);
+        let read = crypto.open(&bank);
+        fs::remove_dir_all(&dir).unwrap();
+        written.unwrap();
+        assert!(is_encrypted(&on_disk.unwrap()));
+        assert_eq!(read.unwrap().0, b"[]");
+        assert!(crypto.plaintext_allowed("The per-question layout").is_err());
+    }
+
+    #[test]
+    fn encrypted_file_without_identity_is_an_error() {
+        let (dir, crypto, recipient) = setup("no-identity");
+        let bank = dir.join("questions.json");
+        fs::write(&bank, encrypt(b"[]", &[recipient]).unwrap()).unwrap();
+        let without = Crypto {
+            identity: None,
+            ..crypto
+        };
+        let opened = without.open(&bank);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(opened.unwrap_err().message.contains("--identity"));
+    }
+}
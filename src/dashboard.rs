@@ -0,0 +1,260 @@
+use ratatui::crossterm::event::{self, Event as TerminalEvent, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use crate::progress::Event;
+use crate::OutputError;
+
+// Terminal dashboard of a batch run (`--dashboard`): a table with the status, page progress, question and warning
+// count of every file, a log pane with the messages and diagnostics of all files, and the aggregate throughput.
+// It redraws from the events sent by the workers until every file is finished, then prints a summary. `q` or Ctrl-C
+// stops the batch.
+
+const REFRESH: Duration = Duration::from_millis(100);
+const LOG_HEIGHT: u16 = 10;
+const LOG_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Status {
+    Queued,
+    Running,
+    Done,
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct FileRow {
+    name: String,
+    status: Status,
+    pages: Option<usize>,
+    page: usize,
+    questions: usize,
+    warnings: usize,
+}
+
+impl FileRow {
+    fn status_text(&self) -> &str {
+        match self.status {
+            Status::Queued => "queued",
+            Status::Running => "running",
+            Status::Done => "done",
+            Status::Failed(_) => "failed",
+        }
+    }
+
+    fn pages_text(&self) -> String {
+        match (&self.status, self.pages) {
+            (Status::Running, Some(pages)) => format!("{}/{}", self.page, pages),
+            (_, Some(pages)) => pages.to_string(),
+            (_, None) => String::new(),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.status, Status::Done | Status::Failed(_))
+    }
+
+    /// Pages extracted so far
+    fn pages_done(&self) -> usize {
+        match self.status {
+            Status::Running => self.page,
+            _ => self.pages.unwrap_or(0),
+        }
+    }
+
+    fn style(&self) -> Style {
+        match self.status {
+            Status::Queued => Style::new(),
+            Status::Running => Style::new().fg(Color::Yellow),
+            Status::Done => Style::new().fg(Color::Green),
+            Status::Failed(_) => Style::new().fg(Color::Red),
+        }
+    }
+}
+
+struct Dashboard {
+    rows: Vec<FileRow>,
+    log: VecDeque<String>,
+    started: Instant,
+}
+
+impl Dashboard {
+    fn new(files: &[PathBuf]) -> Self {
+        Dashboard {
+            rows: files
+                .iter()
+                .map(|file| FileRow {
+                    name: file.file_name().map_or_else(|| file.display().to_string(), |n| n.to_string_lossy().into()),
+                    status: Status::Queued,
+                    pages: None,
+                    page: 0,
+                    questions: 0,
+                    warnings: 0,
+                })
+                .collect(),
+            log: VecDeque::new(),
+            started: Instant::now(),
+        }
+    }
+
+    fn log(&mut self, file: usize, message: String) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(format!("{}: {}", self.rows[file].name, message));
+    }
+
+    fn apply(&mut self, file: usize, event: Event) {
+        let row = &mut self.rows[file];
+        // an abandoned extraction may still report after its file was given up
+        if row.is_finished() {
+            return;
+        }
+        match event {
+            Event::Started => row.status = Status::Running,
+            Event::Pages(count) => row.pages = Some(count),
+            // text extraction is where the time goes, parsing only updates the question count
+            Event::Extracted(pages) => row.page = pages,
+            Event::Page(questions) => row.questions = questions,
+            Event::Info(message) => self.log(file, message),
+            Event::Diagnostic(severity, rule, message) => {
+                row.warnings += 1;
+                self.log(file, format!("{}[{}] {}", severity, rule, message));
+            }
+            Event::Parsed(questions) => row.questions = questions,
+            Event::Timings(timings) => self.log(file, format!("time: {}", timings.summary())),
+            Event::Finished(questions) => {
+                row.status = Status::Done;
+                row.questions = questions;
+            }
+            Event::Failed(message) => {
+                row.status = Status::Failed(message.clone());
+                self.log(file, format!("error[batch] {}", message));
+            }
+        }
+    }
+
+    fn summary(&self) -> String {
+        let finished = self.rows.iter().filter(|r| r.is_finished()).count();
+        let questions: usize = self.rows.iter().map(|r| r.questions).sum();
+        let pages: usize = self.rows.iter().map(FileRow::pages_done).sum();
+        let elapsed = self.started.elapsed().as_secs_f64();
+        format!(
+            "{}/{} files, {} questions, {} pages in {:.0}s ({:.1} pages/s)",
+            finished,
+            self.rows.len(),
+            questions,
+            pages,
+            elapsed,
+            pages as f64 / elapsed.max(f64::EPSILON)
+        )
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [table_area, log_area, footer_area] =
+            Layout::vertical([Constraint::Min(5), Constraint::Length(LOG_HEIGHT), Constraint::Length(1)])
+                .areas(frame.area());
+
+        let header = Row::new(["File", "Status", "Pages", "Questions", "Warnings"]).style(Style::new().bold());
+        let rows = self.rows.iter().map(|row| {
+            Row::new([
+                row.name.clone(),
+                row.status_text().to_string(),
+                row.pages_text(),
+                row.questions.to_string(),
+                row.warnings.to_string(),
+            ])
+            .style(row.style())
+        });
+        let widths = [
+            Constraint::Fill(1),
+            Constraint::Length(8),
+            Constraint::Length(11),
+            Constraint::Length(9),
+            Constraint::Length(8),
+        ];
+        let table = Table::new(rows, widths).header(header).block(Block::bordered().title(" Files "));
+        // keeps the file being extracted in view
+        let mut state = TableState::default().with_selected(self.rows.iter().position(|r| r.status == Status::Running));
+        frame.render_stateful_widget(table, table_area, &mut state);
+
+        let visible = usize::from(log_area.height.saturating_sub(2));
+        let lines: Vec<Line> =
+            self.log.iter().skip(self.log.len().saturating_sub(visible)).map(|l| Line::raw(l.as_str())).collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Log ")), log_area);
+        frame.render_widget(Paragraph::new(format!("{} | q quits", self.summary())), footer_area);
+    }
+
+    /// Redraws until every file is finished. Fails when the user stops the batch.
+    fn show(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        receiver: &Receiver<(usize, Event)>,
+    ) -> Result<(), Box<dyn Error>> {
+        loop {
+            let mut disconnected = false;
+            loop {
+                match receiver.try_recv() {
+                    Ok((file, event)) => self.apply(file, event),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+            if disconnected || self.rows.iter().all(FileRow::is_finished) {
+                return Ok(());
+            }
+
+            if event::poll(REFRESH)? {
+                if let TerminalEvent::Key(key) = event::read()? {
+                    let quit = key.kind == KeyEventKind::Press
+                        && (key.code == KeyCode::Char('q')
+                            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)));
+                    if quit {
+                        return Err(OutputError::from("Batch interrupted").into());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Shows the dashboard for the given files until all are extracted, then prints a summary. Returns the number of
+/// failed files.
+pub(crate) fn run(files: &[PathBuf], receiver: Receiver<(usize, Event)>) -> Result<usize, Box<dyn Error>> {
+    let mut dashboard = Dashboard::new(files);
+    let mut terminal = ratatui::init();
+    let shown = dashboard.show(&mut terminal, &receiver);
+    ratatui::restore();
+    shown?;
+
+    let mut failed = 0;
+    for row in &dashboard.rows {
+        match &row.status {
+            Status::Failed(message) => {
+                eprintln!("error[batch] {}: {}", row.name, message);
+                failed += 1;
+            }
+            _ => println!(
+                "{:<8} {} ({} questions, {} warnings)",
+                row.status_text(),
+                row.name,
+                row.questions,
+                row.warnings
+            ),
+        }
+    }
+    println!("{}", dashboard.summary());
+    Ok(failed)
+}
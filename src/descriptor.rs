@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::manifest::sha256_hex;
+use crate::profile::{Profile, Typography};
+use crate::{ExtractOptions, OutputError};
+
+// Run descriptors: a TOML file with the effective options of an extraction run, i.e. the options given on the command
+// line completed with what was detected (profile, answer strategies). `--record` writes one, `--replay` runs with the
+// options of one. The tool version, the source PDF hash and the normalization steps of the profile are recorded too,
+// so a replay can tell when it will not reproduce the recorded run exactly.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Source {
+    path: String,
+    sha256: String,
+}
+
+/// Normalization steps of the profile as built into the tool that recorded the run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Normalization {
+    replacements: Vec<(String, String)>,
+    #[serde(default)]
+    typography: Vec<Typography>,
+    artifact_patterns: Vec<String>,
+    footnote_pattern: String,
+}
+
+impl Normalization {
+    fn of(profile: &Profile) -> Self {
+        Normalization {
+            replacements: profile.replacements.iter().map(|&(from, to)| (from.to_string(), to.to_string())).collect(),
+            typography: profile.typography.to_vec(),
+            artifact_patterns: profile.artifact_patterns.iter().map(|p| p.to_string()).collect(),
+            footnote_pattern: profile.footnote_pattern.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct RunDescriptor {
+    version: String,
+    source: Source,
+    pub(crate) options: ExtractOptions,
+    normalization: Normalization,
+}
+
+impl RunDescriptor {
+    /// Descriptor of a run over `pdf_path` with the given options and resolved profile.
+    pub(crate) fn new(pdf_path: &str, options: &ExtractOptions, profile: &Profile) -> Result<Self, OutputError> {
+        let mut options = options.clone();
+        options.profile = Some(profile.name.to_string());
+        options.answer_strategy = profile.answer_strategies.to_vec();
+        options.typography = profile.typography.to_vec();
+        options.element_order = Some(profile.element_order);
+        options.record = None;
+        options.replay = None;
+        Ok(RunDescriptor {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            source: Source {
+                path: pdf_path.to_string(),
+                sha256: sha256_hex(&fs::read(pdf_path)?),
+            },
+            options,
+            normalization: Normalization::of(profile),
+        })
+    }
+
+    pub(crate) fn load(path: &str) -> Result<Self, OutputError> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(|e| OutputError {
+            message: format!("Invalid run descriptor {}: {}", path, e),
+        })
+    }
+
+    pub(crate) fn save(&self, path: &str) -> Result<(), OutputError> {
+        let serialized = toml::to_string_pretty(self).map_err(|e| OutputError {
+            message: format!("Cannot serialize the run descriptor: {}", e),
+        })?;
+        fs::write(path, serialized)?;
+        Ok(())
+    }
+
+    /// Reasons why a run described by `current` may not reproduce this recorded run.
+    pub(crate) fn differences(&self, current: &RunDescriptor) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.version != current.version {
+            differences.push(format!("recorded with version {}, running {}", self.version, current.version));
+        }
+        if self.source.sha256 != current.source.sha256 {
+            differences.push(format!("source PDF {} differs from the recorded one", current.source.path));
+        }
+        if self.normalization != current.normalization {
+            differences.push("normalization steps of the profile changed since the recording".to_string());
+        }
+        differences
+    }
+}
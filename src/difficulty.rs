@@ -0,0 +1,54 @@
+use serde::Serialize;
+
+use crate::keywords;
+use crate::Question;
+
+// Heuristic difficulty estimate in [0, 1]. Long stems, several correct answers, negated questions and multi-sentence
+// scenario stems all make a question harder to answer under exam conditions.
+
+const LONG_STEM_WORDS: f64 = 60.0;
+const SCENARIO_SENTENCES: usize = 3;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Level {
+    Easy,
+    Medium,
+    Hard,
+}
+
+pub(crate) const LEVELS: [Level; 3] = [Level::Easy, Level::Medium, Level::Hard];
+
+pub(crate) fn score(question: &Question) -> f64 {
+    let words = question.text.split_whitespace().count() as f64;
+    let mut score = 0.35 * (words / LONG_STEM_WORDS).min(1.0);
+
+    if question.answers.len() > 1 {
+        score += 0.25;
+    }
+
+    if !keywords::detect(&question.text).is_empty() {
+        score += 0.2;
+    }
+
+    let sentences = question.text.split(['.', '?', '!']).filter(|s| !s.trim().is_empty()).count();
+    if sentences >= SCENARIO_SENTENCES {
+        score += 0.2;
+    }
+
+    score.min(1.0)
+}
+
+pub(crate) fn level(score: f64) -> Level {
+    if score < 0.25 {
+        Level::Easy
+    } else if score < 0.5 {
+        Level::Medium
+    } else {
+        Level::Hard
+    }
+}
+
+pub(crate) fn question_level(question: &Question) -> Level {
+    level(question.difficulty.unwrap_or_else(|| score(question)))
+}
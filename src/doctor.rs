@@ -0,0 +1,225 @@
+use clap::Args;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use crate::vite::{find_program, node_instructions, run_command, tool, PackageManager};
+use crate::OutputError;
+
+// `scaffold doctor`: checks the environment a scaffold needs before it runs, instead of finding out halfway through
+// an install. Every check prints `ok`, `warn` or `FAIL` with what it found, and a failing check says how to fix it:
+//
+// - Node.js and the package manager are installed and recent enough (`MIN_NODE`, `PackageManager::minimum`).
+// - The npm registry answers, through the proxy if one is configured, by asking it for the version of create-vite.
+// - The vite of an existing project in the directory is a supported major version (`MIN_VITE`).
+// - The directory the project goes in can be written to.
+
+/// Oldest Node.js the Vite templates run on
+pub(crate) const MIN_NODE: (u64, u64) = (18, 0);
+/// Oldest Vite major the generated configuration is written for
+const MIN_VITE: u64 = 5;
+/// Seconds to wait for the registry
+const REGISTRY_TIMEOUT: u64 = 30;
+const PROXY_VARIABLES: [&str; 5] = ["HTTPS_PROXY", "HTTP_PROXY", "https_proxy", "http_proxy", "npm_config_proxy"];
+
+#[derive(Args, Debug)]
+pub struct DoctorArgs {
+    /// Directory the project would be created in
+    #[arg(default_value = ".")]
+    directory: PathBuf,
+    /// Package manager to check; detected as scaffold does when omitted
+    #[arg(long, value_enum, env = "S4WM_PACKAGE_MANAGER")]
+    package_manager: Option<PackageManager>,
+}
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Result of one check, with the remediation when it did not pass
+struct Check {
+    status: Status,
+    found: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(found: String) -> Check {
+        Check {
+            status: Status::Ok,
+            found,
+            fix: None,
+        }
+    }
+
+    fn warn(found: String, fix: impl Into<String>) -> Check {
+        Check {
+            status: Status::Warn,
+            found,
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(found: String, fix: impl Into<String>) -> Check {
+        Check {
+            status: Status::Fail,
+            found,
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn print(&self) {
+        let label = match self.status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        println!("{:<6}{}", label, self.found);
+        if let Some(fix) = &self.fix {
+            println!("      fix: {}", fix);
+        }
+    }
+}
+
+/// Major and minor of a version such as `v20.11.1` or `9.15.0`.
+fn parse_version(text: &str) -> Option<(u64, u64)> {
+    let mut parts = text.trim().trim_start_matches('v').split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().and_then(|minor| minor.parse().ok()).unwrap_or(0);
+    Some((major, minor))
+}
+
+/// First line of what `program --version` prints, if it runs.
+fn version_of(program: &str) -> Option<String> {
+    let captured = run_command(tool(program).arg("--version"), false, Some(Duration::from_secs(10))).ok()?;
+    captured.status.filter(|status| status.success())?;
+    captured.stdout.lines().next().map(|line| line.trim().to_string())
+}
+
+/// The installed Node.js when it is too old for the templates, with its version.
+pub(crate) fn outdated_node() -> Option<String> {
+    let version = version_of("node")?;
+    (parse_version(&version)? < MIN_NODE).then_some(version)
+}
+
+fn check_node() -> Check {
+    let Some(path) = find_program("node") else {
+        return Check::fail("Node.js is not installed".to_string(), node_instructions());
+    };
+    let Some(version) = version_of("node") else {
+        let found = format!("{} does not run", path.display());
+        return Check::fail(found, "reinstall Node.js: ".to_string() + node_instructions());
+    };
+    let found = format!("Node.js {} ({})", version, path.display());
+    match parse_version(&version) {
+        Some(parsed) if parsed >= MIN_NODE => Check::ok(found),
+        _ => Check::fail(
+            format!("{}, {}.{} or later is needed", found, MIN_NODE.0, MIN_NODE.1),
+            "upgrade Node.js to its current LTS: ".to_string() + node_instructions(),
+        ),
+    }
+}
+
+fn check_manager(manager: PackageManager) -> Check {
+    let program = manager.program();
+    let install = format!("npm install --global {}@latest", program);
+    let Some(version) = version_of(program) else {
+        let found = format!("{} is not installed", program);
+        return Check::fail(found, format!("{} (scaffold also does it)", install));
+    };
+    let minimum = manager.minimum();
+    let found = format!("{} {}", program, version);
+    match parse_version(&version) {
+        Some(parsed) if parsed >= minimum => Check::ok(found),
+        _ => Check::fail(format!("{}, {}.{} or later is needed", found, minimum.0, minimum.1), install),
+    }
+}
+
+fn proxy() -> Option<String> {
+    PROXY_VARIABLES.iter().find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+fn check_registry() -> Check {
+    let registry = run_command(tool("npm").args(["config", "get", "registry"]), false, Some(Duration::from_secs(10)))
+        .ok()
+        .map(|captured| captured.stdout.trim().to_string())
+        .filter(|registry| !registry.is_empty())
+        .unwrap_or_else(|| "https://registry.npmjs.org/".to_string());
+    let through = proxy().map(|proxy| format!(" through {}", proxy)).unwrap_or_default();
+    let timeout = Duration::from_secs(REGISTRY_TIMEOUT);
+    let captured = match run_command(tool("npm").args(["view", "create-vite", "version"]), false, Some(timeout)) {
+        Ok(captured) => captured,
+        Err(e) => {
+            let found = format!("cannot ask the registry: {}", e);
+            return Check::fail(found, "install Node.js, which comes with npm");
+        }
+    };
+    match captured.status {
+        Some(status) if status.success() => {
+            Check::ok(format!("registry {}{} (create-vite {})", registry, through, captured.stdout.trim()))
+        }
+        status => {
+            let reason = match status {
+                Some(_) => captured.stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("").trim(),
+                None => "no answer",
+            };
+            Check::fail(
+                format!("registry {}{} cannot be reached: {}", registry, through, reason),
+                "set the proxy with `npm config set proxy <url>` and `npm config set https-proxy <url>`, or the \
+                 registry mirror with `npm config set registry <url>`; without network use `scaffold --offline`",
+            )
+        }
+    }
+}
+
+/// Vite of a project already in the directory, if any
+fn check_vite(directory: &Path) -> Option<Check> {
+    let path = directory.join("node_modules").join("vite").join("package.json");
+    let package: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+    let version = package["version"].as_str()?.to_string();
+    let found = format!("vite {} in {}", version, directory.display());
+    Some(match parse_version(&version) {
+        Some((major, _)) if major >= MIN_VITE => Check::ok(found),
+        _ => Check::warn(
+            format!("{}, the generated configuration is written for vite {} or later", found, MIN_VITE),
+            "upgrade it with `<package manager> add --save-dev vite@latest` in the project",
+        ),
+    })
+}
+
+fn check_writable(directory: &Path) -> Check {
+    let probe = directory.join(format!(".s4wm-doctor-{}", process::id()));
+    match fs::write(&probe, "") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Check::ok(format!("{} is writable", directory.display()))
+        }
+        Err(e) => Check::fail(
+            format!("cannot write to {}: {}", directory.display(), e),
+            "run scaffold in a directory you own, or give the project a path there",
+        ),
+    }
+}
+
+pub(crate) fn run(args: DoctorArgs) -> Result<(), Box<dyn Error>> {
+    let manager = args.package_manager.unwrap_or_else(PackageManager::detect);
+    let mut checks = vec![check_node(), check_manager(manager), check_registry()];
+    checks.extend(check_vite(&args.directory));
+    checks.push(check_writable(&args.directory));
+
+    for check in &checks {
+        check.print();
+    }
+    let failed = checks.iter().filter(|check| matches!(check.status, Status::Fail)).count();
+    if failed > 0 {
+        let message = format!("{} check(s) failed", failed);
+        return Err(OutputError { message }.into());
+    }
+    println!("Ready to scaffold");
+    Ok(())
+}
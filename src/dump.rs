@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::Path;
+
+use crate::fixtures::PAGE_SEPARATOR;
+use crate::parser::{LineClass, TraceLine};
+use crate::OutputError;
+
+// Intermediate artifacts for finding out why an extraction went wrong. `--dump-text` saves the text handed to the
+// parser. `--dump-intermediate` saves a directory with every stage the text goes through: `raw.txt` as extracted from
+// the PDF, `text.txt` after column reading (what the parser receives), `cleaned.txt` with the lines as the parser
+// matched them once normalized and cleaned, and `trace/page-NNN.txt` with the classification of every line of a page
+// and the pattern that decided it. Text files hold one page per form feed, like the fixture corpus.
+
+const RAW_FILE: &str = "raw.txt";
+const TEXT_FILE: &str = "text.txt";
+const CLEANED_FILE: &str = "cleaned.txt";
+const TRACE_DIR: &str = "trace";
+
+fn join_pages(pages: &[String]) -> String {
+    pages.join(&PAGE_SEPARATOR.to_string())
+}
+
+/// Writes the page texts to `path`.
+pub(crate) fn write_text(path: &Path, pages: &[String]) -> Result<(), OutputError> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, join_pages(pages))?;
+    Ok(())
+}
+
+/// Writes the text stages and the per-page parse trace into `dir`.
+pub(crate) fn write_intermediate(
+    dir: &Path,
+    raw: &[String],
+    pages: &[String],
+    trace: &[TraceLine],
+) -> Result<(), OutputError> {
+    let trace_dir = dir.join(TRACE_DIR);
+    fs::create_dir_all(&trace_dir)?;
+    fs::write(dir.join(RAW_FILE), join_pages(raw))?;
+    fs::write(dir.join(TEXT_FILE), join_pages(pages))?;
+
+    let mut cleaned = vec![String::new(); pages.len()];
+    let mut traces = vec![String::new(); pages.len()];
+    for line in trace {
+        let Some(index) = line.page.and_then(|page| page.checked_sub(1)).filter(|&index| index < pages.len()) else {
+            continue;
+        };
+        if line.class != LineClass::Blank {
+            cleaned[index].push_str(&line.text);
+            cleaned[index].push('\n');
+        }
+        traces[index].push_str(&format!("{}\n", line));
+    }
+    fs::write(dir.join(CLEANED_FILE), join_pages(&cleaned))?;
+    for (index, page_trace) in traces.iter().enumerate() {
+        fs::write(trace_dir.join(format!("page-{:03}.txt", index + 1)), page_trace)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,422 @@
+use clap::Args;
+use std::env;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::slice;
+
+use crate::crypt::Crypto;
+use crate::history::{self, HistoryArgs};
+use crate::kind::{truth_value, QuestionKind};
+use crate::lifecycle::{self, save_bank};
+use crate::progress::Console;
+use crate::validate::{self, ValidationOptions};
+use crate::{open_bank, OutputError, Question};
+
+// Quick corrections of a single question without opening the whole bank, where a stray comma breaks the JSON. The
+// flags set the stem, a choice or the answer; `--open` shows the question as YAML in $VISUAL or $EDITOR instead, and
+// a question that does not read back (bad YAML, an answer naming a missing choice, an empty stem) is shown again
+// with the error on top until it does or the file is left unchanged. The file is created under a random name,
+// readable by the user only, and an encrypted bank is only opened in it when `--plaintext-temp` accepts the decrypted
+// question sitting on disk while it is edited. The number and id of the question are kept, so references to it stay
+// valid. The rest of the validation runs on the saved bank and reports its findings about the question.
+
+const HEADER: &str = "# Edit the question and save to apply, or leave the file unchanged to cancel.
+# The number and id are kept as they are.
+";
+const ERROR_PREFIX: &str = "# Error: ";
+
+#[derive(Args, Debug)]
+pub struct EditArgs {
+    /// Question bank to update
+    input: String,
+    /// Id of the question, or a unique prefix of it
+    #[arg(long)]
+    id: String,
+    /// New stem
+    #[arg(long, value_name = "TEXT")]
+    set_text: Option<String>,
+    /// New answer: choice letters (comma-separated), the letters in sequence for ordering questions, or true or false
+    #[arg(long, value_name = "LETTERS", value_delimiter = ',')]
+    set_answer: Option<Vec<String>>,
+    /// New text of a choice, LETTER=TEXT; a new letter adds a choice and an empty text removes it
+    #[arg(long, value_name = "LETTER=TEXT")]
+    set_choice: Vec<String>,
+    /// Edit the question as YAML in $VISUAL or $EDITOR
+    #[arg(long, conflicts_with_all = ["set_text", "set_answer", "set_choice"])]
+    open: bool,
+    /// With --open on an encrypted bank, accept that the question is written decrypted to a temporary file while it
+    /// is edited
+    #[arg(long, requires = "open")]
+    plaintext_temp: bool,
+    /// Write the updated bank here instead of updating the input in place
+    #[arg(short, long)]
+    output: Option<String>,
+    #[command(flatten)]
+    history: HistoryArgs,
+}
+
+/// The key of the choice `letter` names, ignoring case.
+fn choice_key(question: &Question, letter: &str) -> Result<String, String> {
+    let letter = letter.trim();
+    question
+        .choices
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case(letter))
+        .cloned()
+        .ok_or_else(|| format!("No choice {}", letter))
+}
+
+fn set_answer(question: &mut Question, answer: &[String]) -> Result<(), String> {
+    match question.kind {
+        QuestionKind::Matching => Err("Matching questions take their answer as pairs, edit them with --open".into()),
+        QuestionKind::TrueFalse => {
+            let [value] = answer else {
+                return Err("A true/false question takes a single answer".into());
+            };
+            let truth = match truth_value(value) {
+                Some(truth) => truth,
+                None => {
+                    let letter = choice_key(question, value)?;
+                    let text = &question.choices[&letter];
+                    truth_value(text).ok_or_else(|| format!("Choice {} is not true or false", letter))?
+                }
+            };
+            question.truth = Some(truth);
+            let letters = question.choices.iter().filter(|(_, text)| truth_value(text) == Some(truth));
+            question.answers = letters.map(|(letter, _)| letter.clone()).collect();
+            Ok(())
+        }
+        QuestionKind::Ordering => {
+            let order = answer.iter().map(|letter| choice_key(question, letter)).collect::<Result<Vec<_>, _>>()?;
+            if (1..order.len()).any(|i| order[..i].contains(&order[i])) {
+                return Err("The order names a choice twice".into());
+            }
+            question.order = order;
+            Ok(())
+        }
+        QuestionKind::MultipleChoice | QuestionKind::MultipleSelect => {
+            let mut answers =
+                answer.iter().map(|letter| choice_key(question, letter)).collect::<Result<Vec<_>, _>>()?;
+            answers.sort();
+            answers.dedup();
+            if answers.is_empty() {
+                return Err("Give at least one answer".into());
+            }
+            question.kind = if answers.len() > 1 { QuestionKind::MultipleSelect } else { QuestionKind::MultipleChoice };
+            question.correct_answers = Some(answers.len());
+            question.answers = answers;
+            Ok(())
+        }
+    }
+}
+
+/// Checks that make an edited question unusable and block the save, here and in the editor of `serve`.
+pub(crate) fn check(question: &Question) -> Result<(), String> {
+    if question.text.trim().is_empty() {
+        return Err("The stem cannot be empty".into());
+    }
+    // true/false questions imported without choices answer with true or false
+    let known = |letter: &&String| question.choices.is_empty() || question.choices.contains_key(*letter);
+    let mut letters = question.answers.iter().chain(&question.order).chain(question.feedback.keys());
+    if let Some(unknown) = letters.find(|letter| !known(letter)) {
+        return Err(format!("No choice {}", unknown));
+    }
+    if let Some(pair) = question.pairs.iter().find(|pair| !question.choices.contains_key(&pair.choice)) {
+        return Err(format!("No choice {}", pair.choice));
+    }
+    let premise = |label: &String| question.premises.iter().any(|premise| &premise.label == label);
+    if let Some(pair) = question.pairs.iter().find(|pair| !premise(&pair.premise)) {
+        return Err(format!("No premise {}", pair.premise));
+    }
+    Ok(())
+}
+
+/// Applies the `--set-*` flags to the question.
+fn apply(question: &mut Question, args: &EditArgs) -> Result<(), String> {
+    if let Some(text) = &args.set_text {
+        question.text = text.trim().to_string();
+    }
+    for choice in &args.set_choice {
+        let Some((letter, text)) = choice.split_once('=') else {
+            return Err(format!("--set-choice takes LETTER=TEXT, not {}", choice));
+        };
+        let letter = choice_key(question, letter).unwrap_or_else(|_| letter.trim().to_uppercase());
+        match text.trim() {
+            "" => question.choices.remove(&letter),
+            text => question.choices.insert(letter, text.to_string()),
+        };
+    }
+    if let Some(answer) = &args.set_answer {
+        set_answer(question, answer)?;
+    }
+    check(question)
+}
+
+/// The question as YAML, its mappings sorted by key.
+fn to_yaml(question: &Question) -> Result<String, Box<dyn Error>> {
+    Ok(serde_yaml::to_string(&serde_json::to_value(question)?)?)
+}
+
+fn from_yaml(text: &str, original: &Question) -> Result<Question, String> {
+    let mut question: Question = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+    question.number = original.number.clone();
+    question.id = original.id.clone();
+    question.text = question.text.trim().to_string();
+    check(&question)?;
+    Ok(question)
+}
+
+fn run_editor(path: &Path) -> Result<(), Box<dyn Error>> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    // "code --wait" and the like
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program).args(words).arg(path).status()?;
+    if !status.success() {
+        return Err(format!("{} failed ({}), the question was left unchanged", editor, status).into());
+    }
+    Ok(())
+}
+
+/// Creates a file only the user can read and write, failing when the path exists rather than following a link
+/// someone else placed there.
+#[cfg(unix)]
+fn create_private(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+/// Lets the user edit the question in their editor until it reads back. None when they left it unchanged.
+fn open(question: &Question, path: &Path) -> Result<Option<Question>, Box<dyn Error>> {
+    let mut shown = format!("{}{}", HEADER, to_yaml(question)?);
+    let mut failed = false;
+    loop {
+        // the file created by `run` is rewritten in place, keeping its permissions
+        OpenOptions::new().write(true).truncate(true).open(path)?.write_all(shown.as_bytes())?;
+        run_editor(path)?;
+        let edited = fs::read_to_string(path)?;
+        if edited == shown {
+            if failed {
+                return Err(OutputError::from("The question did not read back, it was left unchanged").into());
+            }
+            return Ok(None);
+        }
+        match from_yaml(&edited, question) {
+            Ok(question) => return Ok(Some(question)),
+            Err(message) => {
+                let body: Vec<&str> = edited.lines().skip_while(|line| line.starts_with(ERROR_PREFIX)).collect();
+                let errors: String = message.lines().map(|line| format!("{}{}\n", ERROR_PREFIX, line)).collect();
+                shown = format!("{}{}\n", errors, body.join("\n"));
+                failed = true;
+            }
+        }
+    }
+}
+
+pub(crate) fn run(args: EditArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    let index = lifecycle::find(&bank.questions, &args.id)?;
+    let before = bank.questions[index].clone();
+
+    let edited = if args.open {
+        if crypto.is_encrypting() && !args.plaintext_temp {
+            return Err(OutputError::from(
+                "The bank is encrypted and --open writes the question decrypted to a temporary file, pass \
+                 --plaintext-temp to accept it or edit with --set-text, --set-choice and --set-answer",
+            )
+            .into());
+        }
+        let path = env::temp_dir().join(format!("s4wm-edit-{:016x}.yaml", rand::random::<u64>()));
+        create_private(&path)?;
+        let edited = open(&before, &path);
+        let _ = fs::remove_file(&path);
+        edited?
+    } else {
+        if args.set_text.is_none() && args.set_answer.is_none() && args.set_choice.is_empty() {
+            return Err(OutputError::from("Pass --set-text, --set-choice, --set-answer or --open").into());
+        }
+        let mut question = before.clone();
+        apply(&mut question, &args).map_err(|message| OutputError { message })?;
+        Some(question)
+    };
+    let Some(edited) = edited.filter(|edited| to_yaml(edited).ok() != to_yaml(&before).ok()) else {
+        println!("No changes to question {}", before.number);
+        return Ok(());
+    };
+    bank.questions[index] = edited;
+
+    save_bank(&bank, args.output.as_deref().unwrap_or(&args.input), &crypto)?;
+    let after = slice::from_ref(&bank.questions[index]);
+    history::record(&args.history, "edit", &args.input, slice::from_ref(&before), after, &crypto)?;
+    println!("Updated question {}", before.number);
+    let mut report = validate::validate(&bank, &ValidationOptions::default());
+    report.findings.retain(|finding| finding.question.as_deref() == Some(before.number.as_str()));
+    report.report(&mut Console::new());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MatchPair, Statement};
+
+    fn new_question(kind: QuestionKind, choices: &[(&str, &str)], answers: &[&str]) -> Question {
+        Question {
+            number: "7".to_string(),
+            id: Some("5f3a9c".to_string()),
+            kind,
+            text: "Which transaction shows the warehouse monitor?".to_string(),
+            choices: choices.iter().map(|(letter, text)| (letter.to_string(), text.to_string())).collect(),
+            answers: answers.iter().map(|answer| answer.to_string()).collect(),
+            ..Question::default()
+        }
+    }
+
+    fn letters(letters: &[&str]) -> Vec<String> {
+        letters.iter().map(|letter| letter.to_string()).collect()
+    }
+
+    fn args(set_text: Option<&str>, set_choice: &[&str], set_answer: Option<&[&str]>) -> EditArgs {
+        EditArgs {
+            input: "questions.json".to_string(),
+            id: "5f3a".to_string(),
+            set_text: set_text.map(str::to_string),
+            set_answer: set_answer.map(letters),
+            set_choice: letters(set_choice),
+            open: false,
+            plaintext_temp: false,
+            output: None,
+            history: HistoryArgs::default(),
+        }
+    }
+
+    const TRUE_FALSE: &[(&str, &str)] = &[("A", "True"), ("B", "False")];
+    const THREE: &[(&str, &str)] = &[("A", "/SCWM/MON"), ("B", "/SCWM/PRDI"), ("C", "/SCWM/WAVE")];
+
+    #[test]
+    fn true_false_answers_by_letter_or_word() {
+        let mut question = new_question(QuestionKind::TrueFalse, TRUE_FALSE, &["A"]);
+        set_answer(&mut question, &letters(&["b"])).unwrap();
+        assert_eq!((question.truth, question.answers.clone()), (Some(false), letters(&["B"])));
+        set_answer(&mut question, &letters(&["TRUE"])).unwrap();
+        assert_eq!((question.truth, question.answers.clone()), (Some(true), letters(&["A"])));
+
+        let both = set_answer(&mut question, &letters(&["A", "B"])).unwrap_err();
+        assert_eq!(both, "A true/false question takes a single answer");
+        assert_eq!(set_answer(&mut question, &letters(&["C"])).unwrap_err(), "No choice C");
+        question.choices.insert("C".to_string(), "Sometimes".to_string());
+        assert_eq!(set_answer(&mut question, &letters(&["C"])).unwrap_err(), "Choice C is not true or false");
+
+        // imported without choices, the answer is the truth alone
+        let mut bare = new_question(QuestionKind::TrueFalse, &[], &[]);
+        set_answer(&mut bare, &letters(&["false"])).unwrap();
+        assert_eq!(bare.truth, Some(false));
+        assert!(bare.answers.is_empty());
+    }
+
+    #[test]
+    fn orders_name_every_choice_once() {
+        let mut question = new_question(QuestionKind::Ordering, THREE, &[]);
+        set_answer(&mut question, &letters(&["c", "A", "b"])).unwrap();
+        assert_eq!(question.order, ["C", "A", "B"]);
+        assert_eq!(set_answer(&mut question, &letters(&["A", "a"])).unwrap_err(), "The order names a choice twice");
+        assert_eq!(set_answer(&mut question, &letters(&["A", "D"])).unwrap_err(), "No choice D");
+        assert_eq!(question.order, ["C", "A", "B"]);
+    }
+
+    #[test]
+    fn answers_switch_between_single_and_multiple_select() {
+        let mut question = new_question(QuestionKind::MultipleChoice, THREE, &["A"]);
+        set_answer(&mut question, &letters(&["c", "a", "C"])).unwrap();
+        assert_eq!(question.kind, QuestionKind::MultipleSelect);
+        assert_eq!((question.answers.clone(), question.correct_answers), (letters(&["A", "C"]), Some(2)));
+        set_answer(&mut question, &letters(&["B"])).unwrap();
+        assert_eq!(question.kind, QuestionKind::MultipleChoice);
+        assert_eq!((question.answers.clone(), question.correct_answers), (letters(&["B"]), Some(1)));
+        assert_eq!(set_answer(&mut question, &[]).unwrap_err(), "Give at least one answer");
+
+        let mut matching = new_question(QuestionKind::Matching, THREE, &[]);
+        assert!(set_answer(&mut matching, &letters(&["A"])).unwrap_err().starts_with("Matching questions"));
+    }
+
+    #[test]
+    fn checks_refuse_dangling_letters() {
+        let statement = |label: &str| Statement {
+            label: label.to_string(),
+            text: "Groups items".to_string(),
+        };
+        let pair = |premise: &str, choice: &str| MatchPair {
+            premise: premise.to_string(),
+            choice: choice.to_string(),
+        };
+        assert!(check(&new_question(QuestionKind::MultipleChoice, THREE, &["A"])).is_ok());
+        let mut blank = new_question(QuestionKind::MultipleChoice, THREE, &["A"]);
+        blank.text = " ".to_string();
+        assert_eq!(check(&blank).unwrap_err(), "The stem cannot be empty");
+        assert_eq!(check(&new_question(QuestionKind::MultipleChoice, THREE, &["D"])).unwrap_err(), "No choice D");
+        let mut feedback = new_question(QuestionKind::MultipleChoice, THREE, &["A"]);
+        feedback.feedback.insert("E".to_string(), "Not a transaction".to_string());
+        assert_eq!(check(&feedback).unwrap_err(), "No choice E");
+        let mut order = new_question(QuestionKind::Ordering, THREE, &[]);
+        order.order = letters(&["A", "F"]);
+        assert_eq!(check(&order).unwrap_err(), "No choice F");
+        // true/false questions imported without choices answer with true or false
+        assert!(check(&new_question(QuestionKind::TrueFalse, &[], &["true"])).is_ok());
+
+        let mut matching = new_question(QuestionKind::Matching, THREE, &[]);
+        matching.premises = vec![statement("1")];
+        matching.pairs = vec![pair("1", "A")];
+        assert!(check(&matching).is_ok());
+        matching.pairs = vec![pair("1", "G")];
+        assert_eq!(check(&matching).unwrap_err(), "No choice G");
+        matching.pairs = vec![pair("2", "A")];
+        assert_eq!(check(&matching).unwrap_err(), "No premise 2");
+    }
+
+    #[test]
+    fn flags_set_the_stem_and_the_choices() {
+        let mut question = new_question(QuestionKind::MultipleChoice, THREE, &["A"]);
+        let stem = "  Which transaction monitors the warehouse? ";
+        let edit = args(Some(stem), &["c=", "a=/SCWM/MON2", "d=/SCWM/RFUI"], None);
+        apply(&mut question, &edit).unwrap();
+        assert_eq!(question.text, "Which transaction monitors the warehouse?");
+        let expected = [("A", "/SCWM/MON2"), ("B", "/SCWM/PRDI"), ("D", "/SCWM/RFUI")];
+        assert_eq!(question.choices, expected.iter().map(|(l, t)| (l.to_string(), t.to_string())).collect());
+
+        // removing the answer's choice leaves the question unusable
+        assert_eq!(apply(&mut question.clone(), &args(None, &["A="], None)).unwrap_err(), "No choice A");
+        assert!(apply(&mut question.clone(), &args(None, &["A= "], Some(&["B"]))).is_ok());
+        let malformed = apply(&mut question.clone(), &args(None, &["A"], None)).unwrap_err();
+        assert_eq!(malformed, "--set-choice takes LETTER=TEXT, not A");
+        assert_eq!(apply(&mut question, &args(Some(" "), &[], None)).unwrap_err(), "The stem cannot be empty");
+    }
+
+    #[test]
+    fn yaml_edits_keep_the_number_and_id() {
+        let original = new_question(QuestionKind::MultipleChoice, THREE, &["A"]);
+        let yaml = to_yaml(&original).unwrap();
+        let edited = yaml
+            .replace("number: '7'", "number: '99'")
+            .replace("id: 5f3a9c", "id: ffffff")
+            .replace("text: Which transaction shows", "text: '  Which transaction opens");
+        let edited = edited.replace("monitor?", "monitor?  '");
+        let question = from_yaml(&format!("{}{}", HEADER, edited), &original).unwrap();
+        assert_eq!((question.number.as_str(), question.id.as_deref()), ("7", Some("5f3a9c")));
+        assert_eq!(question.text, "Which transaction opens the warehouse monitor?");
+
+        assert!(from_yaml("text: [unclosed", &original).is_err());
+        assert_eq!(from_yaml(&yaml.replace("- A", "- D"), &original).unwrap_err(), "No choice D");
+    }
+}
@@ -0,0 +1,232 @@
+use clap::Args;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+use crate::crypt::Crypto;
+use crate::difficulty::{self, Level, LEVELS};
+use crate::lifecycle;
+use crate::{open_bank, save_to_json, Bank, OutputError, Question};
+
+// Mock exam generation: draws a random subset of a bank with an equal share of easy, medium and hard questions.
+// Levels that run short are topped up from whatever is left so the exam always reaches the requested size when the
+// bank is large enough.
+//
+// With `--blueprint` the exam follows the topic weighting SAP publishes for the certification instead. Dumps do not
+// mark topics, so the blueprint gives each topic the terms its questions use; a question belongs to the topic whose
+// terms occur most often in its stem and choices. Every topic gets its share of the exam, drawn by difficulty within
+// the topic, and a topic without enough questions fails the run rather than being topped up from the others.
+
+#[derive(Args, Debug)]
+pub struct ExamArgs {
+    /// Question bank to draw from
+    input: String,
+    /// Where to write the mock exam bank
+    output: String,
+    /// Number of questions in the exam
+    #[arg(short = 'n', long, default_value_t = 80)]
+    count: usize,
+    /// Seed for a reproducible selection
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Also draw retired and superseded questions
+    #[arg(long)]
+    include_retired: bool,
+    /// TOML file of topics with their weight and terms, to draw the exam per topic
+    #[arg(long)]
+    blueprint: Option<String>,
+}
+
+/// Topic weighting of a certification, e.g. "Inbound Processing > 12%"
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Blueprint {
+    #[serde(rename = "topic")]
+    pub(crate) topics: Vec<Topic>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Topic {
+    pub(crate) name: String,
+    /// Share of the exam; the weights are relative, they need not add up to 100
+    weight: f64,
+    /// Words and phrases its questions use, matched whole and ignoring case
+    keywords: Vec<String>,
+}
+
+impl Blueprint {
+    pub(crate) fn load(path: &str) -> Result<Blueprint, Box<dyn Error>> {
+        let blueprint: Blueprint = toml::from_str(&fs::read_to_string(path)?).map_err(|e| OutputError {
+            message: format!("Invalid blueprint {}: {}", path, e),
+        })?;
+        if blueprint.topics.is_empty() {
+            return Err(OutputError::from("The blueprint has no [[topic]]").into());
+        }
+        for topic in &blueprint.topics {
+            if topic.keywords.is_empty() || topic.weight.is_nan() || topic.weight <= 0.0 {
+                let message = format!("Topic \"{}\" needs keywords and a positive weight", topic.name);
+                return Err(OutputError { message }.into());
+            }
+        }
+        Ok(blueprint)
+    }
+
+    /// Number of questions of each topic in an exam of `count`.
+    fn quotas(&self, count: usize) -> Vec<usize> {
+        apportion(&self.topics.iter().map(|topic| topic.weight).collect::<Vec<_>>(), count)
+    }
+
+    /// Questions of each topic; a question goes to the topic whose terms it mentions most, the first one on a tie,
+    /// and to none when it mentions none.
+    pub(crate) fn classify<'a>(&self, questions: &'a [Question]) -> Result<Vec<Vec<&'a Question>>, Box<dyn Error>> {
+        let patterns = self
+            .topics
+            .iter()
+            .map(|topic| {
+                let terms: Vec<String> = topic.keywords.iter().map(|keyword| regex::escape(keyword)).collect();
+                Regex::new(&format!(r"(?i)\b(?:{})\b", terms.join("|")))
+            })
+            .collect::<Result<Vec<Regex>, _>>()?;
+        let mut topics = vec![Vec::new(); self.topics.len()];
+        for question in questions {
+            let mut text = question.text.clone();
+            for choice in question.choices.values() {
+                text.push('\n');
+                text.push_str(choice);
+            }
+            let hits = patterns.iter().map(|pattern| pattern.find_iter(&text).count());
+            // max_by_key keeps the last maximum, so compare on the reversed index too
+            let best = hits.enumerate().filter(|&(_, hits)| hits > 0).max_by_key(|&(i, hits)| (hits, usize::MAX - i));
+            if let Some((i, _)) = best {
+                topics[i].push(question);
+            }
+        }
+        Ok(topics)
+    }
+}
+
+/// Splits `count` in proportion to `weights`, rounding by largest remainder so the parts add up.
+pub(crate) fn apportion(weights: &[f64], count: usize) -> Vec<usize> {
+    let total: f64 = weights.iter().sum();
+    let shares: Vec<f64> = weights.iter().map(|weight| weight / total * count as f64).collect();
+    let mut quotas: Vec<usize> = shares.iter().map(|share| share.floor() as usize).collect();
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    by_remainder.sort_by(|&a, &b| (shares[b] - shares[b].floor()).total_cmp(&(shares[a] - shares[a].floor())));
+    let missing = count - quotas.iter().sum::<usize>();
+    for &i in by_remainder.iter().take(missing) {
+        quotas[i] += 1;
+    }
+    quotas
+}
+
+pub(crate) fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Picks `count` questions, stratified by difficulty level.
+pub(crate) fn sample_by_difficulty(questions: &[Question], count: usize, rng: &mut StdRng) -> Vec<Question> {
+    let mut by_level: BTreeMap<Level, Vec<&Question>> = BTreeMap::new();
+    for question in questions {
+        by_level.entry(difficulty::question_level(question)).or_default().push(question);
+    }
+
+    let mut selected = Vec::with_capacity(count);
+    let mut leftovers = Vec::new();
+    for (i, level) in LEVELS.iter().enumerate() {
+        // spread the remainder over the first levels
+        let target = count / LEVELS.len() + usize::from(i < count % LEVELS.len());
+        let mut pool = by_level.remove(level).unwrap_or_default();
+        pool.shuffle(rng);
+        let take = target.min(pool.len());
+        leftovers.extend(pool.split_off(take));
+        selected.extend(pool);
+    }
+
+    leftovers.shuffle(rng);
+    let missing = count.saturating_sub(selected.len());
+    selected.extend(leftovers.into_iter().take(missing));
+    selected.shuffle(rng);
+
+    selected.into_iter().cloned().collect()
+}
+
+/// Picks the share of every topic of the blueprint, failing with the topics that have too few questions.
+fn sample_by_blueprint(
+    blueprint: &Blueprint,
+    questions: &[Question],
+    count: usize,
+    rng: &mut StdRng,
+) -> Result<Vec<Question>, Box<dyn Error>> {
+    let topics = blueprint.classify(questions)?;
+    let quotas = blueprint.quotas(count);
+    let unmatched = questions.len() - topics.iter().map(Vec::len).sum::<usize>();
+    if unmatched > 0 {
+        println!("{} questions match no topic of the blueprint and are left out", unmatched);
+    }
+
+    let short: Vec<String> = blueprint
+        .topics
+        .iter()
+        .zip(&topics)
+        .zip(&quotas)
+        .filter(|((_, pool), &quota)| pool.len() < quota)
+        .map(|((topic, pool), quota)| {
+            format!(
+                "  {}: {} needed, {} in the bank (keywords: {})",
+                topic.name,
+                quota,
+                pool.len(),
+                topic.keywords.join(", ")
+            )
+        })
+        .collect();
+    if !short.is_empty() {
+        let message = format!(
+            "Not enough questions for the blueprint, add keywords to these topics or draw a smaller exam with -n:\n{}",
+            short.join("\n")
+        );
+        return Err(OutputError { message }.into());
+    }
+
+    let mut selected = Vec::with_capacity(count);
+    for (pool, quota) in topics.into_iter().zip(quotas) {
+        let pool: Vec<Question> = pool.into_iter().cloned().collect();
+        selected.extend(sample_by_difficulty(&pool, quota, rng));
+    }
+    selected.shuffle(rng);
+    Ok(selected)
+}
+
+pub(crate) fn run(args: ExamArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    if !args.include_retired {
+        bank = lifecycle::in_use(&bank);
+    }
+    if bank.questions.len() < args.count {
+        println!(
+            "Bank only has {} questions, the exam will contain all of them",
+            bank.questions.len()
+        );
+    }
+
+    let mut rng = seeded_rng(args.seed);
+    let questions = match &args.blueprint {
+        Some(path) => sample_by_blueprint(&Blueprint::load(path)?, &bank.questions, args.count, &mut rng)?,
+        None => sample_by_difficulty(&bank.questions, args.count, &mut rng),
+    };
+    let exam = Bank {
+        meta: bank.meta.clone(),
+        questions,
+    };
+    save_to_json(&exam, &args.output, &crypto)?;
+    Ok(())
+}
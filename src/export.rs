@@ -0,0 +1,332 @@
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fmt::Write as _;
+
+use crate::anki;
+use crate::crypt::Crypto;
+use crate::keywords;
+use crate::kind::QuestionKind;
+use crate::lifecycle;
+use crate::lms;
+use crate::plugin::{self, Kind, Plugin};
+use crate::text::bidi_isolate;
+use crate::{open_bank, Bank, ExtractOptions, OutputError, Question};
+
+// Exports of a saved question bank. The human-readable formats are bidi-safe: HTML marks every text element with
+// `dir="auto"` so the browser picks the direction per paragraph, Markdown wraps RTL text in directional isolates.
+// The LMS formats live in `lms`, the Anki flashcards in `anki`.
+//
+// Every format is an `Exporter`. The `Registry` holds the built-in ones and the exporter plugins of the plugin
+// directory (see `plugin`), and resolves the names given to `--format` and `export --format`.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Markdown,
+    Html,
+    /// Moodle GIFT
+    Gift,
+    /// IMS QTI 1.2
+    Qti,
+    /// Anki cloze flashcards, the correct answer hidden in the stem
+    Anki,
+}
+
+impl Format {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Html => "html",
+            Format::Gift => "gift",
+            Format::Qti => "xml",
+            Format::Anki => "txt",
+        }
+    }
+}
+
+/// A format a bank can be exported to
+pub(crate) trait Exporter {
+    /// Name of the format, as given to `--format`
+    fn name(&self) -> &str;
+    /// Extension of the exported file, without the dot
+    fn extension(&self) -> &str;
+    fn render(&self, bank: &Bank) -> Result<String, OutputError>;
+}
+
+impl Exporter for Format {
+    fn name(&self) -> &str {
+        match self {
+            Format::Markdown => "markdown",
+            Format::Html => "html",
+            Format::Gift => "gift",
+            Format::Qti => "qti",
+            Format::Anki => "anki",
+        }
+    }
+
+    fn extension(&self) -> &str {
+        Format::extension(*self)
+    }
+
+    fn render(&self, bank: &Bank) -> Result<String, OutputError> {
+        Ok(render(bank, *self))
+    }
+}
+
+impl Exporter for Plugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn extension(&self) -> &str {
+        self.extension.as_deref().unwrap_or_default()
+    }
+
+    fn render(&self, bank: &Bank) -> Result<String, OutputError> {
+        let response = self.call("render", json!({ "bank": bank }))?;
+        match response.get("content").and_then(Value::as_str) {
+            Some(content) => Ok(content.to_string()),
+            None => Err(OutputError {
+                message: format!("plugin {} answered without content", self.name),
+            }),
+        }
+    }
+}
+
+/// The formats known to a run: the built-in ones, then the exporter plugins
+pub(crate) struct Registry {
+    exporters: Vec<Box<dyn Exporter>>,
+    /// Where every exporter comes from, for listings
+    origins: Vec<String>,
+}
+
+impl Registry {
+    pub(crate) fn load(options: &ExtractOptions) -> Result<Self, OutputError> {
+        let mut registry = Registry {
+            exporters: Vec::new(),
+            origins: Vec::new(),
+        };
+        for format in Format::value_variants() {
+            registry.exporters.push(Box::new(*format));
+            registry.origins.push("built-in".to_string());
+        }
+        for plugin in plugin::discover(options)?.into_iter().filter(|plugin| plugin.kind == Kind::Exporter) {
+            if registry.exporters.iter().any(|exporter| exporter.name() == plugin.name) {
+                return Err(OutputError {
+                    message: format!("Exporter plugin {} has the name of another format", plugin.name),
+                });
+            }
+            registry.origins.push(plugin.runtime());
+            registry.exporters.push(Box::new(plugin));
+        }
+        Ok(registry)
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Result<&dyn Exporter, OutputError> {
+        match self.exporters.iter().find(|exporter| exporter.name().eq_ignore_ascii_case(name)) {
+            Some(exporter) => Ok(exporter.as_ref()),
+            None => {
+                let names: Vec<&str> = self.exporters.iter().map(|exporter| exporter.name()).collect();
+                Err(OutputError {
+                    message: format!("Unknown format {}, known formats: {}", name, names.join(", ")),
+                })
+            }
+        }
+    }
+
+    /// Name, extension and origin of every exporter.
+    pub(crate) fn describe(&self) -> Vec<(&str, &str, &str)> {
+        let exporters = self.exporters.iter().zip(&self.origins);
+        exporters.map(|(exporter, origin)| (exporter.name(), exporter.extension(), origin.as_str())).collect()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Question bank to export
+    input: String,
+    /// Output file
+    output: String,
+    /// Output format: markdown, html, gift, qti, anki or the name of an exporter plugin
+    #[arg(long, default_value = "markdown")]
+    format: String,
+    /// Also export retired and superseded questions
+    #[arg(long)]
+    include_retired: bool,
+}
+
+/// Choices of a question as (letter, text) pairs in letter order.
+pub(crate) fn sorted_choices(question: &Question) -> Vec<(&String, &String)> {
+    let mut choices: Vec<_> = question.choices.iter().collect();
+    choices.sort();
+    choices
+}
+
+/// Renders the answer in the representation used by the question kind
+fn answer_text(question: &Question) -> Option<String> {
+    match question.kind {
+        QuestionKind::TrueFalse => question.truth.map(|t| String::from(if t { "True" } else { "False" })),
+        QuestionKind::Ordering if !question.order.is_empty() => Some(question.order.join(" → ")),
+        QuestionKind::Matching if !question.pairs.is_empty() => Some(
+            question
+                .pairs
+                .iter()
+                .map(|pair| format!("{} → {}", pair.premise, pair.choice))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        _ if !question.answers.is_empty() => Some(question.answers.join(", ")),
+        _ => None,
+    }
+}
+
+/// The extra fields of a question with their values as text, lists joined by commas
+pub(crate) fn extra_fields(question: &Question) -> Vec<(&str, String)> {
+    let text = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    question
+        .extra
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(name, value)| {
+            let value = match value {
+                Value::Array(items) => items.iter().map(text).collect::<Vec<_>>().join(", "),
+                other => text(other),
+            };
+            (name.as_str(), value)
+        })
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub(crate) fn to_markdown(bank: &Bank) -> String {
+    let mut out = String::new();
+    if let Some(title) = &bank.meta.title {
+        let _ = writeln!(out, "# {}\n", bidi_isolate(title));
+    }
+    for question in &bank.questions {
+        write_markdown_question(&mut out, question);
+        out.push('\n');
+    }
+    out
+}
+
+/// Answers the forum community voted for with their share of the votes, most voted first
+fn community_text(question: &Question) -> Option<String> {
+    let community = question.community_answer.as_ref()?;
+    let votes: Vec<String> =
+        community.votes.iter().map(|vote| format!("{} ({}%)", vote.answers.join(", "), vote.percent)).collect();
+    Some(votes.join(", "))
+}
+
+/// Markdown section of a single question, headed by its number.
+pub(crate) fn write_markdown_question(out: &mut String, question: &Question) {
+    let synthetic = if question.variant_of.is_some() { " (synthetic variant)" } else { "" };
+    let _ = writeln!(out, "## Question {}{}\n", question.number, synthetic);
+    let stem = keywords::highlight(&question.text, &question.keywords, "**", "**");
+    let _ = writeln!(out, "{}\n", bidi_isolate(&stem));
+    if !question.statements.is_empty() {
+        for statement in &question.statements {
+            let _ = writeln!(out, "{}. {}  ", statement.label, bidi_isolate(&statement.text));
+        }
+        out.push('\n');
+    }
+    for premise in &question.premises {
+        let _ = writeln!(out, "{}) {}  ", premise.label, bidi_isolate(&premise.text));
+    }
+    if !question.premises.is_empty() {
+        out.push('\n');
+    }
+    for (letter, choice) in sorted_choices(question) {
+        let _ = writeln!(out, "- **{}.** {}", letter, bidi_isolate(choice));
+    }
+    if let Some(answer) = answer_text(question) {
+        let _ = writeln!(out, "\n**Answer:** {}", answer);
+    }
+    if let Some(votes) = community_text(question) {
+        let _ = writeln!(out, "\n**Community answer:** {}", votes);
+    }
+    for (name, value) in extra_fields(question) {
+        let _ = writeln!(out, "\n**{}:** {}", name, bidi_isolate(&value));
+    }
+}
+
+pub(crate) fn to_html(bank: &Bank) -> String {
+    let lang = bank.meta.lang.as_deref().unwrap_or("en");
+    let title = escape_html(bank.meta.title.as_deref().unwrap_or("Questions"));
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>",
+        lang, title
+    );
+    for question in &bank.questions {
+        let synthetic = if question.variant_of.is_some() { " (synthetic variant)" } else { "" };
+        let _ = writeln!(out, "<section>\n<h2>Question {}{}</h2>", escape_html(&question.number), synthetic);
+        let stem = keywords::highlight(&escape_html(&question.text), &question.keywords, "<strong>", "</strong>");
+        let _ = writeln!(out, "<p dir=\"auto\">{}</p>", stem);
+        if !question.statements.is_empty() {
+            out.push_str("<ol type=\"I\">\n");
+            for statement in &question.statements {
+                let _ = writeln!(out, "<li dir=\"auto\">{}</li>", escape_html(&statement.text));
+            }
+            out.push_str("</ol>\n");
+        }
+        if !question.premises.is_empty() {
+            out.push_str("<ol type=\"1\">\n");
+            for premise in &question.premises {
+                let _ = writeln!(out, "<li dir=\"auto\">{}</li>", escape_html(&premise.text));
+            }
+            out.push_str("</ol>\n");
+        }
+        out.push_str("<ol type=\"A\">\n");
+        for (_, choice) in sorted_choices(question) {
+            let _ = writeln!(out, "<li dir=\"auto\">{}</li>", escape_html(choice));
+        }
+        out.push_str("</ol>\n");
+        if let Some(answer) = answer_text(question) {
+            let _ = writeln!(out, "<p><strong>Answer:</strong> {}</p>", escape_html(&answer));
+        }
+        if let Some(votes) = community_text(question) {
+            let _ = writeln!(out, "<p><strong>Community answer:</strong> {}</p>", escape_html(&votes));
+        }
+        for (name, value) in extra_fields(question) {
+            let (name, value) = (escape_html(name), escape_html(&value));
+            let _ = writeln!(out, "<p dir=\"auto\"><strong>{}:</strong> {}</p>", name, value);
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+pub(crate) fn render(bank: &Bank, format: Format) -> String {
+    match format {
+        Format::Markdown => to_markdown(bank),
+        Format::Html => to_html(bank),
+        Format::Gift => lms::to_gift(bank),
+        Format::Qti => lms::to_qti(bank),
+        Format::Anki => anki::to_cloze(bank),
+    }
+}
+
+pub(crate) fn run(args: ExportArgs, options: &ExtractOptions) -> Result<(), Box<dyn Error>> {
+    let registry = Registry::load(options)?;
+    let exporter = registry.get(&args.format)?;
+    let (mut bank, crypto) = open_bank(&args.input, &Crypto::new(options))?;
+    if !args.include_retired {
+        bank = lifecycle::in_use(&bank);
+    }
+    crypto.write(&args.output, exporter.render(&bank)?)?;
+    Ok(())
+}
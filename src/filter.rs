@@ -0,0 +1,135 @@
+use clap::Args;
+use std::error::Error;
+
+use crate::crypt::Crypto;
+use crate::readability::{self, Readability};
+use crate::{open_bank, save_to_json};
+
+// Filtering a bank by the length and readability of its stems (see `readability`). Machine-translated junk shows as
+// stems of hundreds of words, or as one endless sentence with a reading ease far below zero, so `--max-words` and
+// `--min-reading-ease` leave those out while keeping the exam's genuinely long scenario questions. Every bound is
+// optional and a question is kept when it meets all of them.
+
+#[derive(Args, Debug)]
+pub struct FilterArgs {
+    /// Question bank to filter
+    input: String,
+    /// Where to write the questions kept
+    output: String,
+    /// Drop stems shorter than this many words
+    #[arg(long)]
+    min_words: Option<usize>,
+    /// Drop stems longer than this many words
+    #[arg(long)]
+    max_words: Option<usize>,
+    /// Drop stems of more sentences than this
+    #[arg(long)]
+    max_sentences: Option<usize>,
+    /// Drop stems harder to read than this Flesch reading ease (higher is easier)
+    #[arg(long, allow_negative_numbers = true)]
+    min_reading_ease: Option<f64>,
+    /// Drop stems easier to read than this Flesch reading ease
+    #[arg(long, allow_negative_numbers = true)]
+    max_reading_ease: Option<f64>,
+    /// List the questions dropped and why
+    #[arg(long)]
+    verbose: bool,
+}
+
+/// Why a stem is dropped, if it is.
+fn reject(args: &FilterArgs, stem: &Readability) -> Option<String> {
+    if let Some(min) = args.min_words.filter(|&min| stem.words < min) {
+        return Some(format!("{} words, fewer than {}", stem.words, min));
+    }
+    if let Some(max) = args.max_words.filter(|&max| stem.words > max) {
+        return Some(format!("{} words, more than {}", stem.words, max));
+    }
+    if let Some(max) = args.max_sentences.filter(|&max| stem.sentences > max) {
+        return Some(format!("{} sentences, more than {}", stem.sentences, max));
+    }
+    if let Some(min) = args.min_reading_ease.filter(|&min| stem.reading_ease < min) {
+        return Some(format!("reading ease {:.0}, below {}", stem.reading_ease, min));
+    }
+    if let Some(max) = args.max_reading_ease.filter(|&max| stem.reading_ease > max) {
+        return Some(format!("reading ease {:.0}, above {}", stem.reading_ease, max));
+    }
+    None
+}
+
+pub(crate) fn run(args: FilterArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    let total = bank.questions.len();
+    let questions = std::mem::take(&mut bank.questions);
+    for question in questions {
+        match reject(&args, &readability::stem(&question)) {
+            Some(reason) => {
+                if args.verbose {
+                    println!("Dropped question {}: {}", question.number, reason);
+                }
+            }
+            None => bank.questions.push(question),
+        }
+    }
+
+    println!("Kept {} of {} questions, dropped {}", bank.questions.len(), total, total - bank.questions.len());
+    save_to_json(&bank, &args.output, &crypto)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args() -> FilterArgs {
+        FilterArgs {
+            input: String::new(),
+            output: String::new(),
+            min_words: None,
+            max_words: None,
+            max_sentences: None,
+            min_reading_ease: None,
+            max_reading_ease: None,
+            verbose: false,
+        }
+    }
+
+    #[test]
+    fn cjk_stems_have_enough_words() {
+        let args = FilterArgs {
+            min_words: Some(5),
+            ..args()
+        };
+        assert_eq!(reject(&args, &readability::measure("倉庫タスクを作成するのはどれですか。")), None);
+        assert_eq!(reject(&args, &readability::measure("以下哪个仓库流程是正确的？")), None);
+        let short = readability::measure("Which one?");
+        assert_eq!(reject(&args, &short), Some("2 words, fewer than 5".to_string()));
+    }
+
+    #[test]
+    fn abbreviations_do_not_add_sentences() {
+        let args = FilterArgs {
+            max_sentences: Some(1),
+            ..args()
+        };
+        let stem = readability::measure("Which RF device, e.g. a scanner at 10.0.0.1, confirms the task?");
+        assert_eq!(reject(&args, &stem), None);
+        let stem = readability::measure("A task exists. It is open. Which bin is used?");
+        assert_eq!(reject(&args, &stem), Some("3 sentences, more than 1".to_string()));
+    }
+
+    #[test]
+    fn stems_must_meet_every_bound() {
+        let args = FilterArgs {
+            max_words: Some(8),
+            min_reading_ease: Some(0.0),
+            ..args()
+        };
+        assert_eq!(reject(&args, &readability::measure("The bin is full. Move the stock.")), None);
+        let stem = readability::measure("Which warehouse process type determines the putaway strategy?");
+        assert_eq!(reject(&args, &stem), None);
+        let stem = readability::measure("Which warehouse process type determines the putaway strategy for returns?");
+        assert_eq!(reject(&args, &stem), Some("10 words, more than 8".to_string()));
+        let stem = readability::measure("Evaluate organizational replenishment prioritization methodologies.");
+        assert_eq!(reject(&args, &stem).map(|reason| reason.starts_with("reading ease")), Some(true));
+    }
+}
@@ -0,0 +1,222 @@
+use clap::{Args, Subcommand};
+use lopdf::Document;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::pages;
+use crate::parser::parse_pages;
+use crate::profile::{self, ParserProfile, Profile};
+use crate::{annotate_questions, meta, read_columns, Bank, OutputError};
+
+// Golden-fixture corpus for regression-testing the parser against real dumps. Each case is a directory holding the
+// text extracted from a PDF (`text.txt`, pages separated by form feeds), the profile it was parsed with
+// (`profile.txt`) and the resulting bank (`expected.json`). Replaying only needs the text, so the corpus can be
+// checked without the PDFs. Answers detected from styling are not part of the fixtures since they need the PDF.
+
+const TEXT_FILE: &str = "text.txt";
+const PROFILE_FILE: &str = "profile.txt";
+const EXPECTED_FILE: &str = "expected.json";
+pub(crate) const PAGE_SEPARATOR: char = '\x0c';
+
+#[derive(Args, Debug)]
+pub struct FixturesArgs {
+    #[command(subcommand)]
+    command: FixturesCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum FixturesCommand {
+    /// Add a PDF to the corpus, writing its extracted text and parsed bank side by side
+    Record {
+        /// PDF to record
+        pdf: String,
+        /// Corpus directory
+        corpus: String,
+        /// Case name (defaults to the PDF file name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Parsing profile (en, de, fr); detected from the document language when omitted
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Replay every case of the corpus and report where the parser output differs from the expected bank
+    Check {
+        /// Corpus directory
+        corpus: String,
+    },
+    /// Time the parser over every case of the corpus and report its throughput
+    Bench {
+        /// Corpus directory
+        corpus: String,
+        /// Number of passes over the corpus
+        #[arg(long, default_value_t = 10)]
+        iterations: usize,
+    },
+}
+
+/// Parses extracted page texts the same way the extraction run does, minus the PDF-only steps.
+fn parse_case(pages: &[String], profile: &Profile) -> Result<Bank, Box<dyn Error>> {
+    let patterns = ParserProfile::cached(profile)?;
+    let mut questions = parse_pages(pages, &patterns, false, |_, _| {}).questions;
+    annotate_questions(&mut questions);
+    let mut meta = meta::preamble_meta(&pages.join("\n"), &patterns.question);
+    meta.lang = Some(profile.lang.to_string());
+    Ok(Bank { meta, questions })
+}
+
+fn record(pdf: &str, corpus: &str, name: Option<String>, profile_name: Option<String>) -> Result<(), Box<dyn Error>> {
+    let mut pages = pages::extract_pages(Document::load(pdf)?, pages::jobs(None), None, &BTreeSet::new(), |_| {}).texts;
+    read_columns(pdf, &mut pages, None)?;
+    let profile = match profile_name {
+        Some(name) => Profile::by_name(&name).ok_or_else(|| OutputError::from("Unknown parsing profile"))?,
+        None => profile::detect(&pages.join("\n")),
+    };
+
+    let name = match name {
+        Some(name) => name,
+        None => Path::new(pdf)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .ok_or_else(|| OutputError::from("Cannot derive a case name from the PDF path"))?,
+    };
+    let case_dir = Path::new(corpus).join(&name);
+    fs::create_dir_all(&case_dir)?;
+
+    let bank = parse_case(&pages, &profile)?;
+    fs::write(case_dir.join(TEXT_FILE), pages.join(&PAGE_SEPARATOR.to_string()))?;
+    fs::write(case_dir.join(PROFILE_FILE), profile.name)?;
+    fs::write(case_dir.join(EXPECTED_FILE), serde_json::to_string_pretty(&bank)?)?;
+    println!("Recorded {} questions into {}", bank.questions.len(), case_dir.display());
+    Ok(())
+}
+
+/// Differences between two JSON values as `path: expected -> actual` lines.
+fn diff_values(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            let mut keys: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let expected = expected.get(key).unwrap_or(&Value::Null);
+                let actual = actual.get(key).unwrap_or(&Value::Null);
+                diff_values(&format!("{}.{}", path, key), expected, actual, out);
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                diff_values(&format!("{}[{}]", path, i), expected, actual, out);
+            }
+        }
+        _ if expected != actual => out.push(format!("{}: {} -> {}", path, expected, actual)),
+        _ => {}
+    }
+}
+
+/// Page texts and profile of a recorded case
+fn load_case(case_dir: &Path) -> Result<(Vec<String>, Profile), Box<dyn Error>> {
+    let text = fs::read_to_string(case_dir.join(TEXT_FILE))?;
+    let pages: Vec<String> = text.split(PAGE_SEPARATOR).map(str::to_string).collect();
+    let profile_name = fs::read_to_string(case_dir.join(PROFILE_FILE))?;
+    let profile =
+        Profile::by_name(profile_name.trim()).ok_or_else(|| OutputError::from("Unknown parsing profile"))?;
+    Ok((pages, profile))
+}
+
+/// Case directories of the corpus, sorted by name
+fn cases(corpus: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut cases: Vec<_> = fs::read_dir(corpus)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.join(TEXT_FILE).is_file())
+        .collect();
+    cases.sort();
+    Ok(cases)
+}
+
+/// Replays one case and returns its differences to the expected bank.
+fn check_case(case_dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let (pages, profile) = load_case(case_dir)?;
+    let expected: Value = serde_json::from_str(&fs::read_to_string(case_dir.join(EXPECTED_FILE))?)?;
+    let actual = serde_json::to_value(parse_case(&pages, &profile)?)?;
+    let mut differences = Vec::new();
+    diff_values("bank", &expected, &actual, &mut differences);
+    Ok(differences)
+}
+
+fn check(corpus: &str) -> Result<(), Box<dyn Error>> {
+    let cases = cases(corpus)?;
+    let mut failed = 0;
+    for case_dir in &cases {
+        let differences = check_case(case_dir)?;
+        if differences.is_empty() {
+            println!("ok    {}", case_dir.display());
+        } else {
+            failed += 1;
+            println!("FAIL  {} ({} difference(s))", case_dir.display(), differences.len());
+            for difference in differences {
+                println!("      {}", difference);
+            }
+        }
+    }
+
+    println!("{} case(s), {} failed", cases.len(), failed);
+    if failed > 0 {
+        return Err(OutputError::from("Parser output differs from the fixture corpus").into());
+    }
+    Ok(())
+}
+
+/// Parses the corpus `iterations` times after a warm-up pass, timing only the parser (the text is read once).
+fn bench(corpus: &str, iterations: usize) -> Result<(), Box<dyn Error>> {
+    let cases = cases(corpus)?
+        .iter()
+        .map(|case_dir| load_case(case_dir))
+        .collect::<Result<Vec<_>, _>>()?;
+    if cases.is_empty() {
+        return Err(OutputError::from("No cases in the fixture corpus").into());
+    }
+    let pages: usize = cases.iter().map(|(pages, _)| pages.len()).sum();
+    let lines: usize = cases.iter().flat_map(|(pages, _)| pages).map(|page| page.lines().count()).sum();
+    let bytes: usize = cases.iter().flat_map(|(pages, _)| pages).map(String::len).sum();
+
+    let mut questions = 0;
+    for (pages, profile) in &cases {
+        questions += parse_case(pages, profile)?.questions.len();
+    }
+    let started = Instant::now();
+    for _ in 0..iterations.max(1) {
+        for (pages, profile) in &cases {
+            parse_case(pages, profile)?;
+        }
+    }
+    let seconds = started.elapsed().as_secs_f64().max(f64::EPSILON) / iterations.max(1) as f64;
+
+    println!(
+        "{} case(s), {} pages, {} lines, {} questions: {:.1} ms per pass",
+        cases.len(),
+        pages,
+        lines,
+        questions,
+        seconds * 1000.0
+    );
+    println!(
+        "{:.0} pages/s, {:.0} lines/s, {:.1} MB/s",
+        pages as f64 / seconds,
+        lines as f64 / seconds,
+        bytes as f64 / seconds / 1_000_000.0
+    );
+    Ok(())
+}
+
+pub(crate) fn run(args: FixturesArgs) -> Result<(), Box<dyn Error>> {
+    match args.command {
+        FixturesCommand::Record { pdf, corpus, name, profile } => record(&pdf, &corpus, name, profile),
+        FixturesCommand::Check { corpus } => check(&corpus),
+        FixturesCommand::Bench { corpus, iterations } => bench(&corpus, iterations),
+    }
+}
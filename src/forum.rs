@@ -0,0 +1,237 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+use crate::html::strip_markup;
+use crate::Question;
+
+// Questions saved from discussion forums in the style of ExamTopics, whose pages hold one or more question blocks:
+// the stem, the lettered choices, the answer the site suggests and the votes of the community. The suggested answer
+// becomes the official `answers`, the votes become `community_answer`, so a reader can weigh one against the other
+// and validation points at the questions where they disagree. The pages are read with patterns on the class names
+// the site uses (`question-body`, `multi-choice-item`, `correct-answer`, `voted-answers-tally`, `vote-bar`) rather
+// than a full HTML parser; a block without choices (hotspot and drag-and-drop questions shown as images) is skipped.
+
+lazy_static! {
+    static ref BODY: Regex = Regex::new(r#"<div[^>]*class="[^"]*\bquestion-body\b[^"]*"[^>]*>"#).unwrap();
+    static ref NUMBER: Regex = Regex::new(r"(?i)question\s*#\s*:?\s*(\d+)").unwrap();
+    static ref CHOICES_START: Regex =
+        Regex::new(r#"class="[^"]*\b(question-choices-container|multi-choice-item|question-answer)\b"#).unwrap();
+    static ref CHOICE: Regex =
+        Regex::new(r#"(?s)<li[^>]*class="[^"]*\bmulti-choice-item\b[^"]*"[^>]*>(.*?)</li>"#).unwrap();
+    static ref CHOICE_LETTER: Regex = Regex::new(r#"data-choice-letter="([A-Z])""#).unwrap();
+    static ref LEADING_LETTER: Regex = Regex::new(r"^([A-Z])\.\s*").unwrap();
+    static ref BADGE: Regex = Regex::new(r#"(?s)<span[^>]*class="[^"]*\bbadge\b[^"]*"[^>]*>.*?</span>"#).unwrap();
+    static ref SUGGESTED: Regex = Regex::new(r#"(?s)class="[^"]*\bcorrect-answer\b[^"]*"[^>]*>(.*?)</span>"#).unwrap();
+    static ref TALLY: Regex =
+        Regex::new(r#"(?s)class="[^"]*\bvoted-answers-tally\b[^"]*"[^>]*>\s*<script[^>]*>(.*?)</script>"#).unwrap();
+    static ref VOTE_BAR: Regex = Regex::new(r"\b([A-Z]{1,8})\s*\((\d{1,3})%\)").unwrap();
+}
+
+/// Answer a discussion forum voted for, next to the official `answers`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct CommunityAnswer {
+    /// Most voted answer
+    pub(crate) answers: Vec<String>,
+    /// Every answer voted for with its share of the votes, most voted first
+    pub(crate) votes: Vec<Vote>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Vote {
+    pub(crate) answers: Vec<String>,
+    /// Share of the votes in percent
+    pub(crate) percent: usize,
+}
+
+/// Entry of the vote tally the site embeds as JSON
+#[derive(Deserialize)]
+struct Tally {
+    voted_answers: String,
+    vote_count: usize,
+}
+
+/// Text of a fragment on one line, without markup.
+fn text(html: &str) -> String {
+    strip_markup(html).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn letters(text: &str) -> Vec<String> {
+    text.chars().filter(char::is_ascii_uppercase).map(String::from).collect()
+}
+
+/// Votes of a block, from the embedded tally when the page has it, else from the `B (67%)` labels of the vote bars.
+fn votes(block: &str) -> Vec<Vote> {
+    let tally = TALLY.captures(block).and_then(|caps| serde_json::from_str::<Vec<Tally>>(&caps[1]).ok());
+    let mut votes: Vec<Vote> = match tally {
+        Some(tally) => {
+            let total: usize = tally.iter().map(|entry| entry.vote_count).sum();
+            tally
+                .iter()
+                .filter(|_| total > 0)
+                .map(|entry| Vote {
+                    answers: letters(&entry.voted_answers),
+                    percent: (entry.vote_count * 100 + total / 2) / total,
+                })
+                .collect()
+        }
+        None => VOTE_BAR
+            .captures_iter(&strip_markup(block))
+            .map(|caps| Vote {
+                answers: letters(&caps[1]),
+                percent: caps[2].parse().unwrap_or_default(),
+            })
+            .collect(),
+    };
+    votes.retain(|vote| !vote.answers.is_empty());
+    votes.sort_by_key(|vote| Reverse(vote.percent));
+    votes
+}
+
+/// The question of a block, or why it is not one.
+fn block_question(block: &str) -> Result<Question, &'static str> {
+    let stem_end = CHOICES_START.find(block).map_or(block.len(), |start| start.start());
+    // The match starts inside the tag opening the choices, cut before it
+    let stem_end = block[..stem_end].rfind('<').unwrap_or(stem_end);
+    let stem = text(&block[..stem_end]);
+    if stem.is_empty() {
+        return Err("empty question");
+    }
+
+    let mut question = Question {
+        text: stem,
+        ..Question::default()
+    };
+    for caps in CHOICE.captures_iter(block) {
+        let item = &caps[0];
+        let choice = text(&BADGE.replace_all(&caps[1], ""));
+        let letter = CHOICE_LETTER.captures(item).map(|letter| letter[1].to_string());
+        let (letter, choice) = match (letter, LEADING_LETTER.captures(&choice)) {
+            (letter, Some(leading)) => {
+                let letter = letter.unwrap_or_else(|| leading[1].to_string());
+                (letter, choice[leading[0].len()..].to_string())
+            }
+            (Some(letter), None) => (letter, choice),
+            (None, None) => continue,
+        };
+        question.choices.insert(letter, choice);
+    }
+    if question.choices.is_empty() {
+        return Err("no lettered choices");
+    }
+
+    if let Some(caps) = SUGGESTED.captures(block) {
+        let suggested = letters(&text(&caps[1]));
+        question.answers = suggested.into_iter().filter(|letter| question.choices.contains_key(letter)).collect();
+    }
+    let votes = votes(block);
+    if let Some(top) = votes.first() {
+        question.community_answer = Some(CommunityAnswer {
+            answers: top.answers.clone(),
+            votes,
+        });
+    }
+    Ok(question)
+}
+
+/// The questions of a saved page in page order, or why a block is not one.
+pub(crate) fn read_page(html: &str) -> Vec<Result<Question, &'static str>> {
+    let starts: Vec<_> = BODY.find_iter(html).collect();
+    let mut questions = Vec::new();
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(html.len(), |next| next.start());
+        let header_start = if i == 0 { 0 } else { starts[i - 1].end() };
+        let header = &html[header_start..start.start()];
+        let result = block_question(&html[start.end()..end]).map(|mut question| {
+            let number = NUMBER.captures_iter(&text(header)).last().and_then(|caps| caps[1].parse().ok());
+            question.source_number = number;
+            question
+        });
+        questions.push(result);
+    }
+    questions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const PAGE: &str = r#"<div class="question-discussion-header"><div>Question #: 12<br>Topic #: 1</div></div>
+<div class="card-body question-body" data-id="101">
+  <p class="card-text">Which transaction shows the <b>warehouse monitor</b>?</p>
+  <div class="question-choices-container"><ul>
+    <li class="multi-choice-item" data-choice-letter="A"><span class="multi-choice-letter">A.</span> /SCWM/MON
+      <span class="badge badge-success most-voted-answer-badge">Most Voted</span></li>
+    <li class="multi-choice-item" data-choice-letter="B"><span class="multi-choice-letter">B.</span> /SCWM/PRDI</li>
+  </ul></div>
+  <p class="card-text question-answer">Suggested Answer: <span class="correct-answer">B</span>
+  <div class="voted-answers-tally"><script type="application/json">
+    [{"voted_answers": "A", "vote_count": 6, "is_most_voted": true}, {"voted_answers": "B", "vote_count": 3}]
+  </script></div></p>
+</div>
+<div class="question-discussion-header"><div>Question #: 13</div></div>
+<div class="card-body question-body">
+  Which objects can a wave hold? (Choose two.)
+  <ul><li class="multi-choice-item">A. Warehouse requests</li><li class="multi-choice-item">B. Bins</li>
+  <li class="multi-choice-item">C. Warehouse request items</li></ul>
+  <p class="question-answer">Suggested Answer: <span class="correct-answer">AC</span></p>
+  <div class="vote-bar">AC (80%)</div><div class="vote-bar">AB (20%)</div>
+</div>
+<div class="question-discussion-header"><div>Question #: 14</div></div>
+<div class="card-body question-body">Drag the steps of the putaway into order.<img src="hotspot.png"></div>
+<div class="card-body question-body"><ul><li class="multi-choice-item">A. Lost stem</li></ul></div>
+"#;
+
+    fn choices(choices: &[(&str, &str)]) -> HashMap<String, String> {
+        choices.iter().map(|(letter, text)| (letter.to_string(), text.to_string())).collect()
+    }
+
+    fn vote(answers: &[&str], percent: usize) -> Vote {
+        Vote {
+            answers: answers.iter().map(|answer| answer.to_string()).collect(),
+            percent,
+        }
+    }
+
+    #[test]
+    fn pages() {
+        let questions = read_page(PAGE);
+        assert_eq!(questions.len(), 4);
+
+        let monitor = questions[0].as_ref().unwrap();
+        assert_eq!(monitor.text, "Which transaction shows the warehouse monitor?");
+        assert_eq!(monitor.choices, choices(&[("A", "/SCWM/MON"), ("B", "/SCWM/PRDI")]));
+        assert_eq!(monitor.answers, ["B"]);
+        assert_eq!(monitor.source_number, Some(12));
+        let community = CommunityAnswer {
+            answers: vec![String::from("A")],
+            votes: vec![vote(&["A"], 67), vote(&["B"], 33)],
+        };
+        assert_eq!(monitor.community_answer, Some(community));
+
+        let wave = questions[1].as_ref().unwrap();
+        assert_eq!(wave.text, "Which objects can a wave hold? (Choose two.)");
+        let expected = [("A", "Warehouse requests"), ("B", "Bins"), ("C", "Warehouse request items")];
+        assert_eq!(wave.choices, choices(&expected));
+        assert_eq!(wave.answers, ["A", "C"]);
+        assert_eq!(wave.source_number, Some(13));
+        let community = CommunityAnswer {
+            answers: vec![String::from("A"), String::from("C")],
+            votes: vec![vote(&["A", "C"], 80), vote(&["A", "B"], 20)],
+        };
+        assert_eq!(wave.community_answer, Some(community));
+
+        assert_eq!(questions[2].as_ref().err(), Some(&"no lettered choices"));
+        assert_eq!(questions[3].as_ref().err(), Some(&"empty question"));
+    }
+
+    #[test]
+    fn broken_tallies_fall_back_to_the_vote_bars() {
+        let block = r#"<div class="voted-answers-tally"><script>[{"voted_answers": "A",</script></div>
+            <div class="vote-bar">B (55%)</div><div class="vote-bar">A (45%)</div>"#;
+        assert_eq!(votes(block), [vote(&["B"], 55), vote(&["A"], 45)]);
+        assert!(votes("<p>No votes yet</p>").is_empty());
+        assert!(read_page("<html><body>Not found</body></html>").is_empty());
+    }
+}
@@ -0,0 +1,479 @@
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::crypt::Crypto;
+use crate::manifest::rfc3339;
+use crate::similarity::{jaccard, stable_id, token_set};
+use crate::{OutputError, Question};
+
+// Append-only change log of a bank maintained by several people. With `--history <log>` every extraction, key merge
+// and bank merge compares the bank it writes with the one it replaces and appends one NDJSON line per added, changed
+// or removed question: when, who, which operation and input, and the fields that changed. Ids follow the stem and
+// choices, so a question whose stem was corrected in a later dump gets a new id; such a question is paired with the
+// most similar removed one and its entry keeps the previous id, which lets `history <id>` follow a question across
+// dump versions. The log is never rewritten. Runs on encrypted banks keep the content of the questions out of the
+// log: their entries name the fields that changed, without the values.
+
+/// Stems at least this similar (token Jaccard) are the same question in two versions of the bank
+const SAME_QUESTION_THRESHOLD: f64 = 0.6;
+/// Fields that move with every extraction without the question changing
+const UNTRACKED_FIELDS: &[&str] = &["number", "page", "source_number", "explain", "difficulty"];
+
+/// Options shared by the commands that write to the change log
+#[derive(Args, Debug, Clone, Default)]
+pub struct HistoryArgs {
+    /// Append the changes made to the bank to this change log (NDJSON)
+    #[arg(long, env = "S4WM_HISTORY", value_name = "NDJSON")]
+    pub(crate) history: Option<String>,
+    /// Name recorded as the author of the changes; defaults to the user name
+    #[arg(long, env = "S4WM_AUTHOR")]
+    pub(crate) author: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryCommandArgs {
+    /// Id of the question, or a prefix of it
+    id: String,
+    /// Change log to read
+    #[arg(long, env = "S4WM_HISTORY", value_name = "NDJSON")]
+    history: String,
+    /// Print the entries as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct FieldChange {
+    pub(crate) field: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub(crate) before: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub(crate) after: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub(crate) enum Change {
+    /// The question appeared, with its content at that time
+    Added { question: Value },
+    Changed { fields: Vec<FieldChange> },
+    Removed,
+}
+
+/// One line of the change log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Entry {
+    pub(crate) at: String,
+    pub(crate) author: String,
+    /// Command that made the change: extract, key, merge, ...
+    pub(crate) operation: String,
+    /// Input the change came from: the PDF, the answer key, the merged banks
+    pub(crate) source: String,
+    pub(crate) id: String,
+    /// Id the question had before its stem or choices changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) previous_id: Option<String>,
+    pub(crate) number: String,
+    #[serde(flatten)]
+    pub(crate) change: Change,
+}
+
+fn question_id(question: &Question) -> String {
+    question.id.clone().unwrap_or_else(|| stable_id(question))
+}
+
+/// The tracked fields of a question as JSON.
+fn tracked(question: &Question) -> Map<String, Value> {
+    let mut fields = match serde_json::to_value(question) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    fields.retain(|field, _| !UNTRACKED_FIELDS.contains(&field.as_str()));
+    fields
+}
+
+fn field_changes(before: &Question, after: &Question) -> Vec<FieldChange> {
+    let (before, after) = (tracked(before), tracked(after));
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| before.get(*name) != after.get(*name))
+        .map(|name| FieldChange {
+            field: name.clone(),
+            before: before.get(name).cloned().unwrap_or(Value::Null),
+            after: after.get(name).cloned().unwrap_or(Value::Null),
+        })
+        .collect()
+}
+
+/// Changes turning the `before` questions into the `after` ones, as entries without time, author and operation.
+pub(crate) fn diff(before: &[Question], after: &[Question]) -> Vec<(String, Option<String>, String, Change)> {
+    let before_ids: Vec<String> = before.iter().map(question_id).collect();
+    let after_ids: Vec<String> = after.iter().map(question_id).collect();
+    let known: HashMap<&String, usize> = before_ids.iter().enumerate().map(|(index, id)| (id, index)).collect();
+    let kept: HashSet<&String> = after_ids.iter().collect();
+
+    // questions whose id changed: pair every new id with the most similar question that disappeared
+    let mut gone: Vec<usize> = (0..before.len()).filter(|&i| !kept.contains(&before_ids[i])).collect();
+    let gone_tokens: Vec<_> = before.iter().map(|q| token_set(&q.text)).collect();
+
+    let mut changes = Vec::new();
+    for (question, id) in after.iter().zip(&after_ids) {
+        if let Some(&old) = known.get(id) {
+            let fields = field_changes(&before[old], question);
+            if !fields.is_empty() {
+                changes.push((id.clone(), None, question.number.clone(), Change::Changed { fields }));
+            }
+            continue;
+        }
+        let tokens = token_set(&question.text);
+        let closest = gone
+            .iter()
+            .enumerate()
+            .map(|(slot, &old)| (slot, jaccard(&tokens, &gone_tokens[old])))
+            .filter(|(_, score)| *score >= SAME_QUESTION_THRESHOLD)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match closest {
+            Some((slot, _)) => {
+                let old = gone.remove(slot);
+                let fields = field_changes(&before[old], question);
+                let previous_id = Some(before_ids[old].clone());
+                changes.push((id.clone(), previous_id, question.number.clone(), Change::Changed { fields }));
+            }
+            None => {
+                let added = Change::Added {
+                    question: Value::Object(tracked(question)),
+                };
+                changes.push((id.clone(), None, question.number.clone(), added));
+            }
+        }
+    }
+    for old in gone {
+        changes.push((before_ids[old].clone(), None, before[old].number.clone(), Change::Removed));
+    }
+    changes
+}
+
+impl Change {
+    /// The change without the content of the question.
+    fn without_content(self) -> Change {
+        match self {
+            Change::Added { .. } => Change::Added {
+                question: Value::Object(Map::new()),
+            },
+            Change::Changed { fields } => Change::Changed {
+                fields: fields
+                    .into_iter()
+                    .map(|change| FieldChange {
+                        field: change.field,
+                        before: Value::Null,
+                        after: Value::Null,
+                    })
+                    .collect(),
+            },
+            Change::Removed => Change::Removed,
+        }
+    }
+}
+
+fn default_author() -> String {
+    ["S4WM_AUTHOR", "USER", "USERNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Appends the changes between `before` and `after` to the change log of `args`, if any, without their content when
+/// `crypto` encrypts the bank. Returns the number of entries written.
+pub(crate) fn record(
+    args: &HistoryArgs,
+    operation: &str,
+    source: &str,
+    before: &[Question],
+    after: &[Question],
+    crypto: &Crypto,
+) -> Result<usize, OutputError> {
+    let Some(path) = &args.history else {
+        return Ok(0);
+    };
+    let mut changes = diff(before, after);
+    if changes.is_empty() {
+        return Ok(0);
+    }
+    if crypto.is_encrypting() {
+        changes = changes
+            .into_iter()
+            .map(|(id, previous, number, change)| (id, previous, number, change.without_content()))
+            .collect();
+    }
+    let at = rfc3339(SystemTime::now());
+    let author = args.author.clone().unwrap_or_else(default_author);
+    let mut lines = String::new();
+    for (id, previous_id, number, change) in &changes {
+        let entry = Entry {
+            at: at.clone(),
+            author: author.clone(),
+            operation: operation.to_string(),
+            source: source.to_string(),
+            id: id.clone(),
+            previous_id: previous_id.clone(),
+            number: number.clone(),
+            change: change.clone(),
+        };
+        lines.push_str(&serde_json::to_string(&entry)?);
+        lines.push('\n');
+    }
+    if let Some(parent) = Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    // a single write keeps the lines of concurrent batch workers whole
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(lines.as_bytes())?;
+    Ok(changes.len())
+}
+
+pub(crate) fn load(path: &str) -> Result<Vec<Entry>, OutputError> {
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(OutputError::from))
+        .collect()
+}
+
+/// The latest id of the question that had `id`, following the ids it got since.
+fn latest_id<'a>(successors: &HashMap<&'a str, &'a str>, mut id: &'a str) -> &'a str {
+    let mut seen = HashSet::from([id]);
+    while let Some(&next) = successors.get(id) {
+        // a question whose stem went back to an earlier wording gets its earlier id again
+        if !seen.insert(next) {
+            break;
+        }
+        id = next;
+    }
+    id
+}
+
+/// Entries of the question with the given id (or id prefix), following its earlier and later ids, in the order
+/// recorded. A prefix of the ids of several questions is an error, as in `lifecycle::find`.
+pub(crate) fn question_history<'a>(entries: &'a [Entry], id: &str) -> Result<Vec<&'a Entry>, OutputError> {
+    let successors: HashMap<&str, &str> = entries
+        .iter()
+        .filter_map(|entry| entry.previous_id.as_deref().map(|previous| (previous, entry.id.as_str())))
+        .collect();
+    let matching = entries.iter().map(|entry| entry.id.as_str()).filter(|known| known.starts_with(id));
+    let latest: BTreeSet<&str> = matching.map(|known| latest_id(&successors, known)).collect();
+    if latest.len() > 1 {
+        return Err(OutputError {
+            message: format!("Id {} matches {} questions, give more characters", id, latest.len()),
+        });
+    }
+    let mut ids: HashSet<&str> = latest.into_iter().collect();
+    let mut selected = Vec::new();
+    for entry in entries.iter().rev() {
+        if ids.contains(entry.id.as_str()) {
+            if let Some(previous_id) = &entry.previous_id {
+                ids.insert(previous_id.as_str());
+            }
+            selected.push(entry);
+        }
+    }
+    selected.reverse();
+    Ok(selected)
+}
+
+fn short(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(text) => format!("{:?}", text),
+        other => other.to_string(),
+    }
+}
+
+pub(crate) fn run(args: HistoryCommandArgs) -> Result<(), Box<dyn Error>> {
+    let entries = load(&args.history)?;
+    let history = question_history(&entries, &args.id)?;
+    if history.is_empty() {
+        return Err(OutputError {
+            message: format!("No question with id {} in {}", args.id, args.history),
+        }
+        .into());
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&history)?);
+        return Ok(());
+    }
+
+    for entry in history {
+        let what = match &entry.change {
+            Change::Added { .. } => "added".to_string(),
+            Change::Changed { .. } => match &entry.previous_id {
+                Some(previous_id) => format!("changed (was {})", previous_id),
+                None => "changed".to_string(),
+            },
+            Change::Removed => "removed".to_string(),
+        };
+        println!(
+            "{}  {}  {} {}: question {} [{}] {}",
+            entry.at, entry.author, entry.operation, entry.source, entry.number, entry.id, what
+        );
+        match &entry.change {
+            Change::Added { question } => {
+                if let Some(text) = question.get("text") {
+                    println!("    text: {}", short(text));
+                }
+                if let Some(answers) = question.get("answers") {
+                    println!("    answers: {}", short(answers));
+                }
+            }
+            Change::Changed { fields } => {
+                for change in fields {
+                    println!("    {}: {} -> {}", change.field, short(&change.before), short(&change.after));
+                }
+            }
+            Change::Removed => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+
+    fn question(number: &str, text: &str, answers: &[&str]) -> Question {
+        Question {
+            number: number.to_string(),
+            text: text.to_string(),
+            choices: [("A", "Wave"), ("B", "Bin")].iter().map(|(l, t)| (l.to_string(), t.to_string())).collect(),
+            answers: answers.iter().map(|answer| answer.to_string()).collect(),
+            ..Question::default()
+        }
+    }
+
+    fn fields(change: &Change) -> Vec<&str> {
+        match change {
+            Change::Changed { fields } => fields.iter().map(|change| change.field.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reworded_questions_pair_from_the_threshold() {
+        // 3 of 5 words shared: a Jaccard of exactly 0.6
+        let before = [question("1", "wave bin queue task", &["A"]), question("2", "storage type section", &["B"])];
+        let after = [question("1", "wave bin queue lane", &["A"]), question("2", "door staging area", &["B"])];
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 3);
+
+        let (id, previous_id, number, change) = &changes[0];
+        assert_eq!((id, number.as_str()), (&stable_id(&after[0]), "1"));
+        assert_eq!(previous_id.as_ref(), Some(&stable_id(&before[0])));
+        assert_eq!(fields(change), ["text"]);
+        // 0 of 6 words shared: a new question and a removed one
+        assert_eq!(changes[1].0, stable_id(&after[1]));
+        assert!(matches!(&changes[1].3, Change::Added { question } if question["text"] == "door staging area"));
+        assert_eq!((changes[2].0.clone(), &changes[2].3), (stable_id(&before[1]), &Change::Removed));
+    }
+
+    #[test]
+    fn kept_ids_report_their_tracked_fields() {
+        let before = [question("1", "Which transaction shows the monitor?", &["A"])];
+        let mut renumbered = question("12", "Which transaction shows the monitor?", &["A"]);
+        renumbered.page = Some(4);
+        assert!(diff(&before, &[renumbered.clone()]).is_empty());
+
+        renumbered.answers = vec!["B".to_string()];
+        let changes = diff(&before, &[renumbered]);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].1, None);
+        let Change::Changed { fields } = &changes[0].3 else { panic!("not a change: {:?}", changes[0].3) };
+        let answers = FieldChange {
+            field: "answers".to_string(),
+            before: json(&["A"]),
+            after: json(&["B"]),
+        };
+        assert_eq!(fields, &[answers]);
+    }
+
+    fn json(answers: &[&str]) -> Value {
+        serde_json::to_value(answers).unwrap()
+    }
+
+    fn entry(id: &str, previous_id: Option<&str>, change: Change) -> Entry {
+        Entry {
+            at: "2024-05-01T10:00:00Z".to_string(),
+            author: "ana".to_string(),
+            operation: "extract".to_string(),
+            source: "dump.pdf".to_string(),
+            id: id.to_string(),
+            previous_id: previous_id.map(str::to_string),
+            number: "1".to_string(),
+            change,
+        }
+    }
+
+    #[test]
+    fn histories_follow_the_ids_of_a_question() {
+        let changed = || Change::Changed { fields: Vec::new() };
+        let added = || Change::Added {
+            question: Value::Null,
+        };
+        let entries = [
+            entry("aa11", None, added()),
+            entry("ab22", None, added()),
+            entry("ac33", Some("aa11"), changed()),
+            entry("ad44", Some("ac33"), changed()),
+            entry("ab22", None, Change::Removed),
+        ];
+        let ids = |history: Vec<&Entry>| history.iter().map(|entry| entry.id.clone()).collect::<Vec<_>>();
+        let whole = ["aa11", "ac33", "ad44"];
+        assert_eq!(ids(question_history(&entries, "ad4").unwrap()), whole);
+        // an earlier id finds the later entries too
+        assert_eq!(ids(question_history(&entries, "aa").unwrap()), whole);
+        assert_eq!(ids(question_history(&entries, "ab").unwrap()), ["ab22", "ab22"]);
+        assert!(question_history(&entries, "b").unwrap().is_empty());
+        let ambiguous = question_history(&entries, "a").unwrap_err();
+        assert_eq!(ambiguous.message, "Id a matches 2 questions, give more characters");
+    }
+
+    #[test]
+    fn encrypted_banks_log_no_content() {
+        let log = std::env::temp_dir().join(format!("s4wm-history-encrypted-{}.ndjson", std::process::id()));
+        let args = HistoryArgs {
+            history: Some(log.to_string_lossy().into_owned()),
+            author: Some("ana".to_string()),
+        };
+        let encrypting = Crypto::new(&ExtractOptions {
+            encrypt: true,
+            ..ExtractOptions::default()
+        });
+        let before = [question("1", "Which transaction shows the monitor?", &["A"])];
+        let after = [question("1", "Which transaction shows the monitor?", &["B"]), question("2", "Secret stem", &[])];
+        let written = record(&args, "key", "key.csv", &before, &after, &encrypting).unwrap();
+        let entries = load(&log.to_string_lossy()).unwrap();
+        fs::remove_file(&log).unwrap();
+
+        assert_eq!(written, 2);
+        let unchanged = FieldChange {
+            field: "answers".to_string(),
+            before: Value::Null,
+            after: Value::Null,
+        };
+        let changed = Change::Changed {
+            fields: vec![unchanged],
+        };
+        let added = Change::Added {
+            question: Value::Object(Map::new()),
+        };
+        assert_eq!(entries[0].change, changed);
+        assert_eq!(entries[1].change, added);
+        assert_eq!((entries[1].author.as_str(), entries[1].operation.as_str()), ("ana", "key"));
+    }
+}
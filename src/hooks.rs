@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::plugin::{self, Plugin};
+use crate::progress::Progress;
+use crate::{Bank, ExtractOptions, OutputError};
+
+// Hooks at the boundaries of an extraction, to run in-house checks such as a profanity or PII filter without patching
+// the crate. A hook is a plugin (see `plugin`) of kind `hook` naming the stages it runs at: `after-download`, once the
+// PDF is on disk, `after-parse`, on the bank as parsed and before it is validated, and `before-export`, on the
+// validated bank before anything is written. Hooks only run when named by `--hook`, in the order given.
+//
+// A hook is called on its `hook` function with `{"stage": ..., "pdf": ...}` and, at the two later stages, the
+// `bank`. It answers `{}` to let the run go on, `{"bank": ...}` to go on with the bank it returns, or `{"veto": "..."}`
+// to stop the run with its reason. Its `warnings` are reported as `warning[hook]` diagnostics.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Stage {
+    AfterDownload,
+    AfterParse,
+    BeforeExport,
+}
+
+impl Stage {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Stage::AfterDownload => "after-download",
+            Stage::AfterParse => "after-parse",
+            Stage::BeforeExport => "before-export",
+        }
+    }
+}
+
+/// The hooks of a run, in the order they run
+pub(crate) struct Hooks {
+    hooks: Vec<Plugin>,
+}
+
+impl Hooks {
+    /// The hook plugins named by `--hook`.
+    pub(crate) fn load(options: &ExtractOptions) -> Result<Self, OutputError> {
+        if options.hook.is_empty() {
+            return Ok(Hooks { hooks: Vec::new() });
+        }
+        let mut plugins = plugin::hook_plugins(options)?;
+        let mut hooks = Vec::new();
+        for name in &options.hook {
+            let Some(index) = plugins.iter().position(|plugin| &plugin.name == name) else {
+                return Err(OutputError {
+                    message: format!("No hook plugin named {} in the plugin directory", name),
+                });
+            };
+            hooks.push(plugins.remove(index));
+        }
+        Ok(Hooks { hooks })
+    }
+
+    pub(crate) fn has(&self, stage: Stage) -> bool {
+        self.hooks.iter().any(|hook| hook.stages.contains(&stage))
+    }
+
+    /// Calls the hooks of `stage` one after the other, each on the bank the previous one left. Returns the bank to go
+    /// on with, or an error when a hook vetoed the run.
+    fn call(
+        &self,
+        stage: Stage,
+        pdf_path: &str,
+        mut bank: Option<Bank>,
+        progress: &mut dyn Progress,
+    ) -> Result<Option<Bank>, OutputError> {
+        for hook in self.hooks.iter().filter(|hook| hook.stages.contains(&stage)) {
+            let mut request = json!({ "stage": stage, "pdf": pdf_path });
+            if let Some(bank) = &bank {
+                request["bank"] = serde_json::to_value(bank)?;
+            }
+            let response = hook.call("hook", request)?;
+            let warnings = response.get("warnings").and_then(Value::as_array).into_iter().flatten();
+            for warning in warnings.filter_map(Value::as_str) {
+                progress.diagnostic("warning", "hook", format!("{}: {}", hook.name, warning));
+            }
+            if let Some(reason) = response.get("veto") {
+                let reason = reason.as_str().map_or_else(|| reason.to_string(), str::to_string);
+                return Err(OutputError {
+                    message: format!("Hook {} stopped the run {}: {}", hook.name, stage.as_str(), reason),
+                });
+            }
+            if let Some(returned) = response.get("bank").filter(|_| bank.is_some()) {
+                let returned: Bank = serde_json::from_value(returned.clone()).map_err(|e| OutputError {
+                    message: format!("Hook {} returned an invalid bank: {}", hook.name, e),
+                })?;
+                progress.info(format!(
+                    "Hook {} changed the bank {} ({} questions)",
+                    hook.name,
+                    stage.as_str(),
+                    returned.questions.len()
+                ));
+                bank = Some(returned);
+            }
+        }
+        Ok(bank)
+    }
+
+    /// Runs the `after-download` hooks on the PDF.
+    pub(crate) fn after_download(&self, pdf_path: &str, progress: &mut dyn Progress) -> Result<(), OutputError> {
+        self.call(Stage::AfterDownload, pdf_path, None, progress).map(|_| ())
+    }
+
+    /// Runs the hooks of `stage` on the bank.
+    pub(crate) fn run(
+        &self,
+        stage: Stage,
+        pdf_path: &str,
+        bank: Bank,
+        progress: &mut dyn Progress,
+    ) -> Result<Bank, OutputError> {
+        Ok(self.call(stage, pdf_path, Some(bank), progress)?.unwrap_or_default())
+    }
+}
@@ -0,0 +1,145 @@
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+
+// HTML remnants in dumps converted from web pages: inline tags (`<b>`, `<i>`, `<span style=...>`), line breaks and
+// character entities (`&nbsp;`, `&amp;`, `&#8217;`). Tags are dropped, or replaced by a space where they separate
+// words (line breaks, paragraphs, cells), and entities are decoded. Only known HTML tag names are touched, so
+// placeholders such as "<warehouse number>" in a stem stay. The text inside bold, italic and underline tags is the
+// emphasis the original page gave it, which the parser keeps on the question.
+
+lazy_static! {
+    static ref TAG_REGEX: Regex = Regex::new(concat!(
+        r"(?i)<(/?)(a|b|big|br|code|div|em|font|h[1-6]|hr|i|img|li|mark|ol|p|pre|small|span|strong|sub|sup|table|td",
+        r"|th|tr|u|ul)\b[^<>]*>"
+    ))
+    .unwrap();
+    static ref ENTITY_REGEX: Regex =
+        Regex::new(r"&(?:#(\d{1,7})|#[xX]([0-9a-fA-F]{1,6})|([a-zA-Z][a-zA-Z0-9]{1,7}));").unwrap();
+}
+
+/// Tags whose text is emphasized
+const EMPHASIS_TAGS: &[&str] = &["b", "strong", "i", "em", "u"];
+/// Tags separating the text around them
+const BREAKING_TAGS: &[&str] = &["br", "div", "hr", "li", "ol", "p", "table", "td", "th", "tr", "ul"];
+
+fn named_entity(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "nbsp" | "ensp" | "emsp" | "thinsp" => " ",
+        "amp" => "&",
+        "lt" => "<",
+        "gt" => ">",
+        "quot" => "\"",
+        "apos" => "'",
+        "shy" | "zwsp" => "",
+        "lsquo" => "\u{2018}",
+        "rsquo" => "\u{2019}",
+        "sbquo" => "\u{201a}",
+        "ldquo" => "\u{201c}",
+        "rdquo" => "\u{201d}",
+        "bdquo" => "\u{201e}",
+        "laquo" => "\u{ab}",
+        "raquo" => "\u{bb}",
+        "ndash" => "\u{2013}",
+        "mdash" => "\u{2014}",
+        "hellip" => "\u{2026}",
+        "bull" => "\u{2022}",
+        "middot" => "\u{b7}",
+        "copy" => "\u{a9}",
+        "reg" => "\u{ae}",
+        "trade" => "\u{2122}",
+        "deg" => "\u{b0}",
+        "euro" => "\u{20ac}",
+        "rarr" => "\u{2192}",
+        "auml" => "ä",
+        "ouml" => "ö",
+        "uuml" => "ü",
+        "Auml" => "Ä",
+        "Ouml" => "Ö",
+        "Uuml" => "Ü",
+        "szlig" => "ß",
+        "eacute" => "é",
+        "egrave" => "è",
+        "ecirc" => "ê",
+        "agrave" => "à",
+        "acirc" => "â",
+        "ccedil" => "ç",
+        "icirc" => "î",
+        "ocirc" => "ô",
+        "ugrave" => "ù",
+        "ucirc" => "û",
+        "Eacute" => "É",
+        _ => return None,
+    })
+}
+
+/// Decodes character entities, leaving unknown ones as written.
+pub(crate) fn decode_entities(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    ENTITY_REGEX.replace_all(text, |caps: &Captures| {
+        let code = match (caps.get(1), caps.get(2)) {
+            (Some(decimal), _) => decimal.as_str().parse().ok(),
+            (_, Some(hex)) => u32::from_str_radix(hex.as_str(), 16).ok(),
+            _ => None,
+        };
+        match (code, caps.get(3)) {
+            (Some(code), _) => match code {
+                0xa0 => " ".to_string(),
+                _ => char::from_u32(code).map_or_else(|| caps[0].to_string(), String::from),
+            },
+            (None, Some(name)) => named_entity(name.as_str()).map_or_else(|| caps[0].to_string(), str::to_string),
+            _ => caps[0].to_string(),
+        }
+    })
+}
+
+/// Drops HTML tags and decodes entities, borrowing from `text` when it has neither.
+pub(crate) fn strip_markup(text: &str) -> Cow<'_, str> {
+    if !text.contains(['<', '&']) {
+        return Cow::Borrowed(text);
+    }
+    let stripped = TAG_REGEX.replace_all(text, |caps: &Captures| {
+        let name = caps[2].to_ascii_lowercase();
+        if BREAKING_TAGS.contains(&name.as_str()) {
+            " "
+        } else {
+            ""
+        }
+    });
+    if let Cow::Owned(decoded) = decode_entities(&stripped) {
+        return Cow::Owned(decoded);
+    }
+    stripped
+}
+
+/// Texts set in bold, italics or underlined, decoded and without the tags nested inside.
+pub(crate) fn emphasized(text: &str) -> Vec<String> {
+    let mut emphasized = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for caps in TAG_REGEX.captures_iter(text) {
+        let Some(tag) = caps.get(0) else {
+            continue;
+        };
+        if !EMPHASIS_TAGS.contains(&caps[2].to_ascii_lowercase().as_str()) {
+            continue;
+        }
+        if caps[1].is_empty() {
+            if depth == 0 {
+                start = tag.end();
+            }
+            depth += 1;
+        } else if depth > 0 {
+            depth -= 1;
+            if depth == 0 {
+                let inner = strip_markup(&text[start..tag.start()]);
+                let inner = inner.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !inner.is_empty() && !emphasized.contains(&inner) {
+                    emphasized.push(inner);
+                }
+            }
+        }
+    }
+    emphasized
+}
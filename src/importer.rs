@@ -0,0 +1,148 @@
+use clap::{Args, ValueEnum};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::anki;
+use crate::crypt::Crypto;
+use crate::forum;
+use crate::meta::BankMeta;
+use crate::progress::Console;
+use crate::spreadsheet::{self, Mapping};
+use crate::validate::ValidationOptions;
+use crate::{annotate_questions, save_to_json, validate_questions, Bank, OutputError};
+
+// Banks kept in other tools, brought into the bank format so they can be validated, merged (`merge`) and exported
+// with the extracted ones. The format is taken from the file extension unless `--format` names it:
+// - `anki`: a deck exported from Anki as a deck package (`.apkg`, `.colpkg`) or as notes in plain text (`.txt`),
+//   read by `anki::read_deck`. A note becomes a question when its front has the stem followed by lettered choices
+//   and its back names the correct ones; other notes (cloze deletions, plain flashcards) are skipped and counted.
+// - `html`: pages saved from an ExamTopics-style discussion forum, one page or a directory of them, read by
+//   `forum::read_page`. The answer the site suggests is the official one, the votes go to `community_answer`.
+// - `csv`: a spreadsheet exported as CSV or TSV, its columns named by the `--mapping` file, read by
+//   `spreadsheet::read_sheet`.
+//
+// The questions are numbered in the order of the file, annotated and validated like the questions of an extraction.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ImportFormat {
+    /// Anki deck package (.apkg, .colpkg) or notes in plain text (.txt)
+    Anki,
+    /// Saved pages of an ExamTopics-style discussion forum (.html), or a directory of them
+    Html,
+    /// Spreadsheet exported as CSV or TSV, laid out as the --mapping file says
+    Csv,
+}
+
+impl ImportFormat {
+    fn detect(path: &Path) -> Option<Self> {
+        if path.is_dir() {
+            return Some(ImportFormat::Html);
+        }
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "apkg" | "colpkg" | "txt" => Some(ImportFormat::Anki),
+            "html" | "htm" => Some(ImportFormat::Html),
+            "csv" | "tsv" => Some(ImportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// File to import, or a directory of saved pages
+    input: String,
+    /// Where to write the bank
+    output: String,
+    /// Format of the file, taken from its extension by default
+    #[arg(long, value_enum)]
+    format: Option<ImportFormat>,
+    /// TOML file naming the columns of a spreadsheet (question, choices, answer, ...), for --format csv
+    #[arg(long)]
+    mapping: Option<String>,
+    /// Title of the bank, the file name by default
+    #[arg(long)]
+    title: Option<String>,
+    /// Exam code of the bank (e.g. C_S4EWM_2020)
+    #[arg(long)]
+    exam_code: Option<String>,
+    /// Fail instead of warning when validation finds errors
+    #[arg(long)]
+    strict: bool,
+}
+
+/// The page itself, or the saved pages of a directory in name order.
+fn pages(path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut pages = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let page = entry?.path();
+        let extension = page.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        if matches!(extension.to_lowercase().as_str(), "html" | "htm") {
+            pages.push(page);
+        }
+    }
+    pages.sort();
+    Ok(pages)
+}
+
+pub(crate) fn run(args: ImportArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(&args.input);
+    let Some(format) = args.format.or_else(|| ImportFormat::detect(path)) else {
+        let message = format!("Cannot tell the format of {}, name it with --format", args.input);
+        return Err(OutputError { message }.into());
+    };
+
+    let results: Vec<Result<_, &str>> = match format {
+        ImportFormat::Anki => anki::read_deck(path)?.iter().map(anki::note_question).collect(),
+        ImportFormat::Html => {
+            let mut results = Vec::new();
+            for page in pages(path)? {
+                results.extend(forum::read_page(&fs::read_to_string(&page)?));
+            }
+            results
+        }
+        ImportFormat::Csv => {
+            let mapping = args.mapping.as_deref().ok_or("Importing a spreadsheet needs a --mapping file")?;
+            spreadsheet::read_sheet(path, &Mapping::load(mapping)?)?
+        }
+    };
+
+    let mut questions = Vec::new();
+    // Notes, page blocks and rows that are not questions, by reason
+    let mut skipped: BTreeMap<&str, usize> = BTreeMap::new();
+    for result in results {
+        match result {
+            Ok(question) => questions.push(question),
+            Err(reason) => *skipped.entry(reason).or_default() += 1,
+        }
+    }
+    for (number, question) in (1..).zip(questions.iter_mut()) {
+        question.number = number.to_string();
+        if !question.answers.is_empty() {
+            question.correct_answers = Some(question.answers.len());
+        }
+    }
+    annotate_questions(&mut questions);
+
+    println!("Imported {} questions from {}", questions.len(), args.input);
+    for (reason, count) in &skipped {
+        println!("Skipped {} item(s): {}", count, reason);
+    }
+    let title = args.title.or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()));
+    let bank = Bank {
+        meta: BankMeta {
+            title,
+            exam_code: args.exam_code,
+            ..BankMeta::default()
+        },
+        questions,
+    };
+    validate_questions(&bank, &ValidationOptions::default(), args.strict, &mut Console::new())?;
+    save_to_json(&bank, &args.output, crypto)?;
+    Ok(())
+}
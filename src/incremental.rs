@@ -0,0 +1,115 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::mem;
+
+use crate::similarity::{fnv1a, stable_id};
+use crate::{Bank, Question};
+
+// Incremental extraction of a revised dump. Every page is still extracted and parsed, since numbering, locations and
+// removed questions come from the whole dump. Parsed questions are then matched against the previous bank by their
+// stable id; the ones found there are taken over as they are, so corrections made during review survive, and only new
+// or changed questions go through answer detection and annotation. Page hashes recorded in the bank header tell which
+// pages changed, which limits the styled-text pass to those pages and the pages of new questions.
+
+/// Changes of a revised dump relative to the previous bank.
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct Delta {
+    /// Pages (1-based) whose text differs from the previous extraction
+    pub(crate) changed_pages: Vec<usize>,
+    /// New or changed questions
+    pub(crate) added: Vec<Question>,
+    /// Ids of previous questions no longer in the dump
+    pub(crate) removed: Vec<String>,
+}
+
+pub(crate) fn page_hash(text: &str) -> String {
+    format!("{:016x}", fnv1a(text.as_bytes()))
+}
+
+pub(crate) fn changed_pages(previous: &Bank, page_hashes: &[String]) -> BTreeSet<usize> {
+    let known: HashSet<&String> = previous.meta.page_hashes.iter().collect();
+    page_hashes.iter().enumerate().filter(|(_, hash)| !known.contains(hash)).map(|(i, _)| i + 1).collect()
+}
+
+fn question_id(question: &Question) -> String {
+    question.id.clone().unwrap_or_else(|| stable_id(question))
+}
+
+/// Replaces parsed questions already present in the previous bank by their previous version, keeping the numbering,
+/// location and explanation of the new extraction. Returns the indices of the questions that are new, and the ids of
+/// previous questions that disappeared.
+pub(crate) fn reuse_previous(previous: &Bank, questions: &mut [Question]) -> (Vec<usize>, Vec<String>) {
+    let mut known: HashMap<String, &Question> = previous.questions.iter().map(|q| (question_id(q), q)).collect();
+
+    let mut fresh = Vec::new();
+    for (index, question) in questions.iter_mut().enumerate() {
+        match known.remove(&stable_id(question)) {
+            Some(old) => {
+                *question = Question {
+                    number: question.number.clone(),
+                    page: question.page,
+                    source_number: question.source_number,
+                    explain: mem::take(&mut question.explain),
+                    ..old.clone()
+                };
+            }
+            None => fresh.push(index),
+        }
+    }
+
+    let mut removed: Vec<String> = known.into_keys().collect();
+    removed.sort();
+    (fresh, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lifecycle::Lifecycle;
+
+    fn question(number: &str, text: &str, page: usize) -> Question {
+        Question {
+            number: number.to_string(),
+            text: text.to_string(),
+            choices: HashMap::from([("A".to_string(), "Yes".to_string()), ("B".to_string(), "No".to_string())]),
+            page: Some(page),
+            ..Question::default()
+        }
+    }
+
+    #[test]
+    fn changed_pages_are_the_ones_with_unknown_hashes() {
+        let mut previous = Bank::default();
+        previous.meta.page_hashes = ["cover", "page one", "page two"].iter().map(|text| page_hash(text)).collect();
+        let revised: Vec<String> =
+            ["cover", "page two", "page one revised", "page three"].iter().map(|text| page_hash(text)).collect();
+        // a page that only moved keeps its hash
+        assert_eq!(changed_pages(&previous, &revised), BTreeSet::from([3, 4]));
+        assert!(changed_pages(&previous, &previous.meta.page_hashes).is_empty());
+    }
+
+    #[test]
+    fn known_questions_keep_their_reviewed_version() {
+        let mut reviewed = question("1", "Which warehouse process types exist?", 2);
+        reviewed.answers = vec!["A".to_string()];
+        reviewed.lifecycle = Lifecycle::NeedsReview;
+        reviewed.id = Some(stable_id(&reviewed));
+        let gone = question("2", "What is a storage bin?", 3);
+        let previous = Bank {
+            questions: vec![reviewed.clone(), gone.clone()],
+            ..Bank::default()
+        };
+
+        let mut moved = question("5", "Which warehouse process types exist?", 4);
+        moved.source_number = Some(7);
+        let mut questions = vec![question("4", "What is a handling unit?", 4), moved];
+        let (fresh, removed) = reuse_previous(&previous, &mut questions);
+
+        assert_eq!(fresh, [0]);
+        assert_eq!(removed, [stable_id(&gone)]);
+        assert_eq!(questions[0].text, "What is a handling unit?");
+        let kept = &questions[1];
+        assert_eq!((kept.number.as_str(), kept.page, kept.source_number), ("5", Some(4), Some(7)));
+        assert_eq!((&kept.answers, &kept.lifecycle, &kept.id), (&reviewed.answers, &reviewed.lifecycle, &reviewed.id));
+    }
+}
@@ -0,0 +1,129 @@
+use clap::ValueEnum;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::config::PROJECT_FILE;
+use crate::export::Format;
+use crate::profile::{Profile, PROFILES};
+use crate::tree::Layout;
+use crate::{is_url, ExtractOptions, OutputError};
+
+// First-run wizard: asks for the input PDF or URL, the parsing profile and the output formats on the terminal and
+// writes them to the project config file, so later runs are a plain `S4WM-extract` in the same directory. Empty
+// answers keep the defaults, invalid ones are asked again.
+
+/// Options the wizard asks for
+const ASKED: &[&str] = &["input", "profile", "layout", "formats"];
+
+/// Prints `question` and reads one answer line, trimmed. Fails when the input is closed.
+fn ask(input: &mut impl BufRead, question: &str) -> Result<String, Box<dyn Error>> {
+    print!("{}: ", question);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(OutputError::from("Input closed before the setup was complete").into());
+    }
+    Ok(line.trim().to_string())
+}
+
+/// Asks until `parse` accepts the answer.
+fn ask_until<T>(
+    input: &mut impl BufRead,
+    question: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<T, Box<dyn Error>> {
+    loop {
+        match parse(&ask(input, question)?) {
+            Ok(value) => return Ok(value),
+            Err(message) => println!("  {}", message),
+        }
+    }
+}
+
+fn ask_yes_no(input: &mut impl BufRead, question: &str, default: bool) -> Result<bool, Box<dyn Error>> {
+    let hint = if default { "Y/n" } else { "y/N" };
+    ask_until(input, &format!("{} [{}]", question, hint), |answer| {
+        match answer.to_lowercase().as_str() {
+            "" => Ok(default),
+            "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            _ => Err("Please answer y or n".to_string()),
+        }
+    })
+}
+
+fn parse_input(answer: &str) -> Result<Option<String>, String> {
+    if answer.is_empty() {
+        Ok(None)
+    } else if is_url(answer) || Path::new(answer).is_file() {
+        Ok(Some(answer.to_string()))
+    } else {
+        Err(format!("{} is neither an existing file nor an http(s) URL", answer))
+    }
+}
+
+fn parse_profile(answer: &str) -> Result<Option<String>, String> {
+    if answer.is_empty() {
+        return Ok(None);
+    }
+    Profile::by_name(answer)
+        .map(|profile| Some(profile.name.to_string()))
+        .ok_or_else(|| format!("Unknown profile {}", answer))
+}
+
+fn parse_formats(answer: &str) -> Result<Vec<String>, String> {
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| match Format::from_str(name, true) {
+            Ok(_) => Ok(name.to_lowercase()),
+            Err(_) => Err(format!("Unknown format {}", name)),
+        })
+        .collect()
+}
+
+/// Runs the wizard on the terminal. Returns whether the extraction should run now with the written config.
+pub(crate) fn run() -> Result<bool, Box<dyn Error>> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    if Path::new(PROJECT_FILE).exists()
+        && !ask_yes_no(&mut input, &format!("{} already exists, overwrite it?", PROJECT_FILE), false)?
+    {
+        return Ok(false);
+    }
+
+    let profile_names: Vec<&str> = PROFILES.iter().map(|profile| profile.name).collect();
+    let options = ExtractOptions {
+        input: ask_until(&mut input, "PDF file or URL to extract (empty for the C_S4EWM_2020 dump)", parse_input)?,
+        profile: ask_until(
+            &mut input,
+            &format!("Parsing profile ({}; empty to detect from the language)", profile_names.join(", ")),
+            parse_profile,
+        )?,
+        layout: ask_until(&mut input, "Output layout (single, per-question) [single]", |answer| {
+            if answer.is_empty() {
+                Ok(Layout::Single)
+            } else {
+                Layout::from_str(answer, true).map_err(|_| format!("Unknown layout {}", answer))
+            }
+        })?,
+        formats: ask_until(
+            &mut input,
+            "Additional output formats, comma-separated (markdown, html, gift, qti, anki; empty for JSON only)",
+            parse_formats,
+        )?,
+        ..ExtractOptions::default()
+    };
+
+    // only the answered options, so the user config file still applies to the others
+    let mut table = toml::Table::try_from(&options)?;
+    table.retain(|key, value| {
+        ASKED.contains(&key) && !matches!(value, toml::Value::Array(values) if values.is_empty())
+    });
+    fs::write(PROJECT_FILE, toml::to_string_pretty(&table)?)?;
+    println!("Wrote {}", PROJECT_FILE);
+    ask_yes_no(&mut input, "Run the extraction now?", true)
+}
@@ -0,0 +1,291 @@
+use clap::Args;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+use crate::crypt::Crypto;
+use crate::history::{self, HistoryArgs};
+use crate::similarity::{jaccard, token_set};
+use crate::{load_from_json, open_bank, save_to_json, OutputError, Question};
+
+// Merging an answer key into a bank. Keys are matched to questions by number, but the numbering of a key often drifts
+// from the questions (a question skipped or split in one of the dumps shifts every following answer by one), which a
+// number-only merge turns into silently wrong answers. When the key repeats the stems, every number match is checked
+// against the stem and a question whose numbered key entry does not fit is aligned to the entry with the most similar
+// stem instead. Every question gets the method and confidence of its alignment in the report.
+
+lazy_static! {
+    /// A line of a plain text key: "12. B", "12) A, C", "12 B and D", "12 BD"
+    static ref KEY_LINE_REGEX: Regex =
+        Regex::new(r"^(?:Question\s+)?(\d+)\s*[.:)\-]?\s+([A-Z](?:\s*(?:,|&|/|and|und|et)?\s*[A-Z])*)$").unwrap();
+}
+
+/// Stems at least this similar (token Jaccard) belong to the same question
+const ALIGNMENT_THRESHOLD: f64 = 0.6;
+/// Alignments below this confidence are reported as warnings
+const LOW_CONFIDENCE: f64 = 0.8;
+
+#[derive(Args, Debug)]
+pub struct KeyArgs {
+    /// Question bank to merge the answers into
+    input: String,
+    /// Answer key: a bank whose questions carry the answers, or a text file with one "<number> <letters>" per line
+    key: String,
+    /// Where to write the bank with the merged answers
+    output: String,
+    /// Write the alignment of every question to this JSON file
+    #[arg(long, value_name = "JSON")]
+    report: Option<String>,
+    #[command(flatten)]
+    history: HistoryArgs,
+}
+
+/// An answer of the key
+#[derive(Debug, Clone)]
+pub(crate) struct KeyEntry {
+    pub(crate) number: String,
+    /// Stem the key repeats, empty for plain text keys
+    pub(crate) text: String,
+    pub(crate) answers: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Method {
+    /// Same number, and the stems match when the key has them
+    Number,
+    /// The numbered entry did not fit, the entry with the most similar stem was taken
+    Text,
+    /// No entry fits, the question keeps its answers
+    Unmatched,
+}
+
+/// How one question was aligned to the key
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct Alignment {
+    pub(crate) number: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) id: Option<String>,
+    pub(crate) method: Method,
+    /// Number of the key entry the answers were taken from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) key_number: Option<String>,
+    /// Stem similarity of the alignment between 0 and 1; absent when the key has no stems to compare
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) confidence: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) answers: Vec<String>,
+    /// Answers the question had before the merge when the key changed them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) replaced: Vec<String>,
+}
+
+/// Reads a key from a saved bank, or from a text file with one answer per line.
+pub(crate) fn load_key(path: &str, crypto: &Crypto) -> Result<Vec<KeyEntry>, OutputError> {
+    if !path.ends_with(".txt") {
+        let bank = load_from_json(path, crypto)?;
+        return Ok(bank
+            .questions
+            .into_iter()
+            .filter(|q| !q.answers.is_empty())
+            .map(|q| KeyEntry {
+                number: q.source_number.map_or(q.number, |n| n.to_string()),
+                text: q.text,
+                answers: q.answers,
+            })
+            .collect());
+    }
+    let text = fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .filter_map(|line| KEY_LINE_REGEX.captures(line.trim()))
+        .map(|caps| KeyEntry {
+            number: caps[1].to_string(),
+            text: String::new(),
+            answers: caps[2].chars().filter(char::is_ascii_uppercase).map(String::from).collect(),
+        })
+        .collect())
+}
+
+/// Number the question was printed with, which is what keys refer to.
+fn printed_number(question: &Question) -> String {
+    question.source_number.map_or_else(|| question.number.clone(), |n| n.to_string())
+}
+
+/// Aligns every question with a key entry and takes over its answers, returning the alignment of each question.
+pub(crate) fn merge(questions: &mut [Question], key: &[KeyEntry]) -> Vec<Alignment> {
+    let key_tokens: Vec<HashSet<String>> = key.iter().map(|entry| token_set(&entry.text)).collect();
+    let mut alignments = Vec::with_capacity(questions.len());
+
+    for question in questions.iter_mut() {
+        let number = printed_number(question);
+        let numbered = key.iter().position(|entry| entry.number == number);
+        let tokens = token_set(&question.text);
+        let similarity = |index: usize| (!key[index].text.is_empty()).then(|| jaccard(&tokens, &key_tokens[index]));
+
+        let (method, index, confidence) = match numbered.map(|index| (index, similarity(index))) {
+            Some((index, None)) => (Method::Number, Some(index), None),
+            Some((index, Some(score))) if score >= ALIGNMENT_THRESHOLD => (Method::Number, Some(index), Some(score)),
+            _ => {
+                let best = (0..key.len())
+                    .filter_map(|index| Some((index, similarity(index)?)))
+                    .max_by(|a, b| a.1.total_cmp(&b.1));
+                match best {
+                    Some((index, score)) if score >= ALIGNMENT_THRESHOLD => (Method::Text, Some(index), Some(score)),
+                    best => (Method::Unmatched, None, best.map(|(_, score)| score)),
+                }
+            }
+        };
+
+        let mut alignment = Alignment {
+            number: question.number.clone(),
+            id: question.id.clone(),
+            method,
+            key_number: index.map(|index| key[index].number.clone()),
+            confidence,
+            answers: question.answers.clone(),
+            replaced: Vec::new(),
+        };
+        if let Some(index) = index {
+            let answers = &key[index].answers;
+            if !question.answers.is_empty() && &question.answers != answers {
+                alignment.replaced = question.answers.clone();
+            }
+            question.answers = answers.clone();
+            question.correct_answers = Some(answers.len());
+            alignment.answers = answers.clone();
+        }
+        alignments.push(alignment);
+    }
+    alignments
+}
+
+/// Findings worth a look: questions left unmatched, weak alignments, and answer letters the question has no choice for.
+fn warnings(questions: &[Question], alignments: &[Alignment]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (question, alignment) in questions.iter().zip(alignments) {
+        let key_number = alignment.key_number.as_deref().unwrap_or_default();
+        match (alignment.method, alignment.confidence) {
+            (Method::Unmatched, Some(score)) => warnings.push(format!(
+                "question {}: no key entry fits, the closest stem is {:.0}% similar",
+                alignment.number,
+                score * 100.0
+            )),
+            (Method::Unmatched, None) => {
+                warnings.push(format!("question {}: no key entry with this number", alignment.number))
+            }
+            (Method::Text, _) => warnings.push(format!(
+                "question {}: aligned by stem to key entry {} instead of its number",
+                alignment.number, key_number
+            )),
+            (Method::Number, Some(score)) if score < LOW_CONFIDENCE => warnings.push(format!(
+                "question {}: stem only {:.0}% similar to key entry {}",
+                alignment.number,
+                score * 100.0,
+                key_number
+            )),
+            _ => {}
+        }
+        let unknown: Vec<&str> = alignment
+            .answers
+            .iter()
+            .filter(|letter| !question.choices.is_empty() && !question.choices.contains_key(*letter))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            warnings.push(format!(
+                "question {}: no choice {} for the key's answer",
+                alignment.number,
+                unknown.join(", ")
+            ));
+        }
+    }
+    warnings
+}
+
+pub(crate) fn run(args: KeyArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    let key = load_key(&args.key, &crypto)?;
+    if key.is_empty() {
+        return Err(OutputError::from("The answer key has no answers").into());
+    }
+
+    let before = args.history.history.as_ref().map(|_| bank.questions.clone());
+    let alignments = merge(&mut bank.questions, &key);
+    for warning in warnings(&bank.questions, &alignments) {
+        eprintln!("warning[key-alignment] {}", warning);
+    }
+    let count = |method: Method| alignments.iter().filter(|a| a.method == method).count();
+    println!(
+        "{} question(s) aligned by number, {} by stem, {} unmatched; {} answer(s) changed",
+        count(Method::Number),
+        count(Method::Text),
+        count(Method::Unmatched),
+        alignments.iter().filter(|a| !a.replaced.is_empty()).count()
+    );
+
+    save_to_json(&bank, &args.output, &crypto)?;
+    if let Some(path) = &args.report {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &alignments)?;
+    }
+    if let Some(before) = &before {
+        history::record(&args.history, "key", &args.key, before, &bank.questions, &crypto)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(number: usize, text: &str, answers: &[&str]) -> Question {
+        Question {
+            number: number.to_string(),
+            source_number: Some(number),
+            text: text.to_string(),
+            answers: answers.iter().map(|a| a.to_string()).collect(),
+            ..Question::default()
+        }
+    }
+
+    fn entry(number: usize, text: &str, answers: &[&str]) -> KeyEntry {
+        KeyEntry {
+            number: number.to_string(),
+            text: text.to_string(),
+            answers: answers.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    const MONITOR: &str = "Which transaction shows the warehouse management monitor?";
+    const SEARCH: &str = "Which transaction defines the storage type search sequence?";
+    const WAVE: &str = "Which object groups warehouse requests for picking?";
+
+    #[test]
+    fn shifted_key_is_aligned_by_stem() {
+        let mut questions = vec![question(1, MONITOR, &[]), question(2, SEARCH, &["A"])];
+        // the key skipped a question, its numbers run one ahead from there
+        let key = [entry(1, MONITOR, &["A"]), entry(2, WAVE, &["C"]), entry(3, SEARCH, &["B"])];
+        let alignments = merge(&mut questions, &key);
+        assert_eq!(alignments[0].method, Method::Number);
+        assert_eq!(alignments[1].method, Method::Text);
+        assert_eq!(alignments[1].key_number.as_deref(), Some("3"));
+        assert_eq!(alignments[1].replaced, ["A"]);
+        assert_eq!(questions[0].answers, ["A"]);
+        assert_eq!(questions[1].answers, ["B"]);
+    }
+
+    #[test]
+    fn key_without_stems_is_aligned_by_number() {
+        let mut questions = vec![question(1, MONITOR, &[]), question(2, SEARCH, &["B"])];
+        let alignments = merge(&mut questions, &[entry(1, "", &["A", "C"])]);
+        assert_eq!(alignments[0].method, Method::Number);
+        assert_eq!(alignments[0].confidence, None);
+        assert_eq!(questions[0].answers, ["A", "C"]);
+        assert_eq!(questions[0].correct_answers, Some(2));
+        assert_eq!(alignments[1].method, Method::Unmatched);
+        assert_eq!(questions[1].answers, ["B"]);
+    }
+}
@@ -0,0 +1,34 @@
+use regex::Regex;
+
+// Emphasis-bearing keywords in stems. Dumps lose the bold or capitalized formatting these words had in the original
+// exam, which makes negated questions easy to misread; recording them lets exports highlight them again.
+
+lazy_static! {
+    static ref KEYWORD_REGEX: Regex = Regex::new(
+        r"(?i)\b(not|except|false|incorrect|untrue|least|never|nicht|kein(?:e|en)?|falsch|außer|pas|sauf|faux|incorrecte?s?)\b"
+    )
+    .unwrap();
+}
+
+/// Keywords found in the stem as written, each reported once.
+pub(crate) fn detect(stem: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    for m in KEYWORD_REGEX.find_iter(stem) {
+        if !keywords.iter().any(|k| k.eq_ignore_ascii_case(m.as_str())) {
+            keywords.push(m.as_str().to_string());
+        }
+    }
+    keywords
+}
+
+/// Wraps every occurrence of the keywords in `open`/`close`.
+pub(crate) fn highlight(text: &str, keywords: &[String], open: &str, close: &str) -> String {
+    if keywords.is_empty() {
+        return text.to_string();
+    }
+    let alternatives: Vec<String> = keywords.iter().map(|k| regex::escape(k)).collect();
+    match Regex::new(&format!(r"(?i)\b({})\b", alternatives.join("|"))) {
+        Ok(regex) => regex.replace_all(text, format!("{}$1{}", open, close).as_str()).into_owned(),
+        Err(_) => text.to_string(),
+    }
+}
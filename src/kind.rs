@@ -0,0 +1,143 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::Question;
+
+// Question kinds and the heuristics classifying parsed questions. The kind decides how the answer is represented:
+// - multiple_choice / multiple_select: `answers` holds the correct choice letters
+// - true_false: `truth` holds the correct value
+// - ordering: `order` holds all choice letters in the correct sequence, `answers` stays empty
+// - matching: `premises` holds the left column, `choices` the right one and `pairs` the correct mapping
+
+lazy_static! {
+    static ref ORDERING_REGEX: Regex =
+        Regex::new(r"(?i)\b(arrange|correct\s+(order|sequence)|in\s+(the\s+)?(right|correct)\s+order|reihenfolge|ordre)\b")
+            .unwrap();
+    static ref TRUE_FALSE_STEM_REGEX: Regex = Regex::new(r"(?i)^\s*(true\s+or\s+false|richtig\s+oder\s+falsch|vrai\s+ou\s+faux)\b").unwrap();
+    /// Instructions to match items ("Match each process to...", "Associez...", "Ordnen Sie ... zu"), not stems merely
+    /// using the words ("associated with", "matches the definition")
+    static ref MATCHING_STEM_REGEX: Regex = Regex::new(concat!(
+        r"(?i)\b(?:match|associate)\s+(?:each|every|all|the\s+following)\b",
+        r"|(?:^|[.:?!]\s+)(?:match|associate)\s+(?:the|these)\b",
+        r"|\bassociez\b|\bfaites\s+correspondre\b|\bordnen\s+sie\b[^.?!]*\bzu\b"
+    ))
+    .unwrap();
+    static ref CHOOSE_REGEX: Regex =
+        Regex::new(r"(?i)\bchoose\s+(two|three|four|2|3|4)\b|\bthere\s+are\s+(two|three|four|2|3|4)\s+correct\s+answers\b")
+            .unwrap();
+}
+
+const TRUE_WORDS: &[&str] = &["true", "richtig", "wahr", "vrai"];
+const FALSE_WORDS: &[&str] = &["false", "falsch", "faux"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum QuestionKind {
+    #[default]
+    MultipleChoice,
+    MultipleSelect,
+    TrueFalse,
+    Ordering,
+    Matching,
+}
+
+pub(crate) fn is_matching_stem(stem: &str) -> bool {
+    MATCHING_STEM_REGEX.is_match(stem)
+}
+
+fn number_word(word: &str) -> Option<usize> {
+    match word.to_lowercase().as_str() {
+        "two" | "2" => Some(2),
+        "three" | "3" => Some(3),
+        "four" | "4" => Some(4),
+        _ => None,
+    }
+}
+
+/// Number of answers a "(Choose two)" style instruction asks for.
+fn stated_answer_count(stem: &str) -> Option<usize> {
+    let caps = CHOOSE_REGEX.captures(stem)?;
+    caps.iter().skip(1).flatten().next().and_then(|m| number_word(m.as_str()))
+}
+
+pub(crate) fn truth_value(text: &str) -> Option<bool> {
+    let text = text.trim().trim_end_matches('.').to_lowercase();
+    if TRUE_WORDS.contains(&text.as_str()) {
+        Some(true)
+    } else if FALSE_WORDS.contains(&text.as_str()) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Gives a question imported without choices the choices True and False, `truth` being the correct one.
+pub(crate) fn true_false_choices(question: &mut Question, truth: bool) {
+    let choices = [("A", "True"), ("B", "False")];
+    question.choices = choices.iter().map(|(letter, text)| (letter.to_string(), text.to_string())).collect();
+    question.answers = vec![String::from(if truth { "A" } else { "B" })];
+}
+
+/// Sets `kind` and moves the answer into its kind-specific representation.
+pub(crate) fn classify(question: &mut Question) {
+    if question.kind == QuestionKind::Matching {
+        return;
+    }
+
+    let true_false_choices = question.choices.len() == 2 && question.choices.values().all(|c| truth_value(c).is_some());
+    if true_false_choices || (question.choices.is_empty() && TRUE_FALSE_STEM_REGEX.is_match(&question.text)) {
+        question.kind = QuestionKind::TrueFalse;
+        question.truth = match question.answers.first() {
+            Some(letter) => question.choices.get(letter).map_or_else(|| truth_value(letter), |c| truth_value(c)),
+            None => None,
+        };
+        return;
+    }
+
+    if ORDERING_REGEX.is_match(&question.text) && question.answers.len() > 1 {
+        question.kind = QuestionKind::Ordering;
+        question.order = std::mem::take(&mut question.answers);
+        question.correct_answers = None;
+        return;
+    }
+
+    if let Some(count) = stated_answer_count(&question.text) {
+        question.correct_answers = Some(count);
+    }
+    question.kind = if question.answers.len() > 1 || question.correct_answers.is_some_and(|n| n > 1) {
+        QuestionKind::MultipleSelect
+    } else {
+        QuestionKind::MultipleChoice
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_instructions_are_matching_stems() {
+        for stem in [
+            "Match each process step to the transaction that executes it.",
+            "Match the following terms with their definitions.",
+            "Drag and drop the objects into place. Match the objects to their level.",
+            "Associez chaque étape à sa transaction.",
+            "Ordnen Sie die Prozessschritte den Transaktionen zu.",
+        ] {
+            assert!(is_matching_stem(stem), "{}", stem);
+        }
+    }
+
+    #[test]
+    fn stems_using_the_words_are_not_matching_stems() {
+        for stem in [
+            "Which storage type is associated with the goods receipt zone?",
+            "Which description matches the definition of a wave?",
+            "What happens when the quantity does not match the delivery?",
+            "Welche Lagertypen kann man einem Lagerbereich zuordnen?",
+            "Quel objet est associé à la vague ?",
+        ] {
+            assert!(!is_matching_stem(stem), "{}", stem);
+        }
+    }
+}
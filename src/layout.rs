@@ -0,0 +1,163 @@
+use crate::styled::StyledLine;
+
+// Multi-column layout analysis. Plain text extraction of two-column dumps interleaves the columns, which merges
+// unrelated questions. The start positions of a page's lines are clustered on the x axis; when they fall into well
+// separated groups the lines of the page text are put in column order, each column from top to bottom.
+
+/// Horizontal distance in PDF units (1/72 in) separating two columns, wider than any indentation inside a column
+pub(crate) const MIN_COLUMN_GAP: f64 = 100.0;
+/// Share of a page's lines a group of line starts needs to count as a column rather than a stray right-aligned line
+const MIN_COLUMN_SHARE: f64 = 0.2;
+
+/// Left edges of the columns of a page: the sorted line starts split at gaps wider than `MIN_COLUMN_GAP`.
+fn detected_column_starts(lines: &[&StyledLine]) -> Vec<f64> {
+    let mut xs: Vec<f64> = lines.iter().map(|l| l.x).collect();
+    xs.sort_by(f64::total_cmp);
+
+    let mut groups: Vec<Vec<f64>> = Vec::new();
+    for x in xs {
+        match groups.last_mut() {
+            Some(group) if group.last().is_some_and(|&last| x - last <= MIN_COLUMN_GAP) => group.push(x),
+            _ => groups.push(vec![x]),
+        }
+    }
+
+    let min_lines = lines.len() as f64 * MIN_COLUMN_SHARE;
+    groups.into_iter().filter(|g| g.len() as f64 >= min_lines).map(|g| g[0]).collect()
+}
+
+/// Left edges of `columns` equally wide columns spanning the line starts of the page.
+fn forced_column_starts(lines: &[&StyledLine], columns: usize) -> Vec<f64> {
+    let min = lines.iter().map(|l| l.x).fold(f64::INFINITY, f64::min);
+    let max = lines.iter().map(|l| l.x).fold(f64::NEG_INFINITY, f64::max);
+    let step = (max - min) / columns as f64;
+    (0..columns).map(|i| min + step * i as f64).collect()
+}
+
+fn column_of(starts: &[f64], x: f64) -> usize {
+    // small tolerance so lines starting slightly left of their column edge are not pushed into the previous column
+    starts.iter().rposition(|&start| x + 1.0 >= start).unwrap_or(0)
+}
+
+/// Text of a page read column by column, or `None` when the page has a single column or its lines cannot be placed.
+/// `text` is the page text of `pdf_extract`, whose lines are reordered rather than replaced: the styled lines only
+/// decode single-byte fonts, so they give the positions but not the text. Every letter and digit of `text` is traced
+/// back to the styled line it comes from, which takes the same letters and digits in the same order; a page where
+/// they differ, such as one set in a CID font, is left as it is. `columns` forces the number of columns instead of
+/// detecting it.
+pub(crate) fn column_text(lines: &[&StyledLine], columns: Option<usize>, text: &str) -> Option<String> {
+    if lines.is_empty() {
+        return None;
+    }
+    let starts = match columns {
+        Some(columns) => forced_column_starts(lines, columns),
+        None => detected_column_starts(lines),
+    };
+    if starts.len() < 2 {
+        return None;
+    }
+
+    // the styled line every letter and digit of the page comes from
+    let owners: Vec<(char, usize)> = lines
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line)| line.text.chars().filter(char::is_ascii_alphanumeric).map(move |c| (c, i)))
+        .collect();
+    if !owners.iter().map(|&(c, _)| c).eq(text.chars().filter(char::is_ascii_alphanumeric)) {
+        return None;
+    }
+
+    // pieces of the text lines split where their letters move to another column, with the styled line they start in
+    let mut owners = owners.into_iter().map(|(_, owner)| owner);
+    let mut pieces: Vec<(usize, String)> = Vec::new();
+    // text before the first letter of a piece, such as a line of dashes
+    let mut pending = String::new();
+    for text_line in text.lines() {
+        let mut current: Option<usize> = None;
+        for c in text_line.chars() {
+            if c.is_ascii_alphanumeric() {
+                let owner = owners.next()?;
+                let column = column_of(&starts, lines[owner].x);
+                if current.is_none_or(|i| column_of(&starts, lines[pieces[i].0].x) != column) {
+                    pieces.push((owner, std::mem::take(&mut pending)));
+                    current = Some(pieces.len() - 1);
+                }
+            }
+            match current {
+                Some(i) => pieces[i].1.push(c),
+                None => pending.push(c),
+            }
+        }
+        if current.is_none() {
+            pending.push('\n');
+        }
+    }
+    let (_, last) = pieces.last_mut()?;
+    last.push('\n');
+    last.push_str(pending.trim_end());
+
+    // PDF y coordinates grow upwards; pieces of the same styled line keep their order
+    pieces.sort_by(|(a, _), (b, _)| {
+        let (a, b) = (lines[*a], lines[*b]);
+        column_of(&starts, a.x).cmp(&column_of(&starts, b.x)).then(b.y.total_cmp(&a.y)).then(a.x.total_cmp(&b.x))
+    });
+    Some(pieces.iter().map(|(_, piece)| piece.trim()).filter(|piece| !piece.is_empty()).collect::<Vec<_>>().join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str, x: f64, y: f64) -> StyledLine {
+        StyledLine {
+            page: 1,
+            text: text.to_string(),
+            x,
+            y,
+            ..StyledLine::default()
+        }
+    }
+
+    /// Two questions side by side, written row by row as pdf_extract joins them
+    fn two_columns() -> Vec<StyledLine> {
+        vec![
+            line("1. Which tool monitors the warehouse?", 50.0, 700.0),
+            line("2. Which object groups tasks?", 320.0, 700.0),
+            line("A. The monitor", 50.0, 685.0),
+            line("A. A wave", 320.0, 685.0),
+            line("B. The cockpit", 50.0, 670.0),
+            line("B. A queue", 320.0, 670.0),
+        ]
+    }
+
+    #[test]
+    fn interleaved_columns_are_read_one_after_the_other() {
+        let lines = two_columns();
+        let lines: Vec<&StyledLine> = lines.iter().collect();
+        let text = "1. Which tool monitors the warehouse?   2. Which object groups tasks?\n\
+                    A. The monitor   A. A wave\n\
+                    B. The cockpit   B. A queue\n";
+        assert_eq!(
+            column_text(&lines, None, text).as_deref(),
+            Some(
+                "1. Which tool monitors the warehouse?\nA. The monitor\nB. The cockpit\n\
+                 2. Which object groups tasks?\nA. A wave\nB. A queue"
+            )
+        );
+    }
+
+    #[test]
+    fn text_the_styled_lines_do_not_decode_is_left_alone() {
+        // a CID font decoded one byte per glyph, next to the Unicode text of pdf_extract
+        let lines = [line("\u{0}0\u{0}B\u{0}F", 50.0, 700.0), line("\u{0}4\u{0}T", 320.0, 700.0)];
+        let lines: Vec<&StyledLine> = lines.iter().collect();
+        assert_eq!(column_text(&lines, None, "1. 倉庫を監視するツールは？   2. タスク"), None);
+    }
+
+    #[test]
+    fn single_column_pages_are_left_alone() {
+        let lines = [line("1. Which tool monitors the warehouse?", 50.0, 700.0), line("A. The monitor", 50.0, 685.0)];
+        let lines: Vec<&StyledLine> = lines.iter().collect();
+        assert_eq!(column_text(&lines, None, "1. Which tool monitors the warehouse?\nA. The monitor"), None);
+    }
+}
@@ -0,0 +1,99 @@
+use clap::{Args, Subcommand};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::OutputError;
+
+// Study profiles of a quiz site (`site`), for a study group sharing one machine. The profiles are listed in
+// `PROFILES_FILE` next to the questions, which every profile reads; the page offers them in a selector and keeps the
+// score, the drill pool, the bookmarks and the notes of each apart in the browser. `profile create` and `profile
+// delete` edit the list, and the page forgets what it kept for a profile that is no longer listed. A site without
+// profiles keeps a single, unnamed set of state as before.
+//
+// Not to be confused with the parsing profiles of the extraction (`profile`, `--profile de`).
+
+pub(crate) const PROFILES_FILE: &str = "profiles.js";
+const PREFIX: &str = "window.PROFILES = ";
+
+#[derive(Args, Debug)]
+pub struct ProfileArgs {
+    #[command(subcommand)]
+    command: ProfileCommand,
+    /// Directory of the quiz site
+    #[arg(long, global = true, default_value = "site")]
+    site: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileCommand {
+    /// List the profiles of the site
+    List,
+    /// Add a profile to the site
+    Create { name: String },
+    /// Remove a profile from the site; the page then forgets its answers, bookmarks and notes
+    Delete { name: String },
+}
+
+/// Profiles listed in the site, none when it has no profiles file.
+pub(crate) fn load(site: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let path = site.join(PROFILES_FILE);
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    let list = text.trim().strip_prefix(PREFIX).map(|list| list.trim_end_matches(';'));
+    let Some(list) = list else {
+        let message = format!("{} is not a profiles file written by S4WM-extract", path.display());
+        return Err(OutputError { message }.into());
+    };
+    Ok(serde_json::from_str(list)?)
+}
+
+pub(crate) fn save(site: &Path, profiles: &[String]) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(site)?;
+    fs::write(site.join(PROFILES_FILE), format!("{}{};\n", PREFIX, serde_json::to_string(profiles)?))?;
+    Ok(())
+}
+
+/// Profile names become part of the browser storage keys and of `?profile=` links.
+pub(crate) fn check_name(name: &str) -> Result<(), OutputError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        let message = format!("Invalid profile name {:?}: use letters, digits, - and _", name);
+        return Err(OutputError { message });
+    }
+    Ok(())
+}
+
+pub(crate) fn run(args: ProfileArgs) -> Result<(), Box<dyn Error>> {
+    let mut profiles = load(&args.site)?;
+    match args.command {
+        ProfileCommand::List => {
+            if profiles.is_empty() {
+                println!("{} has no profiles", args.site.display());
+            }
+            for profile in &profiles {
+                println!("{}", profile);
+            }
+        }
+        ProfileCommand::Create { name } => {
+            check_name(&name)?;
+            if profiles.contains(&name) {
+                let message = format!("Profile {} already exists", name);
+                return Err(OutputError { message }.into());
+            }
+            profiles.push(name.clone());
+            save(&args.site, &profiles)?;
+            println!("Created profile {}, open index.html?profile={}", name, name);
+        }
+        ProfileCommand::Delete { name } => {
+            let Some(position) = profiles.iter().position(|profile| *profile == name) else {
+                let message = format!("No profile {} in {}", name, args.site.display());
+                return Err(OutputError { message }.into());
+            };
+            profiles.remove(position);
+            save(&args.site, &profiles)?;
+            println!("Deleted profile {}", name);
+        }
+    }
+    Ok(())
+}
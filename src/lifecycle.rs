@@ -0,0 +1,220 @@
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+
+use crate::crypt::Crypto;
+use crate::history::{self, HistoryArgs};
+use crate::similarity::stable_id;
+use crate::{open_bank, save_to_json, tree, Bank, OutputError, Question};
+
+// Lifecycle of the questions of a bank. Questions of old exam versions are retired or marked as superseded by their
+// newer version rather than deleted, so their history stays and a later dump still matches them; the exam and the
+// exports leave them out unless asked. Questions flagged for review stay in use until someone looks at them.
+
+/// State of a question in the bank
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Lifecycle {
+    #[default]
+    Active,
+    NeedsReview,
+    Retired,
+    /// Replaced by the question with the given id
+    SupersededBy(String),
+}
+
+impl Lifecycle {
+    pub(crate) fn is_active(&self) -> bool {
+        *self == Lifecycle::Active
+    }
+
+    /// Whether the question is out of use: retired or superseded
+    pub(crate) fn is_retired(&self) -> bool {
+        matches!(self, Lifecycle::Retired | Lifecycle::SupersededBy(_))
+    }
+}
+
+impl std::fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lifecycle::Active => write!(f, "active"),
+            Lifecycle::NeedsReview => write!(f, "needs review"),
+            Lifecycle::Retired => write!(f, "retired"),
+            Lifecycle::SupersededBy(id) => write!(f, "superseded by {}", id),
+        }
+    }
+}
+
+/// States that can be set directly; superseding takes the id of the replacement
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum State {
+    Active,
+    NeedsReview,
+    Retired,
+}
+
+#[derive(Args, Debug)]
+pub struct LifecycleArgs {
+    /// Question bank to update
+    input: String,
+    /// Ids (or unique id prefixes) of the questions to update; without ids, lists the questions that are not active
+    ids: Vec<String>,
+    /// New state of the questions
+    #[arg(long, value_enum, conflicts_with = "superseded_by")]
+    set: Option<State>,
+    /// Mark the questions as superseded by the question with this id
+    #[arg(long, value_name = "ID")]
+    superseded_by: Option<String>,
+    /// Write the updated bank here instead of updating the input in place
+    #[arg(short, long)]
+    output: Option<String>,
+    #[command(flatten)]
+    history: HistoryArgs,
+}
+
+/// The bank without its retired and superseded questions.
+pub(crate) fn in_use(bank: &Bank) -> Bank {
+    Bank {
+        meta: bank.meta.clone(),
+        questions: bank.questions.iter().filter(|q| !q.lifecycle.is_retired()).cloned().collect(),
+    }
+}
+
+fn question_id(question: &Question) -> String {
+    question.id.clone().unwrap_or_else(|| stable_id(question))
+}
+
+/// Index of the question whose id starts with `prefix`.
+pub(crate) fn find(questions: &[Question], prefix: &str) -> Result<usize, OutputError> {
+    let matches: Vec<usize> =
+        (0..questions.len()).filter(|&i| question_id(&questions[i]).starts_with(prefix)).collect();
+    match matches[..] {
+        [index] => Ok(index),
+        [] => Err(OutputError {
+            message: format!("No question with id {}", prefix),
+        }),
+        _ => Err(OutputError {
+            message: format!("Id {} matches {} questions, give more characters", prefix, matches.len()),
+        }),
+    }
+}
+
+/// Saves a bank back where it was loaded from, as a single file or a per-question directory.
+pub(crate) fn save_bank(bank: &Bank, path: &str, crypto: &Crypto) -> Result<(), OutputError> {
+    if Path::new(path).is_dir() {
+        return tree::save_per_question(bank, Path::new(path), crypto);
+    }
+    if path.ends_with(".ndjson") {
+        return Err(OutputError::from("Streamed banks cannot be written back, write them to a .json file instead"));
+    }
+    save_to_json(bank, path, crypto)
+}
+
+pub(crate) fn run(args: LifecycleArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    if args.ids.is_empty() {
+        for question in bank.questions.iter().filter(|q| !q.lifecycle.is_active()) {
+            println!("{}  question {}: {}", question_id(question), question.number, question.lifecycle);
+        }
+        return Ok(());
+    }
+
+    let lifecycle = match (args.set, &args.superseded_by) {
+        (_, Some(id)) => Lifecycle::SupersededBy(question_id(&bank.questions[find(&bank.questions, id)?])),
+        (Some(State::Active), None) => Lifecycle::Active,
+        (Some(State::NeedsReview), None) => Lifecycle::NeedsReview,
+        (Some(State::Retired), None) => Lifecycle::Retired,
+        (None, None) => return Err(OutputError::from("Pass --set <state> or --superseded-by <id>").into()),
+    };
+    let before = args.history.history.as_ref().map(|_| bank.questions.clone());
+    for id in &args.ids {
+        let index = find(&bank.questions, id)?;
+        let question = &mut bank.questions[index];
+        if lifecycle == Lifecycle::SupersededBy(question_id(question)) {
+            return Err(OutputError::from("A question cannot supersede itself").into());
+        }
+        question.lifecycle = lifecycle.clone();
+        println!("{}  question {}: {}", question_id(question), question.number, lifecycle);
+    }
+
+    save_bank(&bank, args.output.as_deref().unwrap_or(&args.input), &crypto)?;
+    if let Some(before) = &before {
+        history::record(&args.history, "lifecycle", &args.input, before, &bank.questions, &crypto)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bank(ids: &[&str]) -> Bank {
+        Bank {
+            questions: ids
+                .iter()
+                .enumerate()
+                .map(|(i, id)| Question {
+                    number: (i + 1).to_string(),
+                    id: Some(id.to_string()),
+                    text: format!("Question {}", i + 1),
+                    ..Question::default()
+                })
+                .collect(),
+            ..Bank::default()
+        }
+    }
+
+    #[test]
+    fn finds_questions_by_id_prefix() {
+        let bank = bank(&["3f2a9c1e", "3f7b0d22", "9e4410aa"]);
+        assert_eq!(find(&bank.questions, "9e").unwrap(), 2);
+        assert_eq!(find(&bank.questions, "3f2").unwrap(), 0);
+        assert_eq!(
+            find(&bank.questions, "3f").err().unwrap().message,
+            "Id 3f matches 2 questions, give more characters"
+        );
+        assert_eq!(find(&bank.questions, "77").err().unwrap().message, "No question with id 77");
+    }
+
+    #[test]
+    fn retired_and_superseded_questions_are_not_in_use() {
+        let mut bank = bank(&["a", "b", "c", "d"]);
+        bank.questions[0].lifecycle = Lifecycle::Retired;
+        bank.questions[1].lifecycle = Lifecycle::SupersededBy("d".to_string());
+        bank.questions[2].lifecycle = Lifecycle::NeedsReview;
+        let ids: Vec<_> = in_use(&bank).questions.iter().map(|q| q.id.clone().unwrap()).collect();
+        assert_eq!(ids, ["c", "d"]);
+    }
+
+    #[test]
+    fn saves_banks_where_they_were_loaded_from() {
+        let dir = std::env::temp_dir().join(format!("s4wm-lifecycle-save-{}", std::process::id()));
+        let tree_dir = dir.join("questions");
+        std::fs::create_dir_all(&tree_dir).unwrap();
+        let crypto = Crypto::default();
+        let mut bank = bank(&["3f2a9c1e", "9e4410aa"]);
+        bank.questions[1].lifecycle = Lifecycle::Retired;
+
+        let json = dir.join("questions.json");
+        save_bank(&bank, json.to_str().unwrap(), &crypto).unwrap();
+        let from_json = open_bank(json.to_str().unwrap(), &crypto).unwrap().0;
+        save_bank(&bank, tree_dir.to_str().unwrap(), &crypto).unwrap();
+        let from_tree = open_bank(tree_dir.to_str().unwrap(), &crypto).unwrap().0;
+        let ndjson = dir.join("questions.ndjson");
+        let streamed = save_bank(&bank, ndjson.to_str().unwrap(), &crypto);
+        let ndjson_written = ndjson.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        for saved in [from_json, from_tree] {
+            assert_eq!(saved.questions.len(), 2);
+            assert_eq!(saved.questions[1].lifecycle, Lifecycle::Retired);
+            assert!(saved.questions[0].lifecycle.is_active());
+        }
+        assert_eq!(
+            streamed.err().unwrap().message,
+            "Streamed banks cannot be written back, write them to a .json file instead"
+        );
+        assert!(!ndjson_written);
+    }
+}
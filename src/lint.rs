@@ -0,0 +1,404 @@
+use clap::Args;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use crate::crypt::Crypto;
+use crate::history::{self, HistoryArgs};
+use crate::kind::QuestionKind;
+use crate::lifecycle::save_bank;
+use crate::progress::{Console, Progress};
+use crate::validate::{self, ValidationOptions};
+use crate::{annotate_questions, open_bank, Question};
+
+// Cleanup of a bank. Validation only reports, so every leftover of the extraction used to be fixed by hand; `lint`
+// finds the leftovers that have a single safe repair and applies it with `--fix`:
+// - `whitespace`: blanks trimmed and runs of blanks collapsed in stems, choices, statements and premises
+// - `trailing-answer`: an "Answer: B" the dump printed after the stem or the last choice, taken as the answer when
+//   the question has none
+// - `choice-letters`: choice letters written as `a`, `B.` or `(c)` turned into `A`, `B`, `C`, with the answers
+// - `split-stem`: a question without choices followed by one whose stem goes on in lower case, merged into one whose
+//   id, kind, keywords and difficulty are derived again from the whole stem
+// Anything else is reported and left alone: a trailing answer contradicting the recorded one, answers naming no
+// choice, gaps in the choice letters, choice questions without an answer, and the findings of the validation.
+
+lazy_static! {
+    static ref TRAILING_ANSWER: Regex =
+        Regex::new(r"(?i)\s*\b(?:correct\s+)?answers?\s*[:\-]\s*([A-H](?:\s*[,;&/]\s*[A-H])*)\s*\.?\s*$").unwrap();
+    static ref CHOICE_LETTER: Regex = Regex::new(r"^\(?([A-Za-z])[.):]?$").unwrap();
+}
+
+#[derive(Args, Debug)]
+pub struct LintArgs {
+    /// Question bank to check
+    input: String,
+    /// Apply the safe repairs and save the bank
+    #[arg(long)]
+    fix: bool,
+    /// Write the repaired bank here instead of updating the input in place
+    #[arg(short, long, requires = "fix")]
+    output: Option<String>,
+    #[command(flatten)]
+    history: HistoryArgs,
+}
+
+/// A repair applied to a question, or an issue left for a person
+struct Lint {
+    rule: &'static str,
+    number: String,
+    message: String,
+}
+
+impl Lint {
+    fn new(rule: &'static str, question: &Question, message: String) -> Self {
+        Lint {
+            rule,
+            number: question.number.clone(),
+            message,
+        }
+    }
+}
+
+fn collapse(text: &mut String) -> bool {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let changed = collapsed != *text;
+    *text = collapsed;
+    changed
+}
+
+fn fix_whitespace(question: &mut Question) -> bool {
+    let mut changed = collapse(&mut question.text);
+    for choice in question.choices.values_mut() {
+        changed |= collapse(choice);
+    }
+    for statement in question.statements.iter_mut().chain(question.premises.iter_mut()) {
+        changed |= collapse(&mut statement.text);
+    }
+    changed
+}
+
+/// Letters of a trailing answer in `text`, with the text before it.
+fn trailing_answer(text: &str) -> Option<(String, Vec<String>)> {
+    let caps = TRAILING_ANSWER.captures(text)?;
+    let mut letters: Vec<String> =
+        caps[1].chars().filter(char::is_ascii_alphabetic).map(|c| c.to_ascii_uppercase().to_string()).collect();
+    letters.sort();
+    letters.dedup();
+    Some((text[..caps.get(0)?.start()].trim_end().to_string(), letters))
+}
+
+fn fix_trailing_answer(question: &mut Question, repairs: &mut Vec<Lint>, issues: &mut Vec<Lint>) {
+    let mut last_choice: Vec<&String> = question.choices.keys().collect();
+    last_choice.sort();
+    let last_choice = last_choice.last().map(|letter| letter.to_string());
+    let found = match trailing_answer(&question.text) {
+        Some((text, letters)) => Some((None, text, letters)),
+        None => last_choice.and_then(|letter| {
+            let (text, letters) = trailing_answer(&question.choices[&letter])?;
+            Some((Some(letter), text, letters))
+        }),
+    };
+    let Some((choice, text, letters)) = found else {
+        return;
+    };
+    let place = choice.as_ref().map_or("the stem".to_string(), |letter| format!("choice {}", letter));
+    let mut sorted = question.answers.clone();
+    sorted.sort();
+    if !question.answers.is_empty() && sorted != letters {
+        let message = format!(
+            "{} ends in answer {} but the bank records {}",
+            place,
+            letters.join(", "),
+            question.answers.join(", ")
+        );
+        issues.push(Lint::new("trailing-answer", question, message));
+        return;
+    }
+    if text.is_empty() || letters.iter().any(|letter| !question.choices.contains_key(letter)) {
+        let message = format!("{} ends in answer {}, which names no choice", place, letters.join(", "));
+        issues.push(Lint::new("trailing-answer", question, message));
+        return;
+    }
+    match &choice {
+        Some(letter) => {
+            question.choices.insert(letter.clone(), text);
+        }
+        None => question.text = text,
+    }
+    let mut message = format!("removed answer {} from {}", letters.join(", "), place);
+    if question.answers.is_empty() {
+        message.push_str(", taken as the answer");
+        question.correct_answers = Some(letters.len());
+        question.answers = letters;
+    }
+    repairs.push(Lint::new("trailing-answer", question, message));
+}
+
+fn fix_choice_letters(question: &mut Question, repairs: &mut Vec<Lint>, issues: &mut Vec<Lint>) {
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    for letter in question.choices.keys() {
+        let Some(caps) = CHOICE_LETTER.captures(letter.trim()) else {
+            let message = format!("choice label {:?} is not a letter", letter);
+            issues.push(Lint::new("choice-letters", question, message));
+            return;
+        };
+        renamed.insert(letter.clone(), caps[1].to_uppercase());
+    }
+    let targets: HashSet<&String> = renamed.values().collect();
+    if targets.len() < renamed.len() {
+        let message = "choice labels differ only in case or punctuation".to_string();
+        issues.push(Lint::new("choice-letters", question, message));
+        return;
+    }
+    if renamed.iter().all(|(from, to)| from == to) {
+        return;
+    }
+
+    let rename = |letter: &String| renamed.get(letter).cloned().unwrap_or_else(|| letter.trim().to_uppercase());
+    question.choices = std::mem::take(&mut question.choices).into_iter().map(|(l, text)| (rename(&l), text)).collect();
+    question.feedback =
+        std::mem::take(&mut question.feedback).into_iter().map(|(l, text)| (rename(&l), text)).collect();
+    question.answers = question.answers.iter().map(rename).collect();
+    question.order = question.order.iter().map(rename).collect();
+    for pair in &mut question.pairs {
+        pair.choice = rename(&pair.choice);
+    }
+    let mut changes: Vec<String> =
+        renamed.iter().filter(|(from, to)| from != to).map(|(from, to)| format!("{} → {}", from, to)).collect();
+    changes.sort();
+    repairs.push(Lint::new("choice-letters", question, format!("renamed choices {}", changes.join(", "))));
+}
+
+/// Issues of a question that have no safe repair.
+fn check(question: &Question, issues: &mut Vec<Lint>) {
+    if question.text.is_empty() {
+        issues.push(Lint::new("empty-stem", question, "the question has no stem".to_string()));
+    }
+    let unknown: Vec<&str> = question
+        .answers
+        .iter()
+        .filter(|letter| !question.choices.contains_key(*letter))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() && !question.choices.is_empty() {
+        let message = format!("answer {} names no choice", unknown.join(", "));
+        issues.push(Lint::new("answer-without-choice", question, message));
+    }
+    let mut letters: Vec<&String> = question.choices.keys().collect();
+    letters.sort();
+    let expected = (b'A'..).map(|letter| char::from(letter).to_string());
+    if letters.iter().zip(expected).any(|(letter, expected)| **letter != expected) {
+        let letters: Vec<&str> = letters.iter().map(|letter| letter.as_str()).collect();
+        let message = format!("choice letters {} have a gap", letters.join(", "));
+        issues.push(Lint::new("choice-gap", question, message));
+    }
+    let choice_kind = matches!(question.kind, QuestionKind::MultipleChoice | QuestionKind::MultipleSelect);
+    if choice_kind && !question.choices.is_empty() && question.answers.is_empty() {
+        issues.push(Lint::new("missing-answer", question, "the question has no answer".to_string()));
+    }
+}
+
+/// Whether `next` obviously goes on with the stem of `question`, the parser having split one question in two.
+fn continues(question: &Question, next: &Question) -> bool {
+    let bare = question.choices.is_empty()
+        && question.answers.is_empty()
+        && question.statements.is_empty()
+        && question.premises.is_empty();
+    bare && !question.text.is_empty() && next.text.starts_with(|c: char| c.is_lowercase())
+}
+
+fn merge_split_stems(questions: &mut Vec<Question>, repairs: &mut Vec<Lint>) {
+    let mut i = 0;
+    while i + 1 < questions.len() {
+        if !continues(&questions[i], &questions[i + 1]) {
+            i += 1;
+            continue;
+        }
+        let next = questions.remove(i + 1);
+        let question = &questions[i];
+        let message = format!("merged with question {}, which continues its stem", next.number);
+        repairs.push(Lint::new("split-stem", question, message));
+        questions[i] = Question {
+            number: question.number.clone(),
+            id: question.id.clone(),
+            text: format!("{} {}", question.text, next.text),
+            page: question.page,
+            source_number: question.source_number,
+            lifecycle: question.lifecycle.clone(),
+            notes: question.notes.iter().chain(&next.notes).cloned().collect(),
+            emphasis: question.emphasis.iter().chain(&next.emphasis).cloned().collect(),
+            ..next
+        };
+        annotate_questions(std::slice::from_mut(&mut questions[i]));
+    }
+}
+
+pub(crate) fn run(args: LintArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    let before = args.history.history.as_ref().filter(|_| args.fix).map(|_| bank.questions.clone());
+
+    let (mut repairs, mut issues) = (Vec::new(), Vec::new());
+    for question in &mut bank.questions {
+        if fix_whitespace(question) {
+            repairs.push(Lint::new("whitespace", question, "trimmed blanks".to_string()));
+        }
+        fix_choice_letters(question, &mut repairs, &mut issues);
+        fix_trailing_answer(question, &mut repairs, &mut issues);
+    }
+    merge_split_stems(&mut bank.questions, &mut repairs);
+    for question in &bank.questions {
+        check(question, &mut issues);
+    }
+
+    let applied = if args.fix { "fixed" } else { "fixable" };
+    for repair in &repairs {
+        println!("{}[{}] question {}: {}", applied, repair.rule, repair.number, repair.message);
+    }
+    let mut console = Console::new();
+    for issue in &issues {
+        console.diagnostic("warning", issue.rule, format!("question {}: {}", issue.number, issue.message));
+    }
+    validate::validate(&bank, &ValidationOptions::default()).report(&mut console);
+
+    if !args.fix {
+        if !repairs.is_empty() {
+            println!("{} repairs can be applied with --fix, {} issues need a look", repairs.len(), issues.len());
+        }
+        return Ok(());
+    }
+    println!("Applied {} repairs, {} issues need a look", repairs.len(), issues.len());
+    if repairs.is_empty() {
+        return Ok(());
+    }
+    save_bank(&bank, args.output.as_deref().unwrap_or(&args.input), &crypto)?;
+    if let Some(before) = &before {
+        history::record(&args.history, "lint", &args.input, before, &bank.questions, &crypto)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::similarity::stable_id;
+    use crate::{Bank, Statement};
+    use std::fs;
+
+    fn question(number: &str, text: &str, choices: &[(&str, &str)], answers: &[&str]) -> Question {
+        Question {
+            number: number.to_string(),
+            text: text.to_string(),
+            choices: choices.iter().map(|(letter, text)| (letter.to_string(), text.to_string())).collect(),
+            answers: answers.iter().map(|letter| letter.to_string()).collect(),
+            ..Question::default()
+        }
+    }
+
+    const CHOICES: [(&str, &str); 3] = [("A", "Putaway"), ("B", "Picking"), ("C", "Invoicing")];
+
+    #[test]
+    fn continues_only_a_bare_stem_with_a_lower_case_sequel() {
+        let bare = question("1", "Which of the following is", &[], &[]);
+        let sequel = question("2", "not a warehouse process?", &CHOICES, &["C"]);
+        assert!(continues(&bare, &sequel));
+        assert!(!continues(&bare, &question("2", "Which process comes first?", &CHOICES, &["A"])));
+        assert!(!continues(&question("1", "", &[], &[]), &sequel));
+        assert!(!continues(&question("1", "Which of the following is", &CHOICES, &[]), &sequel));
+        let mut statements = bare.clone();
+        statements.statements.push(Statement {
+            label: "I".to_string(),
+            text: "Stock is posted".to_string(),
+        });
+        assert!(!continues(&statements, &sequel));
+    }
+
+    #[test]
+    fn merged_stems_are_annotated_again() {
+        let mut first = question("1", "Which of the following is", &[], &[]);
+        first.id = Some(stable_id(&first));
+        first.notes.push("check the source".to_string());
+        let mut questions = vec![first, question("2", "not a warehouse process?", &CHOICES, &["C"])];
+        let mut repairs = Vec::new();
+        merge_split_stems(&mut questions, &mut repairs);
+
+        assert_eq!(questions.len(), 1);
+        let merged = &questions[0];
+        assert_eq!(merged.number, "1");
+        assert_eq!(merged.text, "Which of the following is not a warehouse process?");
+        assert_eq!(merged.id, Some(stable_id(merged)));
+        assert_eq!(merged.kind, QuestionKind::MultipleChoice);
+        assert_eq!(merged.keywords, ["not"]);
+        assert!(merged.difficulty.is_some());
+        assert_eq!(merged.notes, ["check the source"]);
+        assert_eq!(repairs[0].rule, "split-stem");
+    }
+
+    #[test]
+    fn trailing_answers_are_read_off_the_end() {
+        let stem = "Which process comes first?";
+        assert_eq!(trailing_answer(&format!("{} Answer: B", stem)), Some((stem.to_string(), vec!["B".to_string()])));
+        let letters = trailing_answer("Picking. Correct answers - c, a.").map(|(_, letters)| letters);
+        assert_eq!(letters, Some(vec!["A".to_string(), "C".to_string()]));
+        assert_eq!(trailing_answer("The answer is in chapter B"), None);
+
+        let mut unanswered = question("1", &format!("{} Answer: A", stem), &CHOICES, &[]);
+        let (mut repairs, mut issues) = (Vec::new(), Vec::new());
+        fix_trailing_answer(&mut unanswered, &mut repairs, &mut issues);
+        assert_eq!((unanswered.text.as_str(), unanswered.answers.as_slice()), (stem, ["A".to_string()].as_slice()));
+
+        let mut contradicting = question("2", "Which process comes first?", &CHOICES, &["A"]);
+        contradicting.choices.insert("C".to_string(), "Invoicing Answer: C".to_string());
+        fix_trailing_answer(&mut contradicting, &mut repairs, &mut issues);
+        assert_eq!(contradicting.choices["C"], "Invoicing Answer: C");
+        assert_eq!((repairs.len(), issues.len()), (1, 1));
+    }
+
+    #[test]
+    fn choice_letters_are_normalized_with_the_answers() {
+        let mut lettered =
+            question("1", "Which process comes first?", &[("a.", "Putaway"), ("(b)", "Picking")], &["(b)"]);
+        let (mut repairs, mut issues) = (Vec::new(), Vec::new());
+        fix_choice_letters(&mut lettered, &mut repairs, &mut issues);
+        let mut letters: Vec<&String> = lettered.choices.keys().collect();
+        letters.sort();
+        assert_eq!(letters, ["A", "B"]);
+        assert_eq!(lettered.answers, ["B"]);
+        assert_eq!(repairs[0].message, "renamed choices (b) → B, a. → A");
+
+        let mut clashing = question("2", "Which process comes first?", &[("a", "Putaway"), ("A.", "Picking")], &[]);
+        fix_choice_letters(&mut clashing, &mut repairs, &mut issues);
+        let mut worded = question("3", "Which process comes first?", &[("one", "Putaway")], &[]);
+        fix_choice_letters(&mut worded, &mut repairs, &mut issues);
+        assert_eq!(repairs.len(), 1);
+        assert_eq!(issues.len(), 2);
+        assert!(clashing.choices.contains_key("a") && worded.choices.contains_key("one"));
+    }
+
+    #[test]
+    fn lint_without_fix_leaves_the_file_alone() {
+        let dir = std::env::temp_dir().join(format!("s4wm-lint-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("questions.json").to_string_lossy().into_owned();
+        let bank = Bank {
+            questions: vec![question("1", "  Which process   comes first? Answer: A", &CHOICES, &[])],
+            ..Bank::default()
+        };
+        save_bank(&bank, &path, &Crypto::default()).unwrap();
+        let saved = fs::read(&path).unwrap();
+        let args = |fix| LintArgs {
+            input: path.clone(),
+            fix,
+            output: None,
+            history: HistoryArgs::default(),
+        };
+
+        run(args(false), &Crypto::default()).unwrap();
+        let unfixed = fs::read(&path).unwrap();
+        run(args(true), &Crypto::default()).unwrap();
+        let (fixed, _) = open_bank(&path, &Crypto::default()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(unfixed, saved);
+        assert_eq!(fixed.questions[0].text, "Which process comes first?");
+        assert_eq!(fixed.questions[0].answers, ["A"]);
+    }
+}
@@ -0,0 +1,474 @@
+use std::fmt::Write as _;
+
+use crate::export::{extra_fields, sorted_choices};
+use crate::kind::QuestionKind;
+use crate::{Bank, Question};
+
+// Exports for learning management systems: Moodle GIFT and IMS QTI 1.2 (one `questestinterop` document with an item
+// per question, as imported by Moodle, Canvas and Blackboard). Every question kind maps onto the native construct of
+// the format; ordering questions become matchings of position to step since neither format has a portable ordering
+// type. Questions without an answer are skipped because both formats require one. Extra fields (see `schema`) become
+// comments above the GIFT question and QTI item metadata. Commentary on the choices becomes GIFT answer comments and
+// QTI item feedback displayed for the choice picked.
+
+/// QTI score action ("Set" or "Add"), its points and the (response, value) pairs it requires
+type Condition = (String, f64, Vec<(String, String)>);
+
+/// Stem followed by its Roman-numeral statements, one per line.
+fn full_stem(question: &Question) -> String {
+    let mut stem = question.text.clone();
+    for statement in &question.statements {
+        let _ = write!(stem, "\n{}. {}", statement.label, statement.text);
+    }
+    stem
+}
+
+/// (left, right) pairs for matching questions, and (position, step) pairs for ordering questions.
+fn match_pairs(question: &Question) -> Vec<(String, String)> {
+    let choice_text = |letter: &str| question.choices.get(letter).cloned().unwrap_or_else(|| letter.to_string());
+    match question.kind {
+        QuestionKind::Matching => question
+            .pairs
+            .iter()
+            .map(|pair| {
+                let premise = question
+                    .premises
+                    .iter()
+                    .find(|p| p.label == pair.premise)
+                    .map_or_else(|| pair.premise.clone(), |p| p.text.clone());
+                (premise, choice_text(pair.choice.as_str()))
+            })
+            .collect(),
+        QuestionKind::Ordering => question
+            .order
+            .iter()
+            .enumerate()
+            .map(|(i, letter)| ((i + 1).to_string(), choice_text(letter.as_str())))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn has_answer(question: &Question) -> bool {
+    match question.kind {
+        QuestionKind::TrueFalse => question.truth.is_some(),
+        QuestionKind::Matching | QuestionKind::Ordering => !match_pairs(question).is_empty(),
+        _ => !question.answers.is_empty(),
+    }
+}
+
+fn gift_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "~=#{}:\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Commentary on a choice as a GIFT answer comment ("#..."), empty without one
+fn gift_feedback(question: &Question, letter: &str) -> String {
+    question.feedback.get(letter).map_or(String::new(), |feedback| format!(" #{}", gift_escape(feedback)))
+}
+
+/// GIFT only accepts percentages with up to five decimals
+fn gift_weight(weight: f64) -> String {
+    let formatted = format!("{:.5}", weight);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+pub(crate) fn to_gift(bank: &Bank) -> String {
+    let mut out = String::new();
+    for question in &bank.questions {
+        if !has_answer(question) {
+            let _ = writeln!(out, "// Question {} skipped: no answer\n", question.number);
+            continue;
+        }
+
+        for (name, value) in extra_fields(question) {
+            let _ = writeln!(out, "// {}: {}", name, value.replace('\n', " "));
+        }
+        let _ = write!(out, "::Q{}:: {} {{", question.number, gift_escape(&full_stem(question)));
+        match question.kind {
+            QuestionKind::TrueFalse => {
+                out.push_str(if question.truth == Some(true) { "T" } else { "F" });
+            }
+            QuestionKind::Matching | QuestionKind::Ordering => {
+                for (left, right) in match_pairs(question) {
+                    let _ = write!(out, "\n    ={} -> {}", gift_escape(&left), gift_escape(&right));
+                }
+                out.push('\n');
+            }
+            QuestionKind::MultipleSelect => {
+                let right = question.answers.len() as f64;
+                let wrong = (question.choices.len() as f64 - right).max(1.0);
+                for (letter, choice) in sorted_choices(question) {
+                    let weight = if question.answers.contains(letter) { 100.0 / right } else { -100.0 / wrong };
+                    let feedback = gift_feedback(question, letter);
+                    let _ = write!(out, "\n    ~%{}%{}{}", gift_weight(weight), gift_escape(choice), feedback);
+                }
+                out.push('\n');
+            }
+            QuestionKind::MultipleChoice => {
+                for (letter, choice) in sorted_choices(question) {
+                    let marker = if question.answers.contains(letter) { '=' } else { '~' };
+                    let feedback = gift_feedback(question, letter);
+                    let _ = write!(out, "\n    {}{}{}", marker, gift_escape(choice), feedback);
+                }
+                out.push('\n');
+            }
+        }
+        out.push_str("}\n\n");
+    }
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn qti_material(text: &str) -> String {
+    format!("<material><mattext texttype=\"text/plain\">{}</mattext></material>", xml_escape(text))
+}
+
+fn qti_choice_response(out: &mut String, ident: &str, cardinality: &str, labels: &[(String, String)]) {
+    let _ = writeln!(out, "        <response_lid ident=\"{}\" rcardinality=\"{}\">", ident, cardinality);
+    out.push_str("          <render_choice>\n");
+    for (label, text) in labels {
+        let _ = writeln!(
+            out,
+            "            <response_label ident=\"{}\">{}</response_label>",
+            xml_escape(label),
+            qti_material(text)
+        );
+    }
+    out.push_str("          </render_choice>\n        </response_lid>\n");
+}
+
+fn qti_condition(out: &mut String, action: &str, score: f64, conditions: &[(String, String)]) {
+    out.push_str("        <respcondition continue=\"Yes\">\n          <conditionvar>");
+    if conditions.len() > 1 {
+        out.push_str("<and>");
+    }
+    for (ident, value) in conditions {
+        let _ = write!(out, "<varequal respident=\"{}\">{}</varequal>", ident, xml_escape(value));
+    }
+    if conditions.len() > 1 {
+        out.push_str("</and>");
+    }
+    let _ = writeln!(
+        out,
+        "</conditionvar>\n          <setvar action=\"{}\" varname=\"SCORE\">{}</setvar>\n        </respcondition>",
+        action,
+        gift_weight(score)
+    );
+}
+
+fn qti_item(out: &mut String, question: &Question) {
+    let choices: Vec<(String, String)> =
+        sorted_choices(question).into_iter().map(|(l, c)| (l.clone(), c.clone())).collect();
+
+    let _ = writeln!(out, "    <item ident=\"q{0}\" title=\"Question {0}\">", xml_escape(&question.number));
+    let extra = extra_fields(question);
+    if !extra.is_empty() {
+        out.push_str("      <itemmetadata>\n        <qtimetadata>\n");
+        for (name, value) in extra {
+            let _ = writeln!(
+                out,
+                "          <qtimetadatafield><fieldlabel>{}</fieldlabel><fieldentry>{}</fieldentry></qtimetadatafield>",
+                xml_escape(name),
+                xml_escape(&value)
+            );
+        }
+        out.push_str("        </qtimetadata>\n      </itemmetadata>\n");
+    }
+    let _ = writeln!(out, "      <presentation>\n        {}", qti_material(&full_stem(question)));
+
+    let mut conditions: Vec<Condition> = Vec::new();
+    match question.kind {
+        QuestionKind::TrueFalse => {
+            let labels = [("true".to_string(), "True".to_string()), ("false".to_string(), "False".to_string())];
+            qti_choice_response(out, "response1", "Single", &labels);
+            let value = if question.truth == Some(true) { "true" } else { "false" };
+            conditions.push(("Set".to_string(), 100.0, vec![("response1".to_string(), value.to_string())]));
+        }
+        QuestionKind::Matching | QuestionKind::Ordering => {
+            let pairs = match_pairs(question);
+            let targets: Vec<(String, String)> =
+                pairs.iter().enumerate().map(|(i, (_, right))| (format!("T{}", i + 1), right.clone())).collect();
+            for (i, (left, _)) in pairs.iter().enumerate() {
+                let ident = format!("response{}", i + 1);
+                let _ = writeln!(out, "        {}", qti_material(left));
+                qti_choice_response(out, &ident, "Single", &targets);
+                conditions.push((
+                    "Add".to_string(),
+                    100.0 / pairs.len() as f64,
+                    vec![(ident, targets[i].0.clone())],
+                ));
+            }
+        }
+        QuestionKind::MultipleSelect => {
+            qti_choice_response(out, "response1", "Multiple", &choices);
+            let expected = question.answers.iter().map(|a| ("response1".to_string(), a.clone())).collect();
+            conditions.push(("Set".to_string(), 100.0, expected));
+        }
+        QuestionKind::MultipleChoice => {
+            qti_choice_response(out, "response1", "Single", &choices);
+            let expected = vec![("response1".to_string(), question.answers[0].clone())];
+            conditions.push(("Set".to_string(), 100.0, expected));
+        }
+    }
+
+    out.push_str("      </presentation>\n      <resprocessing>\n");
+    out.push_str(
+        "        <outcomes><decvar varname=\"SCORE\" vartype=\"Decimal\" minvalue=\"0\" maxvalue=\"100\"/></outcomes>\n",
+    );
+    for (action, score, expected) in &conditions {
+        qti_condition(out, action, *score, expected);
+    }
+    // commentary on the choice picked, shown whatever the score
+    let feedback: Vec<(&String, &String)> = match question.kind {
+        QuestionKind::MultipleChoice | QuestionKind::MultipleSelect => sorted_choices(question)
+            .into_iter()
+            .filter_map(|(letter, _)| Some((letter, question.feedback.get(letter)?)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    for (letter, _) in &feedback {
+        let ident = xml_escape(letter);
+        out.push_str("        <respcondition continue=\"Yes\">\n");
+        let _ = writeln!(
+            out,
+            "          <conditionvar><varequal respident=\"response1\">{}</varequal></conditionvar>",
+            ident
+        );
+        let _ = writeln!(out, "          <displayfeedback feedbacktype=\"Response\" linkrefid=\"feedback{}\"/>", ident);
+        out.push_str("        </respcondition>\n");
+    }
+    out.push_str("      </resprocessing>\n");
+    for (letter, text) in &feedback {
+        let ident = xml_escape(letter);
+        let _ = writeln!(out, "      <itemfeedback ident=\"feedback{}\">{}</itemfeedback>", ident, qti_material(text));
+    }
+    out.push_str("    </item>\n");
+}
+
+pub(crate) fn to_qti(bank: &Bank) -> String {
+    let title = bank.meta.title.as_deref().or(bank.meta.exam_code.as_deref()).unwrap_or("Questions");
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<questestinterop>\n");
+    let _ = writeln!(out, "  <assessment ident=\"bank\" title=\"{}\">\n  <section ident=\"root\">", xml_escape(title));
+    for question in bank.questions.iter().filter(|q| has_answer(q)) {
+        qti_item(&mut out, question);
+    }
+    out.push_str("  </section>\n  </assessment>\n</questestinterop>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MatchPair, Statement};
+    use std::collections::HashMap;
+
+    fn choices(texts: &[&str]) -> HashMap<String, String> {
+        let letters = ["A", "B", "C", "D"];
+        texts.iter().zip(letters).map(|(text, letter)| (letter.to_string(), text.to_string())).collect()
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    fn bank() -> Bank {
+        let choice = Question {
+            number: "1".to_string(),
+            text: "Which bin type holds {bulk} stock: A=B?".to_string(),
+            choices: choices(&["Rack ~ shelf", "Bulk #1", "Path \\ node"]),
+            answers: strings(&["B"]),
+            feedback: HashMap::from([("A".to_string(), "No: racks hold pallets".to_string())]),
+            extra: [("topic".to_string(), serde_json::json!(["Storage", "Bins"]))].into(),
+            ..Question::default()
+        };
+        let select = Question {
+            number: "2".to_string(),
+            kind: QuestionKind::MultipleSelect,
+            text: "Which are process steps?".to_string(),
+            statements: vec![Statement {
+                label: "I".to_string(),
+                text: "Steps run in order".to_string(),
+            }],
+            choices: choices(&["Pick", "Pack", "Load", "Bill"]),
+            answers: strings(&["A", "B", "C"]),
+            ..Question::default()
+        };
+        let matching = Question {
+            number: "3".to_string(),
+            kind: QuestionKind::Matching,
+            text: "Match the documents.".to_string(),
+            premises: vec![
+                Statement {
+                    label: "1".to_string(),
+                    text: "Inbound".to_string(),
+                },
+                Statement {
+                    label: "2".to_string(),
+                    text: "Outbound".to_string(),
+                },
+            ],
+            choices: choices(&["Delivery", "Receipt"]),
+            pairs: vec![
+                MatchPair {
+                    premise: "1".to_string(),
+                    choice: "B".to_string(),
+                },
+                MatchPair {
+                    premise: "2".to_string(),
+                    choice: "A".to_string(),
+                },
+            ],
+            ..Question::default()
+        };
+        let ordering = Question {
+            number: "4".to_string(),
+            kind: QuestionKind::Ordering,
+            text: "Order the steps.".to_string(),
+            choices: choices(&["Pack", "Pick"]),
+            order: strings(&["B", "A"]),
+            ..Question::default()
+        };
+        let truth = Question {
+            number: "5".to_string(),
+            kind: QuestionKind::TrueFalse,
+            text: "Waves group tasks.".to_string(),
+            truth: Some(true),
+            ..Question::default()
+        };
+        let unanswered = Question {
+            number: "6".to_string(),
+            text: "Which one?".to_string(),
+            choices: choices(&["This", "That"]),
+            ..Question::default()
+        };
+        Bank {
+            questions: vec![choice, select, matching, ordering, truth, unanswered],
+            ..Bank::default()
+        }
+    }
+
+    #[test]
+    fn gift_escapes_and_weighs_choices() {
+        let expected = r#"// topic: Storage, Bins
+::Q1:: Which bin type holds \{bulk\} stock\: A\=B? {
+    ~Rack \~ shelf #No\: racks hold pallets
+    =Bulk \#1
+    ~Path \\ node
+}
+
+::Q2:: Which are process steps?
+I. Steps run in order {
+    ~%33.33333%Pick
+    ~%33.33333%Pack
+    ~%33.33333%Load
+    ~%-100%Bill
+}
+
+::Q3:: Match the documents. {
+    =Inbound -> Receipt
+    =Outbound -> Delivery
+}
+
+::Q4:: Order the steps. {
+    =1 -> Pick
+    =2 -> Pack
+}
+
+::Q5:: Waves group tasks. {T}
+
+// Question 6 skipped: no answer
+
+"#;
+        assert_eq!(to_gift(&bank()), expected);
+    }
+
+    #[test]
+    fn qti_escapes_and_scores_items() {
+        let mut bank = bank();
+        bank.meta.title = Some("EWM & \"Co\"".to_string());
+        bank.questions[0].text = "Is <bulk> & 'rack' the same?".to_string();
+        bank.questions.retain(|q| ["1", "3", "6"].contains(&q.number.as_str()));
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<questestinterop>
+  <assessment ident="bank" title="EWM &amp; &quot;Co&quot;">
+  <section ident="root">
+    <item ident="q1" title="Question 1">
+      <itemmetadata>
+        <qtimetadata>
+          <qtimetadatafield><fieldlabel>topic</fieldlabel><fieldentry>Storage, Bins</fieldentry></qtimetadatafield>
+        </qtimetadata>
+      </itemmetadata>
+      <presentation>
+        <material><mattext texttype="text/plain">Is &lt;bulk&gt; &amp; &apos;rack&apos; the same?</mattext></material>
+        <response_lid ident="response1" rcardinality="Single">
+          <render_choice>
+            <response_label ident="A"><material><mattext texttype="text/plain">Rack ~ shelf</mattext></material></response_label>
+            <response_label ident="B"><material><mattext texttype="text/plain">Bulk #1</mattext></material></response_label>
+            <response_label ident="C"><material><mattext texttype="text/plain">Path \ node</mattext></material></response_label>
+          </render_choice>
+        </response_lid>
+      </presentation>
+      <resprocessing>
+        <outcomes><decvar varname="SCORE" vartype="Decimal" minvalue="0" maxvalue="100"/></outcomes>
+        <respcondition continue="Yes">
+          <conditionvar><varequal respident="response1">B</varequal></conditionvar>
+          <setvar action="Set" varname="SCORE">100</setvar>
+        </respcondition>
+        <respcondition continue="Yes">
+          <conditionvar><varequal respident="response1">A</varequal></conditionvar>
+          <displayfeedback feedbacktype="Response" linkrefid="feedbackA"/>
+        </respcondition>
+      </resprocessing>
+      <itemfeedback ident="feedbackA"><material><mattext texttype="text/plain">No: racks hold pallets</mattext></material></itemfeedback>
+    </item>
+    <item ident="q3" title="Question 3">
+      <presentation>
+        <material><mattext texttype="text/plain">Match the documents.</mattext></material>
+        <material><mattext texttype="text/plain">Inbound</mattext></material>
+        <response_lid ident="response1" rcardinality="Single">
+          <render_choice>
+            <response_label ident="T1"><material><mattext texttype="text/plain">Receipt</mattext></material></response_label>
+            <response_label ident="T2"><material><mattext texttype="text/plain">Delivery</mattext></material></response_label>
+          </render_choice>
+        </response_lid>
+        <material><mattext texttype="text/plain">Outbound</mattext></material>
+        <response_lid ident="response2" rcardinality="Single">
+          <render_choice>
+            <response_label ident="T1"><material><mattext texttype="text/plain">Receipt</mattext></material></response_label>
+            <response_label ident="T2"><material><mattext texttype="text/plain">Delivery</mattext></material></response_label>
+          </render_choice>
+        </response_lid>
+      </presentation>
+      <resprocessing>
+        <outcomes><decvar varname="SCORE" vartype="Decimal" minvalue="0" maxvalue="100"/></outcomes>
+        <respcondition continue="Yes">
+          <conditionvar><varequal respident="response1">T1</varequal></conditionvar>
+          <setvar action="Add" varname="SCORE">50</setvar>
+        </respcondition>
+        <respcondition continue="Yes">
+          <conditionvar><varequal respident="response2">T2</varequal></conditionvar>
+          <setvar action="Add" varname="SCORE">50</setvar>
+        </respcondition>
+      </resprocessing>
+    </item>
+  </section>
+  </assessment>
+</questestinterop>
+"#;
+        assert_eq!(to_qti(&bank), expected);
+    }
+}
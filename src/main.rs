@@ -1,17 +1,16 @@
 
-use indicatif::{ProgressBar, ProgressStyle};
-use pdf_extract::extract_text;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use lopdf::Document;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::error::Error;
-use std::fs::{self, File};
-use std::io::BufWriter;
-use std::path::PathBuf;
-use std::time::{Duration, Instant};
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
-/**
+/*
  * This code snippet demonstrates a Rust program that extracts questions from a PDF file, parses them, validates them,
  * and saves them to a JSON file.
  * 
@@ -27,34 +26,299 @@ use std::borrow::Cow;
  * - `OutputError`: represents an error that can occur during the output process
  * 
  * The program also defines the following functions:
- * - `save_to_json`: saves the questions to a JSON file
+ * - `Bank`: the saved output, a `meta` header with document-level metadata followed by the questions
+ * - `save_to_json`: saves the bank to a JSON file, encrypted when the `crypt::Crypto` context of the bank encrypts
+ * - `load_from_json` / `open_bank`: loads a previously saved bank (or a legacy bare array of questions) from a JSON
+ *   file, from a per-question directory written with `--layout per-question`, or from the NDJSON file written with
+ *   `--stream`
  * - `download_pdf`: downloads a PDF file from a given URL
- * - `parse_questions`: parses the questions from the extracted text
- * - `clean_text`: cleans the text by replacing "<br>" tags with spaces
- * - `validate_questions`: validates the questions
+ * - `fetch_input`: resolves `--input` to a local PDF path, downloading URLs first
+ * - `QuestionParser` (module `parser`): a panic-free state machine parsing the questions from the extracted lines
+ *   using a locale-aware parsing profile, recording the page and printed number of every question and reporting the
+ *   lines it had to skip
+ * - `clean_text` (module `parser`): cleans the text by stripping HTML tags, decoding entities and dropping spaces
+ *   between CJK characters
+ * - `parse_pages` / `annotate_questions`: the text-only part of the extraction, shared with the fixture corpus
+ * - `read_columns`: reorders the text of multi-column pages column by column using the line positions
+ * - `detect_styled_answers`: recovers bold and colored text runs from the PDF content streams for the `bold` and
+ *   `color` answer strategies
+ * - `incremental::reuse_previous`: keeps questions of a previous bank (matched by stable id) in `--previous` runs
+ * - `RunDescriptor`: the effective options of a run, written with `--record run.toml` and reused with `--replay`
+ * - `dump::write_intermediate`: saves the text stages and the parse trace of every page for `--dump-intermediate`
+ * - `history::record`: appends the questions a run added, changed or removed to the `--history` change log
+ * - `manifest::write_manifest`: records the tool version, source PDF hash, profile, timestamps and output checksums
+ * - `timings::Timings`: the time spent downloading, extracting, parsing, validating and exporting, reported with the
+ *   peak memory at the end of the run
+ * - `hooks::Hooks`: runs the `--hook` plugins after the download, after parsing and before the export, which can
+ *   change the bank or stop the run
+ * - `validate_questions`: validates the bank and prints the findings not accepted by the `--baseline`, failing in
+ *   `--strict` mode
  * - `async_main`: the main asynchronous function that orchestrates the program flow
- * - `main`: the main entry point that runs the asynchronous main function
+ * - `extract_file`: the extraction of one PDF into an output directory, reporting to a `progress::Progress`
+ * - `pages::extract_pages`: extracts the text of the pages on a pool of threads, keeping the page order, skipping
+ *   the `--skip-pages` and giving up on pages running past the `--page-timeout`
+ * - `noise::noise_pages`: finds the pages without questions (disclaimers, ads), which are parsed as empty pages
+ * - `outline::chapters`: reads the chapters of the PDF outline, which tag the questions and select the pages of
+ *   `--chapter`
+ * - `stream::extract`: the page-by-page extraction of `--stream`, writing questions as they are parsed
+ * - `batch::run`: extracts every PDF of an `--input` directory with a pool of workers, isolating the files from each
+ *   other (optionally in child processes with timeouts and memory limits) and optionally showing the `dashboard`
+ * - `main`: the main entry point that parses the command line and dispatches to a subcommand
  * 
- * To use the program, provide the path to the PDF file or the URL of the PDF file. The program will download the PDF
- * file if it doesn't exist locally. It will then extract the text from the PDF file, parse the questions, validate
- * them, and save them to a JSON file.
+ * To use the program, provide the path to the PDF file or the URL of the PDF file with `--input`. The program will
+ * download the PDF file if it doesn't exist locally. It will then extract the text from the PDF file, parse the
+ * questions, validate them, and save them to a JSON file together with a `meta` header (exam code, title, version,
+ * date, stated question count, passing score, language) read from the PDF info dictionary and the preamble. Given a
+ * directory, it extracts every PDF in it (`batch`).
+ *
+ * Subcommands:
+ * - `completions <shell>`: prints a completion script for bash, zsh, fish, elvish or PowerShell
+ * - `config show`: prints the effective options merged from defaults, config files, `S4WM_*` variables and flags
+ * - `crawl <index-url>`: follows the links of a provider's site within robots.txt and a rate limit, downloads the PDFs
+ *   it finds and extracts them as a batch
+ * - `edit`: sets the stem, a choice or the answer of a single question, or opens it as YAML in $EDITOR, checking it
+ *   before it is saved
+ * - `exam`: samples a mock exam with an equal share of easy, medium and hard questions, or per topic after a
+ *   blueprint of the exam's topic weights
+ * - `export`: renders a saved bank as bidi-safe Markdown or HTML, as Moodle GIFT / IMS QTI 1.2, or as Anki cloze
+ *   flashcards
+ * - `extract`: the extraction run without a subcommand, which `--scaffold <name>` chains to `scaffold` with the new
+ *   bank
+ * - `filter`: drops the questions whose stem is too long, too short or too hard to read, usually machine-translated
+ *   junk
+ * - `history <id>`: shows the change log entries of a question, following its earlier ids
+ * - `import`: reads a bank kept in another tool, such as an Anki deck of lettered questions, saved forum pages
+ *   with the votes of the community or a spreadsheet laid out by a column mapping, and validates it
+ * - `init`: asks for the input, profile and output formats, writes `./s4wm-extract.toml` and can run the extraction
+ * - `fixtures`: records PDFs into a golden-fixture corpus (extracted text next to the parsed bank), replays it and
+ *   benchmarks the parser on it
+ * - `jobs`: lists the files of a batch job queue by state and runs the failed ones again
+ * - `key`: merges an answer key into a bank by number, falling back to stem similarity when the numbering drifted
+ * - `keygen`: makes the Ed25519 key pair signing the outputs of an extraction with `--sign`
+ * - `lifecycle`: retires questions, marks them as superseded or for review, and lists the ones not active
+ * - `lint`: applies the safe repairs of leftovers of the extraction with `--fix` and reports what it leaves alone
+ * - `overlap`: tells how many questions of a bank another bank already has, with their similarity
+ * - `plugins`: lists the export formats, built-in and from the exporter plugins of the plugin directory, and the hooks
+ * - `profile`: lists, creates and deletes the study profiles of a quiz site, each with its own answers and notes
+ * - `merge`: merges the banks of several dumps, recording every observed answer and resolving conflicts by consensus
+ * - `notes export`: writes the bookmarks and notes taken in the quiz site as Markdown, matched to a bank by id
+ * - `sample`: draws a reproducible subset of a bank keeping the share of its sections, difficulty levels, kinds or
+ *   topics, and the rest for a train/holdout split
+ * - `scaffold`: creates a Vite TypeScript app in React, Vue, Svelte or Solid, or a Tauri desktop app, with
+ *   `--with-questions` wired to a bank
+ * - `redact`: replaces URLs, email addresses, dump-site watermarks and user patterns before a bank is shared
+ * - `replace`: replaces a regular expression in the stems, choices and other texts of a bank, previewing every change
+ *   with `--dry-run`
+ * - `review`: exports a review sheet (CSV) of a bank and applies the reviewers' verdicts back by question id
+ * - `run`: runs a declarative pipeline file (inputs, transforms, validation, exports, destinations), caching every
+ *   stage
+ * - `serve`: hosts a local web page listing the flagged questions, with inline editing and approve/reject buttons
+ *   in `--review` mode
+ * - `site` (or `quiz`): generates a self-contained static quiz site with navigation, scoring and filters
+ * - `stats`: prints bank statistics including the correct-answer letter distribution and its skew, and the length
+ *   and reading ease of the stems
+ * - `translate`: runs an extracted bank through a translation backend (DeepL or LibreTranslate)
+ * - `types`: prints TypeScript declarations of the bank, the validation report and the `serve` API
+ * - `update`: checks the exam sources of `sources.toml` for new versions, extracts them and reports what changed
+ * - `variants`: makes synthetic variants of questions with the distractors of other questions of their topic
+ * - `verify`: checks the signatures of a bank and its manifest and the checksums of the outputs it lists
  */
 
+mod anki;
+mod batch;
+mod config;
+mod crypt;
+mod crawl;
+mod dashboard;
+mod descriptor;
+mod difficulty;
+mod doctor;
+mod dump;
+mod edit;
+mod exam;
+mod export;
+mod filter;
+mod fixtures;
+mod forum;
+mod history;
+mod hooks;
+mod html;
+mod importer;
+mod incremental;
+mod init;
+mod key;
+mod keywords;
+mod kind;
+mod layout;
+mod learner;
+mod lifecycle;
+mod lint;
+mod lms;
+mod manifest;
+mod merge;
+mod meta;
+mod noise;
+mod notes;
+mod ocr;
+mod outline;
+mod overlap;
+mod pages;
+mod parser;
+mod pipeline;
+mod plugin;
+mod profile;
+mod progress;
+mod queue;
+mod readability;
+mod recipe;
+mod redact;
+mod replace;
+mod review;
+mod sample;
+mod schema;
+mod serve;
+mod similarity;
+mod signing;
+mod site;
+mod spreadsheet;
+mod stats;
+mod stream;
+mod styled;
+mod text;
+mod timings;
+mod translate;
+mod tree;
+mod typescript;
+mod update;
+mod validate;
+mod variants;
+mod vite;
+
+use crypt::Crypto;
+use kind::QuestionKind;
+use descriptor::RunDescriptor;
+use hooks::{Hooks, Stage};
+use meta::BankMeta;
+use parser::parse_pages;
+use progress::{Console, Progress};
+use profile::{AnswerStrategy, ParserProfile, Profile};
+use schema::Schema;
+use timings::Timings;
+use validate::{Baseline, ValidationOptions};
+
 #[macro_use]
 extern crate lazy_static;
 
 lazy_static! {
     static ref DIGIT_REGEX: Regex = Regex::new(r"^\d+\.").unwrap();
     static ref CHOICE_REGEX: Regex = Regex::new(r"^[A-D]\.").unwrap();
-    static ref BR_REGEX: Regex = Regex::new(r"<br\s*/?>").unwrap();
 }
+
+/// Dump extracted when no `--input` is given, and where it is downloaded from when missing
+const DEFAULT_PDF: &str = "./C_S4EWM_2020 - Extended Warehouse Management with SAP S4HANA.pdf";
+const DEFAULT_URL: &str = "https://cdn.filestackcontent.com/pTHCm0vSbiGJkwM74n1H";
+const DEFAULT_OUTPUT_DIR: &str = "json";
+/// A numbered statement (I, II, III, ...) that the choices of the question refer to
 #[derive(Serialize, Deserialize, Debug, Clone)]
+struct Statement {
+    label: String,
+    text: String,
+}
+/// Correct mapping of a matching question: premise label to choice letter
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct MatchPair {
+    premise: String,
+    choice: String,
+}
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 struct Question {
     number: String,
+    /// Stable id derived from the normalized stem and choices
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default)]
+    kind: QuestionKind,
     text: String,
     choices: HashMap<String, String>,
+    /// Commentary on the choices by letter, e.g. why a distractor is wrong
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    feedback: HashMap<String, String>,
     correct_answers: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    statements: Vec<Statement>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    answers: Vec<String>,
+    /// Answers given by the sources of a merged bank, with the number of sources giving each
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    observed: Vec<merge::ObservedAnswer>,
+    /// Answer voted for on the discussion forum the question was imported from, next to the official `answers`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    community_answer: Option<forum::CommunityAnswer>,
+    /// Emphasis-bearing words of the stem (NOT, EXCEPT, ...) that exports highlight
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keywords: Vec<String>,
+    /// Text of the stem set in bold, italics or underlined in dumps converted from HTML
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    emphasis: Vec<String>,
+    /// Correct value of a true/false question
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    truth: Option<bool>,
+    /// Choice letters in the correct sequence for ordering questions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    order: Vec<String>,
+    /// Left column of a matching question, the right column is `choices`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    premises: Vec<Statement>,
+    /// Correct premise-to-choice mapping of a matching question
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pairs: Vec<MatchPair>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lang: Option<String>,
+    /// Page the question starts on (1-based)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    page: Option<usize>,
+    /// Title of the innermost chapter of the PDF outline (bookmarks) the question starts in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chapter: Option<String>,
+    /// Number printed in the document, which may differ from the sequential `number`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_number: Option<usize>,
+    /// Heuristic difficulty between 0 (easy) and 1 (hard)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    difficulty: Option<f64>,
+    /// Set when the question was split off the previous one because its number was not recognized; worth a review
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    recovered: bool,
+    /// Whether the question is in use, under review, retired or superseded by a newer version
+    #[serde(default, skip_serializing_if = "lifecycle::Lifecycle::is_active")]
+    lifecycle: lifecycle::Lifecycle,
+    /// Comments left by reviewers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    notes: Vec<String>,
+    /// Id of the question this one is a synthetic variant of, made by `variants` with other distractors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    variant_of: Option<String>,
+    /// Lines the question was parsed from, how each was classified and why, with `--explain`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    explain: Vec<parser::TraceLine>,
+    /// Fields the crate does not know, kept so they survive round-trips (see `schema`)
+    #[serde(flatten)]
+    extra: BTreeMap<String, serde_json::Value>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Bank {
+    meta: BankMeta,
+    questions: Vec<Question>,
+}
+
+// Banks written before the `meta` header existed are a bare array of questions
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredBank {
+    Bank(Bank),
+    Questions(Vec<Question>),
 }
+
 #[derive(Debug)]
 pub struct OutputError {
     message: String,
@@ -118,11 +382,32 @@ impl From<pdf_extract::OutputError> for OutputError {
     }
 }
 
-fn save_to_json(questions: &[Question], output_path: &str) -> Result<(), OutputError> {
-    let file = File::create(output_path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, questions)?;
-    Ok(())
+/// Saves a bank, encrypted when `crypto` encrypts (see `crypt`).
+fn save_to_json(bank: &Bank, output_path: impl AsRef<Path>, crypto: &Crypto) -> Result<(), OutputError> {
+    crypto.write(output_path, serde_json::to_vec_pretty(bank)?)
+}
+
+/// Loads a bank with the context to write it back and what is made of it with, encrypting when the bank was encrypted.
+fn open_bank(input_path: &str, crypto: &Crypto) -> Result<(Bank, Crypto), OutputError> {
+    if PathBuf::from(input_path).is_dir() {
+        return Ok((tree::load_per_question(input_path)?, crypto.clone()));
+    }
+    if input_path.ends_with(".ndjson") {
+        return Ok((stream::load(input_path)?, crypto.clone()));
+    }
+    let (bytes, crypto) = crypto.open(Path::new(input_path))?;
+    let bank = match serde_json::from_slice(&bytes)? {
+        StoredBank::Bank(bank) => bank,
+        StoredBank::Questions(questions) => Bank {
+            meta: BankMeta::default(),
+            questions,
+        },
+    };
+    Ok((bank, crypto))
+}
+
+fn load_from_json(input_path: &str, crypto: &Crypto) -> Result<Bank, OutputError> {
+    open_bank(input_path, crypto).map(|(bank, _)| bank)
 }
 
 async fn download_pdf(url: &str) -> Result<Vec<u8>, reqwest::Error> {
@@ -131,130 +416,953 @@ async fn download_pdf(url: &str) -> Result<Vec<u8>, reqwest::Error> {
     Ok(content.to_vec())
 }
 
-fn parse_questions(full_text: &str) -> Result<Vec<Question>, regex::Error> {
-    let mut questions = Vec::new();
-    let mut current_question: Option<Question> = None;
-    let mut question_number = 1;
-
-    let lines = full_text.split('\n');
-    let digit_regex = Regex::new(r"^\d+\.")?;
-    let choice_regex = Regex::new(r"^[A-D]\.")?;
+fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
 
-    for line in lines {
-        let cleaned_line = clean_text(line);
-        if cleaned_line.is_empty() {
-            continue;
+/// Local path of the PDF to extract, with the time it took to download. URLs are downloaded to a file named after
+/// their last path segment unless that file already exists; without an input the default dump is used.
+async fn fetch_input(input: Option<&str>) -> Result<(String, Option<Duration>), Box<dyn Error>> {
+    let (pdf_path, pdf_url) = match input {
+        None => (DEFAULT_PDF.to_string(), Some(DEFAULT_URL)),
+        Some(url) if is_url(url) => {
+            let name = url.split(['?', '#']).next().unwrap_or(url).trim_end_matches('/').rsplit('/').next();
+            let name = name.filter(|name| !name.is_empty() && !name.contains(':')).unwrap_or("download");
+            let pdf_path = if name.to_lowercase().ends_with(".pdf") {
+                format!("./{}", name)
+            } else {
+                format!("./{}.pdf", name)
+            };
+            (pdf_path, Some(url))
         }
+        Some(path) => (path.to_string(), None),
+    };
 
-        if digit_regex.is_match(&cleaned_line) {
-            if let Some(q) = current_question.take() {
-                questions.push(q);
+    if PathBuf::from(&pdf_path).exists() {
+        return Ok((pdf_path, None));
+    }
+    let pdf_url = pdf_url.ok_or_else(|| OutputError {
+        message: format!("Input PDF {} not found", pdf_path),
+    })?;
+    let started = Instant::now();
+    let pdf_data = download_pdf(pdf_url).await?;
+    fs::write(&pdf_path, &pdf_data)?;
+    Ok((pdf_path, Some(started.elapsed())))
+}
+
+/// Fills the fields derived from the parsed text: id, kind, emphasis keywords and difficulty.
+fn annotate_questions(questions: &mut [Question]) {
+    for question in questions {
+        question.id = Some(similarity::stable_id(question));
+        kind::classify(question);
+        question.keywords = keywords::detect(&question.text);
+        for emphasized in &question.emphasis {
+            if !question.keywords.iter().any(|k| k.eq_ignore_ascii_case(emphasized)) {
+                question.keywords.push(emphasized.clone());
             }
-            current_question = Some(Question {
-                number: question_number.to_string(),
-                text: String::new(),
-                choices: HashMap::new(),
-                correct_answers: None,
-            });
-            question_number += 1;
-        } else if let Some(ref mut question) = current_question {
-            if choice_regex.is_match(&cleaned_line) {
-                let (answer_letter, text_without_answer) = cleaned_line.split_at(2);
-                question.choices.insert(answer_letter.trim().to_string(), text_without_answer.trim().to_string());
-            } else {
-                question.text.push_str(&cleaned_line);
+        }
+        question.difficulty = Some(difficulty::score(question));
+    }
+}
+
+fn validate_questions(
+    bank: &Bank,
+    options: &ValidationOptions,
+    strict: bool,
+    progress: &mut dyn Progress,
+) -> Result<(), OutputError> {
+    let mut report = validate::validate(bank, options);
+    if let Some(baseline) = &options.baseline {
+        let accepted = baseline.accept(&mut report, bank);
+        if accepted > 0 {
+            progress.info(format!("{} known finding(s) accepted by the baseline", accepted));
+        }
+    }
+    report.report(progress);
+    if strict && report.has_errors() {
+        return Err(OutputError {
+            message: format!("Validation failed with {} error(s)", report.error_count()),
+        });
+    }
+    Ok(())
+}
+/// Reorders the lines of the text of multi-column pages so their columns are read one after the other. `columns`
+/// forces the number of columns on every page instead of detecting it.
+/// Returns the number of reordered pages.
+fn read_columns(pdf_path: &str, pages: &mut [String], columns: Option<usize>) -> Result<usize, Box<dyn Error>> {
+    let styled_lines = styled::extract_styled_lines(pdf_path, None)?;
+    let mut reordered = 0;
+    for (index, page) in pages.iter_mut().enumerate() {
+        let page_lines: Vec<&styled::StyledLine> = styled_lines.iter().filter(|l| l.page == index + 1).collect();
+        if let Some(text) = layout::column_text(&page_lines, columns, page) {
+            *page = text;
+            reordered += 1;
+        }
+    }
+    Ok(reordered)
+}
+
+/// Runs the styling-based answer strategies of the profile over the parsed questions.
+/// `pages` restricts the styled-text pass to the given pages.
+fn detect_styled_answers(
+    pdf_path: &str,
+    profile: &ParserProfile,
+    questions: &mut [Question],
+    pages: Option<&BTreeSet<usize>>,
+    progress: &mut dyn Progress,
+) -> Result<(), Box<dyn Error>> {
+    let styled_lines = styled::extract_styled_lines(pdf_path, pages)?;
+
+    let mut outcomes = Vec::new();
+    if profile.profile.uses(AnswerStrategy::Bold) {
+        let outcome = styled::apply_styled_answers(&styled_lines, questions, &profile.choice, |l| l.is_bold());
+        outcomes.push(("bold", outcome));
+    }
+    if profile.profile.uses(AnswerStrategy::Color) {
+        match styled::calibrate_answer_color(&styled_lines, &profile.choice) {
+            Some(answer_color) => {
+                progress.info(format!(
+                    "Answer color calibrated to #{:02x}{:02x}{:02x}",
+                    answer_color[0], answer_color[1], answer_color[2]
+                ));
+                let outcome = styled::apply_styled_answers(&styled_lines, questions, &profile.choice, |l| {
+                    l.color == Some(answer_color)
+                });
+                outcomes.push(("colored", outcome));
             }
+            None => progress.info("No answer color found among the choices".to_string()),
         }
     }
 
-    if let Some(q) = current_question {
-        questions.push(q);
+    for (style, outcome) in outcomes {
+        progress.info(format!("Detected {} answer(s) from {} choices", outcome.answered, style));
+        for conflict in outcome.conflicts {
+            progress.diagnostic(
+                "warning",
+                "style-conflict",
+                format!(
+                    "question {}: answer {} kept, {} choices say {}",
+                    conflict.question,
+                    conflict.marked.join(", "),
+                    style,
+                    conflict.styled.join(", ")
+                ),
+            );
+        }
     }
+    Ok(())
+}
 
-    Ok(questions)
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Extracts exam questions from PDF dumps into JSON question banks",
+    long_about = "Extracts exam questions from PDF dumps into JSON question banks.\n\nWithout a subcommand the \
+                  dump is extracted, validated and saved to json/questions.json, as with `extract`. Options can also \
+                  be set in config files and S4WM_* environment variables (see `config show`).",
+    after_help = "Examples:
+  S4WM-extract
+  S4WM-extract --profile de --answer-strategy marker,bold --strict
+  S4WM-extract --strict --baseline validation-baseline.json --allow similar-question
+  S4WM-extract --input https://example.com/dump.pdf --format markdown,gift
+  S4WM-extract --input dumps/ --dashboard
+  S4WM-extract --input mixed-dumps/ --by-exam
+  S4WM-extract --input dumps/ --queue dumps.sqlite
+  S4WM-extract --encrypt --recipient team-keys.txt --format markdown
+  S4WM-extract --sign pipeline.key
+  S4WM-extract --plugin-dir team/plugins --hook pii-filter --format blackboard
+  S4WM-extract --input dumps/ --jobs 4 --isolate --file-timeout 600 --max-memory 4096
+  S4WM-extract --input consolidated.pdf --stream
+  S4WM-extract --page-timeout 60 --skip-pages 113,200-204
+  S4WM-extract --columns 2 --layout per-question
+  S4WM-extract --previous json/questions.json --record run.toml
+  S4WM-extract help export"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    #[command(flatten)]
+    extract: ExtractOptions,
 }
 
-fn clean_text(text: &str) -> String {
-    BR_REGEX.replace_all(text, " ").trim().into()
+/// The extraction as a subcommand, which can go on with the bank it wrote
+#[derive(Args, Debug)]
+struct ExtractArgs {
+    #[command(flatten)]
+    options: ExtractOptions,
+    /// Create a Vite app with this name wired to the extracted bank once the extraction succeeded
+    #[arg(long, value_name = "NAME")]
+    scaffold: Option<String>,
 }
 
-// Function validate_questions is assumed to be implemented correctly
-fn validate_questions(_questions: &[Question]) -> Result<(), OutputError> {
-    // Assuming implementation here that checks questions and possibly modifies them
-    Ok(())
+/// Options of the extraction run performed when no subcommand is given
+#[derive(Args, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ExtractOptions {
+    /// PDF file or http(s) URL to extract; URLs are downloaded next to the working directory first. Defaults to the
+    /// C_S4EWM_2020 dump
+    #[arg(long, env = "S4WM_INPUT", value_name = "PDF_OR_URL")]
+    input: Option<String>,
+    /// Directory the bank, exports and manifest are written to (json by default); batch runs write a subdirectory per
+    /// file
+    #[arg(long, env = "S4WM_OUTPUT_DIR", value_name = "DIR")]
+    output_dir: Option<String>,
+    /// Parsing profile (en, de, fr); detected from the document language when omitted
+    #[arg(long, env = "S4WM_PROFILE")]
+    profile: Option<String>,
+    /// Answer detection strategies, overriding the profile's (marker, asterisk, bold, color)
+    #[arg(long, env = "S4WM_ANSWER_STRATEGY", value_enum, value_delimiter = ',')]
+    answer_strategy: Vec<AnswerStrategy>,
+    /// Typographic normalization rules, overriding the profile's (quotes, dashes, ellipsis, spaces, whitespace)
+    #[arg(long, env = "S4WM_TYPOGRAPHY", value_enum, value_delimiter = ',')]
+    typography: Vec<profile::Typography>,
+    /// Order of stem and choices in the document, overriding the profile's (stem-first, choices-first)
+    #[arg(long, env = "S4WM_ELEMENT_ORDER", value_enum)]
+    element_order: Option<profile::ElementOrder>,
+    /// Number of text columns per page (1 disables layout analysis); detected per page when omitted
+    #[arg(long, env = "S4WM_COLUMNS")]
+    columns: Option<usize>,
+    /// Bank extracted from an earlier revision of the dump: questions found in it are kept as they are, only new or
+    /// changed ones go through answer detection, and the changes are written to json/questions.delta.json
+    #[arg(long, env = "S4WM_PREVIOUS")]
+    previous: Option<String>,
+    /// Output layout: a single JSON file, or a directory with a JSON and a Markdown file per question
+    #[arg(long, env = "S4WM_LAYOUT", value_enum, default_value = "single")]
+    layout: tree::Layout,
+    /// Additional formats to render the bank in, next to the JSON output (markdown, html, gift, qti, anki or the name
+    /// of an exporter plugin)
+    #[arg(long = "format", env = "S4WM_FORMAT", value_delimiter = ',', value_name = "FORMAT")]
+    formats: Vec<String>,
+    /// Directory of the plugins (see the plugins subcommand); defaults to ~/.config/s4wm-extract/plugins
+    #[arg(long, env = "S4WM_PLUGIN_DIR", value_name = "DIR")]
+    plugin_dir: Option<String>,
+    /// Hook plugins to run at the stages they declare, in this order
+    #[arg(long, env = "S4WM_HOOK", value_delimiter = ',', value_name = "NAME")]
+    hook: Vec<String>,
+    /// With a directory as input, show a dashboard with the status of every file instead of the spinner
+    #[arg(long, env = "S4WM_DASHBOARD")]
+    dashboard: bool,
+    /// Number of pages whose text is extracted at the same time, or in batch runs the number of files extracted at
+    /// the same time; defaults to the number of CPUs
+    #[arg(long, env = "S4WM_JOBS")]
+    jobs: Option<usize>,
+    /// In batch runs, give up on a file after this many seconds
+    #[arg(long, env = "S4WM_FILE_TIMEOUT", value_name = "SECONDS")]
+    file_timeout: Option<u64>,
+    /// In batch runs, save the outputs of every file under a subdirectory named after the exam code in its text
+    #[arg(long, env = "S4WM_BY_EXAM")]
+    by_exam: bool,
+    /// Exam code of the dump, put in front of the names of the output files (`C_S4EWM_2020-questions.json`) and in
+    /// the metadata of the bank; set for every file by --by-exam
+    #[arg(long, env = "S4WM_EXAM_CODE", value_name = "CODE")]
+    exam_code: Option<String>,
+    /// In batch runs, keep the state of every file in this SQLite job queue and only extract the files left
+    #[arg(long, env = "S4WM_QUEUE", value_name = "SQLITE")]
+    queue: Option<String>,
+    /// Give up on the text of a page after this many seconds, leaving the page empty
+    #[arg(long, env = "S4WM_PAGE_TIMEOUT", value_name = "SECONDS")]
+    page_timeout: Option<u64>,
+    /// Pages not to extract, e.g. 113,200-204
+    #[arg(long, env = "S4WM_SKIP_PAGES", value_name = "PAGES")]
+    skip_pages: Option<String>,
+    /// Parse pages without choices that look like disclaimers or ads instead of skipping them
+    #[arg(long, env = "S4WM_KEEP_NOISE_PAGES")]
+    keep_noise_pages: bool,
+    /// Extract only this chapter of the PDF outline (bookmarks), by title
+    #[arg(long, env = "S4WM_CHAPTER", value_name = "TITLE")]
+    chapter: Option<String>,
+    /// In batch runs, extract every file in a child process, so that a crash or runaway memory use only loses that file
+    #[arg(long, env = "S4WM_ISOLATE")]
+    isolate: bool,
+    /// With --isolate, kill a child process whose resident memory exceeds this many MiB (Linux only)
+    #[arg(long, env = "S4WM_MAX_MEMORY", value_name = "MIB")]
+    max_memory: Option<u64>,
+    /// Extract page by page and write the questions to questions.ndjson as they are parsed, keeping memory use low
+    /// on very large documents (fewer checks, see the README)
+    #[arg(long, env = "S4WM_STREAM")]
+    stream: bool,
+    /// Fail instead of warning when validation finds errors (e.g. a question count mismatch)
+    #[arg(long, env = "S4WM_STRICT")]
+    strict: bool,
+    /// Drop questions whose stem and choices exactly repeat an earlier question
+    #[arg(long, env = "S4WM_DROP_DUPLICATES")]
+    drop_duplicates: bool,
+    /// Record on every question the lines it was parsed from, how each was classified and which pattern decided it
+    #[arg(long, env = "S4WM_EXPLAIN")]
+    explain: bool,
+    /// Append the questions this run adds, changes or removes to a change log (NDJSON)
+    #[arg(long, env = "S4WM_HISTORY", value_name = "NDJSON")]
+    history: Option<String>,
+    /// Name recorded as the author in the change log; defaults to the user name
+    #[arg(long, env = "S4WM_AUTHOR")]
+    author: Option<String>,
+    /// Word list for the OCR dictionary check (defaults to /usr/share/dict/words when present)
+    #[arg(long, env = "S4WM_DICTIONARY")]
+    dictionary: Option<String>,
+    /// Schema extension file (TOML) declaring the extra fields of the questions, checked and filled in
+    #[arg(long, env = "S4WM_SCHEMA", value_name = "TOML")]
+    schema: Option<String>,
+    /// Validation rules not to report, by id (e.g. single-choice,duplicate-choice)
+    #[arg(long, env = "S4WM_ALLOW", value_delimiter = ',', value_name = "RULE")]
+    allow: Vec<String>,
+    /// Findings accepted in this bank (JSON); only findings not in it are reported and fail --strict
+    #[arg(long, env = "S4WM_BASELINE", value_name = "JSON")]
+    baseline: Option<String>,
+    /// Write the findings of this run to the --baseline file, accepting them
+    #[arg(long, requires = "baseline")]
+    #[serde(skip)]
+    update_baseline: bool,
+    /// Encrypt the bank and the exports with age to the --recipient keys
+    #[arg(long, env = "S4WM_ENCRYPT")]
+    encrypt: bool,
+    /// age public key (age1...) or file of public keys to encrypt to
+    #[arg(long, env = "S4WM_RECIPIENT", value_delimiter = ',', value_name = "KEY")]
+    recipient: Vec<String>,
+    /// age identity file decrypting encrypted banks
+    #[arg(long, env = "S4WM_IDENTITY", value_name = "FILE")]
+    identity: Option<String>,
+    /// Sign the bank and the manifest with this secret key (see keygen)
+    #[arg(long, env = "S4WM_SIGNING_KEY", value_name = "FILE")]
+    sign: Option<String>,
+    /// Write the effective options of this run to a TOML run descriptor
+    #[arg(long, value_name = "RUN_TOML")]
+    #[serde(skip)]
+    record: Option<String>,
+    /// Run with the options of a recorded run descriptor instead of the other flags
+    #[arg(long, value_name = "RUN_TOML")]
+    #[serde(skip)]
+    replay: Option<String>,
+    /// Save the text handed to the parser, one page per form feed
+    #[arg(long, value_name = "TXT")]
+    #[serde(skip)]
+    dump_text: Option<String>,
+    /// Save the raw, column-ordered and cleaned text and a parse trace per page into a directory
+    #[arg(long, value_name = "DIR")]
+    #[serde(skip)]
+    dump_intermediate: Option<String>,
 }
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print a shell completion script
+    #[command(after_help = "Examples:
+  S4WM-extract completions bash > /etc/bash_completion.d/S4WM-extract
+  S4WM-extract completions zsh > ~/.zfunc/_S4WM-extract
+  S4WM-extract completions fish > ~/.config/fish/completions/S4WM-extract.fish
+  S4WM-extract completions powershell >> $PROFILE")]
+    Completions {
+        /// Shell to generate the script for
+        shell: clap_complete::Shell,
+    },
+    /// Inspect the layered configuration (config files, S4WM_* variables, flags)
+    #[command(after_help = "Examples:
+  S4WM-extract config show
+  S4WM_PROFILE=de S4WM-extract config show")]
+    Config(config::ConfigArgs),
+    /// Find the PDFs of a provider's site from an index page, download them and extract them as a batch
+    #[command(after_help = "Examples:
+  S4WM-extract crawl https://example.com/dumps/
+  S4WM-extract crawl https://example.com/dumps/ --match 'C_S4EWM_*.pdf' --max-depth 2 --list
+  S4WM-extract --output-dir banks crawl https://example.com/dumps/ --delay 5000 --download-dir dumps")]
+    Crawl(crawl::CrawlArgs),
+    /// Correct the stem, a choice or the answer of a single question, or edit it as YAML in $EDITOR
+    #[command(after_help = "Examples:
+  S4WM-extract edit json/questions.json --id 3f2a9c1e --set-answer B
+  S4WM-extract edit json/questions.json --id 3f2a9c1e --set-text 'Which storage type ...' --set-choice C='Putaway'
+  S4WM-extract edit json/questions.json --id 3f2a9c1e --open --history bank-history.ndjson")]
+    Edit(edit::EditArgs),
+    /// Generate a mock exam balanced across difficulty levels
+    #[command(after_help = "Examples:
+  S4WM-extract exam json/questions.json exam.json
+  S4WM-extract exam json/questions.json exam.json -n 40 --seed 7
+  S4WM-extract exam json/questions.json exam.json --blueprint c_s4ewm.toml")]
+    Exam(exam::ExamArgs),
+    /// Render a question bank as Markdown, HTML, Moodle GIFT, IMS QTI or Anki cloze flashcards
+    #[command(after_help = "Examples:
+  S4WM-extract export json/questions.json questions.md
+  S4WM-extract export json/questions.json questions.html --format html
+  S4WM-extract export json/questions.json moodle.gift --format gift
+  S4WM-extract export json/questions.json flashcards.txt --format anki
+  S4WM-extract --plugin-dir team/plugins export json/questions.json course.zip --format blackboard")]
+    Export(export::ExportArgs),
+    /// Extract a dump, as without a subcommand, and optionally scaffold an app around the bank
+    #[command(after_help = "Examples:
+  S4WM-extract extract --input dump.pdf --output-dir json
+  S4WM-extract extract --input dump.pdf --scaffold quiz-app")]
+    Extract(Box<ExtractArgs>),
+    /// Keep the questions whose stem length and reading ease are within bounds
+    #[command(after_help = "Examples:
+  S4WM-extract filter json/questions.json filtered.json --max-words 120
+  S4WM-extract filter json/questions.json filtered.json --max-sentences 6 --min-reading-ease -20 --verbose")]
+    Filter(filter::FilterArgs),
+    /// Record PDFs into a golden-fixture corpus or check the parser against it
+    #[command(after_help = "Examples:
+  S4WM-extract fixtures record dump.pdf tests/corpus --name c_s4ewm_2020
+  S4WM-extract fixtures check tests/corpus
+  S4WM-extract fixtures bench tests/corpus --iterations 50")]
+    Fixtures(fixtures::FixturesArgs),
+    /// Show how a question changed across merges, key merges and dump versions
+    #[command(after_help = "Examples:
+  S4WM-extract history 3f2a9c1e --history bank-history.ndjson
+  S4WM_HISTORY=bank-history.ndjson S4WM-extract history 3f2a9c1e --json")]
+    History(history::HistoryCommandArgs),
+    /// Import a bank kept in another tool (Anki deck, saved forum pages, spreadsheet) and validate it
+    #[command(after_help = "Examples:
+  S4WM-extract import colleagues.apkg anki.json
+  S4WM-extract import deck.txt anki.json --format anki --exam-code C_S4EWM_2020 --strict
+  S4WM-extract import saved-threads/ forum.json --format html
+  S4WM-extract import legacy-bank.csv legacy.json --format csv --mapping mapping.toml
+  S4WM-extract merge json/questions.json anki.json -o merged.json")]
+    Import(importer::ImportArgs),
+    /// Interactively write a project config file and optionally run the first extraction
+    #[command(after_help = "Examples:
+  S4WM-extract init")]
+    Init,
+    /// Inspect the job queue of a batch run with --queue and run its failed files again
+    #[command(after_help = "Examples:
+  S4WM-extract jobs list dumps.sqlite
+  S4WM-extract jobs list dumps.sqlite --state failed
+  S4WM-extract jobs retry-failed dumps.sqlite")]
+    Jobs(queue::JobsArgs),
+    /// Merge an answer key into a bank, aligning drifted numbering by stem similarity
+    #[command(after_help = "Examples:
+  S4WM-extract key json/questions.json key.txt json/questions.json
+  S4WM-extract key json/questions.json solutions/questions.json merged.json --report alignment.json")]
+    Key(key::KeyArgs),
+    /// Make a key pair to sign the outputs of extractions with --sign and check them with verify
+    #[command(after_help = "Examples:
+  S4WM-extract keygen pipeline.key
+  S4WM-extract --sign pipeline.key --format qti")]
+    Keygen(signing::KeygenArgs),
+    /// Retire questions, mark them as superseded or for review, or list the questions that are not active
+    #[command(after_help = "Examples:
+  S4WM-extract lifecycle json/questions.json
+  S4WM-extract lifecycle json/questions.json 3f2a9c1e 77b01d4a --set retired
+  S4WM-extract lifecycle json/questions.json 3f2a9c1e --superseded-by 9e4410aa --history bank-history.ndjson")]
+    Lifecycle(lifecycle::LifecycleArgs),
+    /// Check a bank for leftovers of the extraction and repair the ones that are safe to repair
+    #[command(after_help = "Examples:
+  S4WM-extract lint json/questions.json
+  S4WM-extract lint json/questions.json --fix
+  S4WM-extract lint json/questions.json --fix -o cleaned.json --history bank-history.ndjson")]
+    Lint(lint::LintArgs),
+    /// Merge the banks of several dumps of the same exam, resolving conflicting answers by consensus
+    #[command(after_help = "Examples:
+  S4WM-extract merge dump1/questions.json dump2/questions.json dump3/questions.json -o merged.json
+  S4WM-extract merge a.json b.json -o merged.json --policy unanimous --report merge-report.json")]
+    Merge(merge::MergeArgs),
+    /// Export the bookmarks and notes taken in the quiz site
+    #[command(after_help = "Examples:
+  S4WM-extract notes export notes.json -o notes.md
+  S4WM-extract notes export notes.json -o notes.md --bank json/questions.json --bookmarked")]
+    Notes(notes::NotesArgs),
+    /// Report the questions two banks share, to tell how much a new dump adds
+    #[command(after_help = "Examples:
+  S4WM-extract overlap json/questions.json new-dump/questions.json
+  S4WM-extract overlap json/questions.json new-dump/questions.json --threshold 0.7 --new
+  S4WM-extract overlap json/questions.json new-dump/questions.json --json > overlap.json")]
+    Overlap(overlap::OverlapArgs),
+    /// List the export formats, built-in and from the exporter plugins of the plugin directory, and the hooks
+    #[command(after_help = "Examples:
+  S4WM-extract plugins
+  S4WM-extract --plugin-dir team/plugins plugins")]
+    Plugins,
+    /// List, create or delete the study profiles of a quiz site, which keep their answers and notes apart
+    #[command(after_help = "Examples:
+  S4WM-extract profile create alice --site site
+  S4WM-extract profile list --site site
+  S4WM-extract profile delete bob --site site")]
+    Profile(learner::ProfileArgs),
+    /// Draw a reproducible sample of a bank, stratified by section, difficulty, kind or topic
+    #[command(after_help = "Examples:
+  S4WM-extract sample json/questions.json week-12.json -n 40 --stratify section --seed 42
+  S4WM-extract sample json/questions.json holdout.json -n 100 --stratify difficulty --seed 7 --rest train.json
+  S4WM-extract sample json/questions.json topics.json -n 60 --stratify topic --blueprint c_s4ewm_2020.toml")]
+    Sample(sample::SampleArgs),
+    /// Create a TypeScript app with Vite (React, Vue, Svelte or Solid), optionally wired to a question bank
+    #[command(after_help = "Examples:
+  S4WM-extract scaffold doctor
+  S4WM-extract scaffold quiz-app
+  S4WM-extract scaffold quiz-app --with-questions json/questions.json
+  S4WM-extract scaffold quiz-app --with-questions json/questions.json --full-stack
+  S4WM-extract scaffold quiz-desktop --with-questions json/questions.json --template tauri
+  S4WM-extract scaffold exam-prep --with-questions json/questions.json --workspace
+  S4WM-extract scaffold --print-manifest > scaffold.toml
+  S4WM-extract scaffold quiz-app --manifest scaffold.toml --template my-template
+  S4WM-extract scaffold quiz-app --manifest scaffold.toml --update-pins
+  S4WM-extract scaffold quiz-app --with-questions json/questions.json --offline
+  S4WM-extract scaffold quiz-app --template vue-ts --dry-run
+  S4WM-extract scaffold quiz-app --transcript scaffold.log
+  S4WM-extract scaffold quiz-app --resume
+  S4WM-extract scaffold quiz-app --template svelte-ts --package-manager npm --non-interactive")]
+    Scaffold(vite::ScaffoldArgs),
+    /// Remove URLs, email addresses, watermarks and other patterns from a bank before sharing it
+    #[command(after_help = "Examples:
+  S4WM-extract redact json/questions.json shared.json
+  S4WM-extract redact json/questions.json shared.json --pattern 'Prepared by \\w+' --patterns-from redact.txt
+  S4WM-extract redact json/questions.json shared.json --no-builtin --pattern 'ACME-\\d+' --replacement '[customer]'")]
+    Redact(redact::RedactArgs),
+    /// Replace a pattern in the stems, choices and other texts of a bank, showing every change
+    #[command(after_help = "Examples:
+  S4WM-extract replace json/questions.json --pattern 'SAP EWM 9\\.5' --with 'SAP S/4HANA EWM' --dry-run
+  S4WM-extract replace json/questions.json --pattern 'SAP EWM 9\\.5' --with 'SAP S/4HANA EWM' --fields stem,choices
+  S4WM-extract replace json/questions.json --pattern '(?i)\\bbin (\\w+)' --with 'storage bin $1' -o fixed.json")]
+    Replace(replace::ReplaceArgs),
+    /// Review a bank in a spreadsheet: export a review sheet and import the reviewers' verdicts
+    #[command(after_help = "Examples:
+  S4WM-extract review export json/questions.json review.csv --flagged
+  S4WM-extract review import json/questions.json review.csv --history bank-history.ndjson --author alice")]
+    Review(review::ReviewArgs),
+    /// Run a pipeline file: extract its inputs, transform, validate and export the banks and deliver the outputs
+    #[command(after_help = "Examples:
+  S4WM-extract run pipeline.toml
+  S4WM-extract run pipeline.toml --only ewm-2020
+  S4WM-extract run pipeline.toml --no-cache")]
+    Run(pipeline::RunArgs),
+    /// Serve a bank as a local web page, editable with --review
+    #[command(after_help = "Examples:
+  S4WM-extract serve json/questions.json
+  S4WM-extract serve json/questions.json --review --port 8000 --history bank-history.ndjson")]
+    Serve(serve::ServeArgs),
+    /// Generate a static quiz site from a bank, ready for GitHub Pages or any static host
+    #[command(visible_alias = "quiz", after_help = "Examples:
+  S4WM-extract site json/questions.json -o site
+  S4WM-extract site json/questions.json -o docs --section-size 25
+  S4WM-extract quiz json/questions.json -o drill --missed --streak 3
+  S4WM-extract quiz json/questions.json -o site --profile alice")]
+    Site(site::SiteArgs),
+    /// Print statistics about a question bank
+    #[command(after_help = "Examples:
+  S4WM-extract stats json/questions.json
+  S4WM-extract stats json/questions.json --per-question
+  S4WM-extract stats json/questions.json --json")]
+    Stats(stats::StatsArgs),
+    /// Translate a question bank into another language
+    #[command(after_help = "Examples:
+  DEEPL_AUTH_KEY=... S4WM-extract translate json/questions.json questions.de.json --to de
+  S4WM-extract translate json/questions.json questions.fr.json --to fr --backend libretranslate --endpoint http://localhost:5000")]
+    Translate(translate::TranslateArgs),
+    /// Make synthetic variants of questions with distractors from other questions of the same topic
+    #[command(after_help = "Examples:
+  S4WM-extract variants json/questions.json variants.json --blueprint c_s4ewm.toml
+  S4WM-extract variants json/questions.json practice.json --blueprint c_s4ewm.toml -n 3 --with-originals --seed 7")]
+    Variants(variants::VariantsArgs),
+    /// Check that a bank and its manifest were signed with a key and not changed since
+    #[command(after_help = "Examples:
+  S4WM-extract verify json --key pipeline.pub
+  S4WM-extract verify json/questions.json --key pipeline.pub")]
+    Verify(signing::VerifyArgs),
+    /// Print TypeScript types of the bank, the validation report and the review API
+    #[command(after_help = "Examples:
+  S4WM-extract types
+  S4WM-extract types -o web/src/types/question.ts")]
+    Types(typescript::TypesArgs),
+    /// Check the sources of a registry for new versions, extract them and report how their banks changed
+    #[command(after_help = "Examples:
+  S4WM-extract update
+  S4WM-extract update --check
+  S4WM-extract --output-dir banks --history bank-history.ndjson update ewm-2020 --sources team/sources.toml")]
+    Update(update::UpdateArgs),
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    async_main().await
-}
-
-async fn async_main() -> Result<(), Box<dyn std::error::Error>> {
-    let pdf_path = "./C_S4EWM_2020 - Extended Warehouse Management with SAP S4HANA.pdf";
-    
-    if !PathBuf::from(&pdf_path).exists() {
-        let pdf_url = "https://cdn.filestackcontent.com/pTHCm0vSbiGJkwM74n1H";
-        let pdf_data = download_pdf(pdf_url).await?;
-        fs::write(&pdf_path, &pdf_data)?;
-    }
-
-    let pdf_pages = extract_text(&pdf_path)?; // Handle this Result as well
-    let progress_bar = ProgressBar::new_spinner();
-
-    // Correct way to set the style for the progress bar
-   let style = ProgressStyle::default_spinner()
-    .template("{spinner:.green} [{elapsed_precise}] {wide_msg}")?
-    .tick_strings(&["-", "\\", "|", "/"]);
-    
-    progress_bar.set_style(style);
-    
-
-    let update_frequency = 5;
-    let time_update_frequency = Duration::from_millis(500);
-    let mut last_update = Instant::now(); // Assuming last_update should start at now
-
-    let (all_questions, total_questions_parsed) = pdf_pages.lines().enumerate().try_fold(
-        (Vec::new(), 0),
-        |(mut all_questions_acc, mut total_questions_parsed_acc), (page_number, text)| -> Result<_, Box<dyn std::error::Error>> {
-            let questions = parse_questions(text)?;
-            total_questions_parsed_acc += questions.len();
-            all_questions_acc.extend(questions);
-    
-            if page_number % update_frequency == 0 || last_update.elapsed() >= time_update_frequency {
-                // Directly set the leaked message into the progress bar
-                let msg = format!(
-                    "Processing page {} (total questions: {})",
-                    page_number + 1,
-                    total_questions_parsed_acc
-                );
-                let static_str: &'static str = Box::leak(msg.into_boxed_str());
-                let cow_message: Cow<'static, str> = Cow::Borrowed(static_str);
-                progress_bar.set_message(cow_message);
-                progress_bar.tick(); // Update the progress bar
-                last_update = Instant::now(); // Reset the last update time
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches)?;
+    let config_files = config::apply_files(&mut cli.extract, &matches)?;
+    let crypto = Crypto::new(&cli.extract);
+    match cli.command {
+        Some(Commands::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), env!("CARGO_PKG_NAME"), &mut std::io::stdout());
+            Ok(())
+        }
+        Some(Commands::Config(args)) => config::run(args, &cli.extract, &config_files),
+        Some(Commands::Crawl(args)) => crawl::run(args, &cli.extract).await,
+        Some(Commands::Edit(args)) => edit::run(args, &crypto),
+        Some(Commands::Exam(args)) => exam::run(args, &crypto),
+        Some(Commands::Export(args)) => export::run(args, &cli.extract),
+        Some(Commands::Extract(mut args)) => {
+            if let Some(matches) = matches.subcommand_matches("extract") {
+                config::apply_files(&mut args.options, matches)?;
+            }
+            if args.scaffold.is_some() && args.options.input.as_deref().is_some_and(|input| Path::new(input).is_dir()) {
+                return Err(OutputError::from("--scaffold takes a single input file").into());
             }
-    
-            Ok((all_questions_acc, total_questions_parsed_acc)) // Continue folding
+            let crypto = Crypto::new(&args.options);
+            let bank_path = async_main(args.options).await?;
+            match (args.scaffold, bank_path) {
+                (Some(name), Some(bank_path)) => vite::run(vite::ScaffoldArgs {
+                    name,
+                    with_questions: Some(bank_path.to_string_lossy().into_owned()),
+                    ..Default::default()
+                }, &crypto),
+                _ => Ok(()),
+            }
+        }
+        Some(Commands::Filter(args)) => filter::run(args, &crypto),
+        Some(Commands::Fixtures(args)) => fixtures::run(args),
+        Some(Commands::History(args)) => history::run(args),
+        Some(Commands::Import(args)) => importer::run(args, &crypto),
+        Some(Commands::Init) => {
+            if !init::run()? {
+                return Ok(());
+            }
+            config::apply_files(&mut cli.extract, &matches)?;
+            async_main(cli.extract).await.map(|_| ())
+        }
+        Some(Commands::Jobs(args)) => queue::run(args),
+        Some(Commands::Key(args)) => key::run(args, &crypto),
+        Some(Commands::Keygen(args)) => signing::run_keygen(args),
+        Some(Commands::Lifecycle(args)) => lifecycle::run(args, &crypto),
+        Some(Commands::Lint(args)) => lint::run(args, &crypto),
+        Some(Commands::Merge(args)) => merge::run(args, &crypto),
+        Some(Commands::Notes(args)) => notes::run(args, &crypto),
+        Some(Commands::Overlap(args)) => overlap::run(args, &crypto),
+        Some(Commands::Plugins) => plugin::run(&cli.extract),
+        Some(Commands::Profile(args)) => learner::run(args),
+        Some(Commands::Redact(args)) => redact::run(args, &crypto),
+        Some(Commands::Replace(args)) => replace::run(args, &crypto),
+        Some(Commands::Review(args)) => review::run(args, &crypto),
+        Some(Commands::Run(args)) => pipeline::run(args, &cli.extract).await,
+        Some(Commands::Sample(args)) => sample::run(args, &crypto),
+        Some(Commands::Scaffold(args)) => vite::run(args, &crypto),
+        Some(Commands::Serve(args)) => serve::run(args, &crypto).await,
+        Some(Commands::Site(args)) => site::run(args, &crypto),
+        Some(Commands::Stats(args)) => stats::run(args, &crypto),
+        Some(Commands::Translate(args)) => translate::run(args, &crypto).await,
+        Some(Commands::Types(args)) => typescript::run(args),
+        Some(Commands::Update(args)) => update::run(args, &cli.extract).await,
+        Some(Commands::Variants(args)) => variants::run(args, &crypto),
+        Some(Commands::Verify(args)) => signing::run_verify(args),
+        None => async_main(cli.extract).await.map(|_| ()),
+    }
+}
+
+/// Runs the extraction and returns the path of the bank it wrote, or nothing for a batch run.
+async fn async_main(options: ExtractOptions) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let record = options.record.clone();
+    let replayed = options.replay.as_deref().map(RunDescriptor::load).transpose()?;
+    let options = match &replayed {
+        // the dumps only concern this run, they are not part of the descriptor
+        Some(descriptor) => ExtractOptions {
+            dump_text: options.dump_text,
+            dump_intermediate: options.dump_intermediate,
+            ..descriptor.options.clone()
         },
-    )?;
-   
-    let completion_message = format!("Processing complete: {} questions processed", total_questions_parsed).into_boxed_str();
-    let cow_message: Cow<'static, str> = Cow::Borrowed(Box::leak(completion_message));
-    progress_bar.finish_with_message(cow_message); // Use cow_message, which satisfies the trait bound
-    
-    validate_questions(&all_questions)?;
+        None => options,
+    };
+    if let Some(dir) = options.input.as_deref().filter(|input| Path::new(input).is_dir()) {
+        let single_file = [&record, &options.previous, &options.dump_text, &options.dump_intermediate];
+        if replayed.is_some() || options.update_baseline || single_file.iter().any(|option| option.is_some()) {
+            return Err(OutputError::from(
+                "--record, --replay, --previous, --update-baseline, --dump-text and --dump-intermediate take a single \
+                 input file",
+            )
+            .into());
+        }
+        batch::run(&options, Path::new(dir))?;
+        return Ok(None);
+    }
 
-    // Save the validated questions to JSON
-    let output_path = "json/questions.json";
-    let output_path_buf = PathBuf::from(output_path);
-    let output_dir = output_path_buf.parent().ok_or_else(|| OutputError::from("Failed to get parent directory"))?;
-    
-    // Check if the directory exists and create it if not
-    if !output_dir.exists() {
-        fs::create_dir_all(output_dir)?;
+    let (pdf_path, download) = fetch_input(options.input.as_deref()).await?;
+    let mut timings = Timings::default();
+    if let Some(download) = download {
+        timings.add("download", download);
+    }
+    let output_dir = Path::new(options.output_dir.as_deref().unwrap_or(DEFAULT_OUTPUT_DIR));
+    let mut progress = Console::new();
+    extract_file(&options, &pdf_path, output_dir, replayed.as_ref(), record.as_deref(), timings, &mut progress)?;
+    Ok(Some(bank_path(&options, output_dir)))
+}
+
+/// Path of the bank an extraction with `options` writes into `output_dir`
+fn bank_path(options: &ExtractOptions, output_dir: &Path) -> PathBuf {
+    output_dir.join(output_name(
+        options,
+        match options.layout {
+            _ if options.stream => stream::NDJSON_FILE,
+            tree::Layout::Single => "questions.json",
+            tree::Layout::PerQuestion => "questions",
+        },
+    ))
+}
+
+/// Name of an output file of the run, after the exam code when it has one.
+fn output_name(options: &ExtractOptions, name: &str) -> String {
+    match &options.exam_code {
+        Some(code) => format!("{}-{}", code, name),
+        None => name.to_string(),
+    }
+}
+
+/// Extracts, validates and saves the questions of one PDF into `output_dir`. `replayed` is the descriptor of a
+/// replayed run, `record` where to record this one, `timings` the time already spent on the run (the download).
+/// Returns the number of saved questions.
+fn extract_file(
+    options: &ExtractOptions,
+    pdf_path: &str,
+    output_dir: &Path,
+    replayed: Option<&RunDescriptor>,
+    record: Option<&str>,
+    mut timings: Timings,
+    progress: &mut dyn Progress,
+) -> Result<usize, Box<dyn Error>> {
+    let signing_key = options.sign.as_deref().map(signing::load_signing_key).transpose()?;
+    let hooks = Hooks::load(options)?;
+    hooks.after_download(pdf_path, progress)?;
+    if options.stream {
+        if hooks.has(Stage::AfterParse) || hooks.has(Stage::BeforeExport) {
+            return Err(OutputError::from("--stream only runs after-download hooks").into());
+        }
+        let written = stream::extract(options, pdf_path, output_dir, &mut timings, progress)?;
+        if let Some(key) = &signing_key {
+            signing::sign_run(key, output_dir, &bank_path(options, output_dir), progress)?;
+        }
+        progress.timings(&timings);
+        return Ok(written);
+    }
+    let recipients = crypt::recipients(options)?;
+    let crypto = Crypto::new(options);
+    let registry = export::Registry::load(options)?;
+    let exporters = options.formats.iter().map(|name| registry.get(name)).collect::<Result<Vec<_>, _>>()?;
+    let started = SystemTime::now();
+    let mut skip = options.skip_pages.as_deref().map(pages::parse_page_list).transpose()?.unwrap_or_default();
+    let page_timeout = options.page_timeout.map(Duration::from_secs);
+    timings.start("extract");
+    let document = Document::load(pdf_path)?;
+    let page_count = document.get_pages().len();
+    progress.pages(page_count);
+    let chapters = outline::chapters(&document);
+    let chapter = options.chapter.as_deref().map(|title| outline::find(&chapters, title)).transpose()?;
+    if let Some(chapter) = chapter {
+        progress.info(format!(
+            "Extracting the chapter {} (pages {}-{})",
+            chapter.title, chapter.first_page, chapter.last_page
+        ));
+        skip.extend((1..=page_count).filter(|&page| !chapter.contains(page)));
+    }
+    let page_texts = pages::extract_pages(document, pages::jobs(options.jobs), page_timeout, &skip, |done| {
+        progress.extracted(done)
+    });
+    for (page, message) in &page_texts.failed {
+        progress.diagnostic("warning", "page-text", format!("page {}: {}", page, message));
+    }
+    let beyond: Vec<usize> = skip.iter().copied().filter(|&page| page > page_count).collect();
+    if !beyond.is_empty() {
+        let message = format!("pages {} are not in the document", pages::format_page_list(&beyond));
+        progress.diagnostic("warning", "skip-pages", message);
+    }
+    let mut skipped_pages = manifest::skipped_pages(&page_texts, chapter);
+    let mut pdf_pages = page_texts.texts;
+    let raw_pages = options.dump_intermediate.as_ref().map(|_| pdf_pages.clone());
+    if options.columns != Some(1) {
+        let reordered = read_columns(pdf_path, &mut pdf_pages, options.columns)?;
+        if reordered > 0 {
+            progress.info(format!("Read {} multi-column page(s) column by column", reordered));
+        }
+    }
+    timings.start("parse");
+    let full_text = pdf_pages.join("\n");
+    let mut profile = match &options.profile {
+        Some(name) => Profile::by_name(name).ok_or_else(|| OutputError::from("Unknown parsing profile"))?,
+        None => profile::detect(&full_text),
+    };
+    if !options.answer_strategy.is_empty() {
+        profile.answer_strategies = Cow::Owned(options.answer_strategy.clone());
+    }
+    if !options.typography.is_empty() {
+        profile.typography = Cow::Owned(options.typography.clone());
+    }
+    if let Some(element_order) = options.element_order {
+        profile.element_order = element_order;
+    }
+    let descriptor = RunDescriptor::new(pdf_path, options, &profile)?;
+    if let Some(replayed) = replayed {
+        for difference in replayed.differences(&descriptor) {
+            progress.diagnostic("warning", "replay", difference);
+        }
+    }
+    if let Some(path) = record {
+        descriptor.save(path)?;
+    }
+
+    let patterns = ParserProfile::cached(&profile)?;
+    let trace = options.explain || options.dump_intermediate.is_some();
+    let noise = if options.keep_noise_pages { Vec::new() } else { noise::noise_pages(&pdf_pages, &patterns) };
+    if !noise.is_empty() {
+        let numbers: Vec<usize> = noise.iter().map(|(page, _)| *page).collect();
+        progress.info(format!(
+            "Skipping {} page(s) without questions: {}",
+            numbers.len(),
+            pages::format_page_list(&numbers)
+        ));
+        skipped_pages.extend(noise.iter().map(|(page, reason)| manifest::SkippedPage::new(*page, reason.clone())));
+        skipped_pages.sort_by_key(|skipped| skipped.page);
+    }
+    let parsed_pages = pdf_pages.iter().enumerate().map(|(index, text)| {
+        let is_noise = noise.iter().any(|(page, _)| *page == index + 1);
+        if is_noise { "" } else { text.as_str() }
+    });
+    let mut parsed = parse_pages(parsed_pages, &patterns, trace, |page_number, question_count| {
+        progress.page(page_number, question_count)
+    });
+    if let Some(path) = &options.dump_text {
+        dump::write_text(Path::new(path), &pdf_pages)?;
+    }
+    if let (Some(dir), Some(raw_pages)) = (&options.dump_intermediate, &raw_pages) {
+        dump::write_intermediate(Path::new(dir), raw_pages, &pdf_pages, &parsed.trace)?;
+        progress.info(format!("Intermediate text and parse trace saved to {}", dir));
+    }
+    if options.explain {
+        parsed.explain();
+    }
+    for issue in &parsed.issues {
+        progress.diagnostic("warning", "parse", issue.to_string());
+    }
+    let mut all_questions = parsed.questions;
+    if !chapters.is_empty() {
+        for question in &mut all_questions {
+            let chapter = question.page.and_then(|page| outline::chapter_of(&chapters, page));
+            question.chapter = chapter.map(|chapter| chapter.title.clone());
+        }
+    }
+    let total_questions_parsed = all_questions.len();
+
+    // In incremental runs questions known from the previous bank are kept as they are
+    let page_hashes: Vec<String> = pdf_pages.iter().map(|p| incremental::page_hash(p)).collect();
+    let previous = options.previous.as_deref().map(|path| load_from_json(path, &crypto)).transpose()?;
+    let mut changed_pages = BTreeSet::new();
+    let (fresh, removed) = match &previous {
+        Some(previous) => {
+            changed_pages = incremental::changed_pages(previous, &page_hashes);
+            incremental::reuse_previous(previous, &mut all_questions)
+        }
+        None => ((0..all_questions.len()).collect(), Vec::new()),
+    };
+    let mut fresh_questions: Vec<Question> = fresh.iter().map(|&i| all_questions[i].clone()).collect();
+
+    if profile.uses(AnswerStrategy::Bold) || profile.uses(AnswerStrategy::Color) {
+        // a question's choices may continue on the next page
+        let styled_pages: Option<BTreeSet<usize>> = previous.as_ref().map(|_| {
+            let question_pages = fresh_questions.iter().filter_map(|q| q.page).flat_map(|p| [p, p + 1]);
+            changed_pages.iter().copied().chain(question_pages).collect()
+        });
+        detect_styled_answers(pdf_path, &patterns, &mut fresh_questions, styled_pages.as_ref(), progress)?;
+    }
+    annotate_questions(&mut fresh_questions);
+    for (&index, question) in fresh.iter().zip(&fresh_questions) {
+        all_questions[index] = question.clone();
+    }
+    if options.drop_duplicates {
+        let dropped = similarity::drop_exact_duplicates(&mut all_questions);
+        if dropped > 0 {
+            progress.info(format!("Dropped {} exact duplicate question(s)", dropped));
+        }
     }
+    progress.parsed(total_questions_parsed);
 
-    save_to_json(&all_questions, output_path)
-        .map_err(|e| e.into()) // Convert OutputError into Box<dyn Error>
+    let mut meta = meta::extract_meta(pdf_path, &full_text, &patterns.question);
+    meta.lang = Some(profile.lang.to_string());
+    meta.page_hashes = page_hashes;
+    meta.exam_code = options.exam_code.clone().or(meta.exam_code);
+    let mut bank = Bank {
+        meta,
+        questions: all_questions,
+    };
+    let schema = Schema::load(options.schema.as_deref())?;
+    if let Some(schema) = &schema {
+        let filled = schema.apply(&mut bank);
+        if filled > 0 {
+            progress.info(format!("Filled in {} extra field value(s) declared by the schema", filled));
+        }
+    }
+    let bank = hooks.run(Stage::AfterParse, pdf_path, bank, progress)?;
+
+    timings.start("validate");
+    let mut validation_options = ValidationOptions {
+        dictionary: ocr::load_dictionary(options.dictionary.as_deref())?,
+        allow: validate::allowed(&options.allow)?,
+        baseline: None,
+        schema,
+    };
+    if let Some(path) = &options.baseline {
+        validation_options.baseline = Some(if options.update_baseline {
+            let baseline = Baseline::of(&validate::validate(&bank, &validation_options), &bank);
+            baseline.save(path)?;
+            progress.info(format!("Wrote {} finding(s) to the baseline {}", baseline.len(), path));
+            baseline
+        } else {
+            Baseline::load(path)?
+        });
+    }
+    validate_questions(&bank, &validation_options, options.strict, progress)?;
+
+    // Save the validated questions to JSON
+    timings.start("export");
+    let bank = hooks.run(Stage::BeforeExport, pdf_path, bank, progress)?;
+    fs::create_dir_all(output_dir)?;
+    let bank_path = bank_path(options, output_dir);
+    // the questions this run replaces, compared with the new ones for the change log
+    let replaced = match (&options.history, &previous) {
+        (None, _) => None,
+        (Some(_), Some(previous)) => Some(previous.questions.clone()),
+        (Some(_), None) if bank_path.exists() => Some(load_from_json(&bank_path.to_string_lossy(), &crypto)?.questions),
+        (Some(_), None) => Some(Vec::new()),
+    };
+    let delta_path = output_dir.join(output_name(options, "questions.delta.json"));
+    if previous.is_some() {
+        let delta = incremental::Delta {
+            changed_pages: changed_pages.into_iter().collect(),
+            added: fresh_questions,
+            removed,
+        };
+        progress.info(format!(
+            "{} page(s) changed: {} new or changed question(s), {} removed",
+            delta.changed_pages.len(),
+            delta.added.len(),
+            delta.removed.len()
+        ));
+        crypto.write(&delta_path, serde_json::to_vec_pretty(&delta)?)?;
+    }
 
-    // No need for Ok(()) since save_to_json already returns a Result<(), Box<dyn std::error::Error>>
+    match options.layout {
+        tree::Layout::Single => save_to_json(&bank, &bank_path, &crypto)?,
+        tree::Layout::PerQuestion => tree::save_per_question(&bank, &bank_path, &crypto)?,
+    }
+    let mut outputs = vec![bank_path];
+    if previous.is_some() {
+        outputs.push(delta_path);
+    }
+    let in_use = lifecycle::in_use(&bank);
+    for exporter in &exporters {
+        let path = output_dir.join(output_name(options, &format!("questions.{}", exporter.extension())));
+        crypto.write(&path, exporter.render(&in_use)?)?;
+        outputs.push(path);
+    }
+    if let Some(recipients) = &recipients {
+        progress.info(format!("Encrypted {} output file(s) to {} recipient(s)", outputs.len(), recipients.len()));
+    }
+    timings.stop();
+    manifest::write_manifest(output_dir, pdf_path, &profile, started, &timings, &skipped_pages, &outputs)?;
+    if let Some(key) = &signing_key {
+        signing::sign_run(key, output_dir, &outputs[0], progress)?;
+    }
+    if !skipped_pages.is_empty() {
+        let numbers: Vec<usize> = skipped_pages.iter().map(|skipped| skipped.page).collect();
+        progress.info(format!(
+            "Left out {} page(s), listed in the manifest: {}",
+            numbers.len(),
+            pages::format_page_list(&numbers)
+        ));
+    }
+    if let Some(replaced) = &replaced {
+        let history = history::HistoryArgs {
+            history: options.history.clone(),
+            author: options.author.clone(),
+        };
+        let recorded = history::record(&history, "extract", pdf_path, replaced, &bank.questions, &crypto)?;
+        progress.info(format!("Recorded {} change(s) in the change log", recorded));
+    }
+    progress.timings(&timings);
+    Ok(bank.questions.len())
 }
@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::outline::Chapter;
+use crate::pages::PageTexts;
+use crate::profile::Profile;
+use crate::timings::{StageTimes, Timings};
+use crate::OutputError;
+
+// Manifest written next to the outputs of an extraction run. It records what produced them (tool version, source
+// PDF hash, parsing profile, timestamps), the time of every stage of the run with its peak memory, the pages left out
+// of the bank (skipped with `--skip-pages`, failed or timed out) and a SHA-256 checksum of every output file, so
+// consumers can verify a bank and reproduce it from the same PDF.
+
+pub(crate) const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FileEntry {
+    path: String,
+    sha256: String,
+    bytes: u64,
+}
+
+/// A page whose text is not in the bank
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct SkippedPage {
+    pub(crate) page: usize,
+    reason: String,
+}
+
+impl SkippedPage {
+    pub(crate) fn new(page: usize, reason: String) -> Self {
+        SkippedPage { page, reason }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Manifest {
+    tool: &'static str,
+    version: &'static str,
+    source: FileEntry,
+    profile: &'static str,
+    answer_strategies: Vec<String>,
+    started_at: String,
+    finished_at: String,
+    timings: StageTimes,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    skipped_pages: Vec<SkippedPage>,
+    outputs: Vec<FileEntry>,
+}
+
+/// The part of a manifest read back to verify a run
+#[derive(Deserialize, Debug)]
+struct Outputs {
+    outputs: Vec<FileEntry>,
+}
+
+/// The pages skipped on request, by `--skip-pages` or as outside the `--chapter`, and the pages whose text could not be
+/// extracted, in page order.
+pub(crate) fn skipped_pages(page_texts: &PageTexts, chapter: Option<&Chapter>) -> Vec<SkippedPage> {
+    let requested = page_texts.skipped.iter().map(|&page| SkippedPage {
+        page,
+        reason: match chapter.filter(|chapter| !chapter.contains(page)) {
+            Some(chapter) => format!("outside the chapter {:?}", chapter.title),
+            None => "skipped with --skip-pages".to_string(),
+        },
+    });
+    let failed = page_texts.failed.iter().map(|(page, reason)| SkippedPage {
+        page: *page,
+        reason: reason.clone(),
+    });
+    let mut pages: Vec<SkippedPage> = requested.chain(failed).collect();
+    pages.sort_by_key(|skipped| skipped.page);
+    pages
+}
+
+/// Entry of a file, its path relative to `base` when it is inside it, with `/` separators so the manifest reads the
+/// same on every system.
+fn file_entry(path: &Path, base: Option<&Path>) -> Result<FileEntry, OutputError> {
+    let bytes = fs::read(path)?;
+    let relative = base.and_then(|base| path.strip_prefix(base).ok());
+    let path = match relative {
+        Some(relative) => {
+            relative.components().map(|part| part.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+        }
+        None => path.to_string_lossy().into_owned(),
+    };
+    Ok(FileEntry {
+        path,
+        sha256: sha256_hex(&bytes),
+        bytes: bytes.len() as u64,
+    })
+}
+
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// UTC timestamp in RFC 3339 format, with the civil date computed from the day count (Howard Hinnant's algorithm).
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+/// Output files to list in the manifest: the given files, and the files inside the given directories.
+fn expand(outputs: &[PathBuf]) -> Result<Vec<PathBuf>, OutputError> {
+    let mut files = Vec::new();
+    for output in outputs {
+        if output.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(output)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else if output.is_file() {
+            files.push(output.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// The output files listed in a manifest, relative to its directory, with their SHA-256.
+pub(crate) fn listed_outputs(manifest: &Path) -> Result<Vec<(String, String)>, OutputError> {
+    let outputs: Outputs = serde_json::from_str(&fs::read_to_string(manifest)?)?;
+    Ok(outputs.outputs.into_iter().map(|entry| (entry.path, entry.sha256)).collect())
+}
+
+/// Writes the manifest for the given outputs into `dir`, listing them by their path inside it.
+pub(crate) fn write_manifest(
+    dir: &Path,
+    pdf_path: &str,
+    profile: &Profile,
+    started: SystemTime,
+    timings: &Timings,
+    skipped_pages: &[SkippedPage],
+    outputs: &[PathBuf],
+) -> Result<(), OutputError> {
+    let manifest = Manifest {
+        tool: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        source: file_entry(Path::new(pdf_path), None)?,
+        profile: profile.name,
+        answer_strategies: profile.answer_strategies.iter().map(|s| format!("{:?}", s).to_lowercase()).collect(),
+        started_at: rfc3339(started),
+        finished_at: rfc3339(SystemTime::now()),
+        timings: timings.stage_times(),
+        skipped_pages: skipped_pages.to_vec(),
+        outputs: expand(outputs)?.iter().map(|path| file_entry(path, Some(dir))).collect::<Result<_, _>>()?,
+    };
+    fs::write(dir.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(seconds: u64) -> String {
+        rfc3339(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn timestamps_follow_the_calendar() {
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        assert_eq!(at(1_709_210_096), "2024-02-29T12:34:56Z");
+        // 2000 is a leap year despite being a century, 2100 is not
+        assert_eq!(at(951_782_399), "2000-02-28T23:59:59Z");
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(978_307_199), "2000-12-31T23:59:59Z");
+        assert_eq!(at(4_107_542_399), "2100-02-28T23:59:59Z");
+        assert_eq!(at(4_107_542_400), "2100-03-01T00:00:00Z");
+        assert_eq!(rfc3339(UNIX_EPOCH - Duration::from_secs(1)), "1970-01-01T00:00:00Z");
+    }
+}
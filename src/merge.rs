@@ -0,0 +1,329 @@
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::crypt::Crypto;
+use crate::history::{self, HistoryArgs};
+use crate::similarity::{normalize, question_key};
+use crate::{load_from_json, open_bank, save_to_json, Bank, Question};
+
+// Merging the banks of several dumps covering the same exam. Questions are matched by their normalized stem and
+// choices, independently of numbering and choice order, and answers are compared by choice text so that dumps
+// shuffling the choices still agree. Dumps often disagree on the answers: every answer seen for a question is kept
+// with the sources giving it, and a consensus policy decides which one the merged question takes. Questions the
+// policy cannot decide are left without an answer rather than guessed.
+
+/// How the answer of a question is chosen from the answers of the sources
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Policy {
+    /// The answer given by more than half of the sources answering the question
+    #[default]
+    Majority,
+    /// The answer given by the most sources, unresolved on a tie
+    Plurality,
+    /// The answer all sources agree on
+    Unanimous,
+}
+
+#[derive(Args, Debug)]
+pub struct MergeArgs {
+    /// Banks to merge, the first one gives the order of the questions and their wording
+    #[arg(required = true, num_args = 2..)]
+    inputs: Vec<String>,
+    /// Where to write the merged bank
+    #[arg(short, long)]
+    output: String,
+    /// How conflicting answers are resolved
+    #[arg(long, value_enum, default_value = "majority")]
+    policy: Policy,
+    /// Write the merge report with the answer conflicts to this JSON file
+    #[arg(long, value_name = "JSON")]
+    report: Option<String>,
+    #[command(flatten)]
+    history: HistoryArgs,
+}
+
+/// Answers given for a question by some of the sources
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct ObservedAnswer {
+    pub(crate) answers: Vec<String>,
+    /// Number of sources giving these answers
+    pub(crate) count: usize,
+    pub(crate) sources: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct SourceSummary {
+    path: String,
+    questions: usize,
+}
+
+/// A question the sources disagree on
+#[derive(Serialize, Debug)]
+struct Conflict {
+    number: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    observed: Vec<ObservedAnswer>,
+    /// Answer chosen by the policy, absent when the question was left unresolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolved: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+struct MergeReport {
+    policy: Policy,
+    sources: Vec<SourceSummary>,
+    questions: usize,
+    /// Questions found in more than one source
+    shared: usize,
+    unresolved: usize,
+    conflicts: Vec<Conflict>,
+}
+
+/// Letters of `target` whose choice text is the one `source` gives for `letters`; letters without a matching choice
+/// are kept as they are.
+fn map_letters(letters: &[String], source: &Question, target: &Question) -> Vec<String> {
+    let by_text: HashMap<String, &String> =
+        target.choices.iter().map(|(letter, text)| (normalize(text), letter)).collect();
+    let mut mapped: Vec<String> = letters
+        .iter()
+        .map(|letter| {
+            source
+                .choices
+                .get(letter)
+                .and_then(|text| by_text.get(&normalize(text)))
+                .map_or_else(|| letter.clone(), |letter| (*letter).clone())
+        })
+        .collect();
+    mapped.sort();
+    mapped
+}
+
+/// The answers the policy settles on, given the observations sorted by decreasing count.
+fn resolve(observed: &[ObservedAnswer], policy: Policy) -> Option<Vec<String>> {
+    let top = observed.first()?;
+    let total: usize = observed.iter().map(|o| o.count).sum();
+    let settled = match policy {
+        Policy::Majority => top.count * 2 > total,
+        Policy::Plurality => observed.get(1).is_none_or(|second| second.count != top.count),
+        Policy::Unanimous => observed.len() == 1,
+    };
+    settled.then(|| top.answers.clone())
+}
+
+fn merge_banks(sources: &[(String, Bank)], policy: Policy) -> (Bank, MergeReport) {
+    let mut questions: Vec<Question> = Vec::new();
+    // answers and source of every observation, per merged question
+    let mut observations: Vec<Vec<(Vec<String>, String)>> = Vec::new();
+    let mut sources_seen: Vec<BTreeSet<&str>> = Vec::new();
+    let mut index: HashMap<(String, BTreeSet<String>), usize> = HashMap::new();
+
+    for (path, bank) in sources {
+        for question in &bank.questions {
+            let position = *index.entry(question_key(question)).or_insert_with(|| {
+                let mut merged = question.clone();
+                merged.observed.clear();
+                questions.push(merged);
+                observations.push(Vec::new());
+                sources_seen.push(BTreeSet::new());
+                questions.len() - 1
+            });
+            // extra fields only some sources carry are kept, the first source giving one wins
+            for (name, value) in &question.extra {
+                questions[position].extra.entry(name.clone()).or_insert_with(|| value.clone());
+            }
+            // a dump repeating a question counts once
+            if !sources_seen[position].insert(path.as_str()) || question.answers.is_empty() {
+                continue;
+            }
+            let answers = map_letters(&question.answers, question, &questions[position]);
+            observations[position].push((answers, path.clone()));
+        }
+    }
+
+    let mut conflicts = Vec::new();
+    let mut unresolved = 0;
+    for (position, question) in questions.iter_mut().enumerate() {
+        question.number = (position + 1).to_string();
+        let mut observed: Vec<ObservedAnswer> = Vec::new();
+        for (answers, path) in observations[position].drain(..) {
+            match observed.iter_mut().find(|o| o.answers == answers) {
+                Some(existing) => {
+                    existing.count += 1;
+                    existing.sources.push(path);
+                }
+                None => observed.push(ObservedAnswer {
+                    answers,
+                    count: 1,
+                    sources: vec![path],
+                }),
+            }
+        }
+        // stable, so ties keep the order of the sources
+        observed.sort_by_key(|o| Reverse(o.count));
+        if observed.is_empty() {
+            continue;
+        }
+
+        let resolved = resolve(&observed, policy);
+        match &resolved {
+            Some(answers) => {
+                question.answers = answers.clone();
+                question.correct_answers = Some(answers.len());
+            }
+            None => {
+                question.answers.clear();
+                unresolved += 1;
+            }
+        }
+        if observed.len() > 1 {
+            conflicts.push(Conflict {
+                number: question.number.clone(),
+                id: question.id.clone(),
+                observed: observed.clone(),
+                resolved,
+            });
+        }
+        question.observed = observed;
+    }
+
+    let mut meta = sources.first().map(|(_, bank)| bank.meta.clone()).unwrap_or_default();
+    // page hashes describe a single PDF
+    meta.page_hashes.clear();
+    let report = MergeReport {
+        policy,
+        sources: sources
+            .iter()
+            .map(|(path, bank)| SourceSummary {
+                path: path.clone(),
+                questions: bank.questions.len(),
+            })
+            .collect(),
+        questions: questions.len(),
+        shared: sources_seen.iter().filter(|seen| seen.len() > 1).count(),
+        unresolved,
+        conflicts,
+    };
+    (Bank { meta, questions }, report)
+}
+
+fn describe(observed: &[ObservedAnswer]) -> String {
+    observed
+        .iter()
+        .map(|o| format!("{} ({} source{})", o.answers.join(", "), o.count, if o.count == 1 { "" } else { "s" }))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn run(args: MergeArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    // the merge of an encrypted bank is encrypted
+    let mut writing = crypto.clone();
+    let mut sources = Vec::new();
+    for path in &args.inputs {
+        let (bank, opened) = open_bank(path, crypto)?;
+        if opened.is_encrypting() {
+            writing = opened;
+        }
+        sources.push((path.clone(), bank));
+    }
+    let crypto = writing;
+    let (bank, report) = merge_banks(&sources, args.policy);
+    // a merge into an existing bank is logged against it
+    let before = match &args.history.history {
+        Some(_) if Path::new(&args.output).exists() => Some(load_from_json(&args.output, &crypto)?.questions),
+        Some(_) => Some(Vec::new()),
+        None => None,
+    };
+
+    for conflict in &report.conflicts {
+        let outcome = match &conflict.resolved {
+            Some(answers) => format!("{} kept", answers.join(", ")),
+            None => "left unresolved".to_string(),
+        };
+        eprintln!(
+            "warning[answer-conflict] question {}: {}, {}",
+            conflict.number,
+            describe(&conflict.observed),
+            outcome
+        );
+    }
+    println!(
+        "Merged {} question(s) from {} banks, {} found in several: {} conflict(s), {} unresolved",
+        report.questions,
+        report.sources.len(),
+        report.shared,
+        report.conflicts.len(),
+        report.unresolved
+    );
+
+    save_to_json(&bank, &args.output, &crypto)?;
+    if let Some(path) = &args.report {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &report)?;
+    }
+    if let Some(before) = &before {
+        history::record(&args.history, "merge", &args.inputs.join(", "), before, &bank.questions, &crypto)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bank with the monitor question, its choices in the given order and the answer given by choice text
+    fn source(path: &str, choices: [&str; 2], answer: &str) -> (String, Bank) {
+        let mut question = Question {
+            number: "1".to_string(),
+            text: "Which transaction shows the warehouse management monitor?".to_string(),
+            ..Question::default()
+        };
+        for (letter, text) in ["A", "B"].into_iter().zip(choices) {
+            question.choices.insert(letter.to_string(), text.to_string());
+            if text == answer {
+                question.answers.push(letter.to_string());
+            }
+        }
+        let bank = Bank {
+            questions: vec![question],
+            ..Bank::default()
+        };
+        (path.to_string(), bank)
+    }
+
+    const CHOICES: [&str; 2] = ["/SCWM/MON", "/SCWM/PRDI"];
+    const SHUFFLED: [&str; 2] = ["/SCWM/PRDI", "/SCWM/MON"];
+
+    #[test]
+    fn shuffled_choices_agree_on_the_answer() {
+        let sources = [source("a.json", CHOICES, "/SCWM/MON"), source("b.json", SHUFFLED, "/SCWM/MON")];
+        let (bank, report) = merge_banks(&sources, Policy::Unanimous);
+        assert_eq!(bank.questions.len(), 1);
+        assert_eq!(bank.questions[0].answers, ["A"]);
+        assert_eq!(report.shared, 1);
+        assert!(report.conflicts.is_empty());
+    }
+
+    #[test]
+    fn conflicting_answers_follow_the_policy() {
+        let sources = [
+            source("a.json", CHOICES, "/SCWM/MON"),
+            source("b.json", SHUFFLED, "/SCWM/MON"),
+            source("c.json", CHOICES, "/SCWM/PRDI"),
+        ];
+        let (bank, report) = merge_banks(&sources, Policy::Majority);
+        assert_eq!(bank.questions[0].answers, ["A"]);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].observed[0].sources, ["a.json", "b.json"]);
+
+        let (bank, report) = merge_banks(&sources, Policy::Unanimous);
+        assert!(bank.questions[0].answers.is_empty());
+        assert_eq!(report.unresolved, 1);
+    }
+}
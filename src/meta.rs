@@ -0,0 +1,212 @@
+use lopdf::{Document, Object};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::pages::page_text;
+
+// Document-level metadata written as the `meta` header of a bank. Values come from the PDF info dictionary when
+// present and otherwise from the preamble, i.e. the lines before the first question. A document whose preamble
+// names no exam code gets the code printed most often in its text, usually in the page headers or footers.
+
+const PREAMBLE_LINES: usize = 200;
+/// Pages read to detect the exam code of a document before extracting it
+const EXAM_CODE_PAGES: usize = 3;
+
+lazy_static! {
+    static ref EXAM_CODE_REGEX: Regex = Regex::new(r"\b([A-Z]_[A-Z0-9]+_\d{2,4})\b").unwrap();
+    static ref TITLE_REGEX: Regex = Regex::new(r"^[A-Z]_[A-Z0-9]+_\d{2,4}\s*[-–:]\s*(.+)$").unwrap();
+    static ref VERSION_REGEX: Regex = Regex::new(r"(?i)\bversion\s*:?\s*(v?\d+(?:\.\d+)*)").unwrap();
+    static ref DATE_REGEX: Regex = Regex::new(r"\b(\d{4}-\d{2}-\d{2}|\d{1,2}[./]\d{1,2}[./]\d{4})\b").unwrap();
+    static ref COUNT_REGEX: Regex =
+        Regex::new(r"(?i)(?:number|total)\s+of\s+questions\s*:?\s*(\d+)|\b(\d+)\s+questions\b").unwrap();
+    static ref PASSING_SCORE_REGEX: Regex =
+        Regex::new(r"(?i)(?:passing|cut)[\s-]*(?:score|mark)\s*:?\s*(\d+\s*%?)").unwrap();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct BankMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) exam_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) date: Option<String>,
+    /// Question count announced by the document itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) stated_question_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) passing_score: Option<String>,
+    /// Detected document language (ISO 639-1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) lang: Option<String>,
+    /// Hash of the extracted text of every page, used to tell changed pages apart in incremental runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) page_hashes: Vec<String>,
+}
+
+fn first_capture(regex: &Regex, line: &str) -> Option<String> {
+    let caps = regex.captures(line)?;
+    caps.iter().skip(1).flatten().next().map(|m| m.as_str().trim().to_string())
+}
+
+/// Decodes a PDF text string, which is either UTF-16BE with a byte order mark or PDFDocEncoding (approximated as
+/// Latin-1).
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xfe, 0xff]) {
+        let units: Vec<u16> = bytes[2..].chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+}
+
+fn info_title(document: &Document) -> Option<String> {
+    let info = match document.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => document.get_object(*id).ok()?,
+        object => object,
+    };
+    match info.as_dict().ok()?.get(b"Title").ok()? {
+        Object::String(bytes, _) => Some(decode_pdf_string(bytes)).filter(|t| !t.trim().is_empty()),
+        _ => None,
+    }
+}
+
+fn pdf_info_title(pdf_path: &str) -> Option<String> {
+    info_title(&Document::load(pdf_path).ok()?)
+}
+
+/// The exam code found most often in `text`, the first one on a tie.
+fn most_frequent_exam_code(text: &str) -> Option<String> {
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (order, code) in EXAM_CODE_REGEX.find_iter(text).enumerate() {
+        counts.entry(code.as_str()).or_insert((0, order)).0 += 1;
+    }
+    let (code, _) = counts.into_iter().max_by_key(|&(_, (count, first))| (count, usize::MAX - first))?;
+    Some(code.to_string())
+}
+
+/// Exam code of a PDF from its title and first pages, read before the extraction to route the outputs of a batch.
+pub(crate) fn detect_exam_code(pdf_path: &str) -> Option<String> {
+    let document = Document::load(pdf_path).ok()?;
+    let mut text = info_title(&document).unwrap_or_default();
+    for &page in document.get_pages().keys().take(EXAM_CODE_PAGES) {
+        if let Ok(page_text) = page_text(&document, page) {
+            text.push('\n');
+            text.push_str(&page_text);
+        }
+    }
+    most_frequent_exam_code(&text)
+}
+
+/// Collects metadata from the PDF info dictionary and the preamble of the extracted text. `question_pattern` is the
+/// profile's question number pattern and marks the end of the preamble.
+pub(crate) fn extract_meta(pdf_path: &str, full_text: &str, question_pattern: &Regex) -> BankMeta {
+    let mut meta = preamble_meta(full_text, question_pattern);
+    if let Some(title) = pdf_info_title(pdf_path) {
+        meta.title = Some(title);
+    }
+    if meta.exam_code.is_none() {
+        meta.exam_code = most_frequent_exam_code(full_text);
+    }
+    meta
+}
+
+/// Collects metadata from the preamble of the extracted text alone.
+pub(crate) fn preamble_meta(full_text: &str, question_pattern: &Regex) -> BankMeta {
+    let mut meta = BankMeta::default();
+
+    let preamble = full_text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .take(PREAMBLE_LINES)
+        .take_while(|line| !question_pattern.is_match(line));
+
+    for line in preamble {
+        if meta.exam_code.is_none() {
+            meta.exam_code = first_capture(&EXAM_CODE_REGEX, line);
+        }
+        if meta.title.is_none() {
+            meta.title = first_capture(&TITLE_REGEX, line);
+        }
+        if meta.version.is_none() {
+            meta.version = first_capture(&VERSION_REGEX, line);
+        }
+        if meta.date.is_none() {
+            meta.date = first_capture(&DATE_REGEX, line);
+        }
+        if meta.stated_question_count.is_none() {
+            meta.stated_question_count = first_capture(&COUNT_REGEX, line).and_then(|n| n.parse().ok());
+        }
+        if meta.passing_score.is_none() {
+            meta.passing_score = first_capture(&PASSING_SCORE_REGEX, line);
+        }
+    }
+
+    meta
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// A document of one empty page titled `title`, saved as `name` in the temp directory
+    pub(crate) fn titled_pdf(name: &str, title: &str) -> std::path::PathBuf {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        let pages = dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 };
+        document.objects.insert(pages_id, Object::Dictionary(pages));
+        let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info_id = document.add_object(dictionary! { "Title" => Object::string_literal(title) });
+        document.trailer.set("Root", catalog_id);
+        document.trailer.set("Info", info_id);
+        let path = std::env::temp_dir().join(format!("s4wm-meta-{}-{}.pdf", name, std::process::id()));
+        document.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn the_most_frequent_exam_code_wins() {
+        let footers = "C_TS452_2021 page 1\nSee C_S4EWM_2020\nC_TS452_2021 page 2\nC_S4EWM_2020 or C_TS452_2021";
+        assert_eq!(most_frequent_exam_code(footers).as_deref(), Some("C_TS452_2021"));
+        // a tie goes to the code printed first
+        let tie = "E_S4CON_2022 and C_S4EWM_2020, then C_S4EWM_2020 and E_S4CON_2022";
+        assert_eq!(most_frequent_exam_code(tie).as_deref(), Some("E_S4CON_2022"));
+        // codes are whole words with a year of 2 to 4 digits
+        assert_eq!(most_frequent_exam_code("XC_S4EWM_2020 C_S4EWM_20201 C_S4EWM_1").as_deref(), None);
+        assert_eq!(most_frequent_exam_code("No code here"), None);
+    }
+
+    #[test]
+    fn exam_codes_are_read_from_the_title() {
+        let pdf = titled_pdf("title", "C_S4EWM_2020 - Extended Warehouse Management");
+        let detected = detect_exam_code(&pdf.to_string_lossy());
+        let untitled = titled_pdf("untitled", "Warehouse practice questions");
+        let none = detect_exam_code(&untitled.to_string_lossy());
+        std::fs::remove_file(&pdf).unwrap();
+        std::fs::remove_file(&untitled).unwrap();
+
+        assert_eq!(detected.as_deref(), Some("C_S4EWM_2020"));
+        assert_eq!(none, None);
+        assert_eq!(detect_exam_code("/nonexistent/dump.pdf"), None);
+    }
+
+    #[test]
+    fn preambles_give_the_exam_code_and_title() {
+        let question = Regex::new(r"^\d+\.").unwrap();
+        let text = "C_TS452_2021 - Sourcing and Procurement\nVersion: 3.1\n1. Which transaction...\nC_S4EWM_2020";
+        let meta = preamble_meta(text, &question);
+        assert_eq!(meta.exam_code.as_deref(), Some("C_TS452_2021"));
+        assert_eq!(meta.title.as_deref(), Some("Sourcing and Procurement"));
+        assert_eq!(meta.version.as_deref(), Some("3.1"));
+    }
+}
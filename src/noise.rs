@@ -0,0 +1,79 @@
+use regex::Regex;
+
+use crate::profile::ParserProfile;
+
+// Pages without questions. Dumps open with pages of legal disclaimers, advertising and instructions, and some put ads
+// between the questions; numbered lists on them are taken for questions and their text ends up in stems. A page is
+// noise when it holds no choice and no answer line, so that it cannot be part of a question, and either is thick
+// with links (ads, "visit www..."), or comes before the first page with choices. The page right before that one may
+// hold the stem of the first question, so it only counts as noise without a question number on it. Noise pages are
+// parsed as empty pages, unless `--keep-noise-pages` is given; the header is still read from the whole text.
+
+/// Share of links per non-empty line from which a page without choices is taken for an advertisement
+const LINK_DENSITY: f64 = 0.2;
+/// Fewer links than this never make a page noise, whatever its length
+const MIN_LINKS: usize = 3;
+
+lazy_static! {
+    /// URLs, bare web addresses and e-mail addresses
+    static ref LINK_REGEX: Regex =
+        Regex::new(r"(?i)\bhttps?://\S+|\bwww\.[a-z0-9-]+\.\S+|\b[\w.+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)+").unwrap();
+}
+
+/// What the parser would find on a page
+struct PageLines {
+    lines: usize,
+    questions: usize,
+    choices: usize,
+    answers: usize,
+    links: usize,
+}
+
+fn page_lines(text: &str, patterns: &ParserProfile) -> PageLines {
+    let mut page = PageLines {
+        lines: 0,
+        questions: 0,
+        choices: 0,
+        answers: 0,
+        links: LINK_REGEX.find_iter(text).count(),
+    };
+    for line in text.lines() {
+        let line = patterns.profile.normalize(line.trim());
+        if line.is_empty() {
+            continue;
+        }
+        page.lines += 1;
+        if patterns.question.is_match(&line) {
+            page.questions += 1;
+        } else if patterns.answer.is_match(&line) {
+            page.answers += 1;
+        } else if patterns.choice.is_match(line.trim_start_matches('*').trim_start()) {
+            page.choices += 1;
+        }
+    }
+    page
+}
+
+/// The noise pages (1-based) with the reason they were taken for noise.
+pub(crate) fn noise_pages<P: AsRef<str>>(pages: &[P], patterns: &ParserProfile) -> Vec<(usize, String)> {
+    let pages: Vec<PageLines> = pages.iter().map(|text| page_lines(text.as_ref(), patterns)).collect();
+    let Some(first_question) = pages.iter().position(|page| page.choices > 0 || page.answers > 0) else {
+        // no questions at all, nothing to tell them from
+        return Vec::new();
+    };
+    let mut noise = Vec::new();
+    for (index, page) in pages.iter().enumerate() {
+        if page.lines == 0 || page.choices > 0 || page.answers > 0 {
+            continue;
+        }
+        let reason = if page.links >= MIN_LINKS && page.links as f64 >= LINK_DENSITY * page.lines as f64 {
+            format!("no questions, {} links on {} lines", page.links, page.lines)
+        } else if index + 1 < first_question || (index + 1 == first_question && page.questions == 0) {
+            "no questions, before the first question".to_string()
+        } else {
+            continue;
+        };
+        noise.push((index + 1, reason));
+    }
+    noise
+}
@@ -0,0 +1,131 @@
+use clap::{Args, Subcommand};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::crypt::Crypto;
+use crate::similarity::stable_id;
+use crate::{load_from_json, Question};
+
+// Bookmarks and notes taken in the quiz site (`site`). The page keeps them in the browser by stable question id and
+// its "Export notes" button downloads them as `notes.json`; `notes export` turns that file into Markdown. Given the
+// bank, the questions are looked up by id in it, so the notes follow a question whose number changed when the dump
+// was extracted again, and notes on questions the bank no longer has are listed apart.
+
+#[derive(Args, Debug)]
+pub struct NotesArgs {
+    #[command(subcommand)]
+    command: NotesCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum NotesCommand {
+    /// Write the notes exported from the quiz site as Markdown
+    Export {
+        /// File downloaded with "Export notes"
+        input: String,
+        /// Markdown file to write, printed when not given
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Bank to take the current number and stem of the questions from
+        #[arg(long)]
+        bank: Option<String>,
+        /// Only the bookmarked questions
+        #[arg(long)]
+        bookmarked: bool,
+    },
+}
+
+/// `notes.json` as the quiz site exports it
+#[derive(Deserialize, Debug)]
+struct ExportedNotes {
+    title: String,
+    notes: Vec<Note>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Note {
+    id: String,
+    /// Number and stem of the question in the site the note was taken in
+    number: Option<String>,
+    text: Option<String>,
+    #[serde(default)]
+    bookmarked: bool,
+    #[serde(default)]
+    note: String,
+}
+
+fn write_note(out: &mut String, note: &Note, number: Option<&str>, text: Option<&str>) {
+    let star = if note.bookmarked { " ★" } else { "" };
+    let _ = writeln!(out, "## Question {}{}\n", number.unwrap_or("?"), star);
+    if let Some(text) = text {
+        let _ = writeln!(out, "{}\n", text.trim());
+    }
+    for line in note.note.trim().lines() {
+        let _ = writeln!(out, "> {}", line);
+    }
+    if !note.note.trim().is_empty() {
+        out.push('\n');
+    }
+    let _ = writeln!(out, "<sub>id {}</sub>\n", note.id);
+}
+
+fn to_markdown(exported: &ExportedNotes, bank: Option<&[Question]>) -> String {
+    let mut out = format!("# Notes – {}\n\n", exported.title);
+    let Some(questions) = bank else {
+        for note in &exported.notes {
+            write_note(&mut out, note, note.number.as_deref(), note.text.as_deref());
+        }
+        return out;
+    };
+
+    // Position of every question in the bank by id, to list the notes in the order of the bank
+    let by_id: HashMap<String, usize> = questions
+        .iter()
+        .enumerate()
+        .map(|(i, question)| (question.id.clone().unwrap_or_else(|| stable_id(question)), i))
+        .collect();
+    let (mut found, mut gone): (Vec<&Note>, Vec<&Note>) =
+        exported.notes.iter().partition(|note| by_id.contains_key(&note.id));
+    found.sort_by_key(|note| by_id[&note.id]);
+    for note in found {
+        let question = &questions[by_id[&note.id]];
+        write_note(&mut out, note, Some(&question.number), Some(&question.text));
+    }
+    if !gone.is_empty() {
+        gone.sort_by(|a, b| a.number.cmp(&b.number));
+        out.push_str("# Not in the bank anymore\n\n");
+        for note in gone {
+            write_note(&mut out, note, note.number.as_deref(), note.text.as_deref());
+        }
+    }
+    out
+}
+
+pub(crate) fn run(args: NotesArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    match args.command {
+        NotesCommand::Export {
+            input,
+            output,
+            bank,
+            bookmarked,
+        } => {
+            let mut exported: ExportedNotes = serde_json::from_str(&fs::read_to_string(&input)?)?;
+            if bookmarked {
+                exported.notes.retain(|note| note.bookmarked);
+            }
+            let bank = bank.map(|path| load_from_json(&path, crypto)).transpose()?;
+            let markdown = to_markdown(&exported, bank.as_ref().map(|bank| bank.questions.as_slice()));
+            match output {
+                Some(path) => {
+                    fs::write(&path, markdown)?;
+                    println!("Wrote {} note(s) to {}", exported.notes.len(), path);
+                }
+                None => print!("{}", markdown),
+            }
+        }
+    }
+    Ok(())
+}
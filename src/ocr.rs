@@ -0,0 +1,125 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use crate::Question;
+
+// Heuristics for text damaged by OCR. None of them is conclusive on its own; they only surface questions for a
+// human to look at.
+
+const SYSTEM_DICTIONARY: &str = "/usr/share/dict/words";
+
+/// Words that appear in at least this many questions count as known vocabulary even if the dictionary lacks them,
+/// so product names and domain terms ("putaway", "HU") are not reported as misses.
+const CORPUS_MIN_QUESTIONS: usize = 3;
+
+/// Punctuation that regularly shows up in stems and does not indicate damage
+const COMMON_PUNCTUATION: &str = ".,;:!?'\"()-/%&_";
+
+/// Loads a word list (one word per line), falling back to the system dictionary when no path is given. Returns
+/// `None` when no dictionary is available, which disables the dictionary-miss check.
+pub(crate) fn load_dictionary(path: Option<&str>) -> std::io::Result<Option<HashSet<String>>> {
+    let contents = match path {
+        Some(path) => fs::read_to_string(path)?,
+        None => match fs::read_to_string(SYSTEM_DICTIONARY) {
+            Ok(contents) => contents,
+            Err(_) => return Ok(None),
+        },
+    };
+    Ok(Some(contents.lines().map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()).collect()))
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| c.is_whitespace() || ",;:!?()\"".contains(c))
+        .map(|w| w.trim_matches(|c: char| c == '.' || c == '\''))
+        .filter(|w| !w.is_empty())
+}
+
+fn is_ordinal(word: &str) -> bool {
+    let digits = word.trim_end_matches(char::is_alphabetic);
+    let suffix = &word[digits.len()..];
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) && ["st", "nd", "rd", "th"].contains(&suffix)
+}
+
+/// Finds words mixing letters and look-alike digits the way OCR confuses them ("St0rage", "1nbound", "2O20").
+pub(crate) fn mixed_words(text: &str) -> Vec<String> {
+    words(text)
+        .filter(|word| {
+            let chars: Vec<char> = word.chars().collect();
+            chars.iter().enumerate().any(|(i, &c)| {
+                let prev = i.checked_sub(1).and_then(|p| chars.get(p)).copied();
+                let next = chars.get(i + 1).copied();
+                match c {
+                    // a digit inside a lowercase word, or leading one ("1nbound" but not "1st")
+                    '0' | '1' | '5' | '8' => {
+                        let next_lower = next.is_some_and(|n| n.is_lowercase());
+                        (prev.is_some_and(|p| p.is_lowercase()) && next_lower)
+                            || (i == 0 && next_lower && !is_ordinal(word))
+                    }
+                    // a letter surrounded by digits
+                    'O' | 'o' | 'l' | 'I' | 'S' => {
+                        prev.is_some_and(|p| p.is_ascii_digit()) && next.is_some_and(|n| n.is_ascii_digit())
+                    }
+                    _ => false,
+                }
+            })
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Share of characters that are neither alphanumeric, whitespace nor common punctuation.
+pub(crate) fn symbol_ratio(text: &str) -> f64 {
+    let total = text.chars().filter(|c| !c.is_whitespace()).count();
+    if total == 0 {
+        return 0.0;
+    }
+    let unusual = text
+        .chars()
+        .filter(|&c| !c.is_alphanumeric() && !c.is_whitespace() && !COMMON_PUNCTUATION.contains(c))
+        .count();
+    unusual as f64 / total as f64
+}
+
+/// Lowercase words of a question that are worth looking up: purely alphabetic, at least three letters and not an
+/// all-caps acronym.
+fn lookup_words(question: &Question) -> Vec<String> {
+    std::iter::once(question.text.as_str())
+        .chain(question.choices.values().map(String::as_str))
+        .flat_map(words)
+        .filter(|w| w.chars().count() >= 3 && w.chars().all(char::is_alphabetic))
+        .filter(|w| !w.chars().all(char::is_uppercase))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Vocabulary shared by enough questions of the bank to be considered correct.
+pub(crate) fn corpus_vocabulary(questions: &[Question]) -> HashSet<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for question in questions {
+        let unique: HashSet<String> = lookup_words(question).into_iter().collect();
+        for word in unique {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+    }
+    counts.into_iter().filter(|(_, n)| *n >= CORPUS_MIN_QUESTIONS).map(|(w, _)| w).collect()
+}
+
+/// Words of the question found neither in the dictionary nor in the bank vocabulary, and their share of all
+/// looked-up words.
+pub(crate) fn dictionary_misses(
+    question: &Question,
+    dictionary: &HashSet<String>,
+    vocabulary: &HashSet<String>,
+) -> (Vec<String>, f64) {
+    let words = lookup_words(question);
+    if words.is_empty() {
+        return (Vec::new(), 0.0);
+    }
+    let misses: Vec<String> = words
+        .iter()
+        .filter(|w| !dictionary.contains(*w) && !vocabulary.contains(*w))
+        .cloned()
+        .collect();
+    let density = misses.len() as f64 / words.len() as f64;
+    (misses, density)
+}
@@ -0,0 +1,237 @@
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{HashMap, HashSet};
+
+use crate::OutputError;
+
+// Chapters from the outline (bookmarks) of a PDF. Dumps exported from a word processor usually carry one, with the
+// exact chapter titles and the page each chapter starts on, which is more reliable than guessing headings from the
+// text. Every outline entry becomes a chapter running from its page to the page before the next entry at its level
+// or above; questions are tagged with the innermost chapter their page falls in, and `--chapter` extracts the pages of
+// a single chapter only. Entries whose destination cannot be resolved to a page are left out, and a document without
+// an outline has no chapters.
+
+/// Outline entries nested deeper than this are not read, which also bounds malformed outlines
+const MAX_DEPTH: usize = 16;
+
+/// An entry of the outline and the pages it covers (1-based, inclusive)
+#[derive(Debug, Clone)]
+pub(crate) struct Chapter {
+    pub(crate) title: String,
+    /// Depth in the outline, 0 for top-level entries
+    pub(crate) level: usize,
+    pub(crate) first_page: usize,
+    pub(crate) last_page: usize,
+}
+
+impl Chapter {
+    pub(crate) fn contains(&self, page: usize) -> bool {
+        (self.first_page..=self.last_page).contains(&page)
+    }
+}
+
+/// The object a reference points to, or the object itself.
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> &'a Object {
+    document.dereference(object).map_or(object, |(_, object)| object)
+}
+
+/// Decodes a PDF text string: UTF-16BE or UTF-8 with a byte order mark, else PDFDocEncoding, which agrees with
+/// Latin-1 on the characters titles use.
+fn decode_text(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+        char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+    } else if let Some(utf8) = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+        String::from_utf8_lossy(utf8).into_owned()
+    } else {
+        bytes.iter().map(|&byte| byte as char).collect()
+    }
+}
+
+/// Named destinations of the document, from the `Dests` dictionary of the catalog and the `Dests` name tree.
+fn named_destinations(document: &Document) -> HashMap<Vec<u8>, &Object> {
+    let mut named = HashMap::new();
+    let Ok(catalog) = document.catalog() else {
+        return named;
+    };
+    if let Some(dests) = catalog.get(b"Dests").ok().and_then(|dests| resolve(document, dests).as_dict().ok()) {
+        for (name, destination) in dests.iter() {
+            named.insert(name.clone(), destination);
+        }
+    }
+    let names = catalog.get(b"Names").ok().and_then(|names| resolve(document, names).as_dict().ok());
+    if let Some(tree) = names.and_then(|names| names.get(b"Dests").ok()) {
+        name_tree(document, tree, 0, &mut named, &mut HashSet::new());
+    }
+    named
+}
+
+fn name_tree<'a>(
+    document: &'a Document,
+    node: &'a Object,
+    depth: usize,
+    named: &mut HashMap<Vec<u8>, &'a Object>,
+    visited: &mut HashSet<ObjectId>,
+) {
+    if depth > MAX_DEPTH || node.as_reference().is_ok_and(|id| !visited.insert(id)) {
+        return;
+    }
+    let Ok(node) = resolve(document, node).as_dict() else {
+        return;
+    };
+    if let Some(names) = node.get(b"Names").ok().and_then(|names| resolve(document, names).as_array().ok()) {
+        for pair in names.chunks_exact(2) {
+            if let Ok(name) = resolve(document, &pair[0]).as_str() {
+                named.insert(name.to_vec(), &pair[1]);
+            }
+        }
+    }
+    if let Some(kids) = node.get(b"Kids").ok().and_then(|kids| resolve(document, kids).as_array().ok()) {
+        for kid in kids {
+            name_tree(document, kid, depth + 1, named, visited);
+        }
+    }
+}
+
+/// Page object of an explicit destination, `[page /XYZ ...]` or a dictionary holding one as `D`.
+fn explicit_page(document: &Document, destination: &Object) -> Option<ObjectId> {
+    match resolve(document, destination) {
+        Object::Array(items) => items.first()?.as_reference().ok(),
+        Object::Dictionary(dict) => match resolve(document, dict.get(b"D").ok()?) {
+            Object::Array(items) => items.first()?.as_reference().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Page object an outline entry leads to, through its `Dest` or its GoTo action, by name or explicitly.
+fn entry_page(document: &Document, entry: &Dictionary, named: &HashMap<Vec<u8>, &Object>) -> Option<ObjectId> {
+    let destination = match entry.get(b"Dest") {
+        Ok(destination) => destination,
+        Err(_) => resolve(document, entry.get(b"A").ok()?).as_dict().ok()?.get(b"D").ok()?,
+    };
+    match resolve(document, destination) {
+        Object::Name(name) | Object::String(name, _) => explicit_page(document, named.get(name)?),
+        _ => explicit_page(document, destination),
+    }
+}
+
+struct Entry {
+    title: String,
+    level: usize,
+    page: usize,
+}
+
+struct Walk<'a> {
+    document: &'a Document,
+    pages: HashMap<ObjectId, usize>,
+    named: HashMap<Vec<u8>, &'a Object>,
+    visited: HashSet<ObjectId>,
+    entries: Vec<Entry>,
+}
+
+impl<'a> Walk<'a> {
+    /// Reads the entry `item` and its following siblings, children first.
+    fn siblings(&mut self, mut item: &'a Object, level: usize) {
+        let document = self.document;
+        if level > MAX_DEPTH {
+            return;
+        }
+        loop {
+            let Ok(id) = item.as_reference() else {
+                return;
+            };
+            if !self.visited.insert(id) {
+                return;
+            }
+            let Ok(entry) = document.get_dictionary(id) else {
+                return;
+            };
+            let title = entry.get(b"Title").ok().and_then(|title| resolve(document, title).as_str().ok());
+            let page = entry_page(document, entry, &self.named).and_then(|page| self.pages.get(&page).copied());
+            if let (Some(title), Some(page)) = (title, page) {
+                let title = decode_text(title).split_whitespace().collect::<Vec<_>>().join(" ");
+                self.entries.push(Entry { title, level, page });
+            }
+            if let Ok(child) = entry.get(b"First") {
+                self.siblings(child, level + 1);
+            }
+            match entry.get(b"Next") {
+                Ok(next) => item = next,
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+/// The chapters of the document in outline order, empty when it has no outline.
+pub(crate) fn chapters(document: &Document) -> Vec<Chapter> {
+    let first = document
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|outlines| resolve(document, outlines).as_dict().ok())
+        .and_then(|outlines| outlines.get(b"First").ok());
+    let Some(first) = first else {
+        return Vec::new();
+    };
+    let pages = document.get_pages();
+    let page_count = pages.len();
+    let mut walk = Walk {
+        document,
+        pages: pages.into_iter().map(|(number, id)| (id, number as usize)).collect(),
+        named: named_destinations(document),
+        visited: HashSet::new(),
+        entries: Vec::new(),
+    };
+    walk.siblings(first, 0);
+    let entries = walk.entries;
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let next = entries[i + 1..].iter().find(|next| next.level <= entry.level);
+            Chapter {
+                title: entry.title.clone(),
+                level: entry.level,
+                first_page: entry.page,
+                last_page: next.map_or(page_count, |next| next.page.saturating_sub(1)).max(entry.page),
+            }
+        })
+        .collect()
+}
+
+/// The innermost chapter `page` falls in.
+pub(crate) fn chapter_of(chapters: &[Chapter], page: usize) -> Option<&Chapter> {
+    chapters.iter().filter(|chapter| chapter.contains(page)).max_by_key(|chapter| (chapter.level, chapter.first_page))
+}
+
+/// The chapter named `title`: the one with that title, ignoring case, or else the only one whose title contains it
+/// ("Outbound Processing" for "5 Outbound Processing").
+pub(crate) fn find<'c>(chapters: &'c [Chapter], title: &str) -> Result<&'c Chapter, OutputError> {
+    if chapters.is_empty() {
+        return Err(OutputError::from("--chapter needs a PDF with an outline (bookmarks), this one has none"));
+    }
+    let wanted = title.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    if let Some(chapter) = chapters.iter().find(|chapter| chapter.title.to_lowercase() == wanted) {
+        return Ok(chapter);
+    }
+    let matching: Vec<&Chapter> =
+        chapters.iter().filter(|chapter| chapter.title.to_lowercase().contains(&wanted)).collect();
+    match matching[..] {
+        [chapter] => Ok(chapter),
+        [] => {
+            let titles: Vec<&str> =
+                chapters.iter().filter(|chapter| chapter.level == 0).map(|chapter| chapter.title.as_str()).collect();
+            Err(OutputError {
+                message: format!("No chapter {:?} in the outline, the chapters are: {}", title, titles.join(", ")),
+            })
+        }
+        _ => {
+            let titles: Vec<&str> = matching.iter().map(|chapter| chapter.title.as_str()).collect();
+            Err(OutputError {
+                message: format!("Chapter {:?} is ambiguous, it matches: {}", title, titles.join(", ")),
+            })
+        }
+    }
+}
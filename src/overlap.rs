@@ -0,0 +1,156 @@
+use clap::Args;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use crate::crypt::Crypto;
+use crate::similarity::{jaccard, question_key, stable_id, token_set};
+use crate::{load_from_json, Question};
+
+// Overlap between two banks, to tell how much a newly bought dump adds to the questions already at hand before it is
+// reviewed. Every question of the second bank is compared with every question of the first: questions with the same
+// normalized stem and choices are identical, others are scored by the Jaccard similarity of the words of their stem
+// and choices, and the best match at or above the threshold makes the question shared. The rest is what the second
+// bank adds.
+
+#[derive(Args, Debug)]
+pub struct OverlapArgs {
+    /// Bank already at hand
+    a: String,
+    /// Bank to compare with it
+    b: String,
+    /// Similarity from which two questions count as the same (0 to 1)
+    #[arg(long, default_value_t = 0.8)]
+    threshold: f64,
+    /// Also list the questions of the second bank that the first does not have
+    #[arg(long)]
+    new: bool,
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct Match {
+    /// Number of the question in the second bank
+    b: String,
+    /// Number of the most similar question in the first bank
+    a: String,
+    id: String,
+    similarity: f64,
+    identical: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct OverlapReport {
+    a_questions: usize,
+    b_questions: usize,
+    threshold: f64,
+    /// Questions of the second bank with a match in the first, most similar first
+    shared: Vec<Match>,
+    /// Numbers of the questions only the second bank has
+    new: Vec<String>,
+}
+
+fn tokens(question: &Question) -> HashSet<String> {
+    let mut tokens = token_set(&question.text);
+    for choice in question.choices.values() {
+        tokens.extend(token_set(choice));
+    }
+    tokens
+}
+
+/// Index and similarity of the question of `a` most similar to `question`.
+fn best_match(question: &HashSet<String>, a: &[HashSet<String>], threshold: f64) -> Option<(usize, f64)> {
+    let mut best: Option<(usize, f64)> = None;
+    for (index, other) in a.iter().enumerate() {
+        // Jaccard cannot exceed the ratio of the set sizes, skip the questions that can never reach the threshold
+        let (x, y) = (question.len(), other.len());
+        if (x.min(y) as f64) < threshold * x.max(y) as f64 {
+            continue;
+        }
+        let score = jaccard(question, other);
+        if score >= threshold && !best.is_some_and(|(_, best)| best >= score) {
+            best = Some((index, score));
+        }
+    }
+    best
+}
+
+fn overlap(a: &[Question], b: &[Question], threshold: f64) -> OverlapReport {
+    let keys: HashMap<_, usize> =
+        a.iter().enumerate().map(|(index, question)| (question_key(question), index)).collect();
+    let a_tokens: Vec<HashSet<String>> = a.iter().map(tokens).collect();
+    let (mut shared, mut new) = (Vec::new(), Vec::new());
+    for question in b {
+        let found = match keys.get(&question_key(question)) {
+            Some(&index) => Some((index, 1.0, true)),
+            None => best_match(&tokens(question), &a_tokens, threshold).map(|(index, score)| (index, score, false)),
+        };
+        match found {
+            Some((index, similarity, identical)) => shared.push(Match {
+                b: question.number.clone(),
+                a: a[index].number.clone(),
+                id: question.id.clone().unwrap_or_else(|| stable_id(question)),
+                similarity,
+                identical,
+            }),
+            None => new.push(question.number.clone()),
+        }
+    }
+    shared.sort_by(|x, y| y.similarity.total_cmp(&x.similarity));
+    OverlapReport {
+        a_questions: a.len(),
+        b_questions: b.len(),
+        threshold,
+        shared,
+        new,
+    }
+}
+
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+pub(crate) fn run(args: OverlapArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    if !(0.0..=1.0).contains(&args.threshold) {
+        return Err("--threshold is a similarity between 0 and 1".into());
+    }
+    let (a, b) = (load_from_json(&args.a, crypto)?, load_from_json(&args.b, crypto)?);
+    let report = overlap(&a.questions, &b.questions, args.threshold);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    for shared in &report.shared {
+        let similarity = if shared.identical {
+            "identical".to_string()
+        } else {
+            format!("{:.0}%", shared.similarity * 100.0)
+        };
+        println!("{} question {} ~ {} question {}: {}", args.b, shared.b, args.a, shared.a, similarity);
+    }
+    if args.new {
+        for number in &report.new {
+            println!("{} question {}: new", args.b, number);
+        }
+    }
+    let identical = report.shared.iter().filter(|shared| shared.identical).count();
+    println!(
+        "{} of the {} questions of {} are in {} ({:.0}%, {} identical), {} are new ({:.0}%)",
+        report.shared.len(),
+        report.b_questions,
+        args.b,
+        args.a,
+        percent(report.shared.len(), report.b_questions),
+        identical,
+        report.new.len(),
+        percent(report.new.len(), report.b_questions)
+    );
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use lopdf::Document;
+use pdf_extract::{output_doc_page, PlainTextOutput};
+use std::collections::{BTreeSet, HashMap};
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::OutputError;
+
+// Text extraction per page. The document is parsed once and its pages are rendered to text by `--jobs` threads,
+// which is where most of the time goes on image-heavy PDFs. Threads take the next page in turn and the texts are put
+// back in page order, so the result does not depend on which thread finishes first. A page whose text cannot be
+// extracted, including one the backend panics on, is left empty and reported instead of failing the document.
+//
+// Some malformed pages make the backend spin for minutes. With `--page-timeout` a page still running after the
+// timeout is given up on like a failed one: its thread cannot be stopped, so it is abandoned and another thread takes
+// over the remaining pages. Pages listed in `--skip-pages` are not extracted at all and left empty.
+
+/// How often the running pages are checked against the timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Text of the pages in document order, the 1-based pages that could not be extracted with the reason, and the
+/// pages skipped on request
+#[derive(Debug, Default)]
+pub(crate) struct PageTexts {
+    pub(crate) texts: Vec<String>,
+    pub(crate) failed: Vec<(usize, String)>,
+    pub(crate) skipped: Vec<usize>,
+}
+
+/// Number of threads for the given `--jobs`, defaulting to the number of CPUs.
+pub(crate) fn jobs(requested: Option<usize>) -> usize {
+    requested.unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get)).max(1)
+}
+
+/// Pages of a list such as `113,200-204`, counted from 1.
+pub(crate) fn parse_page_list(list: &str) -> Result<BTreeSet<usize>, OutputError> {
+    let mut pages = BTreeSet::new();
+    for item in list.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let invalid = || OutputError {
+            message: format!("Invalid page or page range {:?}, expected e.g. 113,200-204", item),
+        };
+        let (first, last) = item.split_once('-').unwrap_or((item, item));
+        let first: usize = first.trim().parse().map_err(|_| invalid())?;
+        let last: usize = last.trim().parse().map_err(|_| invalid())?;
+        if first == 0 || last < first {
+            return Err(invalid());
+        }
+        pages.extend(first..=last);
+    }
+    Ok(pages)
+}
+
+/// Lists pages compactly, e.g. `113, 200-204`.
+pub(crate) fn format_page_list(pages: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &page in pages {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == page => *last = page,
+            _ => ranges.push((page, page)),
+        }
+    }
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|&(first, last)| if first == last { first.to_string() } else { format!("{}-{}", first, last) })
+        .collect();
+    ranges.join(", ")
+}
+
+pub(crate) fn page_text(document: &Document, page: u32) -> Result<String, pdf_extract::OutputError> {
+    let mut text = String::new();
+    output_doc_page(document, &mut PlainTextOutput::new(&mut text), page)?;
+    Ok(text)
+}
+
+/// Renders a page of the document to text
+type Render = fn(&Document, u32) -> Result<String, String>;
+
+fn render_page(document: &Document, page: u32) -> Result<String, String> {
+    page_text(document, page).map_err(|e| e.to_string())
+}
+
+/// Pages shared by the extraction threads
+struct Work {
+    document: Document,
+    render: Render,
+    /// Page numbers of the document
+    pages: Vec<u32>,
+    /// Indexes into `pages` to extract, taken in turn
+    queue: Vec<usize>,
+    next: AtomicUsize,
+}
+
+/// A thread started (`None`) or finished a page, by index
+type Report = (usize, usize, Option<Result<String, String>>);
+
+fn spawn_worker(id: usize, work: &Arc<Work>, sender: &Sender<Report>) {
+    let (work, sender) = (Arc::clone(work), sender.clone());
+    thread::spawn(move || {
+        while let Some(&index) = work.queue.get(work.next.fetch_add(1, Ordering::Relaxed)) {
+            if sender.send((id, index, None)).is_err() {
+                break;
+            }
+            let result = panic::catch_unwind(AssertUnwindSafe(|| (work.render)(&work.document, work.pages[index])))
+                .map_err(|_| "text extraction panicked".to_string())
+                .and_then(|text| text);
+            if sender.send((id, index, Some(result))).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Extracts the text of the pages of `document`, calling `extracted` with the number of pages done so far as they
+/// complete.
+pub(crate) fn extract_pages(
+    document: Document,
+    jobs: usize,
+    timeout: Option<Duration>,
+    skip: &BTreeSet<usize>,
+    extracted: impl FnMut(usize),
+) -> PageTexts {
+    extract_with(render_page, document, jobs, timeout, skip, extracted)
+}
+
+fn extract_with(
+    render: Render,
+    document: Document,
+    jobs: usize,
+    timeout: Option<Duration>,
+    skip: &BTreeSet<usize>,
+    mut extracted: impl FnMut(usize),
+) -> PageTexts {
+    let pages: Vec<u32> = document.get_pages().keys().copied().collect();
+    let mut results: Vec<Option<Result<String, String>>> = vec![None; pages.len()];
+    let mut page_texts = PageTexts::default();
+    for index in (0..pages.len()).filter(|index| skip.contains(&(index + 1))) {
+        results[index] = Some(Ok(String::new()));
+        page_texts.skipped.push(index + 1);
+    }
+    let queue: Vec<usize> = (0..pages.len()).filter(|&index| results[index].is_none()).collect();
+    let mut remaining = queue.len();
+    let total = pages.len();
+    extracted(total - remaining);
+    let work = Arc::new(Work {
+        document,
+        render,
+        pages,
+        queue,
+        next: AtomicUsize::new(0),
+    });
+
+    let (sender, receiver) = mpsc::channel();
+    let mut workers = jobs.clamp(1, remaining.max(1));
+    for id in 0..workers {
+        spawn_worker(id, &work, &sender);
+    }
+    // page being extracted by each thread, with when it started
+    let mut running: HashMap<usize, (usize, Instant)> = HashMap::new();
+    while remaining > 0 {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok((worker, index, None)) => {
+                running.insert(worker, (index, Instant::now()));
+            }
+            Ok((worker, index, Some(result))) => {
+                running.remove(&worker);
+                // a page that timed out is already settled
+                if results[index].is_none() {
+                    results[index] = Some(result);
+                    remaining -= 1;
+                    extracted(total - remaining);
+                }
+            }
+            // every thread is gone, the pages left were not extracted
+            Err(RecvTimeoutError::Timeout) if Arc::strong_count(&work) == 1 => break,
+            Err(_) => {}
+        }
+        let Some(timeout) = timeout else {
+            continue;
+        };
+        let stuck: Vec<usize> = running
+            .iter()
+            .filter(|(_, (_, started))| started.elapsed() >= timeout)
+            .map(|(&worker, _)| worker)
+            .collect();
+        for worker in stuck {
+            let Some((index, _)) = running.remove(&worker) else {
+                continue;
+            };
+            results[index] = Some(Err(format!("timed out after {}s, the page was abandoned", timeout.as_secs())));
+            remaining -= 1;
+            extracted(total - remaining);
+            spawn_worker(workers, &work, &sender);
+            workers += 1;
+        }
+    }
+
+    for (index, result) in results.into_iter().enumerate() {
+        match result.unwrap_or_else(|| Err("page was not extracted".to_string())) {
+            Ok(text) => page_texts.texts.push(text),
+            Err(message) => {
+                page_texts.texts.push(String::new());
+                page_texts.failed.push((index + 1, message));
+            }
+        }
+    }
+    page_texts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Object};
+
+    /// A document of empty pages
+    fn document(pages: usize) -> Document {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let kids: Vec<Object> = (0..pages)
+            .map(|_| {
+                let page = dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                };
+                document.add_object(page).into()
+            })
+            .collect();
+        let count = kids.len() as i64;
+        document
+            .objects
+            .insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }));
+        let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    /// Names the page, hangs on page 2 and panics on page 3
+    fn troubled(_: &Document, page: u32) -> Result<String, String> {
+        match page {
+            2 => thread::sleep(Duration::from_secs(3)),
+            3 => panic!("malformed content stream"),
+            _ => {}
+        }
+        Ok(format!("page {}", page))
+    }
+
+    #[test]
+    fn page_lists_take_pages_and_ranges() {
+        let pages = |list| parse_page_list(list).map(|pages| pages.into_iter().collect::<Vec<_>>());
+        assert_eq!(pages("113, 200-204").unwrap(), [113, 200, 201, 202, 203, 204]);
+        assert_eq!(pages("1-3,2-4,3").unwrap(), [1, 2, 3, 4]);
+        assert_eq!(pages("7-7,").unwrap(), [7]);
+        assert!(pages("").unwrap().is_empty());
+        for invalid in ["0", "0-2", "5-3", "1-", "-4", "two", "1-2-3"] {
+            assert!(pages(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn page_lists_are_formatted_as_ranges() {
+        assert_eq!(format_page_list(&[113, 200, 201, 202, 203, 204]), "113, 200-204");
+        assert_eq!(format_page_list(&[1, 2, 4, 6, 7]), "1-2, 4, 6-7");
+        assert_eq!(format_page_list(&[]), "");
+        let pages: Vec<usize> = parse_page_list("9,1-3,5").unwrap().into_iter().collect();
+        assert_eq!(format_page_list(&pages), "1-3, 5, 9");
+    }
+
+    #[test]
+    fn skipped_pages_are_left_empty() {
+        let skip = parse_page_list("2-3").unwrap();
+        let mut progress = Vec::new();
+        let page_texts = extract_with(troubled, document(4), 2, None, &skip, |done| progress.push(done));
+        assert_eq!(page_texts.texts, ["page 1", "", "", "page 4"]);
+        assert_eq!(page_texts.skipped, [2, 3]);
+        assert!(page_texts.failed.is_empty());
+        assert_eq!(progress.first(), Some(&2));
+        assert_eq!(progress.last(), Some(&4));
+    }
+
+    #[test]
+    fn stuck_and_panicking_pages_fail_alone() {
+        let started = Instant::now();
+        let timeout = Some(Duration::from_millis(300));
+        let page_texts = extract_with(troubled, document(5), 1, timeout, &BTreeSet::new(), |_| {});
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(page_texts.texts, ["page 1", "", "", "page 4", "page 5"]);
+        let failed: Vec<usize> = page_texts.failed.iter().map(|(page, _)| *page).collect();
+        assert_eq!(failed, [2, 3]);
+        assert!(page_texts.failed[0].1.contains("timed out"));
+        assert_eq!(page_texts.failed[1].1, "text extraction panicked");
+    }
+}
@@ -0,0 +1,1007 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+
+use crate::html;
+use crate::kind::{self, QuestionKind};
+use crate::profile::{AnswerStrategy, ElementOrder, ParserProfile};
+use crate::text;
+use crate::{MatchPair, Question, Statement};
+
+// Line-oriented question parser. `QuestionParser` is a state machine fed the lines of a document one at a time: the
+// state is the question being built, and each line either starts a new question or adds to the current one. It only
+// depends on its input and never panics on malformed text, patterns of a profile included. Lines it cannot place are
+// skipped and reported as `ParseIssue`s so the caller decides whether they matter. When choice letters restart within
+// a question, the number of the next question was missed; the text after the choices is split off into a question of
+// its own, flagged `recovered`.
+
+lazy_static! {
+    static ref ROMAN_STATEMENT_REGEX: Regex = Regex::new(r"^(I{1,3}|IV|VI{0,3}|IX|X)[.)]\s*(.*)$").unwrap();
+    static ref MATCH_PREMISE_REGEX: Regex = Regex::new(r"^(\d+)\)\s*(.*)$").unwrap();
+    static ref MATCH_PAIR_REGEX: Regex = Regex::new(r"(\d+)\s*(?:-+>?|–|=|:|→)\s*([A-Za-z])\b").unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum IssueKind {
+    /// An answer line before the first question
+    OrphanAnswer,
+    /// A choice line before the first question
+    OrphanChoice,
+    /// A choice letter given twice in one question, the later text replaced the earlier one
+    DuplicateChoice(String),
+    /// An answer line naming no choice letter
+    UnreadableAnswer,
+    /// Choice letters restarting within a question, taken for a question whose number was not recognized; holds the
+    /// number given to the split-off question
+    RecoveredBoundary(String),
+}
+
+/// A line the parser could not place, located by page and line within the page (both 1-based).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ParseIssue {
+    pub(crate) page: Option<usize>,
+    pub(crate) line: usize,
+    pub(crate) kind: IssueKind,
+}
+
+impl fmt::Display for ParseIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(page) = self.page {
+            write!(f, "page {} ", page)?;
+        }
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            IssueKind::OrphanAnswer => write!(f, "answer line before the first question"),
+            IssueKind::OrphanChoice => write!(f, "choice line before the first question"),
+            IssueKind::DuplicateChoice(letter) => write!(f, "choice {} repeated within a question", letter),
+            IssueKind::UnreadableAnswer => write!(f, "answer line without a choice letter"),
+            IssueKind::RecoveredBoundary(number) => {
+                write!(f, "choice letters restart, split off question {} without a recognized number", number)
+            }
+        }
+    }
+}
+
+/// What the parser took a line for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum LineClass {
+    /// Nothing left once cleaned
+    Blank,
+    /// Page furniture matched by an artifact pattern
+    Artifact,
+    /// Text before the first question
+    Preamble,
+    Question,
+    /// Numbered premise of a matching question
+    Premise,
+    Choice,
+    /// Continuation of the last choice
+    ChoiceText,
+    Answer,
+    /// Answer line consumed without effect because the marker strategy is off
+    IgnoredAnswer,
+    /// Roman-numbered statement
+    Statement,
+    /// Continuation of the last statement
+    StatementText,
+    /// Continuation of the question text
+    Stem,
+    /// Commentary on a choice following the answer
+    Feedback,
+    /// Continuation of the last commentary
+    FeedbackText,
+}
+
+impl LineClass {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            LineClass::Blank => "blank",
+            LineClass::Artifact => "artifact",
+            LineClass::Preamble => "preamble",
+            LineClass::Question => "question",
+            LineClass::Premise => "premise",
+            LineClass::Choice => "choice",
+            LineClass::ChoiceText => "choice-text",
+            LineClass::Answer => "answer",
+            LineClass::IgnoredAnswer => "ignored-answer",
+            LineClass::Statement => "statement",
+            LineClass::StatementText => "statement-text",
+            LineClass::Stem => "stem",
+            LineClass::Feedback => "feedback",
+            LineClass::FeedbackText => "feedback-text",
+        }
+    }
+}
+
+/// A line as the parser saw it once cleaned, how it was classified and why. Recorded by a tracing parser, and kept
+/// on the questions with `--explain`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct TraceLine {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) page: Option<usize>,
+    pub(crate) line: usize,
+    pub(crate) text: String,
+    pub(crate) class: LineClass,
+    pub(crate) reason: String,
+    /// Number of the question the line went into or was dropped from
+    #[serde(skip)]
+    pub(crate) question: Option<String>,
+}
+
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>4} {:<14} {}", self.line, self.class.name(), self.text)?;
+        if let Some(question) = &self.question {
+            write!(f, "  -> question {}", question)?;
+        }
+        write!(f, "\n     {}", self.reason)
+    }
+}
+
+/// Result of parsing a document: the questions and the lines that had to be skipped or overwrote earlier ones, plus
+/// the trace of every line when tracing.
+#[derive(Debug, Default)]
+pub(crate) struct Parsed {
+    pub(crate) questions: Vec<Question>,
+    pub(crate) issues: Vec<ParseIssue>,
+    pub(crate) trace: Vec<TraceLine>,
+}
+
+impl Parsed {
+    /// Moves the traced lines into the `explain` of the questions they went into or were dropped from, leaving out
+    /// blank lines.
+    pub(crate) fn explain(&mut self) {
+        let mut explained: HashMap<String, Vec<TraceLine>> = HashMap::new();
+        for line in mem::take(&mut self.trace) {
+            if let Some(question) = line.question.clone().filter(|_| line.class != LineClass::Blank) {
+                explained.entry(question).or_default().push(line);
+            }
+        }
+        for question in &mut self.questions {
+            question.explain = explained.remove(&question.number).unwrap_or_default();
+        }
+    }
+}
+
+/// Removes an asterisk marking a choice as correct ("*B. Picking", "B. Picking *", "B. Picking (*)").
+fn strip_asterisk(line: &str) -> (&str, bool) {
+    let stripped = line
+        .strip_prefix('*')
+        .map(str::trim_start)
+        .or_else(|| line.strip_suffix("(*)").map(str::trim_end))
+        .or_else(|| line.strip_suffix('*').map(str::trim_end));
+    match stripped {
+        Some(stripped) => (stripped, true),
+        None => (line, false),
+    }
+}
+
+/// Strips HTML remnants and drops spaces between CJK characters. Borrows from `text` unless something actually has
+/// to go, which keeps the common line allocation-free.
+fn clean_text(text: &str) -> Cow<'_, str> {
+    match html::strip_markup(text) {
+        Cow::Borrowed(text) => text::collapse_cjk_spaces(text.trim()),
+        Cow::Owned(text) => Cow::Owned(text::collapse_cjk_spaces(text.trim()).into_owned()),
+    }
+}
+
+/// Start of the text following the choices: the stem of the next question if its number was missed
+struct RunOn {
+    /// Choice the text was added to, or `None` for the stem
+    choice: Option<String>,
+    /// Offset in the choice or stem text
+    offset: usize,
+    page: Option<usize>,
+    /// Index of its first line in the trace
+    trace: usize,
+}
+
+pub(crate) struct QuestionParser<'a> {
+    patterns: &'a ParserProfile,
+    questions: Vec<Question>,
+    issues: Vec<ParseIssue>,
+    current_question: Option<Question>,
+    question_number: usize,
+    page: Option<usize>,
+    line: usize,
+    trace: Option<Vec<TraceLine>>,
+    run_on: Option<RunOn>,
+    /// The last line was blank, page furniture aside
+    after_blank: bool,
+    /// Letter of the choice the last lines went into; lines matching no pattern continue it
+    continued_choice: Option<String>,
+    /// An answer line was placed in the current question, so lines commenting on its choices may follow
+    answered: bool,
+    /// Letter of the choice the last lines commented on; lines matching no pattern continue the commentary
+    continued_feedback: Option<String>,
+}
+
+impl<'a> QuestionParser<'a> {
+    pub(crate) fn new(patterns: &'a ParserProfile) -> Self {
+        QuestionParser {
+            patterns,
+            questions: Vec::new(),
+            issues: Vec::new(),
+            current_question: None,
+            question_number: 1,
+            page: None,
+            line: 0,
+            trace: None,
+            run_on: None,
+            after_blank: false,
+            continued_choice: None,
+            answered: false,
+            continued_feedback: None,
+        }
+    }
+
+    /// Records a `TraceLine` for every line parsed from now on.
+    pub(crate) fn traced(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    // Pages are fed one after another into the same parser so questions spanning a page break stay intact
+    pub(crate) fn set_page(&mut self, page: usize) {
+        self.page = Some(page);
+        self.line = 0;
+    }
+
+    /// Questions parsed so far, including those already taken.
+    pub(crate) fn question_count(&self) -> usize {
+        self.question_number - 1
+    }
+
+    /// Takes the questions that are complete so far, i.e. all but the one being parsed, and the issues found so far.
+    /// Lets callers write questions out while the document is still being parsed.
+    pub(crate) fn take_complete(&mut self) -> Parsed {
+        Parsed {
+            questions: mem::take(&mut self.questions),
+            issues: mem::take(&mut self.issues),
+            trace: self.trace.as_mut().map(mem::take).unwrap_or_default(),
+        }
+    }
+
+    fn issue(&mut self, kind: IssueKind) {
+        self.issues.push(ParseIssue {
+            page: self.page,
+            line: self.line,
+            kind,
+        });
+    }
+
+    /// Choice letter and the offset where the choice text starts, if the line is a choice.
+    fn choice_label(&self, line: &str) -> Option<(String, usize)> {
+        let caps = self.patterns.choice.captures(line)?;
+        Some((caps.get(1)?.as_str().to_uppercase(), caps.get(0)?.end()))
+    }
+
+    /// Splits a choice line holding several choices ("A. Putaway B. Picking C. Counting") before each label that
+    /// follows whitespace and carries the letter after the previous one, so that words like "Vitamin C." do not split.
+    /// An asterisk right before a label goes with it. A line with a single choice is returned whole.
+    fn inline_choices<'l>(&self, line: &'l str, first: &str) -> Vec<&'l str> {
+        let next_letter = |letter: &str| letter.chars().next().and_then(|c| char::from_u32(c as u32 + 1));
+        let mut segments = Vec::new();
+        let mut start = 0;
+        let mut expected = next_letter(first);
+        for (i, c) in line.char_indices() {
+            let Some(letter) = expected else {
+                break;
+            };
+            if !c.eq_ignore_ascii_case(&letter) {
+                continue;
+            }
+            let head = &line[..i];
+            let split = match head.strip_suffix('*') {
+                Some(before) if before.ends_with(char::is_whitespace) => before.len(),
+                _ if head.ends_with(char::is_whitespace) => i,
+                _ => continue,
+            };
+            if split <= start || line[start..split].trim().is_empty() {
+                continue;
+            }
+            if let Some((label, _)) = self.choice_label(&line[i..]).filter(|(label, _)| label.starts_with(letter)) {
+                segments.push(line[start..split].trim());
+                start = split;
+                expected = next_letter(label.as_str());
+            }
+        }
+        segments.push(line[start..].trim());
+        segments
+    }
+
+    pub(crate) fn parse_line(&mut self, line: &str) {
+        self.line += 1;
+        // entities are decoded first so the profile's replacements apply to the characters they stand for
+        let decoded = clean_text(line);
+        let cleaned = self.patterns.profile.normalize(&decoded);
+        // Page numbers and footnotes would otherwise be appended to the question preceding them
+        let artifact = if cleaned.is_empty() {
+            None
+        } else {
+            self.patterns.artifacts.iter().position(|r| r.is_match(&cleaned))
+        };
+        let without_footnotes = match artifact {
+            Some(_) => Cow::Borrowed(""),
+            None => self.patterns.footnote.replace_all(&cleaned, ""),
+        };
+        let cleaned_line = without_footnotes.trim();
+        let class = match artifact {
+            Some(_) => LineClass::Artifact,
+            None if cleaned_line.is_empty() => LineClass::Blank,
+            None => self.place_line(cleaned_line),
+        };
+        if matches!(class, LineClass::Question | LineClass::Stem) && line.contains('<') {
+            if let Some(question) = self.current_question.as_mut() {
+                for emphasized in html::emphasized(line) {
+                    if !question.emphasis.contains(&emphasized) {
+                        question.emphasis.push(emphasized);
+                    }
+                }
+            }
+        }
+        self.after_blank = class == LineClass::Blank || (self.after_blank && class == LineClass::Artifact);
+        // a choice wraps across blank lines and page breaks, anything else placed ends it
+        let continues_choice = matches!(
+            class,
+            LineClass::Choice | LineClass::ChoiceText | LineClass::Blank | LineClass::Artifact
+        );
+        if !continues_choice {
+            self.continued_choice = None;
+        }
+        // commentary wraps onto the following lines until a blank line
+        if !matches!(class, LineClass::Feedback | LineClass::FeedbackText | LineClass::Artifact) {
+            self.continued_feedback = None;
+        }
+
+        if self.trace.is_some() {
+            let text = if artifact.is_some() { &cleaned } else { cleaned_line };
+            let question = match class {
+                LineClass::Preamble => None,
+                _ => self.current_question.as_ref().map(|q| q.number.clone()),
+            };
+            let traced = TraceLine {
+                page: self.page,
+                line: self.line,
+                text: text.to_string(),
+                class,
+                reason: self.reason(class, text, artifact),
+                question,
+            };
+            if let Some(trace) = self.trace.as_mut() {
+                trace.push(traced);
+            }
+        }
+    }
+
+    /// Why a line was classified as `class`, naming the pattern that matched.
+    fn reason(&self, class: LineClass, text: &str, artifact: Option<usize>) -> String {
+        let profile = &self.patterns.profile;
+        let matched = |regex: &Regex, text: &str| regex.find(text).map_or("", |m| m.as_str()).to_string();
+        match class {
+            LineClass::Blank => "nothing left after normalization and cleaning".to_string(),
+            LineClass::Artifact => format!(
+                "page furniture, matches artifact pattern `{}`",
+                artifact.map_or("", |i| profile.artifact_patterns[i])
+            ),
+            LineClass::Preamble => "before the first question".to_string(),
+            LineClass::Question => format!(
+                "question pattern `{}` matched `{}`",
+                profile.question_pattern,
+                matched(&self.patterns.question, text)
+            ),
+            LineClass::Premise => format!("premise pattern `{}` after a matching stem", MATCH_PREMISE_REGEX.as_str()),
+            LineClass::Choice => {
+                let (choice_line, starred) = strip_asterisk(text);
+                let inline = match self.choice_label(choice_line) {
+                    Some((letter, _)) => self.inline_choices(text, &letter).len(),
+                    None => 1,
+                };
+                format!(
+                    "choice pattern `{}` matched `{}`{}{}",
+                    profile.choice_pattern,
+                    matched(&self.patterns.choice, choice_line),
+                    if starred { ", starred" } else { "" },
+                    if inline > 1 { format!(", split into {} choices", inline) } else { String::new() }
+                )
+            }
+            LineClass::Answer => format!("answer pattern `{}` matched", profile.answer_pattern),
+            LineClass::IgnoredAnswer => {
+                format!("answer pattern `{}` matched, ignored as the marker strategy is off", profile.answer_pattern)
+            }
+            LineClass::Statement => {
+                format!("statement pattern `{}` matched before any choice", ROMAN_STATEMENT_REGEX.as_str())
+            }
+            LineClass::ChoiceText => "no pattern matched, continues the last choice".to_string(),
+            LineClass::StatementText => "no pattern matched, continues the last statement".to_string(),
+            LineClass::Stem => "no pattern matched, continues the question text".to_string(),
+            LineClass::Feedback => format!("feedback pattern `{}` matched after the answer", profile.feedback_pattern),
+            LineClass::FeedbackText => "no pattern matched, continues the last choice feedback".to_string(),
+        }
+    }
+
+    /// Splits the text added to the stem since the choices began off the current question into a new question,
+    /// whose choices are about to follow.
+    fn split_run_on(&mut self) {
+        let Some(mut question) = self.current_question.take() else {
+            return;
+        };
+        let number = self.question_number.to_string();
+        let (text, page) = match self.run_on.take() {
+            Some(run_on) => {
+                let source = match &run_on.choice {
+                    Some(letter) => question.choices.get_mut(letter),
+                    None => Some(&mut question.text),
+                };
+                // the choice may have been replaced by a repeated letter since
+                let text = match source.filter(|source| source.is_char_boundary(run_on.offset)) {
+                    Some(source) => {
+                        let text = source.split_off(run_on.offset).trim().to_string();
+                        source.truncate(source.trim_end().len());
+                        text
+                    }
+                    None => String::new(),
+                };
+                if let Some(trace) = self.trace.as_mut() {
+                    for line in trace.iter_mut().skip(run_on.trace) {
+                        if line.question.as_ref() == Some(&question.number) {
+                            line.question = Some(number.clone());
+                        }
+                    }
+                }
+                (text, run_on.page)
+            }
+            None => (String::new(), self.page),
+        };
+        self.issue(IssueKind::RecoveredBoundary(number.clone()));
+        self.questions.push(question);
+        self.current_question = Some(Question {
+            number,
+            text,
+            lang: Some(self.patterns.profile.lang.to_string()),
+            page,
+            recovered: true,
+            ..Question::default()
+        });
+        self.question_number += 1;
+        self.answered = false;
+    }
+
+    /// Adds a cleaned, non-empty line to the question being built and returns what it was taken for.
+    fn place_line(&mut self, cleaned_line: &str) -> LineClass {
+        // Matching premises ("1) Putaway") would otherwise be taken for question numbers by profiles accepting "1)".
+        // Only the next premise of an unanswered matching question counts, and only while its number is below the
+        // next question number or no choices were read yet, so "4) ..." after question 3 still starts question 4.
+        let question_number = self.question_number;
+        let answered = self.answered;
+        let matching = self.current_question.as_mut().filter(|q| !answered && kind::is_matching_stem(&q.text));
+        if let Some(question) = matching {
+            let next_number = question.source_number.map_or(question_number, |number| number + 1);
+            let premise_caps = MATCH_PREMISE_REGEX.captures(cleaned_line).filter(|caps| {
+                let label: usize = caps[1].parse().unwrap_or(0);
+                label == question.premises.len() + 1 && (label < next_number || question.choices.is_empty())
+            });
+            if let Some(premise_caps) = premise_caps {
+                question.kind = QuestionKind::Matching;
+                question.premises.push(Statement {
+                    label: premise_caps[1].to_string(),
+                    text: premise_caps[2].trim().to_string(),
+                });
+                return LineClass::Premise;
+            }
+        }
+
+        if let Some(number_match) = self.patterns.question.find(cleaned_line) {
+            if let Some(q) = self.current_question.take() {
+                self.questions.push(q);
+            }
+            let source_number = number_match
+                .as_str()
+                .chars()
+                .filter(char::is_ascii_digit)
+                .collect::<String>()
+                .parse()
+                .ok();
+            self.current_question = Some(Question {
+                number: self.question_number.to_string(),
+                text: cleaned_line[number_match.end()..].trim().to_string(),
+                lang: Some(self.patterns.profile.lang.to_string()),
+                page: self.page,
+                source_number,
+                ..Question::default()
+            });
+            self.question_number += 1;
+            self.run_on = None;
+            self.answered = false;
+            return LineClass::Question;
+        }
+
+        // Commentary on the choices ("A. Incorrect: ...") would otherwise be taken for choice letters restarting
+        if let Some(question) = self.current_question.as_mut().filter(|_| self.answered) {
+            let commented = self.patterns.feedback.captures(cleaned_line).and_then(|caps| {
+                let letter = caps.get(1)?.as_str().to_uppercase();
+                question.choices.contains_key(&letter).then(|| (letter, caps[2].trim().to_string()))
+            });
+            if let Some((letter, feedback)) = commented {
+                question.feedback.insert(letter.clone(), feedback);
+                self.continued_feedback = Some(letter);
+                return LineClass::Feedback;
+            }
+        }
+
+        let (choice_line, _) = strip_asterisk(cleaned_line);
+        let answer_spec = self
+            .patterns
+            .answer
+            .captures(cleaned_line)
+            .map(|caps| caps.get(1).or_else(|| caps.get(0)).map_or("", |m| m.as_str()).to_string());
+        let choice = if answer_spec.is_none() { self.choice_label(choice_line) } else { None };
+        // letter, text and asterisk of every choice on the line
+        let choices: Vec<(String, String, bool)> = match &choice {
+            Some((letter, _)) => self
+                .inline_choices(cleaned_line, letter)
+                .into_iter()
+                .filter_map(|segment| {
+                    let (segment, starred) = strip_asterisk(segment);
+                    let (letter, text_start) = self.choice_label(segment)?;
+                    Some((letter, segment[text_start..].trim().to_string(), starred))
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        // The lowest choice letter coming again ("A." after "D.") means the next question's number was missed
+        let restarts = match (&choice, &self.current_question) {
+            (Some((letter, _)), Some(question)) => question.choices.keys().min() == Some(letter),
+            _ => false,
+        };
+        if restarts {
+            self.split_run_on();
+        }
+
+        let Some(question) = self.current_question.as_mut() else {
+            // anything else before the first question is preamble
+            if answer_spec.is_some() {
+                self.issue(IssueKind::OrphanAnswer);
+            } else if choice.is_some() {
+                self.issue(IssueKind::OrphanChoice);
+            }
+            return LineClass::Preamble;
+        };
+
+        let (class, issue) = if let Some(spec) = answer_spec {
+            // answer lines are consumed even when the marker strategy is off so they do not end up in the stem
+            if !self.patterns.profile.uses(AnswerStrategy::Marker) {
+                return LineClass::IgnoredAnswer;
+            }
+            let readable = if question.kind == QuestionKind::Matching {
+                question.pairs = MATCH_PAIR_REGEX
+                    .captures_iter(&spec)
+                    .map(|pair| MatchPair {
+                        premise: pair[1].to_string(),
+                        choice: pair[2].to_uppercase(),
+                    })
+                    .collect();
+                !question.pairs.is_empty()
+            } else {
+                question.answers = self.patterns.profile.answer_letters(&spec);
+                question.correct_answers = Some(question.answers.len());
+                !question.answers.is_empty()
+            };
+            self.answered = true;
+            (LineClass::Answer, (!readable).then_some(IssueKind::UnreadableAnswer))
+        } else if !choices.is_empty() {
+            let asterisk = self.patterns.profile.uses(AnswerStrategy::Asterisk);
+            let mut duplicate = None;
+            for (answer_letter, text, starred) in choices {
+                if starred && asterisk && !question.answers.contains(&answer_letter) {
+                    question.answers.push(answer_letter.clone());
+                    question.correct_answers = Some(question.answers.len());
+                }
+                self.continued_choice = Some(answer_letter.clone());
+                if question.choices.insert(answer_letter.clone(), text).is_some() && duplicate.is_none() {
+                    duplicate = Some(IssueKind::DuplicateChoice(answer_letter));
+                }
+            }
+            (LineClass::Choice, duplicate)
+        } else if self.patterns.profile.element_order == ElementOrder::ChoicesFirst
+            && question.text.is_empty()
+            && !question.choices.is_empty()
+            && cleaned_line.chars().next().is_some_and(char::is_uppercase)
+        {
+            // the stem follows the choices, starting with the first capitalized line; lines before it wrap a choice
+            question.text = cleaned_line.to_string();
+            (LineClass::Stem, None)
+        } else if let Some(feedback) =
+            self.continued_feedback.as_ref().and_then(|letter| question.feedback.get_mut(letter))
+        {
+            text::join_line(feedback, cleaned_line);
+            (LineClass::FeedbackText, None)
+        } else if let Some((letter, choice_text)) = self
+            .continued_choice
+            .as_ref()
+            .and_then(|letter| Some((letter, question.choices.get_mut(letter)?)))
+        {
+            // A capitalized line after a choice may rather be the stem of a question whose number was missed
+            let capitalized = cleaned_line.chars().next().is_some_and(char::is_uppercase);
+            if capitalized && self.run_on.is_none() {
+                self.run_on = Some(RunOn {
+                    choice: Some(letter.clone()),
+                    offset: choice_text.len(),
+                    page: self.page,
+                    trace: self.trace.as_ref().map_or(0, Vec::len),
+                });
+            }
+            text::join_line(choice_text, cleaned_line);
+            (LineClass::ChoiceText, None)
+        } else if let Some(statement_caps) =
+            ROMAN_STATEMENT_REGEX.captures(cleaned_line).filter(|_| question.choices.is_empty())
+        {
+            question.statements.push(Statement {
+                label: statement_caps[1].to_string(),
+                text: statement_caps[2].trim().to_string(),
+            });
+            (LineClass::Statement, None)
+        } else {
+            // Until the choices start, lines after a statement continue it, except for the question that
+            // follows the statement list ("Which of the above ...?")
+            match question.statements.last_mut() {
+                Some(statement) if question.choices.is_empty() && !cleaned_line.ends_with('?') => {
+                    text::join_line(&mut statement.text, cleaned_line);
+                    (LineClass::StatementText, None)
+                }
+                _ => {
+                    // Text after the choices may be the stem of a question whose number was missed. It starts at the
+                    // first such line, or at a capitalized line following a blank one when some text came before.
+                    let past_choices = self.patterns.profile.element_order == ElementOrder::StemFirst
+                        && (!question.choices.is_empty() || !question.answers.is_empty());
+                    let capitalized = cleaned_line.chars().next().is_some_and(char::is_uppercase);
+                    if past_choices && (self.run_on.is_none() || (self.after_blank && capitalized)) {
+                        self.run_on = Some(RunOn {
+                            choice: None,
+                            offset: question.text.len(),
+                            page: self.page,
+                            trace: self.trace.as_ref().map_or(0, Vec::len),
+                        });
+                    }
+                    text::join_line(&mut question.text, cleaned_line);
+                    (LineClass::Stem, None)
+                }
+            }
+        };
+        if let Some(kind) = issue {
+            self.issue(kind);
+        }
+        class
+    }
+
+    pub(crate) fn finish(mut self) -> Parsed {
+        if let Some(q) = self.current_question.take() {
+            self.questions.push(q);
+        }
+        Parsed {
+            questions: self.questions,
+            issues: self.issues,
+            trace: self.trace.unwrap_or_default(),
+        }
+    }
+}
+
+/// Parses the text of every page in order, tracing every line if `trace` is set. `on_page` is called after each page
+/// with its index and the number of questions parsed so far.
+pub(crate) fn parse_pages<P: AsRef<str>>(
+    pages: impl IntoIterator<Item = P>,
+    profile: &ParserProfile,
+    trace: bool,
+    mut on_page: impl FnMut(usize, usize),
+) -> Parsed {
+    let mut parser = QuestionParser::new(profile);
+    if trace {
+        parser = parser.traced();
+    }
+    for (page_number, text) in pages.into_iter().enumerate() {
+        parser.set_page(page_number + 1);
+        for line in text.as_ref().split('\n') {
+            parser.parse_line(line);
+        }
+        on_page(page_number, parser.question_count());
+    }
+    parser.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::{Profile, ENGLISH, FRENCH, GERMAN, PROFILES};
+    use proptest::prelude::*;
+
+    const LETTERS: [&str; 6] = ["A", "B", "C", "D", "E", "F"];
+
+    /// A well-formed question: stem, choice texts and the indices of the correct choices
+    fn question_strategy() -> impl Strategy<Value = (String, Vec<String>, Vec<usize>)> {
+        let words = "[a-z]{3,8}( [a-z]{3,8}){0,6}";
+        (words, prop::collection::vec(words, 2..=6)).prop_flat_map(|(stem, choices)| {
+            let count = choices.len();
+            (Just(stem), Just(choices), prop::collection::btree_set(0..count, 1..=count.min(3)))
+                .prop_map(|(stem, choices, answers)| (format!("{}?", stem), choices, answers.into_iter().collect()))
+        })
+    }
+
+    fn render(questions: &[(String, Vec<String>, Vec<usize>)]) -> String {
+        let mut text = String::new();
+        for (i, (stem, choices, answers)) in questions.iter().enumerate() {
+            text.push_str(&format!("{}. {}\n", i + 1, stem));
+            for (letter, choice) in LETTERS.iter().zip(choices) {
+                text.push_str(&format!("{}. {}\n", letter, choice));
+            }
+            let answers: Vec<&str> = answers.iter().map(|&a| LETTERS[a]).collect();
+            text.push_str(&format!("Answer: {}\n", answers.join(", ")));
+        }
+        text
+    }
+
+    fn parse_english(pages: &[&str]) -> Parsed {
+        parse_pages(pages, &ParserProfile::cached(&ENGLISH).unwrap(), false, |_, _| {})
+    }
+
+    #[test]
+    fn wrapped_choice_continues_the_choice() {
+        let parsed = parse_english(&["\
+12. Which transaction do you use to define the storage type search sequence?
+A. /SCWM/SPRO, under Goods Receipt Process and Strategies
+for the putaway of products
+B. /SCWM/MON
+C. /SCWM/PRDI
+Answer: A"]);
+        let question = &parsed.questions[0];
+        assert_eq!(question.text, "Which transaction do you use to define the storage type search sequence?");
+        assert_eq!(
+            question.choices["A"],
+            "/SCWM/SPRO, under Goods Receipt Process and Strategies for the putaway of products"
+        );
+        assert_eq!(question.choices["B"], "/SCWM/MON");
+        assert_eq!(question.answers, ["A"]);
+    }
+
+    #[test]
+    fn choice_wraps_across_page_break_and_blank_lines() {
+        let parsed = parse_english(&[
+            "1. What does the warehouse monitor show?\nA. Open warehouse tasks and\n",
+            "17\n\nexception codes of the warehouse\nB. Nothing\nAnswer: A",
+        ]);
+        let question = &parsed.questions[0];
+        assert_eq!(question.choices["A"], "Open warehouse tasks and exception codes of the warehouse");
+        assert_eq!(question.choices["B"], "Nothing");
+    }
+
+    #[test]
+    fn page_numbers_and_footnotes_are_dropped() {
+        let parsed = parse_english(&[
+            "1. Which monitor¹ shows open warehouse [12] tasks?\nA. /SCWM/MON\nPage 3 of 120\n",
+            "¹ Available from SAP EWM 9.0\n3/120\nB. /SCWM/PRDI\nAnswer: A",
+        ]);
+        assert_eq!(parsed.questions.len(), 1);
+        let question = &parsed.questions[0];
+        assert_eq!(question.text, "Which monitor shows open warehouse tasks?");
+        assert_eq!(question.choices["A"], "/SCWM/MON");
+        assert_eq!(question.choices["B"], "/SCWM/PRDI");
+        assert_eq!(question.answers, ["A"]);
+    }
+
+    #[test]
+    fn lines_after_the_answer_do_not_continue_the_choice() {
+        let parsed = parse_english(&["\
+1. Which statements are true?
+A. Waves group warehouse requests
+B. Waves are released
+manually or automatically
+Answer: A, B
+(Choose two)"]);
+        let question = &parsed.questions[0];
+        assert_eq!(question.choices["B"], "Waves are released manually or automatically");
+        assert_eq!(question.text, "Which statements are true? (Choose two)");
+    }
+
+    #[test]
+    fn commentary_after_the_answer_is_choice_feedback() {
+        let parsed = parse_english(&["\
+1. Which process step creates the warehouse task?
+A. Putaway
+B. Goods receipt posting
+Answer: A
+A. Correct: the putaway warehouse task moves the stock
+to its final bin.
+B is incorrect because posting only updates the stock
+
+2. Which monitor shows open tasks?
+A. /SCWM/MON
+B. /SCWM/PRDI
+Answer: A"]);
+        assert_eq!(parsed.questions.len(), 2);
+        let question = &parsed.questions[0];
+        assert_eq!(question.choices["A"], "Putaway");
+        assert_eq!(question.feedback["A"], "the putaway warehouse task moves the stock to its final bin.");
+        assert_eq!(question.feedback["B"], "because posting only updates the stock");
+        assert!(!question.recovered && !parsed.questions[1].recovered);
+    }
+
+    #[test]
+    fn html_markup_is_stripped_and_stem_emphasis_kept() {
+        let parsed = parse_english(&["\
+1. Which transaction is <b>NOT</b> used for<br/>the putaway &amp; picking?
+A. /SCWM/MON&nbsp;<i>(monitor)</i>
+B. /SCWM/PRDI
+Answer: B"]);
+        let question = &parsed.questions[0];
+        assert_eq!(question.text, "Which transaction is NOT used for the putaway & picking?");
+        assert_eq!(question.emphasis, ["NOT"]);
+        assert_eq!(question.choices["A"], "/SCWM/MON (monitor)");
+        assert_eq!(question.answers, ["B"]);
+    }
+
+    #[test]
+    fn restarting_choice_letters_split_off_the_question() {
+        let parsed = parse_english(&["\
+1. Which process step creates the warehouse task?
+A. Putaway
+B. Goods receipt posting
+Answer: A
+Which monitor shows open warehouse tasks?
+A. /SCWM/MON
+B. /SCWM/PRDI
+Answer: A"]);
+        assert_eq!(parsed.questions.len(), 2);
+        assert_eq!(parsed.questions[0].text, "Which process step creates the warehouse task?");
+        assert!(!parsed.questions[0].recovered);
+        let question = &parsed.questions[1];
+        assert_eq!(question.number, "2");
+        assert_eq!(question.text, "Which monitor shows open warehouse tasks?");
+        assert_eq!(question.choices["A"], "/SCWM/MON");
+        assert_eq!(question.answers, ["A"]);
+        assert!(question.recovered);
+        assert!(parsed.issues.iter().any(|issue| issue.kind == IssueKind::RecoveredBoundary("2".to_string())));
+    }
+
+    #[test]
+    fn truncated_trailing_question_is_recovered() {
+        let parsed = parse_english(&["\
+1. Which process step creates the warehouse task?
+A. Putaway
+B. Goods receipt posting
+Answer: A
+Which monitor shows open warehouse tasks?
+A. /SCWM/MON"]);
+        assert_eq!(parsed.questions.len(), 2);
+        let question = &parsed.questions[1];
+        assert_eq!(question.text, "Which monitor shows open warehouse tasks?");
+        assert!(question.recovered);
+        assert!(question.answers.is_empty());
+    }
+
+    #[test]
+    fn numbered_questions_are_not_recovered() {
+        let parsed = parse_english(&[
+            "1. Which process step creates the warehouse task?\nA. Putaway\nB. Goods receipt posting\nAnswer: A\n",
+            "2. Which monitor shows open warehouse tasks?\nA. /SCWM/MON\nB. /SCWM/PRDI\nAnswer: A",
+        ]);
+        assert_eq!(parsed.questions.len(), 2);
+        assert!(parsed.questions.iter().all(|question| !question.recovered));
+        assert!(!parsed.issues.iter().any(|issue| matches!(issue.kind, IssueKind::RecoveredBoundary(_))));
+    }
+
+    #[test]
+    fn inline_choices_are_split() {
+        let parsed = parse_english(&["\
+4. Which process moves products from the GR zone to their final bin?
+A. Putaway B. Picking *C. Counting D. Vitamin C. Replenishment
+Answer: A"]);
+        let question = &parsed.questions[0];
+        assert_eq!(question.choices.len(), 4);
+        assert_eq!(question.choices["A"], "Putaway");
+        assert_eq!(question.choices["B"], "Picking");
+        assert_eq!(question.choices["C"], "Counting");
+        assert_eq!(question.choices["D"], "Vitamin C. Replenishment");
+        assert_eq!(question.answers, ["A"]);
+    }
+
+    #[test]
+    fn stem_after_choices_with_choices_first_order() {
+        let profile = Profile { element_order: ElementOrder::ChoicesFirst, ..ENGLISH };
+        let parsed = parse_pages(
+            ["\
+7.
+A. /SCWM/PRDI
+B. /SCWM/MON including the
+alert monitor
+Which transaction shows the warehouse
+management monitor?
+Answer: B"],
+            &ParserProfile::compile(profile).unwrap(),
+            false,
+            |_, _| {},
+        );
+        let question = &parsed.questions[0];
+        assert_eq!(question.text, "Which transaction shows the warehouse management monitor?");
+        assert_eq!(question.choices["B"], "/SCWM/MON including the alert monitor");
+        assert_eq!(question.answers, ["B"]);
+    }
+
+    #[test]
+    fn question_number_after_matching_premises_starts_the_next_question() {
+        let parsed = parse_pages(
+            ["\
+3) Ordnen Sie die Prozessschritte den Transaktionen zu.
+1) Wareneingang buchen
+2) Einlagerung quittieren
+A. /SCWM/PRDI
+B. /SCWM/MON
+4) Welche Transaktion zeigt den Lagerverwaltungsmonitor?
+A. /SCWM/MON
+B. /SCWM/PRDI
+Antwort: A"],
+            &ParserProfile::cached(&GERMAN).unwrap(),
+            false,
+            |_, _| {},
+        );
+        assert_eq!(parsed.questions.len(), 2);
+        let matching = &parsed.questions[0];
+        assert_eq!(matching.kind, QuestionKind::Matching);
+        let labels: Vec<&str> = matching.premises.iter().map(|premise| premise.label.as_str()).collect();
+        assert_eq!(labels, ["1", "2"]);
+        assert_eq!(parsed.questions[1].text, "Welche Transaktion zeigt den Lagerverwaltungsmonitor?");
+        assert_eq!(parsed.questions[1].answers, ["A"]);
+    }
+
+    #[test]
+    fn question_number_after_an_answered_matching_question_is_not_a_premise() {
+        let parsed = parse_pages(
+            ["\
+1) Associez chaque étape à sa transaction.
+1) Entrée de marchandises
+A. /SCWM/PRDI
+Réponse : 1-A
+2) Quelle transaction affiche le moniteur ?
+A. /SCWM/MON
+B. /SCWM/PRDI
+Réponse : A"],
+            &ParserProfile::cached(&FRENCH).unwrap(),
+            false,
+            |_, _| {},
+        );
+        assert_eq!(parsed.questions.len(), 2);
+        assert_eq!(parsed.questions[0].premises.len(), 1);
+        assert_eq!(parsed.questions[1].text, "Quelle transaction affiche le moniteur ?");
+    }
+
+
+    proptest! {
+        #[test]
+        fn never_panics_on_arbitrary_lines(pages in prop::collection::vec("(\\PC{0,40}\n){0,20}", 0..4)) {
+            for profile in PROFILES {
+                let parsed = parse_pages(&pages, &ParserProfile::cached(profile).unwrap(), false, |_, _| {});
+                let line_count: usize = pages.iter().map(|p| p.split('\n').count()).sum();
+                prop_assert!(parsed.questions.len() <= line_count);
+            }
+        }
+
+        #[test]
+        fn recovers_well_formed_questions(questions in prop::collection::vec(question_strategy(), 1..10)) {
+            let parsed = parse_pages([render(&questions)], &ParserProfile::cached(&ENGLISH).unwrap(), false, |_, _| {});
+            prop_assert!(parsed.issues.is_empty());
+            prop_assert_eq!(parsed.questions.len(), questions.len());
+            for (question, (stem, choices, answers)) in parsed.questions.iter().zip(&questions) {
+                prop_assert_eq!(&question.text, stem);
+                prop_assert_eq!(question.choices.len(), choices.len());
+                for (letter, choice) in LETTERS.iter().zip(choices) {
+                    prop_assert_eq!(question.choices.get(*letter), Some(choice));
+                }
+                let expected: Vec<String> = answers.iter().map(|&a| LETTERS[a].to_string()).collect();
+                prop_assert_eq!(&question.answers, &expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,586 @@
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::crypt::Crypto;
+use crate::export::Registry;
+use crate::lifecycle;
+use crate::manifest::sha256_hex;
+use crate::ocr;
+use crate::plugin::{self, Plugin};
+use crate::progress::Console;
+use crate::schema::Schema;
+use crate::timings::Timings;
+use crate::tree::Layout;
+use crate::validate::{self, Baseline, ValidationOptions};
+use crate::{
+    bank_path, download_pdf, extract_file, is_url, open_bank, validate_questions, ExtractOptions, OutputError,
+};
+
+// Declarative pipelines, for the runs made of several invocations strung together in a shell script: extract a dump,
+// filter and redact the bank, validate it, export it and publish the result. A pipeline file names its inputs (PDF
+// files or URLs, each with its parsing profile and extraction options), the transforms applied to every bank in order
+// (subcommands taking `<input> <output>`, such as `filter`, `redact` or `translate`), the validation, the export
+// formats and the destinations the outputs are copied to or handed to.
+//
+// Every stage result is cached in the cache directory under the hash of everything it depends on: the extraction
+// under the tool version, the PDF, the options and the contents of the files they name (a schema, a dictionary, the
+// hook plugins), each transform under the tool version, the result it was applied to, its arguments and the contents
+// of the files they name, each export under the bank and the format. Running a pipeline again only runs the stages
+// whose inputs changed; editing the third transform reruns it and what follows, not the extraction. Validation always
+// runs, as it reports. A stage writes its result under a partial name and moves it into place once it succeeded, so a
+// failed or killed stage leaves nothing a later run would take for cached. Downloaded PDFs are cached under their URL
+// only: a PDF changed at the same URL is not fetched again before a run with `--no-cache`. The identity and recipients
+// of the run are passed on to the transforms, and with `encrypt` every bank and export the pipeline writes is
+// encrypted.
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// Pipeline definition (TOML)
+    pipeline: String,
+    /// Only run these inputs, by name
+    #[arg(long, value_delimiter = ',', value_name = "NAME")]
+    only: Vec<String>,
+    /// Run every stage again instead of reusing the cached results
+    #[arg(long)]
+    no_cache: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Pipeline {
+    /// Where the outputs go, in a directory per input
+    #[serde(default = "default_output")]
+    output: PathBuf,
+    /// Where the stage results are kept between runs
+    #[serde(default = "default_cache")]
+    cache: PathBuf,
+    /// Extraction options of every input, as in a config file
+    #[serde(default)]
+    options: toml::Table,
+    #[serde(rename = "input")]
+    inputs: Vec<Input>,
+    #[serde(default, rename = "transform")]
+    transforms: Vec<Transform>,
+    #[serde(default)]
+    validate: Validation,
+    /// Formats the final bank is exported to, built-in or from exporter plugins
+    #[serde(default)]
+    exports: Vec<String>,
+    #[serde(default, rename = "destination")]
+    destinations: Vec<Destination>,
+}
+
+fn default_output() -> PathBuf {
+    PathBuf::from("pipeline")
+}
+
+fn default_cache() -> PathBuf {
+    PathBuf::from(".s4wm-cache")
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Input {
+    name: String,
+    /// PDF file or http(s) URL
+    source: String,
+    #[serde(default)]
+    profile: Option<String>,
+    /// Extraction options of this input, over the pipeline's
+    #[serde(default)]
+    options: toml::Table,
+}
+
+/// A subcommand run on the bank as `<run> <input> <output> <args>`, or with the `{input}` and `{output}` placeholders
+/// of its arguments replaced when it has them
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Transform {
+    run: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct Validation {
+    #[serde(default)]
+    strict: bool,
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    baseline: Option<String>,
+    #[serde(default)]
+    dictionary: Option<String>,
+}
+
+/// Where the outputs of an input go: a directory they are copied to, or a command run on the output directory.
+/// `{input}` stands for the input name and, in commands, `{dir}` for its output directory.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Destination {
+    #[serde(default)]
+    dir: Option<String>,
+    #[serde(default)]
+    command: Vec<String>,
+}
+
+struct Cache {
+    dir: PathBuf,
+    enabled: bool,
+}
+
+impl Cache {
+    /// Path of the result of a stage whose inputs hash to `key`
+    fn path(&self, stage: &str, key: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}-{}{}", stage, &key[..16], extension))
+    }
+
+    fn hit(&self, path: &Path) -> bool {
+        self.enabled && path.exists()
+    }
+}
+
+/// Where a stage writes the result it will have at `path` while it runs, with the same extension since the stages
+/// read the format from it. What a failed or killed run of the stage left there is removed first.
+fn partial(path: &Path) -> Result<PathBuf, OutputError> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let partial = match name.split_once('.') {
+        Some((stem, extension)) => path.with_file_name(format!("{}.partial.{}", stem, extension)),
+        None => path.with_file_name(format!("{}.partial", name)),
+    };
+    if partial.is_dir() {
+        fs::remove_dir_all(&partial)?;
+    } else if partial.exists() {
+        fs::remove_file(&partial)?;
+    }
+    Ok(partial)
+}
+
+/// Moves the complete result of a stage from its partial path into the cache, over the result of a `--no-cache` run.
+fn complete(partial: &Path, path: &Path) -> Result<(), OutputError> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    }
+    fs::rename(partial, path)?;
+    Ok(())
+}
+
+fn key(parts: &[&str]) -> String {
+    sha256_hex(parts.join("\n").as_bytes())
+}
+
+/// The names and contents hashes of the files among `values`, sorted, for the keys of the stages reading them.
+fn file_hashes(values: impl IntoIterator<Item = PathBuf>) -> Result<Vec<String>, OutputError> {
+    let mut files: Vec<PathBuf> = values.into_iter().filter(|path| path.is_file()).collect();
+    files.sort();
+    files.dedup();
+    files.iter().map(|path| Ok(format!("{} {}", path.display(), sha256_hex(&fs::read(path)?)))).collect()
+}
+
+/// Key of the extraction of `pdf`: the tool version, the PDF, the options and the files they name, including the hook
+/// plugins run on the bank.
+fn extraction_key(pdf: &Path, options: &ExtractOptions) -> Result<String, Box<dyn Error>> {
+    // the files read by the extraction; those it writes to, like the change log, are left out
+    let read = [&options.profile, &options.previous, &options.schema, &options.dictionary, &options.baseline];
+    let keys = [&options.replay, &options.identity, &options.sign];
+    let mut named: Vec<PathBuf> = read.into_iter().chain(keys).flatten().map(PathBuf::from).collect();
+    named.extend(options.recipient.iter().map(PathBuf::from));
+    if !options.hook.is_empty() {
+        named.extend(plugin::hook_plugins(options)?.iter().flat_map(Plugin::files));
+    }
+    let mut parts = vec![env!("CARGO_PKG_VERSION").to_string(), sha256_hex(&fs::read(pdf)?), toml::to_string(options)?];
+    parts.extend(file_hashes(named)?);
+    Ok(key(&parts.iter().map(String::as_str).collect::<Vec<_>>()))
+}
+
+/// Key of `transform` applied to the result keyed `previous`: the tool version, that result, the transform and the
+/// files its arguments name.
+fn transform_key(previous: &str, transform: &Transform) -> Result<String, Box<dyn Error>> {
+    let mut parts = vec![env!("CARGO_PKG_VERSION").to_string(), previous.to_string(), toml::to_string(transform)?];
+    parts.extend(file_hashes(transform.args.iter().map(PathBuf::from))?);
+    Ok(key(&parts.iter().map(String::as_str).collect::<Vec<_>>()))
+}
+
+fn load(path: &str) -> Result<Pipeline, OutputError> {
+    let text = fs::read_to_string(path).map_err(|e| OutputError {
+        message: format!("Cannot read the pipeline {}: {}", path, e),
+    })?;
+    let pipeline: Pipeline = toml::from_str(&text).map_err(|e| OutputError {
+        message: format!("Invalid pipeline {}: {}", path, e),
+    })?;
+    if pipeline.inputs.is_empty() {
+        return Err(OutputError::from("The pipeline has no [[input]]"));
+    }
+    if pipeline.destinations.iter().any(|destination| destination.dir.is_some() != destination.command.is_empty()) {
+        return Err(OutputError::from("Every [[destination]] needs either a dir or a command"));
+    }
+    Ok(pipeline)
+}
+
+/// The extraction options of an input: the options of the command line, then the pipeline's, then the input's.
+fn input_options(base: &ExtractOptions, pipeline: &Pipeline, input: &Input) -> Result<ExtractOptions, OutputError> {
+    let mut table = toml::Table::try_from(base).map_err(|e| OutputError { message: e.to_string() })?;
+    table.extend(pipeline.options.clone());
+    table.extend(input.options.clone());
+    let mut options: ExtractOptions = toml::Value::Table(table).try_into().map_err(|e| OutputError {
+        message: format!("Invalid options for input {}: {}", input.name, e),
+    })?;
+    options.profile = input.profile.clone().or(options.profile);
+    // the pipeline does the exports and writes the outputs
+    options.formats.clear();
+    options.input = None;
+    options.output_dir = None;
+    if options.layout != Layout::Single {
+        return Err(OutputError::from("Pipelines need the single-file layout"));
+    }
+    Ok(options)
+}
+
+/// Local path of the PDF of an input, downloaded into the cache the first time for URLs. The download is cached under
+/// the URL alone, a PDF replaced at the same URL is only fetched again with `--no-cache`.
+async fn fetch(input: &Input, cache: &Cache) -> Result<PathBuf, Box<dyn Error>> {
+    if !is_url(&input.source) {
+        return Ok(PathBuf::from(&input.source));
+    }
+    let path = cache.path("download", &key(&[&input.source]), ".pdf");
+    if !cache.hit(&path) {
+        println!("download   {}", input.source);
+        let partial = partial(&path)?;
+        fs::write(&partial, download_pdf(&input.source).await?)?;
+        complete(&partial, &path)?;
+    }
+    Ok(path)
+}
+
+/// The options of the run a transform needs to read and write encrypted banks.
+fn encryption_args(options: &ExtractOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(identity) = &options.identity {
+        args.extend(["--identity".to_string(), identity.clone()]);
+    }
+    for recipient in &options.recipient {
+        args.extend(["--recipient".to_string(), recipient.clone()]);
+    }
+    if options.encrypt {
+        args.push("--encrypt".to_string());
+    }
+    args
+}
+
+/// The command line of a transform reading the bank `input` and writing `output`.
+fn transform_args(transform: &Transform, options: &ExtractOptions, input: &Path, output: &Path) -> Vec<String> {
+    let (input, output) = (input.to_string_lossy(), output.to_string_lossy());
+    let placeholders = transform.args.iter().any(|arg| arg.contains("{input}") || arg.contains("{output}"));
+    let mut args = encryption_args(options);
+    args.push(transform.run.clone());
+    if placeholders {
+        args.extend(transform.args.iter().map(|arg| arg.replace("{input}", &input).replace("{output}", &output)));
+    } else {
+        args.extend([input.to_string(), output.to_string()]);
+        args.extend(transform.args.iter().cloned());
+    }
+    args
+}
+
+fn run_transform(
+    transform: &Transform,
+    options: &ExtractOptions,
+    input: &Path,
+    output: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let args = transform_args(transform, options, input, output);
+    let status = Command::new(env::current_exe()?).args(&args).status()?;
+    if !status.success() {
+        return Err(format!("transform `{}` failed ({})", args.join(" "), status).into());
+    }
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), OutputError> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)?.filter_map(Result::ok) {
+        if entry.path().is_file() {
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+fn deliver(destination: &Destination, name: &str, dir: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(target) = &destination.dir {
+        let target = PathBuf::from(target.replace("{input}", name));
+        copy_dir(dir, &target)?;
+        println!("deliver    {}", target.display());
+        return Ok(());
+    }
+    let dir = dir.to_string_lossy();
+    let args: Vec<String> =
+        destination.command.iter().map(|arg| arg.replace("{input}", name).replace("{dir}", &dir)).collect();
+    let status = Command::new(&args[0]).args(&args[1..]).status()?;
+    if !status.success() {
+        return Err(format!("destination `{}` failed ({})", args.join(" "), status).into());
+    }
+    println!("deliver    {}", args.join(" "));
+    Ok(())
+}
+
+/// Runs the pipeline for one input. Returns the number of questions of its final bank.
+async fn run_input(
+    pipeline: &Pipeline,
+    input: &Input,
+    base: &ExtractOptions,
+    cache: &Cache,
+) -> Result<usize, Box<dyn Error>> {
+    let options = input_options(base, pipeline, input)?;
+    let crypto = Crypto::new(&options);
+    let pdf = fetch(input, cache).await?;
+
+    let mut bank_key = extraction_key(&pdf, &options)?;
+    let extract_dir = cache.path("extract", &bank_key, "");
+    let mut bank = bank_path(&options, &extract_dir);
+    if cache.hit(&bank) {
+        println!("extract    cached");
+    } else {
+        println!("extract    {}", pdf.display());
+        let pdf_path = pdf.to_string_lossy();
+        let partial = partial(&extract_dir)?;
+        extract_file(&options, &pdf_path, &partial, None, None, Timings::default(), &mut Console::new())?;
+        complete(&partial, &extract_dir)?;
+    }
+
+    for transform in &pipeline.transforms {
+        bank_key = transform_key(&bank_key, transform)?;
+        let output = cache.path("transform", &bank_key, ".json");
+        if cache.hit(&output) {
+            println!("transform  {} cached", transform.run);
+        } else {
+            println!("transform  {}", transform.run);
+            let partial = partial(&output)?;
+            run_transform(transform, &options, &bank, &partial)?;
+            complete(&partial, &output)?;
+        }
+        bank = output;
+    }
+
+    let (final_bank, crypto) = open_bank(&bank.to_string_lossy(), &crypto)?;
+    let validation = ValidationOptions {
+        dictionary: ocr::load_dictionary(pipeline.validate.dictionary.as_deref())?,
+        allow: validate::allowed(&pipeline.validate.allow)?,
+        baseline: pipeline.validate.baseline.as_deref().map(Baseline::load).transpose()?,
+        schema: Schema::load(options.schema.as_deref())?,
+    };
+    println!("validate");
+    validate_questions(&final_bank, &validation, pipeline.validate.strict, &mut Console::new())?;
+
+    let output_dir = pipeline.output.join(&input.name);
+    fs::create_dir_all(&output_dir)?;
+    let file_name = if pipeline.transforms.is_empty() { bank.file_name() } else { None };
+    fs::copy(&bank, output_dir.join(file_name.unwrap_or(OsStr::new("questions.json"))))?;
+    let registry = Registry::load(&options)?;
+    let in_use = lifecycle::in_use(&final_bank);
+    for name in &pipeline.exports {
+        let exporter = registry.get(name)?;
+        let extension = format!(".{}", exporter.extension());
+        let exported = cache.path("export", &key(&[&bank_key, name]), &extension);
+        if cache.hit(&exported) {
+            println!("export     {} cached", name);
+        } else {
+            println!("export     {}", name);
+            let partial = partial(&exported)?;
+            crypto.write(&partial, exporter.render(&in_use)?)?;
+            complete(&partial, &exported)?;
+        }
+        fs::copy(&exported, output_dir.join(format!("questions{}", extension)))?;
+    }
+
+    for destination in &pipeline.destinations {
+        deliver(destination, &input.name, &output_dir)?;
+    }
+    Ok(final_bank.questions.len())
+}
+
+pub(crate) async fn run(args: RunArgs, options: &ExtractOptions) -> Result<(), Box<dyn Error>> {
+    let pipeline = load(&args.pipeline)?;
+    if let Some(unknown) = args.only.iter().find(|name| !pipeline.inputs.iter().any(|input| &input.name == *name)) {
+        return Err(format!("No input named {} in {}", unknown, args.pipeline).into());
+    }
+    let cache = Cache {
+        dir: pipeline.cache.clone(),
+        enabled: !args.no_cache,
+    };
+    fs::create_dir_all(&cache.dir)?;
+    let mut failed = 0;
+    for input in &pipeline.inputs {
+        if !args.only.is_empty() && !args.only.contains(&input.name) {
+            continue;
+        }
+        println!("== {}", input.name);
+        match run_input(&pipeline, input, options, &cache).await {
+            Ok(questions) => {
+                let output_dir = pipeline.output.join(&input.name);
+                println!("{}: {} question(s) in {}", input.name, questions, output_dir.display());
+            }
+            Err(error) => {
+                eprintln!("error[pipeline] {}: {}", input.name, error);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} input(s) of the pipeline failed", failed).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(run: &str, args: &[&str]) -> Transform {
+        Transform {
+            run: run.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn cache_hits_existing_results_only_when_enabled() {
+        let dir = std::env::temp_dir().join(format!("s4wm-pipeline-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = Cache {
+            dir: dir.clone(),
+            enabled: true,
+        };
+        let result = cache.path("transform", &key(&["bank"]), ".json");
+        let missing = cache.hit(&result);
+        fs::write(&result, "[]").unwrap();
+        let found = cache.hit(&result);
+        let disabled = Cache {
+            dir: dir.clone(),
+            enabled: false,
+        }
+        .hit(&result);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(!missing);
+        assert!(found);
+        assert!(!disabled);
+    }
+
+    #[test]
+    fn stages_write_partial_results_until_complete() {
+        let dir = std::env::temp_dir().join(format!("s4wm-pipeline-partial-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = Cache {
+            dir: dir.clone(),
+            enabled: true,
+        };
+        let result = cache.path("transform", &key(&["bank"]), ".json");
+        let partial_result = partial(&result).unwrap();
+        // a transform that failed after writing part of its output
+        fs::write(&partial_result, "[").unwrap();
+        let failed = cache.hit(&result);
+        let retried = partial(&result).unwrap();
+        let cleaned = retried.exists();
+        fs::write(&retried, "[]").unwrap();
+        complete(&retried, &result).unwrap();
+        let completed = (cache.hit(&result), retried.exists());
+
+        // an extraction writes a directory, rerun over the one of an earlier run
+        let extracted = cache.path("extract", &key(&["pdf"]), "");
+        fs::create_dir_all(&extracted).unwrap();
+        fs::write(extracted.join("questions.json"), "old").unwrap();
+        let partial_dir = partial(&extracted).unwrap();
+        fs::create_dir_all(&partial_dir).unwrap();
+        fs::write(partial_dir.join("questions.json"), "new").unwrap();
+        complete(&partial_dir, &extracted).unwrap();
+        let bank = fs::read_to_string(extracted.join("questions.json")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let name = format!("transform-{}.partial.json", &key(&["bank"])[..16]);
+        assert_eq!(partial_result.file_name().unwrap(), name.as_str());
+        assert!(!failed);
+        assert!(!cleaned);
+        assert_eq!(completed, (true, false));
+        assert_eq!(partial_dir.extension().unwrap(), "partial");
+        assert_eq!(bank, "new");
+    }
+
+    #[test]
+    fn extraction_key_follows_the_files_the_options_name() {
+        let dir = std::env::temp_dir().join(format!("s4wm-pipeline-key-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (pdf, schema) = (dir.join("dump.pdf"), dir.join("schema.toml"));
+        fs::write(&pdf, "%PDF-1.4").unwrap();
+        fs::write(&schema, "[fields.topic]\ntype = \"string\"\n").unwrap();
+        let options = ExtractOptions {
+            schema: Some(schema.to_string_lossy().into_owned()),
+            ..ExtractOptions::default()
+        };
+        let first = extraction_key(&pdf, &options).unwrap();
+        let again = extraction_key(&pdf, &options).unwrap();
+        fs::write(&schema, "[fields.topic]\ntype = \"string\"\nrequired = true\n").unwrap();
+        let edited = extraction_key(&pdf, &options).unwrap();
+        let without = extraction_key(&pdf, &ExtractOptions::default()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(first, again);
+        assert_ne!(first, edited);
+        assert_ne!(first, without);
+    }
+
+    #[test]
+    fn transform_keys_chain_on_the_previous_stage() {
+        let (filter, redact) = (transform("filter", &["--max-words", "40"]), transform("redact", &[]));
+        let filtered = transform_key("extracted", &filter).unwrap();
+        let redacted = transform_key(&filtered, &redact).unwrap();
+        assert_eq!(transform_key("extracted", &filter).unwrap(), filtered);
+
+        // editing the first transform reruns the second, and so does a new extraction
+        let refiltered = transform_key("extracted", &transform("filter", &["--max-words", "30"])).unwrap();
+        assert_ne!(refiltered, filtered);
+        assert_ne!(transform_key(&refiltered, &redact).unwrap(), redacted);
+        assert_ne!(transform_key("reextracted", &filter).unwrap(), filtered);
+    }
+
+    #[test]
+    fn transform_keys_follow_the_files_their_arguments_name() {
+        let patterns = std::env::temp_dir().join(format!("s4wm-pipeline-patterns-{}.txt", std::process::id()));
+        fs::write(&patterns, "examtopics\n").unwrap();
+        let redact = transform("redact", &["--patterns-from", &patterns.to_string_lossy()]);
+        let first = transform_key("extracted", &redact).unwrap();
+        fs::write(&patterns, "dumps\n").unwrap();
+        let edited = transform_key("extracted", &redact).unwrap();
+        fs::remove_file(&patterns).unwrap();
+        assert_ne!(first, edited);
+    }
+
+    #[test]
+    fn transforms_take_the_banks_as_arguments_or_placeholders() {
+        let options = ExtractOptions::default();
+        let (input, output) = (Path::new("in.json"), Path::new("out.json"));
+        assert_eq!(
+            transform_args(&transform("filter", &["--max-words", "40"]), &options, input, output),
+            ["filter", "in.json", "out.json", "--max-words", "40"]
+        );
+        assert_eq!(
+            transform_args(&transform("translate", &["{input}", "{output}", "--to", "de"]), &options, input, output),
+            ["translate", "in.json", "out.json", "--to", "de"]
+        );
+
+        let encrypting = ExtractOptions {
+            encrypt: true,
+            recipient: vec!["age1key".to_string()],
+            ..ExtractOptions::default()
+        };
+        assert_eq!(
+            transform_args(&transform("redact", &[]), &encrypting, input, output),
+            ["--recipient", "age1key", "--encrypt", "redact", "in.json", "out.json"]
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::export::Registry;
+use crate::hooks::Stage;
+use crate::{ExtractOptions, OutputError};
+
+// External plugins, so niche formats can be added without forking the crate. Every `*.toml` file of the plugin
+// directory (`--plugin-dir`, by default `~/.config/s4wm-extract/plugins`) describes one plugin: its name, its kind and
+// how to run it, either as a subprocess (`command`, run in the plugin directory) or as a WebAssembly module (`wasm`).
+//
+// Both speak JSON. A subprocess plugin reads one request object on stdin and writes one response object on stdout;
+// exiting with an error fails the call with what it printed on stderr, and one still running after its `timeout` is
+// killed. A WASM plugin exports its `memory`, an `alloc(len: i32) -> i32` function reserving `len` bytes for the
+// request, and one function per call taking the pointer and length of the request and returning the pointer and length
+// of the response, packed in an i64 (pointer in the high 32 bits). WASM plugins are given no imports, so they cannot
+// reach files or the network, and run on a `fuel` budget with a capped memory, so a module stuck in a loop or growing
+// its memory without end fails the call instead of the run. Requests carry the `protocol` version; a response with an
+// `error` string fails the call.
+//
+// Exporter plugins (`kind = "exporter"`, with the `extension` of their files) are called with `{"bank": ...}` on the
+// `render` function and answer `{"content": "..."}`; their name is then accepted by `--format` and `export`. Hook
+// plugins (`kind = "hook"`, with the `stages` they run at) are described in `hooks`.
+
+pub(crate) const PROTOCOL_VERSION: u64 = 1;
+
+/// Seconds a subprocess plugin may run when its description gives no `timeout`
+const DEFAULT_TIMEOUT: u64 = 60;
+/// Instructions, roughly, a WASM plugin may execute per call when its description gives no `fuel`
+const DEFAULT_FUEL: u64 = 1_000_000_000;
+/// Bytes of memory a WASM plugin may grow to
+const WASM_MEMORY: usize = 256 << 20;
+/// How often a running subprocess plugin is checked against its timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Kind {
+    Exporter,
+    Hook,
+}
+
+/// A plugin described by a file of the plugin directory
+#[derive(Debug, Deserialize)]
+pub(crate) struct Plugin {
+    pub(crate) name: String,
+    pub(crate) kind: Kind,
+    /// Extension of the files written by an exporter
+    #[serde(default)]
+    pub(crate) extension: Option<String>,
+    /// Stages a hook runs at
+    #[serde(default)]
+    pub(crate) stages: Vec<Stage>,
+    /// Program and arguments of a subprocess plugin
+    #[serde(default)]
+    command: Vec<String>,
+    /// Seconds a subprocess plugin may run before it is killed
+    #[serde(default)]
+    timeout: Option<u64>,
+    /// WebAssembly module of a WASM plugin, relative to the plugin directory
+    #[serde(default)]
+    wasm: Option<PathBuf>,
+    /// Instructions, roughly, a WASM plugin may execute per call
+    #[serde(default)]
+    fuel: Option<u64>,
+    /// The file describing the plugin
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+fn error(message: impl ToString) -> OutputError {
+    OutputError {
+        message: message.to_string(),
+    }
+}
+
+/// The plugin directory given, or else the user plugin directory when it exists.
+fn directory(options: &ExtractOptions) -> Option<PathBuf> {
+    match &options.plugin_dir {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => config::config_dir().map(|dir| dir.join("plugins")).filter(|dir| dir.is_dir()),
+    }
+}
+
+/// The plugins of the plugin directory, sorted by file name.
+pub(crate) fn discover(options: &ExtractOptions) -> Result<Vec<Plugin>, OutputError> {
+    let Some(dir) = directory(options) else {
+        return Ok(Vec::new());
+    };
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+        .map_err(|e| error(format!("Cannot read the plugin directory {}: {}", dir.display(), e)))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    let mut plugins = Vec::new();
+    for path in files {
+        let mut plugin: Plugin = toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| error(format!("Invalid plugin {}: {}", path.display(), e)))?;
+        if plugin.command.is_empty() == plugin.wasm.is_none() {
+            return Err(error(format!("Plugin {} needs either a command or a wasm module", path.display())));
+        }
+        if plugin.kind == Kind::Exporter && plugin.extension.is_none() {
+            return Err(error(format!("Exporter plugin {} needs the extension of its files", path.display())));
+        }
+        if plugin.kind == Kind::Hook && plugin.stages.is_empty() {
+            return Err(error(format!("Hook plugin {} needs the stages it runs at", path.display())));
+        }
+        plugin.path = path;
+        plugins.push(plugin);
+    }
+    Ok(plugins)
+}
+
+impl Plugin {
+    fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// The files the plugin is made of: its description, its module or the program of its command when that sits in
+    /// the plugin directory
+    pub(crate) fn files(&self) -> Vec<PathBuf> {
+        let program = self.command.first().map(|program| self.dir().join(program)).filter(|path| path.is_file());
+        let module = self.wasm.as_ref().map(|module| self.dir().join(module));
+        [Some(self.path.clone()), module, program].into_iter().flatten().collect()
+    }
+
+    /// How the plugin runs, for listings
+    pub(crate) fn runtime(&self) -> String {
+        match &self.wasm {
+            Some(module) => format!("wasm {}", module.display()),
+            None => format!("command {}", self.command.join(" ")),
+        }
+    }
+
+    /// Sends `request` to the `function` of the plugin and returns its response.
+    pub(crate) fn call(&self, function: &str, mut request: Value) -> Result<Value, OutputError> {
+        if let Value::Object(fields) = &mut request {
+            fields.insert("protocol".to_string(), PROTOCOL_VERSION.into());
+        }
+        let request = serde_json::to_vec(&request)?;
+        let response = match &self.wasm {
+            Some(module) => self.call_wasm(module, function, &request),
+            None => self.call_command(&request),
+        }
+        .map_err(|e| error(format!("plugin {}: {}", self.name, e)))?;
+        let response: Value = serde_json::from_slice(&response)
+            .map_err(|e| error(format!("plugin {} answered invalid JSON: {}", self.name, e)))?;
+        if let Some(message) = response.get("error").and_then(Value::as_str) {
+            return Err(error(format!("plugin {}: {}", self.name, message)));
+        }
+        Ok(response)
+    }
+
+    fn call_command(&self, request: &[u8]) -> Result<Vec<u8>, OutputError> {
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .current_dir(self.dir())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| error(format!("cannot run {}: {}", self.command[0], e)))?;
+        // written and read from other threads, so a plugin answering before it read everything cannot block us
+        let mut stdin = child.stdin.take().ok_or_else(|| error("no stdin"))?;
+        let request = request.to_vec();
+        let writer = thread::spawn(move || stdin.write_all(&request));
+        let stdout = read_all(child.stdout.take());
+        let stderr = read_all(child.stderr.take());
+
+        let timeout = self.timeout.unwrap_or(DEFAULT_TIMEOUT);
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() >= Duration::from_secs(timeout) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(error(format!("timed out after {}s", timeout)));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        // a plugin may exit without reading the request, its exit status tells whether that is a failure
+        let _ = writer.join();
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        if !status.success() {
+            return Err(error(format!("{} ({})", String::from_utf8_lossy(&stderr).trim(), status)));
+        }
+        Ok(stdout)
+    }
+
+    fn call_wasm(&self, module: &Path, function: &str, request: &[u8]) -> Result<Vec<u8>, OutputError> {
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = wasmi::Engine::new(&config);
+        let module = wasmi::Module::new(&engine, &fs::read(self.dir().join(module))?[..]).map_err(error)?;
+        let limits = wasmi::StoreLimitsBuilder::new().memory_size(WASM_MEMORY).build();
+        let mut store = wasmi::Store::new(&engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel.unwrap_or(DEFAULT_FUEL)).map_err(error)?;
+        let instance = wasmi::Linker::<wasmi::StoreLimits>::new(&engine)
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(error)?;
+        let memory = instance.get_memory(&store, "memory").ok_or_else(|| error("the module exports no memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc").map_err(error)?;
+        let entry = instance.get_typed_func::<(i32, i32), i64>(&store, function).map_err(error)?;
+
+        let length = i32::try_from(request.len()).map_err(|_| error("the request is too large"))?;
+        let pointer = alloc.call(&mut store, length).map_err(error)?;
+        memory.write(&mut store, pointer as u32 as usize, request).map_err(error)?;
+        let packed = entry.call(&mut store, (pointer, length)).map_err(error)? as u64;
+        // the module chooses the length, so it is checked against the module memory before copying the response
+        let (pointer, length) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let response = pointer.checked_add(length).and_then(|end| memory.data(&store).get(pointer..end));
+        let response = response.ok_or_else(|| {
+            error(format!("the response ({} bytes at {}) lies outside the module memory", length, pointer))
+        })?;
+        Ok(response.to_vec())
+    }
+}
+
+/// Reads a pipe of a subprocess to its end on another thread.
+fn read_all(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+pub(crate) fn hook_plugins(options: &ExtractOptions) -> Result<Vec<Plugin>, OutputError> {
+    Ok(discover(options)?.into_iter().filter(|plugin| plugin.kind == Kind::Hook).collect())
+}
+
+pub(crate) fn run(options: &ExtractOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let registry = Registry::load(options)?;
+    for (name, extension, origin) in registry.describe() {
+        println!("exporter  {:<12} .{:<6} {}", name, extension, origin);
+    }
+    for hook in hook_plugins(options)? {
+        let stages: Vec<&str> = hook.stages.iter().map(|stage| stage.as_str()).collect();
+        println!("hook      {:<12} {}  {}", hook.name, stages.join(","), hook.runtime());
+    }
+    if directory(options).is_none() {
+        println!("No plugin directory, give one with --plugin-dir or create ~/.config/s4wm-extract/plugins");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A module echoing its request from `render` and looping forever in `spin`
+    const ECHO_MODULE: [u8; 100] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x02,
+        0x7f, 0x7f, 0x01, 0x7e, 0x03, 0x04, 0x03, 0x00, 0x01, 0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x22, 0x04,
+        0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x05, 0x61, 0x6c, 0x6c, 0x6f, 0x63, 0x00, 0x00, 0x06,
+        0x72, 0x65, 0x6e, 0x64, 0x65, 0x72, 0x00, 0x01, 0x04, 0x73, 0x70, 0x69, 0x6e, 0x00, 0x02, 0x0a, 0x1d, 0x03,
+        0x05, 0x00, 0x41, 0x80, 0x08, 0x0b, 0x0c, 0x00, 0x20, 0x00, 0xad, 0x42, 0x20, 0x86, 0x20, 0x01, 0xad, 0x84,
+        0x0b, 0x08, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x00, 0x0b,
+    ];
+
+    /// A module whose `render` answers 4 GiB at the start of its single 64 KiB page
+    const OVERSIZED_MODULE: [u8; 79] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x02,
+        0x7f, 0x7f, 0x01, 0x7e, 0x03, 0x03, 0x02, 0x00, 0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x1b, 0x03, 0x06,
+        0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00, 0x05, 0x61, 0x6c, 0x6c, 0x6f, 0x63, 0x00, 0x00, 0x06, 0x72,
+        0x65, 0x6e, 0x64, 0x65, 0x72, 0x00, 0x01, 0x0a, 0x10, 0x02, 0x05, 0x00, 0x41, 0x80, 0x08, 0x0b, 0x08, 0x00,
+        0x42, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x0b,
+    ];
+
+    /// A plugin directory holding the given files, and the options pointing at it
+    fn setup(name: &str, files: &[(&str, &[u8])]) -> (PathBuf, ExtractOptions) {
+        let dir = std::env::temp_dir().join(format!("s4wm-plugin-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, content) in files {
+            fs::write(dir.join(file), content).unwrap();
+        }
+        let options = ExtractOptions {
+            plugin_dir: Some(dir.to_string_lossy().into_owned()),
+            ..ExtractOptions::default()
+        };
+        (dir, options)
+    }
+
+    #[test]
+    fn discover_reads_the_descriptions_in_file_name_order() {
+        let (dir, options) = setup(
+            "order",
+            &[
+                ("b.toml", b"name = \"lint\"\nkind = \"hook\"\nstages = [\"after-parse\"]\ncommand = [\"lint\"]\n"),
+                ("a.toml", b"name = \"moodle\"\nkind = \"exporter\"\nextension = \"xml\"\nwasm = \"moodle.wasm\"\n"),
+                ("notes.txt", b"not a plugin"),
+            ],
+        );
+        let plugins = discover(&options).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let names: Vec<&str> = plugins.iter().map(|plugin| plugin.name.as_str()).collect();
+        assert_eq!(names, ["moodle", "lint"]);
+        assert_eq!(plugins[0].runtime(), "wasm moodle.wasm");
+        assert_eq!(plugins[1].stages, [Stage::AfterParse]);
+    }
+
+    #[test]
+    fn discover_rejects_incomplete_descriptions() {
+        let cases = [
+            ("name = \"x\"\nkind = \"hook\"\nstages = [\"after-parse\"]\n", "either a command or a wasm module"),
+            ("name = \"x\"\nkind = \"hook\"\ncommand = [\"x\"]\nwasm = \"x.wasm\"\n", "either a command"),
+            ("name = \"x\"\nkind = \"exporter\"\ncommand = [\"x\"]\n", "needs the extension"),
+            ("name = \"x\"\nkind = \"hook\"\ncommand = [\"x\"]\n", "needs the stages"),
+            ("name = \"x\"\nkind = \"importer\"\ncommand = [\"x\"]\n", "Invalid plugin"),
+        ];
+        for (index, (description, expected)) in cases.into_iter().enumerate() {
+            let (dir, options) = setup(&format!("invalid-{}", index), &[("plugin.toml", description.as_bytes())]);
+            let result = discover(&options);
+            fs::remove_dir_all(&dir).unwrap();
+            let message = result.unwrap_err().message;
+            assert!(message.contains(expected), "{}: {}", description, message);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_plugins_round_trip_json() {
+        let description = b"name = \"echo\"\nkind = \"exporter\"\nextension = \"txt\"\ncommand = [\"cat\"]\n";
+        let (dir, options) = setup("command", &[("echo.toml", description)]);
+        let plugins = discover(&options).unwrap();
+        let response = plugins[0].call("render", json!({"bank": {"title": "Été"}}));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(response.unwrap(), json!({"bank": {"title": "Été"}, "protocol": PROTOCOL_VERSION}));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_plugins_are_killed_after_their_timeout() {
+        let description = b"name = \"slow\"\nkind = \"exporter\"\nextension = \"txt\"\ncommand = [\"sleep\", \"5\"]\n\
+            timeout = 1\n";
+        let (dir, options) = setup("timeout", &[("slow.toml", description)]);
+        let plugins = discover(&options).unwrap();
+        let started = Instant::now();
+        let response = plugins[0].call("render", json!({}));
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(response.unwrap_err().message.contains("timed out after 1s"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn wasm_plugins_round_trip_json() {
+        let description = b"name = \"echo\"\nkind = \"exporter\"\nextension = \"txt\"\nwasm = \"echo.wasm\"\n";
+        let (dir, options) = setup("wasm", &[("echo.toml", description), ("echo.wasm", &ECHO_MODULE)]);
+        let plugins = discover(&options).unwrap();
+        let response = plugins[0].call("render", json!({"bank": {"title": "Été"}}));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(response.unwrap(), json!({"bank": {"title": "Été"}, "protocol": PROTOCOL_VERSION}));
+    }
+
+    #[test]
+    fn wasm_plugins_stop_when_out_of_fuel() {
+        let description = b"name = \"spin\"\nkind = \"exporter\"\nextension = \"txt\"\nwasm = \"spin.wasm\"\n\
+            fuel = 10000\n";
+        let (dir, options) = setup("fuel", &[("spin.toml", description), ("spin.wasm", &ECHO_MODULE)]);
+        let plugins = discover(&options).unwrap();
+        let response = plugins[0].call("spin", json!({}));
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(response.unwrap_err().message.contains("fuel"));
+    }
+
+    #[test]
+    fn wasm_responses_outside_the_module_memory_fail() {
+        let description = b"name = \"huge\"\nkind = \"exporter\"\nextension = \"txt\"\nwasm = \"huge.wasm\"\n";
+        let (dir, options) = setup("oversized", &[("huge.toml", description), ("huge.wasm", &OVERSIZED_MODULE)]);
+        let plugins = discover(&options).unwrap();
+        let response = plugins[0].call("render", json!({}));
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(response.unwrap_err().message.contains("(4294967295 bytes at 0) lies outside the module memory"));
+    }
+}
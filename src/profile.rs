@@ -0,0 +1,295 @@
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// Locale-aware parsing profiles. A profile bundles the line patterns the parser looks for (question numbers, choice
+// labels, answer markers) together with the normalization rules that have to run before those patterns can match,
+// e.g. the narrow no-break space French typography puts in front of the colon in "Réponse :".
+//
+// Patterns are kept as strings so profiles stay `const`. A run compiles them once into a `ParserProfile`, which the
+// parser and the other pattern users share; compiled profiles are cached by profile so the files of a batch run using
+// the same profile compile it only once.
+
+/// Ways of telling which choice is correct. A profile lists the strategies it applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AnswerStrategy {
+    /// "Answer: B" lines
+    Marker,
+    /// Choices flagged with an asterisk ("*B. Picking" or "B. Picking *")
+    Asterisk,
+    /// Choices set in a bold font
+    Bold,
+    /// Choices printed in a fill color other than the body text's, calibrated per document
+    Color,
+}
+
+const DEFAULT_STRATEGIES: &[AnswerStrategy] = &[AnswerStrategy::Marker, AnswerStrategy::Asterisk];
+
+/// Typographic variants folded into one form in every line, so that the same text typeset differently by two dumps
+/// hashes and diffs the same. A profile lists the rules it applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Typography {
+    /// Curly, low and angle quotes to straight quotes
+    Quotes,
+    /// Hyphen variants, en and em dashes and the minus sign to "-"
+    Dashes,
+    /// The ellipsis character to "..."
+    Ellipsis,
+    /// No-break, thin and other fixed-width spaces to a plain space, zero-width spaces removed
+    Spaces,
+    /// Runs of whitespace to a single space
+    Whitespace,
+}
+
+impl Typography {
+    /// Replacement of a character under this rule, if it applies to it
+    fn replacement(self, c: char) -> Option<&'static str> {
+        match (self, c) {
+            (Typography::Quotes, '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' | '\u{2039}' | '\u{203a}') => {
+                Some("'")
+            }
+            (Typography::Quotes, '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{ab}' | '\u{bb}') => Some("\""),
+            (Typography::Dashes, '\u{2010}'..='\u{2015}' | '\u{2212}') => Some("-"),
+            (Typography::Ellipsis, '\u{2026}') => Some("..."),
+            (Typography::Spaces, '\u{a0}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{205f}') => Some(" "),
+            (Typography::Spaces, '\u{200b}' | '\u{feff}') => Some(""),
+            _ => None,
+        }
+    }
+
+    fn apply<'a>(self, line: Cow<'a, str>) -> Cow<'a, str> {
+        if self == Typography::Whitespace {
+            let mut previous_space = false;
+            let needed = line.chars().any(|c| {
+                let irregular = c.is_whitespace() && (previous_space || c != ' ');
+                previous_space = c.is_whitespace();
+                irregular
+            });
+            return if needed { Cow::Owned(line.split_whitespace().collect::<Vec<_>>().join(" ")) } else { line };
+        }
+        if !line.chars().any(|c| self.replacement(c).is_some()) {
+            return line;
+        }
+        let mut out = String::with_capacity(line.len());
+        for c in line.chars() {
+            match self.replacement(c) {
+                Some(replacement) => out.push_str(replacement),
+                None => out.push(c),
+            }
+        }
+        Cow::Owned(out)
+    }
+}
+
+/// Order in which a question's stem and choices are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ElementOrder {
+    /// Number and stem, then the choices
+    #[default]
+    StemFirst,
+    /// Number, then the choices, then the stem, as in dumps extracted from tables
+    ChoicesFirst,
+}
+
+const ALL_TYPOGRAPHY: &[Typography] =
+    &[Typography::Quotes, Typography::Dashes, Typography::Ellipsis, Typography::Spaces, Typography::Whitespace];
+
+/// Superscript reference numbers ("warehouse¹") and bracketed references ("[12]")
+const FOOTNOTE_MARKERS: &str = r"[\u{b9}\u{b2}\u{b3}\u{2070}-\u{2079}]+|\[\d{1,3}\]";
+/// Footnote text at the bottom of a page ("¹ See SAP note 2021")
+const FOOTNOTE_BODY: &str = r"^[\u{b9}\u{b2}\u{b3}\u{2070}-\u{2079}]+\s*\S";
+/// Bare page numbers: lone integers and "12/120"
+const LONE_NUMBER: &str = r"^\d{1,4}(?:\s*/\s*\d{1,4})?$";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Profile {
+    pub(crate) name: &'static str,
+    /// ISO 639-1 code recorded on every question parsed with this profile
+    pub(crate) lang: &'static str,
+    /// Matches the question number at the start of a line, the rest of the line is the stem
+    pub(crate) question_pattern: &'static str,
+    /// Matches a choice label, capture 1 is the choice letter
+    pub(crate) choice_pattern: &'static str,
+    /// Matches an answer line, capture 1 holds the answer letters
+    pub(crate) answer_pattern: &'static str,
+    /// Matches a line commenting on a choice after the answer ("A. Incorrect: putaway comes later"), capture 1 is the
+    /// choice letter and capture 2 the commentary following the verdict
+    pub(crate) feedback_pattern: &'static str,
+    /// Words allowed between answer letters ("B and D")
+    pub(crate) conjunctions: &'static [&'static str],
+    /// Literal replacements applied to every line before matching
+    pub(crate) replacements: &'static [(&'static str, &'static str)],
+    /// Typographic normalization applied to every line after the replacements, borrowed from the built-in list unless
+    /// `--typography` overrides it
+    pub(crate) typography: Cow<'static, [Typography]>,
+    /// Borrowed from the built-in list unless `--answer-strategy` overrides it
+    pub(crate) answer_strategies: Cow<'static, [AnswerStrategy]>,
+    /// Whether the choices are printed after the stem or before it
+    pub(crate) element_order: ElementOrder,
+    /// Matches lines dropped entirely because they are page furniture (page numbers, footnote text)
+    pub(crate) artifact_patterns: &'static [&'static str],
+    /// Matches inline footnote markers removed from every line
+    pub(crate) footnote_pattern: &'static str,
+}
+
+pub(crate) const ENGLISH: Profile = Profile {
+    name: "en",
+    lang: "en",
+    question_pattern: r"^\d+\.",
+    choice_pattern: r"^([A-Fa-f])[.)]",
+    answer_pattern: r"(?i)^(?:correct\s+)?answers?\s*:\s*(.+)$",
+    feedback_pattern: concat!(
+        r"(?i)^\(?([a-f])\)?(?:\s*[.):-]\s*|\s+is\s+)",
+        r"(?:incorrect|correct|wrong|right)\b[\s.:,;-]*(.*)$"
+    ),
+    conjunctions: &["and"],
+    replacements: &[("\u{a0}", " ")],
+    typography: Cow::Borrowed(ALL_TYPOGRAPHY),
+    answer_strategies: Cow::Borrowed(DEFAULT_STRATEGIES),
+    element_order: ElementOrder::StemFirst,
+    artifact_patterns: &[r"(?i)^page\s+\d+(?:\s+of\s+\d+)?$", LONE_NUMBER, FOOTNOTE_BODY],
+    footnote_pattern: FOOTNOTE_MARKERS,
+};
+
+pub(crate) const GERMAN: Profile = Profile {
+    name: "de",
+    lang: "de",
+    question_pattern: r"^(?:Frage\s+)?\d+[.:)]",
+    choice_pattern: r"^([A-Fa-f])[.)]",
+    answer_pattern: r"(?i)^(?:richtige\s+)?antwort(?:en)?\s*:\s*(.+)$",
+    feedback_pattern: concat!(
+        r"(?i)^\(?([a-f])\)?(?:\s*[.):-]\s*|\s+ist\s+)",
+        r"(?:falsch|richtig|inkorrekt|korrekt)\b[\s.:,;-]*(.*)$"
+    ),
+    conjunctions: &["und"],
+    replacements: &[("\u{a0}", " "), ("\u{201e}", "\""), ("\u{201c}", "\""), ("\u{201a}", "'"), ("\u{2018}", "'")],
+    typography: Cow::Borrowed(ALL_TYPOGRAPHY),
+    answer_strategies: Cow::Borrowed(DEFAULT_STRATEGIES),
+    element_order: ElementOrder::StemFirst,
+    artifact_patterns: &[r"(?i)^seite\s+\d+(?:\s+von\s+\d+)?$", LONE_NUMBER, FOOTNOTE_BODY],
+    footnote_pattern: FOOTNOTE_MARKERS,
+};
+
+pub(crate) const FRENCH: Profile = Profile {
+    name: "fr",
+    lang: "fr",
+    question_pattern: r"^(?:Question\s+)?\d+[.:)]",
+    choice_pattern: r"^([A-Fa-f])[.)]",
+    answer_pattern: r"(?i)^(?:bonnes?\s+)?r[ée]ponses?\s*:\s*(.+)$",
+    feedback_pattern: concat!(
+        r"(?i)^\(?([a-f])\)?(?:\s*[.):-]\s*|\s+est\s+)",
+        r"(?:incorrecte?|correcte?|fausse|faux|vraie?)\b[\s.:,;-]*(.*)$"
+    ),
+    conjunctions: &["et"],
+    replacements: &[
+        ("\u{a0}", " "),
+        ("\u{202f}", " "),
+        ("\u{ab} ", "\""),
+        (" \u{bb}", "\""),
+        ("\u{ab}", "\""),
+        ("\u{bb}", "\""),
+    ],
+    typography: Cow::Borrowed(ALL_TYPOGRAPHY),
+    answer_strategies: Cow::Borrowed(DEFAULT_STRATEGIES),
+    element_order: ElementOrder::StemFirst,
+    artifact_patterns: &[r"(?i)^page\s+\d+(?:\s+sur\s+\d+)?$", LONE_NUMBER, FOOTNOTE_BODY],
+    footnote_pattern: FOOTNOTE_MARKERS,
+};
+
+pub(crate) const PROFILES: &[Profile] = &[ENGLISH, GERMAN, FRENCH];
+
+impl Profile {
+    pub(crate) fn by_name(name: &str) -> Option<Profile> {
+        PROFILES.iter().find(|p| p.name.eq_ignore_ascii_case(name)).cloned()
+    }
+
+    pub(crate) fn uses(&self, strategy: AnswerStrategy) -> bool {
+        self.answer_strategies.contains(&strategy)
+    }
+
+    pub(crate) fn normalize<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut line = Cow::Borrowed(line);
+        for &(from, to) in self.replacements {
+            if line.contains(from) {
+                line = Cow::Owned(line.replace(from, to));
+            }
+        }
+        for &rule in self.typography.iter() {
+            line = rule.apply(line);
+        }
+        line
+    }
+
+    /// Splits the captured part of an answer line into choice letters, stopping at the first word that is neither a
+    /// letter nor a conjunction so explanations following the letters are ignored.
+    pub(crate) fn answer_letters(&self, spec: &str) -> Vec<String> {
+        spec.split(|c: char| !c.is_alphanumeric())
+            .filter(|token| !token.is_empty())
+            .take_while(|token| token.len() == 1 || self.conjunctions.contains(&token.to_lowercase().as_str()))
+            .filter(|token| token.len() == 1 && token.chars().all(|c| c.is_ascii_alphabetic()))
+            .map(|token| token.to_uppercase())
+            .collect()
+    }
+}
+
+lazy_static! {
+    static ref COMPILED: Mutex<HashMap<Profile, Arc<ParserProfile>>> = Mutex::new(HashMap::new());
+}
+
+/// A profile with its patterns compiled
+#[derive(Debug)]
+pub(crate) struct ParserProfile {
+    pub(crate) profile: Profile,
+    pub(crate) question: Regex,
+    pub(crate) choice: Regex,
+    pub(crate) answer: Regex,
+    pub(crate) feedback: Regex,
+    pub(crate) artifacts: Vec<Regex>,
+    pub(crate) footnote: Regex,
+}
+
+impl ParserProfile {
+    pub(crate) fn compile(profile: Profile) -> Result<Self, regex::Error> {
+        Ok(ParserProfile {
+            question: Regex::new(profile.question_pattern)?,
+            choice: Regex::new(profile.choice_pattern)?,
+            answer: Regex::new(profile.answer_pattern)?,
+            feedback: Regex::new(profile.feedback_pattern)?,
+            artifacts: profile.artifact_patterns.iter().map(|p| Regex::new(p)).collect::<Result<_, _>>()?,
+            // the whitespace in front of a marker goes with it so "term [3] continues" keeps a single space
+            footnote: Regex::new(&format!(r"\s*(?:{})", profile.footnote_pattern))?,
+            profile,
+        })
+    }
+
+    /// The compiled profile, compiling it on first use.
+    pub(crate) fn cached(profile: &Profile) -> Result<Arc<Self>, regex::Error> {
+        let mut compiled = COMPILED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(parser_profile) = compiled.get(profile) {
+            return Ok(Arc::clone(parser_profile));
+        }
+        let parser_profile = Arc::new(ParserProfile::compile(profile.clone())?);
+        compiled.insert(profile.clone(), Arc::clone(&parser_profile));
+        Ok(parser_profile)
+    }
+}
+
+/// Detects the document language from a sample of its text and returns the matching profile, falling back to
+/// English when the language is unsupported or detection is unreliable.
+pub(crate) fn detect(text: &str) -> Profile {
+    let sample: String = text.chars().take(10_000).collect();
+    match whatlang::detect(&sample) {
+        Some(info) if info.is_reliable() => match info.lang() {
+            whatlang::Lang::Deu => GERMAN,
+            whatlang::Lang::Fra => FRENCH,
+            _ => ENGLISH,
+        },
+        _ => ENGLISH,
+    }
+}
@@ -0,0 +1,267 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use crate::timings::Timings;
+
+// Progress reporting of an extraction run. The extraction itself never prints: it reports the page count, read from
+// the PDF before any text is extracted, the pages extracted so far, parsing progress, messages and diagnostics to a
+// `Progress`. `Console` draws a progress bar of the pages with their throughput and the time left, which is mostly
+// text extraction, and prints to the terminal; `Channel` forwards the events of one file of a batch to the dashboard.
+//
+// `Phases` reports a task made of steps run one after the other, the steps of `scaffold`: a spinner with the elapsed
+// time while a step runs, a line with its outcome and duration once it is over, and a summary table at the end.
+
+pub(crate) trait Progress {
+    /// Number of pages of the document, before its text is extracted
+    fn pages(&mut self, count: usize);
+    /// Text of `pages` pages extracted so far
+    fn extracted(&mut self, pages: usize);
+    /// Parsing reached the 0-based `page` with `questions` found so far
+    fn page(&mut self, page: usize, questions: usize);
+    fn info(&mut self, message: String);
+    /// A finding printed as `severity[rule] message`, e.g. `warning[parse] ...`
+    fn diagnostic(&mut self, severity: &'static str, rule: &str, message: String);
+    /// Parsing is complete
+    fn parsed(&mut self, questions: usize);
+    /// The run is over, having spent `timings` in its stages
+    fn timings(&mut self, timings: &Timings);
+}
+
+/// Progress bar and terminal output of a single run
+pub(crate) struct Console {
+    bar: ProgressBar,
+    last_update: Instant,
+}
+
+const UPDATE_FREQUENCY: usize = 5;
+const TIME_UPDATE_FREQUENCY: Duration = Duration::from_millis(500);
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::default_spinner()
+        .template("{spinner:.green} [{elapsed_precise}] {wide_msg}")
+        .expect("valid progress template")
+        .tick_strings(&["-", "\\", "|", "/"])
+}
+
+fn pages_style() -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:30}] {pos}/{len} pages ({per_sec}, ETA {eta}) {wide_msg}")
+        .expect("valid progress template")
+        .tick_strings(&["-", "\\", "|", "/"])
+        .progress_chars("=> ")
+}
+
+impl Console {
+    pub(crate) fn new() -> Self {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(spinner_style());
+        Console {
+            bar,
+            last_update: Instant::now(),
+        }
+    }
+}
+
+impl Progress for Console {
+    fn pages(&mut self, count: usize) {
+        self.bar.set_length(count as u64);
+        self.bar.set_style(pages_style());
+        self.bar.reset_eta();
+        // keeps the elapsed time and the estimate moving while a slow page holds up the position
+        self.bar.enable_steady_tick(TIME_UPDATE_FREQUENCY);
+    }
+
+    fn extracted(&mut self, pages: usize) {
+        self.bar.set_position(pages as u64);
+    }
+
+    fn page(&mut self, page: usize, questions: usize) {
+        if page.is_multiple_of(UPDATE_FREQUENCY) || self.last_update.elapsed() >= TIME_UPDATE_FREQUENCY {
+            self.bar.set_message(format!("parsing page {}, {} questions", page + 1, questions));
+            self.bar.tick();
+            self.last_update = Instant::now();
+        }
+    }
+
+    fn info(&mut self, message: String) {
+        println!("{}", message);
+    }
+
+    fn diagnostic(&mut self, severity: &'static str, rule: &str, message: String) {
+        eprintln!("{}[{}] {}", severity, rule, message);
+    }
+
+    fn parsed(&mut self, questions: usize) {
+        self.bar.finish_with_message(format!("Processing complete: {} questions processed", questions));
+    }
+
+    fn timings(&mut self, timings: &Timings) {
+        println!("Time: {}", timings.summary());
+    }
+}
+
+/// What happened to one file of a batch
+#[derive(Debug)]
+pub(crate) enum Event {
+    Started,
+    Pages(usize),
+    Extracted(usize),
+    /// Questions parsed so far, after a page
+    Page(usize),
+    Info(String),
+    Diagnostic(&'static str, String, String),
+    Parsed(usize),
+    Timings(Timings),
+    Finished(usize),
+    Failed(String),
+}
+
+/// Forwards the progress of the file with the given index to a receiver
+pub(crate) struct Channel {
+    pub(crate) file: usize,
+    pub(crate) sender: Sender<(usize, Event)>,
+}
+
+impl Channel {
+    pub(crate) fn send(&self, event: Event) {
+        // the receiver only goes away when the dashboard was closed, the batch then stops reporting
+        let _ = self.sender.send((self.file, event));
+    }
+}
+
+impl Progress for Channel {
+    fn pages(&mut self, count: usize) {
+        self.send(Event::Pages(count));
+    }
+
+    fn extracted(&mut self, pages: usize) {
+        self.send(Event::Extracted(pages));
+    }
+
+    fn page(&mut self, _page: usize, questions: usize) {
+        self.send(Event::Page(questions));
+    }
+
+    fn info(&mut self, message: String) {
+        self.send(Event::Info(message));
+    }
+
+    fn diagnostic(&mut self, severity: &'static str, rule: &str, message: String) {
+        self.send(Event::Diagnostic(severity, rule.to_string(), message));
+    }
+
+    fn parsed(&mut self, questions: usize) {
+        self.send(Event::Parsed(questions));
+    }
+
+    fn timings(&mut self, timings: &Timings) {
+        self.send(Event::Timings(timings.clone()));
+    }
+}
+
+/// How a step of a `Phases` task ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Done,
+    /// Not run, its work was already there
+    Skipped,
+    Failed,
+}
+
+impl Outcome {
+    fn label(self) -> &'static str {
+        match self {
+            Outcome::Done => "done",
+            Outcome::Skipped => "skip",
+            Outcome::Failed => "FAIL",
+        }
+    }
+}
+
+/// Step being run, with its spinner
+struct Running {
+    name: String,
+    bar: ProgressBar,
+    started: Instant,
+}
+
+/// Spinners and timings of the steps of a task, run one at a time
+pub(crate) struct Phases {
+    /// Draws no spinner, for output that is written while a step runs (`scaffold --verbose`)
+    hidden: bool,
+    running: RefCell<Option<Running>>,
+    finished: RefCell<Vec<(String, Outcome, Option<Duration>)>>,
+}
+
+impl Phases {
+    pub(crate) fn new(hidden: bool) -> Self {
+        Phases {
+            hidden,
+            running: RefCell::new(None),
+            finished: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Starts the spinner of a step, finishing the one before as done if it was still running.
+    pub(crate) fn start(&self, name: &str) {
+        self.finish(Outcome::Done);
+        let bar = if self.hidden { ProgressBar::hidden() } else { ProgressBar::new_spinner() };
+        bar.set_style(spinner_style());
+        bar.set_message(name.to_string());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        *self.running.borrow_mut() = Some(Running {
+            name: name.to_string(),
+            bar,
+            started: Instant::now(),
+        });
+    }
+
+    /// Prints a line above the spinner of the running step.
+    pub(crate) fn println(&self, message: impl Display) {
+        match &*self.running.borrow() {
+            Some(running) => running.bar.suspend(|| println!("{}", message)),
+            None => println!("{}", message),
+        }
+    }
+
+    /// Ends the running step, if any, printing its outcome and how long it took.
+    pub(crate) fn finish(&self, outcome: Outcome) {
+        let Some(running) = self.running.borrow_mut().take() else {
+            return;
+        };
+        running.bar.finish_and_clear();
+        let elapsed = running.started.elapsed();
+        println!("{:<6}{} ({:.1}s)", outcome.label(), running.name, elapsed.as_secs_f64());
+        self.finished.borrow_mut().push((running.name, outcome, Some(elapsed)));
+    }
+
+    /// Records a step that was not run.
+    pub(crate) fn skip(&self, name: &str, reason: &str) {
+        self.finish(Outcome::Done);
+        println!("{:<6}{}, {}", Outcome::Skipped.label(), name, reason);
+        self.finished.borrow_mut().push((name.to_string(), Outcome::Skipped, None));
+    }
+
+    /// Table of the steps with their outcome and duration, and the total time.
+    pub(crate) fn summary(&self) -> String {
+        let finished = self.finished.borrow();
+        let width = finished.iter().map(|(name, ..)| name.len()).max().unwrap_or(0).max("Total".len());
+        let mut table = format!("{:<width$}  {:<6}  {:>8}\n", "Step", "Result", "Time");
+        let mut total = Duration::ZERO;
+        for (name, outcome, elapsed) in finished.iter() {
+            let time = match elapsed {
+                Some(elapsed) => {
+                    total += *elapsed;
+                    format!("{:.1}s", elapsed.as_secs_f64())
+                }
+                None => "-".to_string(),
+            };
+            table.push_str(&format!("{:<width$}  {:<6}  {:>8}\n", name, outcome.label(), time));
+        }
+        table.push_str(&format!("{:<width$}  {:<6}  {:>8}", "Total", "", format!("{:.1}s", total.as_secs_f64())));
+        table
+    }
+}
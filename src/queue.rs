@@ -0,0 +1,340 @@
+use clap::{Args, Subcommand, ValueEnum};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::batch;
+use crate::manifest::{rfc3339, sha256_hex};
+use crate::{ExtractOptions, OutputError};
+
+// Resumable batch runs. With `--queue <file>` a batch records every file of its directory as a job in an SQLite
+// database, with its state (pending, running, done or failed), the number of attempts, the error of the last failed
+// one and the questions saved. Run again with the same queue, the batch only takes the pending jobs: an interrupted
+// run continues where it stopped, the jobs it left running being pending again, and files added to the directory
+// since are queued as well, like files whose SHA-256 changed since their job ran. Failed jobs are otherwise left alone
+// until `jobs retry-failed` puts them back and runs them with the directory and extraction options recorded in the
+// queue by the first run.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum State {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+impl State {
+    fn as_str(self) -> &'static str {
+        match self {
+            State::Pending => "pending",
+            State::Running => "running",
+            State::Done => "done",
+            State::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct JobsArgs {
+    #[command(subcommand)]
+    command: JobsCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum JobsCommand {
+    /// List the jobs of a queue with their state
+    List {
+        /// Queue written by a batch run with --queue
+        queue: String,
+        /// Only list the jobs in this state
+        #[arg(long, value_enum)]
+        state: Option<State>,
+    },
+    /// Put the failed jobs back and run them with the directory and options of the batch
+    RetryFailed {
+        /// Queue written by a batch run with --queue
+        queue: String,
+    },
+}
+
+/// Jobs of a batch, persisted in SQLite
+pub(crate) struct Queue {
+    connection: Connection,
+}
+
+impl Queue {
+    pub(crate) fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS batch (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             CREATE TABLE IF NOT EXISTS jobs (
+                 path TEXT PRIMARY KEY,
+                 state TEXT NOT NULL,
+                 attempts INTEGER NOT NULL DEFAULT 0,
+                 error TEXT,
+                 questions INTEGER,
+                 updated_at TEXT NOT NULL
+             );",
+        )?;
+        // queues written before the hash of the files was recorded
+        let hashed: bool = connection.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('jobs') WHERE name = 'sha256'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !hashed {
+            connection.execute("ALTER TABLE jobs ADD COLUMN sha256 TEXT", [])?;
+        }
+        Ok(Queue { connection })
+    }
+
+    fn existing(path: &str) -> Result<Self, Box<dyn Error>> {
+        if !Path::new(path).is_file() {
+            return Err(format!("No job queue at {}", path).into());
+        }
+        Queue::open(path)
+    }
+
+    /// Queues the files of a batch not queued yet or changed since, puts the jobs an interrupted run left running
+    /// back, and returns the files left to extract in the order given. The first run records the directory and the
+    /// options.
+    pub(crate) fn prepare(
+        &self,
+        options: &ExtractOptions,
+        dir: &Path,
+        files: &[PathBuf],
+    ) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let now = rfc3339(SystemTime::now());
+        self.connection.execute(
+            "INSERT OR IGNORE INTO batch (key, value) VALUES ('dir', ?1), ('options', ?2)",
+            params![dir.to_string_lossy(), toml::to_string(options)?],
+        )?;
+        self.connection.execute("UPDATE jobs SET state = 'pending', updated_at = ?1 WHERE state = 'running'", [&now])?;
+        let mut pending = Vec::new();
+        for file in files {
+            let path = file.to_string_lossy();
+            let sha256 = sha256_hex(&fs::read(file)?);
+            self.connection.execute(
+                "INSERT OR IGNORE INTO jobs (path, state, sha256, updated_at) VALUES (?1, 'pending', ?2, ?3)",
+                params![path, sha256, now],
+            )?;
+            let (state, seen): (String, Option<String>) = self.connection.query_row(
+                "SELECT state, sha256 FROM jobs WHERE path = ?1",
+                [&path],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            // a file replaced by another version is a new job; one queued before hashes were kept only gets its hash
+            let state = if seen.as_ref().is_some_and(|seen| *seen != sha256) {
+                self.connection.execute(
+                    "UPDATE jobs SET state = 'pending', attempts = 0, error = NULL, questions = NULL, sha256 = ?2,
+                     updated_at = ?3 WHERE path = ?1",
+                    params![path, sha256, now],
+                )?;
+                State::Pending.as_str().to_string()
+            } else {
+                if seen.is_none() {
+                    self.connection.execute("UPDATE jobs SET sha256 = ?2 WHERE path = ?1", params![path, sha256])?;
+                }
+                state
+            };
+            if state == State::Pending.as_str() {
+                pending.push(file.clone());
+            }
+        }
+        Ok(pending)
+    }
+
+    pub(crate) fn start(&self, file: &Path) -> Result<(), Box<dyn Error>> {
+        self.connection.execute(
+            "UPDATE jobs SET state = 'running', attempts = attempts + 1, updated_at = ?2 WHERE path = ?1",
+            params![file.to_string_lossy(), rfc3339(SystemTime::now())],
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn finish(&self, file: &Path, result: &Result<usize, String>) -> Result<(), Box<dyn Error>> {
+        let (state, questions, error) = match result {
+            Ok(questions) => (State::Done, Some(*questions as i64), None),
+            Err(message) => (State::Failed, None, Some(message.as_str())),
+        };
+        self.connection.execute(
+            "UPDATE jobs SET state = ?2, questions = ?3, error = ?4, updated_at = ?5 WHERE path = ?1",
+            params![file.to_string_lossy(), state.as_str(), questions, error, rfc3339(SystemTime::now())],
+        )?;
+        Ok(())
+    }
+
+    /// Number of jobs in each state, in the order of `State`.
+    fn counts(&self) -> Result<Vec<(&'static str, usize)>, Box<dyn Error>> {
+        let mut counts = Vec::new();
+        for state in [State::Pending, State::Running, State::Done, State::Failed] {
+            let count: i64 =
+                self.connection.query_row("SELECT COUNT(*) FROM jobs WHERE state = ?1", [state.as_str()], |row| {
+                    row.get(0)
+                })?;
+            counts.push((state.as_str(), count as usize));
+        }
+        Ok(counts)
+    }
+
+    /// Puts the failed jobs back, returning how many.
+    fn retry_failed(&self) -> Result<usize, Box<dyn Error>> {
+        let retried = self.connection.execute(
+            "UPDATE jobs SET state = 'pending', updated_at = ?1 WHERE state = 'failed'",
+            [rfc3339(SystemTime::now())],
+        )?;
+        Ok(retried)
+    }
+
+    fn recorded(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let value = self
+            .connection
+            .query_row("SELECT value FROM batch WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?;
+        Ok(value)
+    }
+}
+
+fn list(queue: &Queue, state: Option<State>) -> Result<(), Box<dyn Error>> {
+    let mut statement = queue.connection.prepare(
+        "SELECT path, state, attempts, error, questions, updated_at FROM jobs
+         WHERE ?1 IS NULL OR state = ?1 ORDER BY path",
+    )?;
+    let rows = statement.query_map([state.map(State::as_str)], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<i64>>(4)?,
+            row.get::<_, String>(5)?,
+        ))
+    })?;
+    for row in rows {
+        let (path, state, attempts, error, questions, updated_at) = row?;
+        let detail = match (questions, error) {
+            (Some(questions), _) => format!(", {} question(s)", questions),
+            (None, Some(error)) => format!(": {}", error),
+            (None, None) => String::new(),
+        };
+        println!("{:<8} {}  ({} attempt(s), {}){}", state, path, attempts, updated_at, detail);
+    }
+    Ok(())
+}
+
+fn summary(queue: &Queue) -> Result<String, Box<dyn Error>> {
+    let counts: Vec<String> = queue.counts()?.iter().map(|(state, count)| format!("{} {}", count, state)).collect();
+    Ok(counts.join(", "))
+}
+
+pub(crate) fn run(args: JobsArgs) -> Result<(), Box<dyn Error>> {
+    match args.command {
+        JobsCommand::List { queue: path, state } => {
+            let queue = Queue::existing(&path)?;
+            list(&queue, state)?;
+            println!("{}", summary(&queue)?);
+            Ok(())
+        }
+        JobsCommand::RetryFailed { queue: path } => {
+            let queue = Queue::existing(&path)?;
+            let (Some(dir), Some(options)) = (queue.recorded("dir")?, queue.recorded("options")?) else {
+                return Err(OutputError::from("The queue records no batch to retry").into());
+            };
+            let retried = queue.retry_failed()?;
+            if retried == 0 {
+                println!("No failed jobs in {}", path);
+                return Ok(());
+            }
+            println!("Retrying {} failed job(s) of {}", retried, dir);
+            let mut options: ExtractOptions = toml::from_str(&options)?;
+            options.queue = Some(path);
+            drop(queue);
+            batch::run(&options, Path::new(&dir))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three PDFs in a directory of their own, and an empty queue in memory
+    fn setup(name: &str) -> (PathBuf, Vec<PathBuf>, Queue) {
+        let dir = std::env::temp_dir().join(format!("s4wm-queue-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let files: Vec<PathBuf> = ["a.pdf", "b.pdf", "c.pdf"].iter().map(|name| dir.join(name)).collect();
+        for file in &files {
+            fs::write(file, format!("%PDF-1.5 {}", file.display())).unwrap();
+        }
+        (dir, files, Queue::open(":memory:").unwrap())
+    }
+
+    fn state(queue: &Queue, file: &Path) -> (String, i64) {
+        queue
+            .connection
+            .query_row("SELECT state, attempts FROM jobs WHERE path = ?1", [file.to_string_lossy()], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap()
+    }
+
+    /// Runs the pending jobs, failing the ones named
+    fn run_jobs(queue: &Queue, pending: &[PathBuf], failing: &[&PathBuf]) {
+        for file in pending {
+            queue.start(file).unwrap();
+            let result = if failing.contains(&file) { Err("no questions found".to_string()) } else { Ok(12) };
+            queue.finish(file, &result).unwrap();
+        }
+    }
+
+    #[test]
+    fn done_jobs_are_skipped_and_failed_ones_wait_for_a_retry() {
+        let (dir, files, queue) = setup("states");
+        let options = ExtractOptions::default();
+        let pending = queue.prepare(&options, &dir, &files).unwrap();
+        assert_eq!(pending, files);
+        run_jobs(&queue, &pending, &[&files[1]]);
+
+        let again = queue.prepare(&options, &dir, &files).unwrap();
+        assert!(again.is_empty());
+        assert_eq!(state(&queue, &files[0]), ("done".to_string(), 1));
+        assert_eq!(state(&queue, &files[1]), ("failed".to_string(), 1));
+
+        assert_eq!(queue.retry_failed().unwrap(), 1);
+        let retried = queue.prepare(&options, &dir, &files).unwrap();
+        run_jobs(&queue, &retried, &[]);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(retried, [files[1].clone()]);
+        assert_eq!(state(&queue, &files[1]), ("done".to_string(), 2));
+        assert_eq!(summary(&queue).unwrap(), "0 pending, 0 running, 3 done, 0 failed");
+    }
+
+    #[test]
+    fn interrupted_jobs_run_again() {
+        let (dir, files, queue) = setup("interrupted");
+        let options = ExtractOptions::default();
+        let pending = queue.prepare(&options, &dir, &files).unwrap();
+        queue.start(&pending[0]).unwrap();
+        let again = queue.prepare(&options, &dir, &files).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(again, files);
+        assert_eq!(state(&queue, &files[0]), ("pending".to_string(), 1));
+    }
+
+    #[test]
+    fn changed_files_are_queued_again() {
+        let (dir, files, queue) = setup("changed");
+        let options = ExtractOptions::default();
+        let pending = queue.prepare(&options, &dir, &files).unwrap();
+        run_jobs(&queue, &pending, &[&files[2]]);
+        fs::write(&files[0], "%PDF-1.5 second version").unwrap();
+        fs::write(&files[2], "%PDF-1.5 repaired").unwrap();
+        let again = queue.prepare(&options, &dir, &files).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(again, [files[0].clone(), files[2].clone()]);
+        assert_eq!(state(&queue, &files[0]), ("pending".to_string(), 0));
+        assert_eq!(state(&queue, &files[1]), ("done".to_string(), 1));
+    }
+}
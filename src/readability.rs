@@ -0,0 +1,166 @@
+use serde::Serialize;
+
+use crate::text::is_cjk;
+use crate::Question;
+
+// Length and readability of stems. Absurdly long stems with run-on sentences are a strong sign of a junk question,
+// typically one machine-translated by the dump site, so `stats` summarizes them and `filter` leaves them out. The
+// reading ease is Flesch's formula for English, with syllables counted as groups of vowels: rough, but enough to set
+// the outliers apart. Higher is easier, plain English scores 60 to 70. CJK scripts do not separate words with spaces,
+// so every ideograph or kana counts as a word. A full stop only ends a sentence before a space and a word that does not
+// start in lowercase, and not after initials, so abbreviations like "e.g." and numbers like "10.0.0.1" stay inside
+// their sentence.
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Readability {
+    pub(crate) words: usize,
+    pub(crate) sentences: usize,
+    /// Flesch reading ease, higher is easier
+    pub(crate) reading_ease: f64,
+}
+
+/// Syllables of a word: its groups of vowels, without a silent final e, at least one.
+fn syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in word.chars() {
+        let vowel = matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y' | 'ä' | 'ö' | 'ü' | 'é' | 'è' | 'à');
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    if word.ends_with('e') && !word.ends_with("le") && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+/// Words of a text, each CJK character being a word of its own.
+fn words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for mut token in text.split_whitespace() {
+        while let Some((i, c)) = token.char_indices().find(|&(_, c)| is_cjk(c)) {
+            words.push(&token[..i]);
+            if c.is_alphabetic() {
+                words.push(&token[i..i + c.len_utf8()]);
+            }
+            token = &token[i + c.len_utf8()..];
+        }
+        words.push(token);
+    }
+    words
+        .into_iter()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|word| word.chars().any(char::is_alphabetic))
+        .collect()
+}
+
+/// Whether the text ends in an abbreviation of single letters such as "e.g" or "i.e".
+fn ends_in_initials(text: &str) -> bool {
+    let word = text.rsplit(char::is_whitespace).next().unwrap_or_default();
+    let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+    word.contains('.') && word.split('.').all(|part| part.chars().count() == 1 && part.chars().all(char::is_alphabetic))
+}
+
+/// Sentences of a text; a full stop inside a word, after initials or before a lowercase word does not end one.
+fn sentences(text: &str) -> usize {
+    let mut count = 0;
+    let mut has_words = false;
+    for (i, c) in text.char_indices() {
+        let end = match c {
+            '?' | '!' | ';' | '。' | '？' | '！' | '；' => true,
+            '.' => {
+                let rest = &text[i + 1..];
+                let next = rest.trim_start().chars().next();
+                (rest.is_empty() || rest.starts_with(char::is_whitespace))
+                    && !next.is_some_and(char::is_lowercase)
+                    && !ends_in_initials(&text[..i])
+            }
+            _ => {
+                has_words |= c.is_alphabetic();
+                false
+            }
+        };
+        if end && has_words {
+            count += 1;
+            has_words = false;
+        }
+    }
+    count + usize::from(has_words)
+}
+
+pub(crate) fn measure(text: &str) -> Readability {
+    let words = words(text);
+    let sentences = sentences(text).max(1);
+    if words.is_empty() {
+        return Readability {
+            words: 0,
+            sentences: 0,
+            reading_ease: 0.0,
+        };
+    }
+    let syllables: usize = words.iter().map(|word| syllables(word)).sum();
+    let (words_count, sentences_count) = (words.len() as f64, sentences as f64);
+    Readability {
+        words: words.len(),
+        sentences,
+        reading_ease: 206.835 - 1.015 * (words_count / sentences_count) - 84.6 * (syllables as f64 / words_count),
+    }
+}
+
+pub(crate) fn stem(question: &Question) -> Readability {
+    measure(&question.text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_words_and_sentences() {
+        let stem = measure("Which bin holds bulk stock? Pick one.");
+        assert_eq!((stem.words, stem.sentences), (7, 2));
+        let stem = measure("Where is a warehouse task created; in the delivery!");
+        assert_eq!((stem.words, stem.sentences), (9, 2));
+        assert_eq!(
+            measure("1. 2. 3."),
+            Readability {
+                words: 0,
+                sentences: 0,
+                reading_ease: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn abbreviations_and_numbers_stay_in_their_sentence() {
+        let stem =
+            measure("Which node, e.g. the one at 10.0.0.1, runs version 9.2 of the RF framework (i.e. SAPConsole)?");
+        assert_eq!((stem.words, stem.sentences), (14, 1));
+        assert_eq!(measure("Stock is put away. Then it is counted.").sentences, 2);
+        assert_eq!(measure("Which storage types exist in the U.S. and the EU.").sentences, 1);
+    }
+
+    #[test]
+    fn cjk_characters_are_words() {
+        let stem = measure("倉庫タスクを作成するのはどれですか。正しいものを選択してください。");
+        assert_eq!((stem.words, stem.sentences), (31, 2));
+        assert_eq!(measure("SAP EWMで倉庫タスク？").words, 8);
+        assert_eq!(measure("哪个仓库流程是正确的？").words, 10);
+    }
+
+    #[test]
+    fn longer_words_and_sentences_read_harder() {
+        let plain = measure("The bin is full. Move the stock.");
+        let dense = measure(
+            "Determine the appropriate consolidation methodology considering organizational warehouse configuration \
+             requirements and simultaneously evaluate replenishment prioritization alternatives",
+        );
+        assert!(plain.reading_ease > 90.0);
+        assert!(dense.reading_ease < 0.0);
+        assert_eq!(syllables("table"), 2);
+        assert_eq!(syllables("store"), 1);
+    }
+}
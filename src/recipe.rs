@@ -0,0 +1,339 @@
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::OutputError;
+
+// The scaffold manifest: the packages, generated files and init steps of every `scaffold` template, read from TOML
+// so that adding a tool or a framework does not mean editing Rust code. The default manifest and the files it names
+// are compiled into the binary; `--manifest` merges a user manifest over it. `Recipe` is what one template resolves
+// to once the shared part is added, the `full_stack` parts with `--full-stack` and the `workspace` one with
+// `--workspace`.
+//
+// The `pins` table fixes the exact version installed of a package, whatever the range the part asks for, so two
+// scaffolds with the same manifest install the same packages. `scaffold --update-pins` installs the ranges and
+// records what they resolved to with `write_pins`.
+
+pub(crate) const DEFAULT_MANIFEST: &str = include_str!("assets/scaffold/manifest.toml");
+/// Newest manifest format this version reads
+const MANIFEST_VERSION: u32 = 1;
+
+/// Files a manifest can name with `template`
+const TEMPLATES: &[(&str, &str)] = &[
+    ("shared/prettierrc.json", include_str!("assets/scaffold/shared/prettierrc.json")),
+    ("shared/prettierignore", include_str!("assets/scaffold/shared/prettierignore")),
+    ("shared/tailwind.config.js", include_str!("assets/scaffold/shared/tailwind.config.js")),
+    ("shared/postcss.config.js", include_str!("assets/scaffold/shared/postcss.config.js")),
+    ("shared/tailwind.css", include_str!("assets/scaffold/shared/tailwind.css")),
+    ("shared/questions.ts", include_str!("assets/scaffold/shared/questions.ts")),
+    ("react/eslint.config.js", include_str!("assets/scaffold/react/eslint.config.js")),
+    ("react/tsconfig.json", include_str!("assets/scaffold/react/tsconfig.json")),
+    ("react/tsconfig.app.json", include_str!("assets/scaffold/react/tsconfig.app.json")),
+    ("react/vite.config.ts", include_str!("assets/scaffold/react/vite.config.ts")),
+    ("react/useQuestions.ts", include_str!("assets/scaffold/react/useQuestions.ts")),
+    ("react/QuizPage.tsx", include_str!("assets/scaffold/react/QuizPage.tsx")),
+    ("react/App.tsx", include_str!("assets/scaffold/react/App.tsx")),
+    ("vue/eslint.config.js", include_str!("assets/scaffold/vue/eslint.config.js")),
+    ("vue/useQuestions.ts", include_str!("assets/scaffold/vue/useQuestions.ts")),
+    ("vue/QuizPage.vue", include_str!("assets/scaffold/vue/QuizPage.vue")),
+    ("vue/App.vue", include_str!("assets/scaffold/vue/App.vue")),
+    ("svelte/eslint.config.js", include_str!("assets/scaffold/svelte/eslint.config.js")),
+    ("svelte/QuizPage.svelte", include_str!("assets/scaffold/svelte/QuizPage.svelte")),
+    ("svelte/App.svelte", include_str!("assets/scaffold/svelte/App.svelte")),
+    ("solid/eslint.config.js", include_str!("assets/scaffold/solid/eslint.config.js")),
+    ("solid/QuizPage.tsx", include_str!("assets/scaffold/solid/QuizPage.tsx")),
+    ("solid/App.tsx", include_str!("assets/scaffold/solid/App.tsx")),
+    ("react/vite.config.full-stack.ts", include_str!("assets/scaffold/react/vite.config.full-stack.ts")),
+    ("vue/vite.config.full-stack.ts", include_str!("assets/scaffold/vue/vite.config.full-stack.ts")),
+    ("svelte/vite.config.full-stack.ts", include_str!("assets/scaffold/svelte/vite.config.full-stack.ts")),
+    ("solid/vite.config.full-stack.ts", include_str!("assets/scaffold/solid/vite.config.full-stack.ts")),
+    ("full-stack/api.Cargo.toml", include_str!("assets/scaffold/full-stack/api.Cargo.toml")),
+    ("full-stack/api.main.rs", include_str!("assets/scaffold/full-stack/api.main.rs")),
+    ("full-stack/api.Dockerfile", include_str!("assets/scaffold/full-stack/api.Dockerfile")),
+    ("full-stack/web.Dockerfile", include_str!("assets/scaffold/full-stack/web.Dockerfile")),
+    ("full-stack/dockerignore", include_str!("assets/scaffold/full-stack/dockerignore")),
+    ("full-stack/docker-compose.yml", include_str!("assets/scaffold/full-stack/docker-compose.yml")),
+    ("full-stack/questions.ts", include_str!("assets/scaffold/full-stack/questions.ts")),
+    ("tauri/src-tauri.Cargo.toml", include_str!("assets/scaffold/tauri/src-tauri.Cargo.toml")),
+    ("tauri/tauri.conf.json", include_str!("assets/scaffold/tauri/tauri.conf.json")),
+    ("tauri/capabilities.json", include_str!("assets/scaffold/tauri/capabilities.json")),
+    ("tauri/main.rs", include_str!("assets/scaffold/tauri/main.rs")),
+    ("tauri/lib.rs", include_str!("assets/scaffold/tauri/lib.rs")),
+    ("tauri/questions.ts", include_str!("assets/scaffold/tauri/questions.ts")),
+    ("tauri/App.tsx", include_str!("assets/scaffold/tauri/App.tsx")),
+    ("workspace/questions.ts", include_str!("assets/scaffold/workspace/questions.ts")),
+    ("offline/index-tsx.html", include_str!("assets/scaffold/offline/index-tsx.html")),
+    ("offline/index-ts.html", include_str!("assets/scaffold/offline/index-ts.html")),
+    ("offline/vite-env.d.ts", include_str!("assets/scaffold/offline/vite-env.d.ts")),
+    ("offline/tsconfig.node.json", include_str!("assets/scaffold/offline/tsconfig.node.json")),
+    ("react/main.tsx", include_str!("assets/scaffold/react/main.tsx")),
+    ("react/App.starter.tsx", include_str!("assets/scaffold/react/App.starter.tsx")),
+    ("vue/main.ts", include_str!("assets/scaffold/vue/main.ts")),
+    ("vue/App.starter.vue", include_str!("assets/scaffold/vue/App.starter.vue")),
+    ("vue/vite.config.ts", include_str!("assets/scaffold/vue/vite.config.ts")),
+    ("vue/tsconfig.json", include_str!("assets/scaffold/vue/tsconfig.json")),
+    ("svelte/main.ts", include_str!("assets/scaffold/svelte/main.ts")),
+    ("svelte/App.starter.svelte", include_str!("assets/scaffold/svelte/App.starter.svelte")),
+    ("svelte/vite.config.ts", include_str!("assets/scaffold/svelte/vite.config.ts")),
+    ("svelte/svelte.config.js", include_str!("assets/scaffold/svelte/svelte.config.js")),
+    ("svelte/tsconfig.json", include_str!("assets/scaffold/svelte/tsconfig.json")),
+    ("solid/main.tsx", include_str!("assets/scaffold/solid/main.tsx")),
+    ("solid/App.starter.tsx", include_str!("assets/scaffold/solid/App.starter.tsx")),
+    ("solid/vite.config.ts", include_str!("assets/scaffold/solid/vite.config.ts")),
+    ("solid/tsconfig.json", include_str!("assets/scaffold/solid/tsconfig.json")),
+];
+
+/// A file written into the project
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct FileSpec {
+    /// Path relative to the project
+    pub(crate) path: String,
+    /// Name of a file built into the binary
+    template: Option<String>,
+    /// File on disk, relative to the manifest naming it
+    source: Option<PathBuf>,
+    contents: Option<String>,
+}
+
+impl FileSpec {
+    pub(crate) fn contents(&self) -> Result<Cow<'static, str>, OutputError> {
+        match (&self.template, &self.source, &self.contents) {
+            (Some(name), None, None) => TEMPLATES
+                .iter()
+                .find(|(template, _)| *template == name.as_str())
+                .map(|(_, contents)| Cow::Borrowed(*contents))
+                .ok_or_else(|| OutputError {
+                    message: format!("{}: no built-in template {}", self.path, name),
+                }),
+            (None, Some(source), None) => Ok(Cow::Owned(fs::read_to_string(source).map_err(|e| OutputError {
+                message: format!("{}: cannot read {}: {}", self.path, source.display(), e),
+            })?)),
+            (None, None, Some(contents)) => Ok(Cow::Owned(contents.clone())),
+            _ => Err(OutputError {
+                message: format!("{}: give exactly one of template, source and contents", self.path),
+            }),
+        }
+    }
+}
+
+/// A command run once the dependencies are installed
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct InitStep {
+    pub(crate) name: String,
+    /// Binary of the project, run with the package manager's exec
+    pub(crate) exec: Option<String>,
+    /// Package downloaded and run with the package manager's dlx
+    pub(crate) dlx: Option<String>,
+    #[serde(default)]
+    pub(crate) args: Vec<String>,
+    /// Arguments added with `--non-interactive`
+    #[serde(default)]
+    pub(crate) non_interactive_args: Vec<String>,
+    /// Files written once the step ran, over the ones it generated
+    #[serde(default)]
+    pub(crate) files: Vec<FileSpec>,
+}
+
+/// Packages, files and init steps, of every template (`shared`) or of one
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct Part {
+    pub(crate) dependencies: Vec<String>,
+    pub(crate) dev_dependencies: Vec<String>,
+    pub(crate) files: Vec<FileSpec>,
+    /// Files written with `--with-questions` only
+    pub(crate) quiz_files: Vec<FileSpec>,
+    pub(crate) init: Vec<InitStep>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct TemplateSpec {
+    /// Template of create-vite the project starts from
+    pub(crate) vite_template: String,
+    /// Added with `--full-stack`
+    pub(crate) full_stack: Option<Part>,
+    /// Path in the project the running S4WM-extract binary is copied to, for apps embedding the extraction
+    pub(crate) extractor: Option<String>,
+    /// Vite project written with `--offline` instead of running create-vite
+    pub(crate) offline: Option<Part>,
+    #[serde(flatten)]
+    pub(crate) part: Part,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct ScaffoldManifest {
+    version: u32,
+    shared: Option<Part>,
+    /// Added to every template with `--full-stack`
+    full_stack: Option<Part>,
+    /// Added to every template with `--workspace`
+    workspace: Option<Part>,
+    /// Exact version of each package, by name
+    #[serde(default)]
+    pins: BTreeMap<String, String>,
+    #[serde(default)]
+    templates: BTreeMap<String, TemplateSpec>,
+}
+
+/// Everything the scaffold of one template installs, writes and runs
+#[derive(Debug, Clone)]
+pub(crate) struct Recipe {
+    pub(crate) template: String,
+    pub(crate) vite_template: String,
+    pub(crate) extractor: Option<String>,
+    pub(crate) offline: Option<Part>,
+    pub(crate) pins: BTreeMap<String, String>,
+    /// Shared part first, then the template's, then the full-stack ones and the workspace one
+    pub(crate) part: Part,
+}
+
+fn parse(text: &str, origin: &str) -> Result<ScaffoldManifest, OutputError> {
+    let manifest: ScaffoldManifest = toml::from_str(text).map_err(|e| OutputError {
+        message: format!("Invalid scaffold manifest {}: {}", origin, e),
+    })?;
+    if manifest.version > MANIFEST_VERSION {
+        return Err(OutputError {
+            message: format!(
+                "Scaffold manifest {} has version {}, this version of S4WM-extract reads up to {}",
+                origin, manifest.version, MANIFEST_VERSION
+            ),
+        });
+    }
+    Ok(manifest)
+}
+
+/// Makes the `source` paths of a user manifest relative to its directory.
+fn resolve_sources(part: &mut Part, dir: &Path) {
+    let init_files = part.init.iter_mut().flat_map(|step| step.files.iter_mut());
+    for file in part.files.iter_mut().chain(part.quiz_files.iter_mut()).chain(init_files) {
+        if let Some(source) = &mut file.source {
+            *source = dir.join(&*source);
+        }
+    }
+}
+
+impl ScaffoldManifest {
+    /// The default manifest, with the one at `path` merged over it when given.
+    pub(crate) fn load(path: Option<&str>) -> Result<ScaffoldManifest, OutputError> {
+        let mut manifest = parse(DEFAULT_MANIFEST, "(built-in)")?;
+        let Some(path) = path else {
+            return Ok(manifest);
+        };
+        let mut user = parse(&fs::read_to_string(path)?, path)?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        for part in [&mut user.shared, &mut user.full_stack, &mut user.workspace].into_iter().flatten() {
+            resolve_sources(part, dir);
+        }
+        for spec in user.templates.values_mut() {
+            resolve_sources(&mut spec.part, dir);
+            for part in [&mut spec.full_stack, &mut spec.offline].into_iter().flatten() {
+                resolve_sources(part, dir);
+            }
+        }
+        if user.shared.is_some() {
+            manifest.shared = user.shared;
+        }
+        if user.full_stack.is_some() {
+            manifest.full_stack = user.full_stack;
+        }
+        if user.workspace.is_some() {
+            manifest.workspace = user.workspace;
+        }
+        manifest.pins.extend(user.pins);
+        manifest.templates.extend(user.templates);
+        Ok(manifest)
+    }
+
+    pub(crate) fn recipe(&self, template: &str, full_stack: bool, workspace: bool) -> Result<Recipe, OutputError> {
+        let spec = self.templates.get(template).ok_or_else(|| OutputError {
+            message: format!(
+                "Unknown template {}, the manifest has {}",
+                template,
+                self.templates.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        })?;
+        let mut parts = vec![self.shared.clone().unwrap_or_default(), spec.part.clone()];
+        if full_stack {
+            let Some(own) = &spec.full_stack else {
+                return Err(OutputError {
+                    message: format!("Template {} has no full_stack section for --full-stack", template),
+                });
+            };
+            parts.extend([self.full_stack.clone().unwrap_or_default(), own.clone()]);
+        }
+        if workspace {
+            parts.push(self.workspace.clone().unwrap_or_default());
+        }
+        let mut part = Part::default();
+        for next in parts {
+            part.dependencies.extend(next.dependencies);
+            part.dev_dependencies.extend(next.dev_dependencies);
+            part.files.extend(next.files);
+            part.quiz_files.extend(next.quiz_files);
+            part.init.extend(next.init);
+        }
+        Ok(Recipe {
+            template: template.to_string(),
+            vite_template: spec.vite_template.clone(),
+            extractor: spec.extractor.clone(),
+            offline: spec.offline.clone(),
+            pins: self.pins.clone(),
+            part,
+        })
+    }
+}
+
+impl Recipe {
+    /// The package to install for a dependency of the manifest: the pinned version when there is one.
+    pub(crate) fn pinned(&self, spec: &str) -> String {
+        let name = package_name(spec);
+        match self.pins.get(name) {
+            Some(version) => format!("{}@{}", name, version),
+            None => spec.to_string(),
+        }
+    }
+
+    /// Name and version range of a dependency as package.json lists them, the pinned version first.
+    pub(crate) fn requirement<'a>(&'a self, spec: &'a str) -> (&'a str, &'a str) {
+        let name = package_name(spec);
+        let range = match self.pins.get(name) {
+            Some(version) => version.as_str(),
+            None => spec.get(name.len() + 1..).unwrap_or("latest"),
+        };
+        (name, range)
+    }
+}
+
+/// Name of the package of a dependency such as `tailwindcss@3` or `@types/node@22`.
+pub(crate) fn package_name(spec: &str) -> &str {
+    match spec.char_indices().skip(1).find(|&(_, c)| c == '@') {
+        Some((at, _)) => &spec[..at],
+        None => spec,
+    }
+}
+
+/// Replaces the `[pins]` table of a manifest file, keeping the rest of the file as written.
+pub(crate) fn write_pins(path: &str, pins: &BTreeMap<String, String>) -> Result<(), OutputError> {
+    let text = fs::read_to_string(path)?;
+    let mut kept = Vec::new();
+    let mut in_pins = false;
+    for line in text.lines() {
+        let header = line.trim_start().starts_with('[');
+        if header {
+            in_pins = line.trim() == "[pins]";
+        }
+        if !in_pins {
+            kept.push(line);
+        }
+    }
+    let table: toml::Table = pins.iter().map(|(name, version)| (name.clone(), version.clone().into())).collect();
+    let table = toml::to_string(&table).map_err(|e| OutputError {
+        message: format!("Cannot write the pins: {}", e),
+    })?;
+    let mut text = kept.join("\n").trim_end().to_string();
+    text.push_str(&format!("\n\n[pins]\n{}", table));
+    fs::write(path, text)?;
+    Ok(())
+}
@@ -0,0 +1,287 @@
+use clap::Args;
+use regex::Regex;
+use serde_json::Value;
+use std::error::Error;
+use std::fs;
+
+use crate::crypt::Crypto;
+use crate::{open_bank, save_to_json, Question};
+
+// Redaction of a bank before it is shared outside the team. Dump sites leave their URLs, contact addresses and
+// advertising ("Get the latest dumps at ...") in the text, and the extraction keeps them. `redact` replaces them in
+// the stems, choices, choice feedback, statements, premises, notes, parse traces, emphasized text and the text of the
+// extra fields, and in the bank title, with built-in patterns for URLs and bare domains, email addresses and the
+// watermark phrases of dump sites, plus the regular expressions given with `--pattern` or in a `--patterns-from`
+// file. Only the blanks around a dropped match are tidied, the rest of the text keeps its line breaks and spacing. It
+// writes a new bank and counts the replacements of every pattern.
+
+/// Built-in patterns with what they are replaced by; watermarks are dropped. A lone "dump" is left alone, ABAP short
+/// dumps are part of the subject.
+const BUILTIN: &[(&str, &str, &str)] = &[
+    ("email", r"(?i)\b[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}\b", "[email]"),
+    (
+        "url",
+        concat!(
+            r"(?i)\b(?:https?://|www\.)[^\s<>()\[\]]+",
+            r"|\b[a-z0-9-]+(?:\.[a-z0-9-]+)*\.(?:com|net|org|info|biz|io)\b(?:/[^\s<>()\[\]]*)?"
+        ),
+        "[url]",
+    ),
+    (
+        "watermark",
+        concat!(
+            r"(?i)\b(?:get|download|visit|try)\b[^.\n]{0,60}?\b(?:braindumps?|exam\s+dumps|vce)\b[^.\n]{0,40}",
+            r"|\b(?:100%\s+)?(?:valid|real|actual|latest)\s+(?:exam\s+)?(?:dumps|questions\s+and\s+answers)\b",
+            r"|\b(?:exam\s+)?braindumps?\b|\bexam\s+dumps?\b"
+        ),
+        "",
+    ),
+];
+
+#[derive(Args, Debug)]
+pub struct RedactArgs {
+    /// Question bank to redact
+    input: String,
+    /// Where to write the redacted bank
+    output: String,
+    /// Regular expression to redact as well, may be repeated
+    #[arg(long = "pattern", value_name = "REGEX")]
+    patterns: Vec<String>,
+    /// File of regular expressions to redact, one per line (lines starting with # are comments)
+    #[arg(long, value_name = "FILE")]
+    patterns_from: Option<String>,
+    /// Text replacing the matches of the user patterns
+    #[arg(long, default_value = "[redacted]")]
+    replacement: String,
+    /// Only apply the user patterns
+    #[arg(long)]
+    no_builtin: bool,
+}
+
+struct Pattern {
+    name: String,
+    regex: Regex,
+    replacement: String,
+    count: usize,
+}
+
+/// Blanks a dropped match may leave behind on a line
+const BLANKS: [char; 2] = [' ', '\t'];
+
+impl Pattern {
+    /// Replaces the matches in `text`. A dropped match takes the blanks on one side along, so it leaves neither a
+    /// double blank nor a blank at the end of a line or before punctuation.
+    fn apply(&mut self, text: &mut String) {
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+        let mut matches = 0;
+        for caps in self.regex.captures_iter(text) {
+            let Some(found) = caps.get(0) else {
+                continue;
+            };
+            matches += 1;
+            redacted.push_str(text.get(last..found.start()).unwrap_or_default());
+            last = last.max(found.end());
+            caps.expand(&self.replacement, &mut redacted);
+            if !self.replacement.is_empty() {
+                continue;
+            }
+            let after = &text[last..];
+            let blanks_after = after.len() - after.trim_start_matches(BLANKS).len();
+            let next = after[blanks_after..].chars().next();
+            let kept = redacted.trim_end_matches(BLANKS).len();
+            let line_start = kept == 0 || redacted[..kept].ends_with('\n');
+            if line_start || kept < redacted.len() {
+                last += blanks_after;
+            }
+            if next.is_none_or(|c| c == '\n' || c.is_ascii_punctuation()) {
+                redacted.truncate(kept);
+            }
+        }
+        if matches == 0 {
+            return;
+        }
+        self.count += matches;
+        redacted.push_str(&text[last..]);
+        *text = redacted;
+    }
+
+    /// Replaces the matches in every string of a JSON value.
+    fn apply_value(&mut self, value: &mut Value) {
+        match value {
+            Value::String(text) => self.apply(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.apply_value(item)),
+            Value::Object(fields) => fields.values_mut().for_each(|field| self.apply_value(field)),
+            _ => {}
+        }
+    }
+}
+
+fn patterns(args: &RedactArgs) -> Result<Vec<Pattern>, Box<dyn Error>> {
+    let mut patterns = Vec::new();
+    if !args.no_builtin {
+        for (name, regex, replacement) in BUILTIN {
+            patterns.push(Pattern {
+                name: name.to_string(),
+                regex: Regex::new(regex)?,
+                replacement: replacement.to_string(),
+                count: 0,
+            });
+        }
+    }
+    let mut user = args.patterns.clone();
+    if let Some(path) = &args.patterns_from {
+        let file = fs::read_to_string(path)?;
+        let lines = file.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        user.extend(lines.map(str::to_string));
+    }
+    for regex in user {
+        patterns.push(Pattern {
+            regex: Regex::new(&regex)?,
+            name: regex,
+            replacement: args.replacement.clone(),
+            count: 0,
+        });
+    }
+    Ok(patterns)
+}
+
+fn redact(question: &mut Question, pattern: &mut Pattern) {
+    pattern.apply(&mut question.text);
+    for choice in question.choices.values_mut().chain(question.feedback.values_mut()) {
+        pattern.apply(choice);
+    }
+    for statement in question.statements.iter_mut().chain(question.premises.iter_mut()) {
+        pattern.apply(&mut statement.text);
+    }
+    for note in question.notes.iter_mut().chain(question.emphasis.iter_mut()) {
+        pattern.apply(note);
+    }
+    for line in &mut question.explain {
+        pattern.apply(&mut line.text);
+    }
+    for value in question.extra.values_mut() {
+        pattern.apply_value(value);
+    }
+}
+
+pub(crate) fn run(args: RedactArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    let mut patterns = patterns(&args)?;
+    if patterns.is_empty() {
+        return Err("Nothing to redact: pass --pattern or leave the built-in patterns on".into());
+    }
+    for pattern in &mut patterns {
+        if let Some(title) = &mut bank.meta.title {
+            pattern.apply(title);
+        }
+        for question in &mut bank.questions {
+            redact(question, pattern);
+        }
+    }
+
+    for pattern in &patterns {
+        println!("{:>6}  {}", pattern.count, pattern.name);
+    }
+    let total: usize = patterns.iter().map(|pattern| pattern.count).sum();
+    println!("Made {} replacements", total);
+    save_to_json(&bank, &args.output, &crypto)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(patterns: &[&str], no_builtin: bool) -> RedactArgs {
+        RedactArgs {
+            input: String::new(),
+            output: String::new(),
+            patterns: patterns.iter().map(|pattern| pattern.to_string()).collect(),
+            patterns_from: None,
+            replacement: "[redacted]".to_string(),
+            no_builtin,
+        }
+    }
+
+    /// `text` with every pattern applied in turn
+    fn redacted(patterns: &mut [Pattern], text: &str) -> String {
+        let mut text = text.to_string();
+        for pattern in patterns {
+            pattern.apply(&mut text);
+        }
+        text
+    }
+
+    #[test]
+    fn builtin_patterns_replace_addresses_and_drop_watermarks() {
+        let mut builtin = patterns(&args(&[], false)).unwrap();
+        let cases = [
+            ("Mail admin@dumps.example.org for the PDF", "Mail [email] for the PDF"),
+            ("See https://www.examtopics.com/sap/c_s4ewm for more", "See [url] for more"),
+            ("Questions from examtopics.com", "Questions from [url]"),
+            ("Which 100% valid exam dumps transaction shows the monitor?", "Which transaction shows the monitor?"),
+            ("Which transaction shows the monitor? Latest dumps", "Which transaction shows the monitor?"),
+            ("Braindumps\nWhich transaction shows the monitor?", "\nWhich transaction shows the monitor?"),
+            ("An ABAP short dump is raised", "An ABAP short dump is raised"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(redacted(&mut builtin, text), expected);
+        }
+        let counts: Vec<usize> = builtin.iter().map(|pattern| pattern.count).collect();
+        assert_eq!(counts, [1, 2, 3]);
+    }
+
+    #[test]
+    fn only_the_blanks_around_a_match_are_tidied() {
+        let mut builtin = patterns(&args(&[], false)).unwrap();
+        let text = "Which  transaction   shows the monitor?\n\nA.  /SCWM/MON  exam dumps\nB.\t/SCWM/PRDI";
+        let expected = "Which  transaction   shows the monitor?\n\nA.  /SCWM/MON\nB.\t/SCWM/PRDI";
+        assert_eq!(redacted(&mut builtin, text), expected);
+        assert_eq!(redacted(&mut builtin, "Untouched  text\n"), "Untouched  text\n");
+    }
+
+    #[test]
+    fn custom_patterns_use_the_replacement() {
+        let file = std::env::temp_dir().join(format!("s4wm-redact-patterns-{}.txt", std::process::id()));
+        fs::write(&file, "# customer names\nACME (Corp|Inc)\n\n").unwrap();
+        let mut custom = args(&[r"SAP-\d+"], false);
+        custom.patterns_from = Some(file.to_string_lossy().into_owned());
+        custom.replacement = "[customer $1]".to_string();
+        let mut all = patterns(&custom);
+        fs::remove_file(&file).unwrap();
+        let all = all.as_mut().unwrap();
+        let names: Vec<&str> = all.iter().map(|pattern| pattern.name.as_str()).collect();
+        assert_eq!(names, ["email", "url", "watermark", r"SAP-\d+", "ACME (Corp|Inc)"]);
+        assert_eq!(redacted(all, "Ticket SAP-1234 of ACME Corp"), "Ticket [customer ] of [customer Corp]");
+    }
+
+    #[test]
+    fn no_builtin_keeps_only_the_user_patterns() {
+        let mut user = patterns(&args(&["ACME"], true)).unwrap();
+        assert_eq!(user.len(), 1);
+        assert_eq!(redacted(&mut user, "ACME at www.acme.com"), "[redacted] at www.acme.com");
+        assert!(patterns(&args(&[], true)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn feedback_emphasis_and_extra_fields_are_redacted() {
+        let mut question = Question {
+            text: "Which transaction shows the monitor?".to_string(),
+            emphasis: vec!["see www.dumps4u.com".to_string()],
+            ..Question::default()
+        };
+        question.choices.insert("A".to_string(), "/SCWM/MON".to_string());
+        question.feedback.insert("A".to_string(), "Explained on https://example.net/mon".to_string());
+        question.extra.insert("source".to_string(), json!({"site": "examtopics.com", "pages": [3, "dumps.io"]}));
+        let mut builtin = patterns(&args(&[], false)).unwrap();
+        for pattern in &mut builtin {
+            redact(&mut question, pattern);
+        }
+        assert_eq!(question.feedback["A"], "Explained on [url]");
+        assert_eq!(question.emphasis, ["see [url]"]);
+        assert_eq!(question.extra["source"], json!({"site": "[url]", "pages": [3, "[url]"]}));
+        assert_eq!(builtin[1].count, 4);
+    }
+}
@@ -0,0 +1,315 @@
+use clap::{Args, ValueEnum};
+use regex::Regex;
+use std::error::Error;
+
+use crate::crypt::Crypto;
+use crate::history::{self, HistoryArgs};
+use crate::lifecycle::save_bank;
+use crate::similarity::stable_id;
+use crate::{open_bank, OutputError, Question};
+
+// Find-and-replace across a bank, for terminology updates (a renamed product, a changed transaction code) that would
+// otherwise be done with sed over the JSON, where a match inside a key or an escape sequence breaks the file. The
+// pattern is a regular expression and the replacement may refer to its groups as $1 or ${name}; only the text of
+// the chosen fields is touched. Every changed text is printed before and after the replacement, and `--dry-run`
+// stops there. Ids are kept, like with the other edits, so notes and change logs still find the questions.
+
+/// Text fields of a question that replacements apply to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Field {
+    Stem,
+    Choices,
+    Statements,
+    Premises,
+    Feedback,
+    Notes,
+}
+
+#[derive(Args, Debug)]
+pub struct ReplaceArgs {
+    /// Question bank to update
+    input: String,
+    /// Regular expression to find
+    #[arg(long, value_name = "REGEX")]
+    pattern: String,
+    /// Replacement text; $1 or ${name} insert a group of the pattern, $$ a dollar sign
+    #[arg(long = "with", value_name = "TEXT")]
+    replacement: String,
+    /// Fields to replace in
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "stem,choices,statements,premises,feedback")]
+    fields: Vec<Field>,
+    /// Show the changes without writing the bank
+    #[arg(long)]
+    dry_run: bool,
+    /// Write the updated bank here instead of updating the input in place
+    #[arg(short, long)]
+    output: Option<String>,
+    #[command(flatten)]
+    history: HistoryArgs,
+}
+
+/// A text changed by the replacement
+struct Change {
+    /// Field and, for choices, statements and the like, the letter or label
+    place: String,
+    before: String,
+    after: String,
+}
+
+struct Replacer<'a> {
+    regex: &'a Regex,
+    replacement: &'a str,
+    matches: usize,
+    changes: Vec<Change>,
+}
+
+impl Replacer<'_> {
+    fn apply(&mut self, place: impl Into<String>, text: &mut String) {
+        let matches = self.regex.find_iter(text).count();
+        if matches == 0 {
+            return;
+        }
+        let after = self.regex.replace_all(text, self.replacement).into_owned();
+        self.matches += matches;
+        if after != *text {
+            let before = std::mem::replace(text, after.clone());
+            self.changes.push(Change {
+                place: place.into(),
+                before,
+                after,
+            });
+        }
+    }
+}
+
+fn replace(question: &mut Question, fields: &[Field], replacer: &mut Replacer) {
+    if fields.contains(&Field::Stem) {
+        replacer.apply("stem", &mut question.text);
+    }
+    if fields.contains(&Field::Choices) {
+        let mut choices: Vec<_> = question.choices.iter_mut().collect();
+        choices.sort_by(|a, b| a.0.cmp(b.0));
+        for (letter, text) in choices {
+            replacer.apply(format!("choice {}", letter), text);
+        }
+    }
+    if fields.contains(&Field::Statements) {
+        for statement in &mut question.statements {
+            replacer.apply(format!("statement {}", statement.label), &mut statement.text);
+        }
+    }
+    if fields.contains(&Field::Premises) {
+        for premise in &mut question.premises {
+            replacer.apply(format!("premise {}", premise.label), &mut premise.text);
+        }
+    }
+    if fields.contains(&Field::Feedback) {
+        let mut feedback: Vec<_> = question.feedback.iter_mut().collect();
+        feedback.sort_by(|a, b| a.0.cmp(b.0));
+        for (letter, text) in feedback {
+            replacer.apply(format!("feedback {}", letter), text);
+        }
+    }
+    if fields.contains(&Field::Notes) {
+        for (i, note) in question.notes.iter_mut().enumerate() {
+            replacer.apply(format!("note {}", i + 1), note);
+        }
+    }
+}
+
+/// A changed text as removed and added lines.
+fn format_change(change: &Change) -> String {
+    let mut out = format!("  {}\n", change.place);
+    for line in change.before.lines() {
+        out.push_str(&format!("  - {}\n", line));
+    }
+    for line in change.after.lines() {
+        out.push_str(&format!("  + {}\n", line));
+    }
+    out
+}
+
+pub(crate) fn run(args: ReplaceArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let regex = Regex::new(&args.pattern).map_err(|e| OutputError {
+        message: format!("Invalid --pattern: {}", e),
+    })?;
+    if regex.is_match("") {
+        return Err(
+            OutputError::from("--pattern matches the empty text, it would insert the replacement everywhere").into()
+        );
+    }
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    let before = bank.questions.clone();
+
+    let mut matches = 0;
+    let mut changed = 0;
+    for question in &mut bank.questions {
+        let mut replacer = Replacer {
+            regex: &regex,
+            replacement: &args.replacement,
+            matches: 0,
+            changes: Vec::new(),
+        };
+        replace(question, &args.fields, &mut replacer);
+        matches += replacer.matches;
+        if replacer.changes.is_empty() {
+            continue;
+        }
+        changed += 1;
+        let id = question.id.clone().unwrap_or_else(|| stable_id(question));
+        println!("{}  question {}", id, question.number);
+        for change in &replacer.changes {
+            print!("{}", format_change(change));
+        }
+    }
+
+    println!("{} match(es) in {} question(s)", matches, changed);
+    if args.dry_run {
+        println!("Dry run, the bank was left unchanged");
+        return Ok(());
+    }
+    if changed == 0 {
+        return Ok(());
+    }
+    save_bank(&bank, args.output.as_deref().unwrap_or(&args.input), &crypto)?;
+    history::record(&args.history, "replace", &args.input, &before, &bank.questions, &crypto)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bank, Statement};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn question() -> Question {
+        let mut question = Question {
+            number: "1".to_string(),
+            id: Some("5f3a9c".to_string()),
+            text: "Which EWM transaction shows the EWM monitor?".to_string(),
+            notes: vec!["EWM 9.5 only".to_string()],
+            ..Question::default()
+        };
+        question.choices.insert("A".to_string(), "/SCWM/MON".to_string());
+        question.choices.insert("B".to_string(), "EWM cockpit".to_string());
+        question.feedback.insert("B".to_string(), "The EWM cockpit is a Fiori app".to_string());
+        question.statements.push(Statement {
+            label: "I".to_string(),
+            text: "EWM runs embedded".to_string(),
+        });
+        question
+    }
+
+    fn replacer(regex: &Regex) -> Replacer<'_> {
+        Replacer {
+            regex,
+            replacement: "Extended Warehouse Management",
+            matches: 0,
+            changes: Vec::new(),
+        }
+    }
+
+    /// A bank of one question saved in a directory of its own, and the arguments replacing in it
+    fn setup(name: &str, pattern: &str) -> (PathBuf, ReplaceArgs) {
+        let dir = std::env::temp_dir().join(format!("s4wm-replace-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("questions.json").to_string_lossy().into_owned();
+        let bank = Bank {
+            questions: vec![question()],
+            ..Bank::default()
+        };
+        save_bank(&bank, &input, &Crypto::default()).unwrap();
+        let args = ReplaceArgs {
+            input,
+            pattern: pattern.to_string(),
+            replacement: "Extended Warehouse Management".to_string(),
+            fields: vec![Field::Stem, Field::Choices],
+            dry_run: false,
+            output: None,
+            history: HistoryArgs::default(),
+        };
+        (dir, args)
+    }
+
+    #[test]
+    fn only_the_chosen_fields_are_replaced() {
+        let regex = Regex::new(r"\bEWM\b").unwrap();
+        let mut scoped = question();
+        let mut replacer = replacer(&regex);
+        replace(&mut scoped, &[Field::Choices, Field::Feedback], &mut replacer);
+        assert_eq!(scoped.text, question().text);
+        assert_eq!(scoped.choices["B"], "Extended Warehouse Management cockpit");
+        assert_eq!(scoped.feedback["B"], "The Extended Warehouse Management cockpit is a Fiori app");
+        assert_eq!(scoped.statements[0].text, "EWM runs embedded");
+        assert_eq!(scoped.notes, ["EWM 9.5 only"]);
+        let places: Vec<&str> = replacer.changes.iter().map(|change| change.place.as_str()).collect();
+        assert_eq!(places, ["choice B", "feedback B"]);
+        assert_eq!(replacer.matches, 2);
+    }
+
+    #[test]
+    fn changes_are_shown_as_removed_and_added_lines() {
+        let change = Change {
+            place: "stem".to_string(),
+            before: "Which EWM transaction\nshows it?".to_string(),
+            after: "Which Extended Warehouse Management transaction\nshows it?".to_string(),
+        };
+        let expected = "  stem\n  - Which EWM transaction\n  - shows it?\n  + Which Extended Warehouse Management \
+                        transaction\n  + shows it?\n";
+        assert_eq!(format_change(&change), expected);
+    }
+
+    #[test]
+    fn patterns_matching_the_empty_text_are_refused() {
+        let (dir, args) = setup("empty", "(EWM)?");
+        let saved = fs::read(&args.input).unwrap();
+        let result = run(args, &Crypto::default());
+        let after = fs::read(dir.join("questions.json")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(result.unwrap_err().to_string().contains("matches the empty text"));
+        assert_eq!(after, saved);
+    }
+
+    #[test]
+    fn dry_run_leaves_the_bank_and_the_log_alone() {
+        let (dir, mut args) = setup("dry-run", r"\bEWM\b");
+        let log = dir.join("history.ndjson");
+        args.dry_run = true;
+        args.history.history = Some(log.to_string_lossy().into_owned());
+        let saved = fs::read(&args.input).unwrap();
+        run(args, &Crypto::default()).unwrap();
+        let after = fs::read(dir.join("questions.json")).unwrap();
+        let logged = log.exists();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(after, saved);
+        assert!(!logged);
+    }
+
+    #[test]
+    fn replacements_are_saved_and_logged_under_the_same_id() {
+        let (dir, mut args) = setup("history", r"\bEWM\b");
+        let log = dir.join("history.ndjson");
+        args.history.history = Some(log.to_string_lossy().into_owned());
+        let input = args.input.clone();
+        run(args, &Crypto::default()).unwrap();
+        let (bank, _) = open_bank(&input, &Crypto::default()).unwrap();
+        let entries = fs::read_to_string(&log).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let question = &bank.questions[0];
+        assert_eq!(
+            question.text,
+            "Which Extended Warehouse Management transaction shows the Extended Warehouse Management monitor?"
+        );
+        assert_eq!(question.id.as_deref(), Some("5f3a9c"));
+        let entries: Vec<serde_json::Value> = entries.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["operation"], "replace");
+        assert_eq!(entries[0]["id"], "5f3a9c");
+        assert_eq!(entries[0]["kind"], "changed");
+        let fields: Vec<&str> =
+            entries[0]["fields"].as_array().unwrap().iter().filter_map(|f| f["field"].as_str()).collect();
+        assert_eq!(fields, ["choices", "text"]);
+    }
+}
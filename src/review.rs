@@ -0,0 +1,239 @@
+use clap::{Args, Subcommand, ValueEnum};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::crypt::Crypto;
+use crate::history::{self, HistoryArgs};
+use crate::lifecycle::{self, Lifecycle};
+use crate::similarity::stable_id;
+use crate::{open_bank, OutputError, Question};
+
+// Review of a bank by subject matter experts who work in spreadsheets rather than JSON. `review export` writes one row
+// per question with the stem, the choices and the proposed answer, followed by blank columns for the reviewer's
+// verdict, corrected answer and comments; `review import` reads the filled-in sheet and applies the verdicts to the
+// bank by question id, so rows may be sorted, filtered or split between reviewers in the meantime.
+
+/// Columns of the review sheet; the last ones are filled in by the reviewer
+const COLUMNS: &[&str] = &[
+    "id",
+    "number",
+    "page",
+    "question",
+    "choices",
+    "proposed answer",
+    "state",
+    "verdict",
+    "corrected answer",
+    "comments",
+    "reviewer",
+];
+
+#[derive(Args, Debug)]
+pub struct ReviewArgs {
+    #[command(subcommand)]
+    command: ReviewCommand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SheetFormat {
+    /// Comma-separated values, UTF-8 with a byte order mark so spreadsheet programs detect the encoding
+    Csv,
+}
+
+#[derive(Subcommand, Debug)]
+enum ReviewCommand {
+    /// Write a review sheet for the questions of a bank
+    Export {
+        /// Question bank to review
+        input: String,
+        /// Sheet to write
+        output: String,
+        #[arg(long, value_enum, default_value = "csv")]
+        format: SheetFormat,
+        /// Only the questions worth a look: flagged for review, recovered by the parser, or without an answer
+        #[arg(long)]
+        flagged: bool,
+    },
+    /// Apply the verdicts of a filled-in review sheet to a bank
+    Import {
+        /// Question bank to update
+        input: String,
+        /// Review sheet filled in by the reviewers
+        sheet: String,
+        /// Write the updated bank here instead of updating the input in place
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        history: HistoryArgs,
+    },
+}
+
+/// What a reviewer decided about a question
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    /// The question and its (possibly corrected) answer are right
+    Approve,
+    /// The question is unusable and is retired
+    Reject,
+    /// Someone else should look at it
+    Unsure,
+}
+
+impl Verdict {
+    fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "approve" | "approved" | "ok" | "yes" | "y" | "correct" | "fixed" => Some(Verdict::Approve),
+            "reject" | "rejected" | "retire" | "no" | "n" | "wrong" => Some(Verdict::Reject),
+            "unsure" | "?" | "review" | "needs review" => Some(Verdict::Unsure),
+            _ => None,
+        }
+    }
+}
+
+/// Questions worth a reviewer's attention.
+pub(crate) fn is_flagged(question: &Question) -> bool {
+    question.lifecycle == Lifecycle::NeedsReview || question.recovered || question.answers.is_empty()
+}
+
+fn question_id(question: &Question) -> String {
+    question.id.clone().unwrap_or_else(|| stable_id(question))
+}
+
+fn choices_cell(question: &Question) -> String {
+    let mut choices: Vec<_> = question.choices.iter().collect();
+    choices.sort();
+    choices.iter().map(|(letter, text)| format!("{}. {}", letter, text)).collect::<Vec<_>>().join("\n")
+}
+
+/// Choice letters in a corrected answer cell ("B", "b, d", "A and C").
+fn answer_letters(cell: &str) -> Vec<String> {
+    let mut letters: Vec<String> = cell
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() == 1)
+        .map(str::to_uppercase)
+        .collect();
+    letters.sort();
+    letters.dedup();
+    letters
+}
+
+fn export(input: &str, output: &str, flagged: bool, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (bank, crypto) = open_bank(input, crypto)?;
+    let mut sheet = "\u{feff}".as_bytes().to_vec();
+    let mut writer = csv::Writer::from_writer(&mut sheet);
+    writer.write_record(COLUMNS)?;
+    let mut rows = 0;
+    for question in bank.questions.iter().filter(|q| !flagged || is_flagged(q)) {
+        writer.write_record([
+            question_id(question),
+            question.number.clone(),
+            question.page.map(|page| page.to_string()).unwrap_or_default(),
+            question.text.clone(),
+            choices_cell(question),
+            question.answers.join(", "),
+            question.lifecycle.to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+        ])?;
+        rows += 1;
+    }
+    writer.flush()?;
+    drop(writer);
+    crypto.write(output, sheet)?;
+    println!("Wrote {} question(s) to {}", rows, output);
+    Ok(())
+}
+
+fn import(
+    input: &str,
+    sheet: &str,
+    output: Option<&str>,
+    history_args: &HistoryArgs,
+    crypto: &Crypto,
+) -> Result<(), Box<dyn Error>> {
+    let (mut bank, mut crypto) = open_bank(input, crypto)?;
+    let before = history_args.history.as_ref().map(|_| bank.questions.clone());
+    let positions: HashMap<String, usize> =
+        bank.questions.iter().enumerate().map(|(index, question)| (question_id(question), index)).collect();
+
+    let (contents, opened) = crypto.open(Path::new(sheet))?;
+    // the answers of an encrypted sheet stay encrypted in the bank
+    if opened.is_encrypting() {
+        crypto = opened;
+    }
+    let mut reader = csv::Reader::from_reader(Cursor::new(contents));
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name));
+    let id_column = column("id").ok_or_else(|| OutputError::from("The review sheet has no id column"))?;
+    let (verdict_column, answer_column) = (column("verdict"), column("corrected answer"));
+    let (comments_column, reviewer_column) = (column("comments"), column("reviewer"));
+
+    let mut updated = 0;
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        // the header is line 1
+        let line = row + 2;
+        let cell = |column: Option<usize>| column.and_then(|c| record.get(c)).map(str::trim).unwrap_or_default();
+        let id = cell(Some(id_column));
+        let (verdict, answer, comments, reviewer) =
+            (cell(verdict_column), cell(answer_column), cell(comments_column), cell(reviewer_column));
+        if verdict.is_empty() && answer.is_empty() && comments.is_empty() {
+            continue;
+        }
+        let Some(&index) = positions.get(id) else {
+            eprintln!("warning[review-import] line {}: no question with id {}", line, id);
+            continue;
+        };
+        let question = &mut bank.questions[index];
+
+        if !answer.is_empty() {
+            let letters = answer_letters(answer);
+            let unknown = letters.iter().any(|letter| !question.choices.contains_key(letter));
+            if letters.is_empty() || (!question.choices.is_empty() && unknown) {
+                eprintln!(
+                    "warning[review-import] line {}: question {} has no choice for the corrected answer {:?}",
+                    line, question.number, answer
+                );
+            } else {
+                question.correct_answers = Some(letters.len());
+                question.answers = letters;
+            }
+        }
+        match (verdict, Verdict::parse(verdict)) {
+            ("", _) => {}
+            (_, Some(Verdict::Approve)) => question.lifecycle = Lifecycle::Active,
+            (_, Some(Verdict::Reject)) => question.lifecycle = Lifecycle::Retired,
+            (_, Some(Verdict::Unsure)) => question.lifecycle = Lifecycle::NeedsReview,
+            (_, None) => eprintln!("warning[review-import] line {}: unknown verdict {:?}", line, verdict),
+        }
+        if !comments.is_empty() {
+            let note = if reviewer.is_empty() { comments.to_string() } else { format!("{}: {}", reviewer, comments) };
+            if !question.notes.contains(&note) {
+                question.notes.push(note);
+            }
+        }
+        updated += 1;
+    }
+
+    lifecycle::save_bank(&bank, output.unwrap_or(input), &crypto)?;
+    println!("Applied the review of {} question(s)", updated);
+    if let Some(before) = &before {
+        history::record(history_args, "review", sheet, before, &bank.questions, &crypto)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn run(args: ReviewArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    match args.command {
+        ReviewCommand::Export { input, output, format: SheetFormat::Csv, flagged } => {
+            export(&input, &output, flagged, crypto)
+        }
+        ReviewCommand::Import { input, sheet, output, history } => {
+            import(&input, &sheet, output.as_deref(), &history, crypto)
+        }
+    }
+}
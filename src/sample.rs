@@ -0,0 +1,245 @@
+use clap::{Args, ValueEnum};
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::crypt::Crypto;
+use crate::difficulty;
+use crate::exam::{self, Blueprint};
+use crate::lifecycle;
+use crate::{open_bank, save_to_json, Bank, Question};
+
+// Reproducible subsets of a bank, for weekly practice sets that stay the same for everyone and for train/holdout
+// splits. Unlike `exam`, which draws a balanced mock exam, `sample` keeps the make-up of the bank: the questions are
+// grouped into strata (the sections of the quiz site, difficulty levels, question kinds or blueprint topics) and every
+// stratum gets its share of the sample in proportion to its size, rounded by largest remainder. The same bank, seed
+// and options always give the same sample; without `--seed` one is drawn and printed so the sample can be redrawn.
+// The sample keeps the order of the bank, and `--rest` writes the questions left over as the other half of a split.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Stratify {
+    /// Blocks of consecutive questions, as the sections of the quiz site (--section-size)
+    Section,
+    /// Easy, medium and hard questions
+    Difficulty,
+    /// Question kinds (multiple choice, true/false, ordering, ...)
+    Kind,
+    /// Topics of the --blueprint file
+    Topic,
+    /// No strata, a plain random sample
+    None,
+}
+
+#[derive(Args, Debug)]
+pub struct SampleArgs {
+    /// Question bank to draw from
+    input: String,
+    /// Where to write the sample
+    output: String,
+    /// Number of questions in the sample
+    #[arg(short = 'n', long)]
+    count: usize,
+    /// Groups that keep their share of the bank in the sample
+    #[arg(long, value_enum, default_value_t = Stratify::None)]
+    stratify: Stratify,
+    /// Number of consecutive questions per section, for --stratify section
+    #[arg(long, default_value_t = 50)]
+    section_size: usize,
+    /// TOML file of topics with their weight and terms, for --stratify topic
+    #[arg(long, required_if_eq("stratify", "topic"))]
+    blueprint: Option<String>,
+    /// Seed for a reproducible sample, printed when not given
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Also write the questions not drawn, as the other half of a train/holdout split
+    #[arg(long, value_name = "FILE")]
+    rest: Option<String>,
+    /// Also draw retired and superseded questions
+    #[arg(long)]
+    include_retired: bool,
+}
+
+/// A group of questions by index into the bank
+struct Stratum {
+    name: String,
+    questions: Vec<usize>,
+}
+
+fn label<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default().replace('"', "")
+}
+
+/// The strata of the bank in order of first appearance.
+fn strata(args: &SampleArgs, questions: &[Question]) -> Result<Vec<Stratum>, Box<dyn Error>> {
+    let names: Vec<String> = match args.stratify {
+        Stratify::Section => {
+            let size = args.section_size.max(1);
+            (0..questions.len())
+                .map(|index| {
+                    let first = index / size * size + 1;
+                    format!("{}–{}", first, (first + size - 1).min(questions.len()))
+                })
+                .collect()
+        }
+        Stratify::Difficulty => questions.iter().map(|q| label(&difficulty::question_level(q))).collect(),
+        Stratify::Kind => questions.iter().map(|q| label(&q.kind)).collect(),
+        Stratify::Topic => {
+            let blueprint = Blueprint::load(args.blueprint.as_deref().unwrap_or_default())?;
+            let mut names = vec!["(no topic)".to_string(); questions.len()];
+            for (topic, members) in blueprint.topics.iter().zip(blueprint.classify(questions)?) {
+                for member in members {
+                    if let Some(index) = questions.iter().position(|question| std::ptr::eq(question, member)) {
+                        names[index] = topic.name.clone();
+                    }
+                }
+            }
+            names
+        }
+        Stratify::None => vec!["all".to_string(); questions.len()],
+    };
+
+    let mut strata: Vec<Stratum> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (index, name) in names.into_iter().enumerate() {
+        let position = *positions.entry(name.clone()).or_insert_with(|| {
+            strata.push(Stratum {
+                name,
+                questions: Vec::new(),
+            });
+            strata.len() - 1
+        });
+        strata[position].questions.push(index);
+    }
+    Ok(strata)
+}
+
+pub(crate) fn run(args: SampleArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    if !args.include_retired {
+        bank = lifecycle::in_use(&bank);
+    }
+    let count = args.count.min(bank.questions.len());
+    if count < args.count {
+        println!("Bank only has {} questions, the sample will contain all of them", bank.questions.len());
+    }
+    let seed = args.seed.unwrap_or_else(rand::random);
+    if args.seed.is_none() {
+        println!("Seed: {} (pass --seed {} to draw the same sample again)", seed, seed);
+    }
+
+    let mut strata = strata(&args, &bank.questions)?;
+    let sizes: Vec<f64> = strata.iter().map(|stratum| stratum.questions.len() as f64).collect();
+    let quotas = exam::apportion(&sizes, count);
+    let mut rng = exam::seeded_rng(Some(seed));
+    let mut drawn = vec![false; bank.questions.len()];
+    for (stratum, quota) in strata.iter_mut().zip(quotas) {
+        stratum.questions.shuffle(&mut rng);
+        for &index in &stratum.questions[..quota] {
+            drawn[index] = true;
+        }
+        if args.stratify != Stratify::None {
+            println!("{:>6} of {:>4}  {}", quota, stratum.questions.len(), stratum.name);
+        }
+    }
+
+    let (sample, rest): (Vec<(Question, bool)>, Vec<_>) =
+        bank.questions.into_iter().zip(drawn).partition(|(_, drawn)| *drawn);
+    let split = |questions: Vec<(Question, bool)>| Bank {
+        meta: bank.meta.clone(),
+        questions: questions.into_iter().map(|(question, _)| question).collect(),
+    };
+    println!("Drew {} questions, {} left", sample.len(), rest.len());
+    save_to_json(&split(sample), &args.output, &crypto)?;
+    if let Some(path) = &args.rest {
+        save_to_json(&split(rest), path, &crypto)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kind::QuestionKind;
+    use crate::load_from_json;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A bank of 20 multiple choice and 10 true/false questions saved in a directory of its own, and the arguments
+    /// drawing 9 of them by kind
+    fn setup(name: &str) -> (PathBuf, SampleArgs) {
+        let dir = std::env::temp_dir().join(format!("s4wm-sample-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let questions = (1..=30)
+            .map(|number| Question {
+                number: number.to_string(),
+                text: format!("Question {}", number),
+                kind: if number % 3 == 0 { QuestionKind::TrueFalse } else { QuestionKind::MultipleChoice },
+                ..Question::default()
+            })
+            .collect();
+        let input = dir.join("questions.json").to_string_lossy().into_owned();
+        save_to_json(
+            &Bank {
+                questions,
+                ..Bank::default()
+            },
+            &input,
+            &Crypto::default(),
+        )
+        .unwrap();
+        let args = SampleArgs {
+            input,
+            output: dir.join("sample.json").to_string_lossy().into_owned(),
+            count: 9,
+            stratify: Stratify::Kind,
+            section_size: 50,
+            blueprint: None,
+            seed: Some(42),
+            rest: Some(dir.join("rest.json").to_string_lossy().into_owned()),
+            include_retired: false,
+        };
+        (dir, args)
+    }
+
+    fn numbers(path: &str) -> Vec<usize> {
+        let bank = load_from_json(path, &Crypto::default()).unwrap();
+        bank.questions.iter().map(|question| question.number.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn same_seed_draws_the_same_sample() {
+        let (dir, args) = setup("seed");
+        let output = args.output.clone();
+        let again = SampleArgs {
+            output: dir.join("again.json").to_string_lossy().into_owned(),
+            rest: None,
+            ..setup("seed").1
+        };
+        let again_output = again.output.clone();
+        run(args, &Crypto::default()).unwrap();
+        run(again, &Crypto::default()).unwrap();
+        let (first, second) = (numbers(&output), numbers(&again_output));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(first.len(), 9);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn sample_and_rest_split_the_bank_by_quota() {
+        let (dir, args) = setup("split");
+        let (output, rest) = (args.output.clone(), args.rest.clone().unwrap());
+        run(args, &Crypto::default()).unwrap();
+        let (sample, rest) = (numbers(&output), numbers(&rest));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // 20 and 10 questions share 9 places as 6 and 3
+        assert_eq!(sample.iter().filter(|number| *number % 3 == 0).count(), 3);
+        assert_eq!(sample.len(), 9);
+        assert_eq!(rest.len(), 21);
+        assert!(sample.is_sorted() && rest.is_sorted());
+        let mut all: Vec<usize> = sample.into_iter().chain(rest).collect();
+        all.sort();
+        assert_eq!(all, (1..=30).collect::<Vec<_>>());
+    }
+}
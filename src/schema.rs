@@ -0,0 +1,208 @@
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+
+use crate::validate::Severity;
+use crate::{Bank, OutputError, Question};
+
+// User-defined question fields, for teams that track data the crate knows nothing about (the SAP note a question
+// refers to, the course chapter, an internal difficulty rating). Fields a bank does not know are kept in the `extra`
+// map of the question, so they survive being loaded, merged, transformed and saved again, and exports show them;
+// declaring them in a schema extension file (`--schema`, TOML) additionally checks them and fills them in:
+//
+//     [[field]]
+//     name = "sap_note"
+//     type = "string"            # string, integer, number, boolean or list (of strings)
+//     pattern = 'SAP Note (\d+)' # filled from the stem at extraction, from the first group when there is one
+//
+//     [[field]]
+//     name = "chapter"
+//     required = true            # reported as an extra-field error when missing
+//     default = "unsorted"       # set at extraction when the question has no value
+//
+// Validation reports declared fields that are missing or have the wrong type, and undeclared extra fields, which are
+// usually typos, under the `extra-field` rule.
+
+/// Names of the fields of `Question`, which extra fields cannot take
+const RESERVED: &[&str] = &[
+    "number",
+    "id",
+    "kind",
+    "text",
+    "choices",
+    "feedback",
+    "correct_answers",
+    "statements",
+    "answers",
+    "observed",
+    "community_answer",
+    "keywords",
+    "emphasis",
+    "truth",
+    "order",
+    "premises",
+    "pairs",
+    "lang",
+    "page",
+    "chapter",
+    "source_number",
+    "difficulty",
+    "recovered",
+    "lifecycle",
+    "notes",
+    "variant_of",
+    "explain",
+];
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum FieldType {
+    #[default]
+    String,
+    Integer,
+    Number,
+    Boolean,
+    List,
+}
+
+impl FieldType {
+    fn name(self) -> &'static str {
+        match self {
+            FieldType::String => "a string",
+            FieldType::Integer => "an integer",
+            FieldType::Number => "a number",
+            FieldType::Boolean => "a boolean",
+            FieldType::List => "a list of strings",
+        }
+    }
+
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            FieldType::String => value.is_string(),
+            FieldType::Integer => value.is_i64() || value.is_u64(),
+            FieldType::Number => value.is_number(),
+            FieldType::Boolean => value.is_boolean(),
+            FieldType::List => value.as_array().is_some_and(|items| items.iter().all(Value::is_string)),
+        }
+    }
+
+    /// The value of a field of this type read from the matches of its pattern, if they make one.
+    fn read_matches(self, matches: Vec<String>) -> Option<Value> {
+        let first = matches.first();
+        match self {
+            FieldType::String => first.map(|text| Value::from(text.as_str())),
+            FieldType::Integer => first?.parse::<i64>().ok().map(Value::from),
+            FieldType::Number => first?.replace(',', ".").parse::<f64>().ok().map(Value::from),
+            FieldType::Boolean => Some(Value::Bool(first.is_some())),
+            FieldType::List => (!matches.is_empty()).then(|| Value::from(matches)),
+        }
+    }
+}
+
+/// A field declared by the schema extension file
+#[derive(Deserialize, Debug)]
+struct Field {
+    name: String,
+    #[serde(default, rename = "type")]
+    kind: FieldType,
+    #[serde(default)]
+    required: bool,
+    #[serde(default)]
+    default: Option<toml::Value>,
+    /// Regular expression reading the value from the stem
+    #[serde(default)]
+    pattern: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct Schema {
+    #[serde(default, rename = "field")]
+    fields: Vec<Field>,
+    /// The compiled `pattern` of every field
+    #[serde(skip)]
+    patterns: Vec<Option<Regex>>,
+}
+
+fn error(path: &str, message: impl std::fmt::Display) -> OutputError {
+    OutputError {
+        message: format!("Invalid schema {}: {}", path, message),
+    }
+}
+
+impl Schema {
+    pub(crate) fn load(path: Option<&str>) -> Result<Option<Schema>, OutputError> {
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let mut schema: Schema = toml::from_str(&fs::read_to_string(path)?).map_err(|e| error(path, e))?;
+        for (i, field) in schema.fields.iter().enumerate() {
+            if RESERVED.contains(&field.name.as_str()) {
+                return Err(error(path, format!("{} is a field of every question", field.name)));
+            }
+            if schema.fields[..i].iter().any(|other| other.name == field.name) {
+                return Err(error(path, format!("{} is declared twice", field.name)));
+            }
+            if let Some(default) = &field.default {
+                if !field.kind.accepts(&json(default)) {
+                    return Err(error(path, format!("the default of {} is not {}", field.name, field.kind.name())));
+                }
+            }
+            let pattern = field.pattern.as_deref().map(Regex::new).transpose().map_err(|e| error(path, e))?;
+            schema.patterns.push(pattern);
+        }
+        Ok(Some(schema))
+    }
+
+    /// Fills in the declared fields a question has no value for, from their pattern or else their default. Returns
+    /// the number of values set.
+    pub(crate) fn apply(&self, bank: &mut Bank) -> usize {
+        let mut filled = 0;
+        for question in &mut bank.questions {
+            for (field, pattern) in self.fields.iter().zip(&self.patterns) {
+                if question.extra.contains_key(&field.name) {
+                    continue;
+                }
+                let found = pattern.as_ref().and_then(|pattern| {
+                    let matches = pattern
+                        .captures_iter(&question.text)
+                        .map(|captures| captures.get(1).or(captures.get(0)).map_or("", |m| m.as_str()).to_string())
+                        .collect();
+                    field.kind.read_matches(matches)
+                });
+                if let Some(value) = found.or_else(|| field.default.as_ref().map(json)) {
+                    question.extra.insert(field.name.clone(), value);
+                    filled += 1;
+                }
+            }
+        }
+        filled
+    }
+
+    /// Problems with the extra fields of a question.
+    pub(crate) fn check(&self, question: &Question) -> Vec<(Severity, String)> {
+        let mut problems = Vec::new();
+        for field in &self.fields {
+            match question.extra.get(&field.name) {
+                None | Some(Value::Null) if field.required => {
+                    problems.push((Severity::Error, format!("required field {} is missing", field.name)));
+                }
+                Some(value) if !value.is_null() && !field.kind.accepts(value) => {
+                    let message = format!("field {} is not {}: {}", field.name, field.kind.name(), value);
+                    problems.push((Severity::Error, message));
+                }
+                _ => {}
+            }
+        }
+        for name in question.extra.keys() {
+            if !self.fields.iter().any(|field| &field.name == name) {
+                problems.push((Severity::Warning, format!("field {} is not declared by the schema", name)));
+            }
+        }
+        problems
+    }
+}
+
+fn json(value: &toml::Value) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
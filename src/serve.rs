@@ -0,0 +1,375 @@
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::crypt::Crypto;
+use crate::edit;
+use crate::history::{self, HistoryArgs};
+use crate::lifecycle::{self, Lifecycle};
+use crate::review::is_flagged;
+use crate::{open_bank, Bank, Question};
+
+// Local web page for reviewing a bank in the browser. The page and its script are compiled into the binary, so
+// `serve` needs nothing but the bank. It lists the flagged questions (or all of them) and, with `--review`, lets
+// reviewers edit the stem, the choices and the answers inline and approve or reject a question. Edits go through the
+// checks of `edit`; every accepted edit is written back to the bank file right away and logged to the change log when
+// `--history` is given.
+
+const PAGE: &str = include_str!("assets/review.html");
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Question bank to serve
+    input: String,
+    /// Allow editing, approving and rejecting questions; without it the page is read-only
+    #[arg(long)]
+    review: bool,
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+    #[command(flatten)]
+    history: HistoryArgs,
+}
+
+struct ServeState {
+    bank: Bank,
+    path: String,
+    /// How the bank is written back: encrypted when it was read encrypted
+    crypto: Crypto,
+    review: bool,
+    history: HistoryArgs,
+}
+
+type Shared = Arc<Mutex<ServeState>>;
+
+#[derive(Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    all: bool,
+}
+
+/// Response of `GET /api/questions`
+#[derive(Serialize)]
+pub(crate) struct Listing<'a> {
+    pub(crate) review: bool,
+    pub(crate) total: usize,
+    pub(crate) questions: Vec<&'a Question>,
+}
+
+/// Changes made to a question from the page, the body of `POST /api/questions/<id>`
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct Edit {
+    pub(crate) text: Option<String>,
+    pub(crate) choices: Option<HashMap<String, String>>,
+    pub(crate) answers: Option<Vec<String>>,
+    /// "approve" or "reject"
+    pub(crate) verdict: Option<String>,
+    pub(crate) note: Option<String>,
+    /// Reviewer name, recorded with the note and in the change log
+    pub(crate) reviewer: Option<String>,
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, message.into()).into_response()
+}
+
+async fn page() -> Html<&'static str> {
+    Html(PAGE)
+}
+
+async fn list(State(state): State<Shared>, Query(query): Query<ListQuery>) -> Response {
+    let Ok(state) = state.lock() else {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "state lock poisoned");
+    };
+    let questions = state.bank.questions.iter().filter(|q| query.all || is_flagged(q)).collect();
+    Json(Listing {
+        review: state.review,
+        total: state.bank.questions.len(),
+        questions,
+    })
+    .into_response()
+}
+
+/// The whole bank, for frontends that show its metadata (`scaffold --full-stack`)
+async fn bank(State(state): State<Shared>) -> Response {
+    let Ok(state) = state.lock() else {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "state lock poisoned");
+    };
+    Json(&state.bank).into_response()
+}
+
+/// Applies an edit to a question, returning an error message when it is not acceptable.
+fn apply(question: &mut Question, edit: Edit) -> Result<(), String> {
+    if let Some(text) = edit.text {
+        question.text = text.trim().to_string();
+    }
+    if let Some(choices) = edit.choices {
+        let choices: HashMap<String, String> =
+            choices.into_iter().map(|(letter, text)| (letter, text.trim().to_string())).collect();
+        if let Some(letter) = choices.iter().filter(|(_, text)| text.is_empty()).map(|(letter, _)| letter).min() {
+            return Err(format!("Choice {} cannot be empty", letter));
+        }
+        question.choices = choices;
+    }
+    if let Some(mut answers) = edit.answers {
+        answers.sort();
+        answers.dedup();
+        question.correct_answers = Some(answers.len());
+        question.answers = answers;
+    }
+    // the checks of `edit`, so removing a choice cannot leave an answer, feedback, order or pair pointing at it
+    edit::check(question)?;
+    match edit.verdict.as_deref() {
+        None => {}
+        Some("approve") => question.lifecycle = Lifecycle::Active,
+        Some("reject") => question.lifecycle = Lifecycle::Retired,
+        Some(other) => return Err(format!("Unknown verdict {}", other)),
+    }
+    if let Some(note) = edit.note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty()) {
+        question.notes.push(match edit.reviewer.as_deref().filter(|name| !name.is_empty()) {
+            Some(reviewer) => format!("{}: {}", reviewer, note),
+            None => note,
+        });
+    }
+    Ok(())
+}
+
+async fn update(State(state): State<Shared>, UrlPath(id): UrlPath<String>, Json(edit): Json<Edit>) -> Response {
+    let Ok(mut state) = state.lock() else {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "state lock poisoned");
+    };
+    if !state.review {
+        return error(StatusCode::FORBIDDEN, "The bank is served read-only, restart with --review to edit");
+    }
+    let index = match lifecycle::find(&state.bank.questions, &id) {
+        Ok(index) => index,
+        Err(e) => return error(StatusCode::NOT_FOUND, e.to_string()),
+    };
+    let before = state.bank.questions[index].clone();
+    let mut history = state.history.clone();
+    if let Some(reviewer) = edit.reviewer.clone().filter(|name| !name.is_empty()) {
+        history.author = Some(reviewer);
+    }
+    if let Err(message) = apply(&mut state.bank.questions[index], edit) {
+        state.bank.questions[index] = before;
+        return error(StatusCode::UNPROCESSABLE_ENTITY, message);
+    }
+
+    let after = state.bank.questions[index].clone();
+    if let Err(e) = lifecycle::save_bank(&state.bank, &state.path, &state.crypto) {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    let recorded = history::record(&history, "review", "serve", &[before], std::slice::from_ref(&after), &state.crypto);
+    if let Err(e) = recorded {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    Json(after).into_response()
+}
+
+pub(crate) async fn run(args: ServeArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (loaded, crypto) = open_bank(&args.input, crypto)?;
+    let state = Arc::new(Mutex::new(ServeState {
+        bank: loaded,
+        path: args.input.clone(),
+        crypto,
+        review: args.review,
+        history: args.history,
+    }));
+    let app = Router::new()
+        .route("/", get(page))
+        .route("/api/bank", get(bank))
+        .route("/api/questions", get(list))
+        .route("/api/questions/:id", post(update))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind((args.host.as_str(), args.port)).await?;
+    println!(
+        "Serving {} {} on http://{}",
+        args.input,
+        if args.review { "for review" } else { "read-only" },
+        listener.local_addr()?
+    );
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A bank of one question saved in a directory of its own, served for review or read-only
+    fn setup(name: &str, review: bool) -> (PathBuf, Shared) {
+        let dir = std::env::temp_dir().join(format!("s4wm-serve-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut question = Question {
+            number: "1".to_string(),
+            id: Some("5f3a9c".to_string()),
+            text: "Which transaction shows the warehouse management monitor?".to_string(),
+            answers: vec!["A".to_string()],
+            correct_answers: Some(1),
+            ..Question::default()
+        };
+        question.choices.insert("A".to_string(), "/SCWM/MON".to_string());
+        question.choices.insert("B".to_string(), "/SCWM/PRDI".to_string());
+        let bank = Bank {
+            questions: vec![question],
+            ..Bank::default()
+        };
+        let path = dir.join("questions.json").to_string_lossy().into_owned();
+        lifecycle::save_bank(&bank, &path, &Crypto::default()).unwrap();
+        let state = ServeState {
+            bank,
+            path,
+            crypto: Crypto::default(),
+            review,
+            history: HistoryArgs::default(),
+        };
+        (dir, Arc::new(Mutex::new(state)))
+    }
+
+    async fn post(state: &Shared, id: &str, edit: Edit) -> StatusCode {
+        update(State(state.clone()), UrlPath(id.to_string()), Json(edit)).await.status()
+    }
+
+    fn choices(pairs: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+        Some(pairs.iter().map(|(letter, text)| (letter.to_string(), text.to_string())).collect())
+    }
+
+    #[test]
+    fn choices_cannot_drop_an_answer_or_be_empty() {
+        let (dir, state) = setup("apply", true);
+        fs::remove_dir_all(&dir).unwrap();
+        let question = state.lock().unwrap().bank.questions[0].clone();
+
+        let dropped = Edit {
+            choices: choices(&[("B", "/SCWM/PRDI")]),
+            ..Edit::default()
+        };
+        assert_eq!(apply(&mut question.clone(), dropped).unwrap_err(), "No choice A");
+        let emptied = Edit {
+            choices: choices(&[("A", "/SCWM/MON"), ("B", "  ")]),
+            ..Edit::default()
+        };
+        assert_eq!(apply(&mut question.clone(), emptied).unwrap_err(), "Choice B cannot be empty");
+
+        let mut edited = question.clone();
+        let moved = Edit {
+            choices: choices(&[("B", "/SCWM/MON")]),
+            answers: Some(vec!["B".to_string()]),
+            ..Edit::default()
+        };
+        apply(&mut edited, moved).unwrap();
+        assert_eq!(edited.answers, ["B"]);
+    }
+
+    #[test]
+    fn edits_share_the_checks_of_edit() {
+        let (dir, state) = setup("checks", true);
+        fs::remove_dir_all(&dir).unwrap();
+        let mut question = state.lock().unwrap().bank.questions[0].clone();
+        question.choices.insert("C".to_string(), "/SCWM/WAVE".to_string());
+        let without_c = || Edit {
+            choices: choices(&[("A", "/SCWM/MON"), ("B", "/SCWM/PRDI")]),
+            ..Edit::default()
+        };
+
+        let mut feedback = question.clone();
+        feedback.feedback.insert("C".to_string(), "Waves are not monitored".to_string());
+        assert_eq!(apply(&mut feedback, without_c()).unwrap_err(), "No choice C");
+        let mut order = question.clone();
+        order.order = vec!["B".to_string(), "C".to_string()];
+        assert_eq!(apply(&mut order, without_c()).unwrap_err(), "No choice C");
+        let mut matching = question.clone();
+        matching.premises = vec![crate::Statement {
+            label: "1".to_string(),
+            text: "Shows the stock".to_string(),
+        }];
+        matching.pairs = vec![crate::MatchPair {
+            premise: "1".to_string(),
+            choice: "C".to_string(),
+        }];
+        assert_eq!(apply(&mut matching, without_c()).unwrap_err(), "No choice C");
+
+        let blank = Edit {
+            text: Some("  ".to_string()),
+            ..Edit::default()
+        };
+        assert_eq!(apply(&mut question.clone(), blank).unwrap_err(), "The stem cannot be empty");
+        assert!(apply(&mut question, without_c()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn unknown_ids_are_not_found() {
+        let (dir, state) = setup("unknown", true);
+        let status = post(&state, "0000", Edit::default()).await;
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn read_only_banks_refuse_edits() {
+        let (dir, state) = setup("read-only", false);
+        let status = post(
+            &state,
+            "5f3a",
+            Edit {
+                verdict: Some("approve".to_string()),
+                ..Edit::default()
+            },
+        )
+        .await;
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn invalid_edits_leave_the_bank_untouched() {
+        let (dir, state) = setup("invalid", true);
+        let path = state.lock().unwrap().path.clone();
+        let saved = fs::read(&path).unwrap();
+        let question = serde_json::to_value(&state.lock().unwrap().bank.questions[0]).unwrap();
+
+        let edit = Edit {
+            text: Some("Which transaction monitors the warehouse?".to_string()),
+            choices: choices(&[("B", "/SCWM/PRDI")]),
+            ..Edit::default()
+        };
+        let status = post(&state, "5f3a", edit).await;
+        let after = fs::read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(after, saved);
+        assert_eq!(serde_json::to_value(&state.lock().unwrap().bank.questions[0]).unwrap(), question);
+    }
+
+    #[tokio::test]
+    async fn valid_edits_are_saved() {
+        let (dir, state) = setup("valid", true);
+        let edit = Edit {
+            answers: Some(vec!["B".to_string()]),
+            verdict: Some("approve".to_string()),
+            ..Edit::default()
+        };
+        let response = update(State(state.clone()), UrlPath("5f3a".to_string()), Json(edit)).await;
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let path = state.lock().unwrap().path.clone();
+        let (saved, _) = open_bank(&path, &Crypto::default()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<Question>(&body).unwrap().answers, ["B"]);
+        assert_eq!(saved.questions[0].answers, ["B"]);
+    }
+}
@@ -0,0 +1,279 @@
+use clap::Args;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::manifest::{self, sha256_hex, MANIFEST_FILE};
+use crate::progress::Progress;
+use crate::OutputError;
+
+// Signed outputs, so the consumers of the banks a team publishes can tell they come from its pipeline and were not
+// edited by hand since. With `--sign <key>` an extraction writes detached Ed25519 signatures of its bank and of its
+// manifest next to them (`questions.json.sig`, `manifest.json.sig`); as the manifest lists the SHA-256 of every
+// output, its signature covers the exports too. `keygen` makes the key pair: the secret key stays with the pipeline,
+// the public key goes to the consumers, who check a run with `verify`. Keys and signatures are stored as hex text.
+
+const SIGNATURE_EXTENSION: &str = "sig";
+
+#[derive(Args, Debug)]
+pub struct KeygenArgs {
+    /// Where to write the secret key; the public key goes next to it with a `.pub` extension
+    secret_key: String,
+    /// Replace an existing key
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Output directory of an extraction, or a single signed file
+    path: String,
+    /// Public key of the signer, as written by keygen
+    #[arg(long, env = "S4WM_PUBLIC_KEY", value_name = "FILE")]
+    key: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Bytes of a hex string of exactly `N` bytes.
+fn from_hex<const N: usize>(text: &str) -> Option<[u8; N]> {
+    let text = text.trim();
+    if text.len() != N * 2 || !text.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn read_key<const N: usize>(path: &str, what: &str) -> Result<[u8; N], OutputError> {
+    let text = fs::read_to_string(path).map_err(|e| OutputError {
+        message: format!("Cannot read the {} {}: {}", what, path, e),
+    })?;
+    from_hex(&text).ok_or_else(|| OutputError {
+        message: format!("{} is not a {} written by keygen", path, what),
+    })
+}
+
+pub(crate) fn load_signing_key(path: &str) -> Result<SigningKey, OutputError> {
+    Ok(SigningKey::from_bytes(&read_key(path, "secret key")?))
+}
+
+fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Signs the manifest of a run in `output_dir` and its bank, unless the bank is a directory (per-question layout).
+pub(crate) fn sign_run(
+    key: &SigningKey,
+    output_dir: &Path,
+    bank: &Path,
+    progress: &mut dyn Progress,
+) -> Result<(), OutputError> {
+    let mut files = vec![output_dir.join(MANIFEST_FILE)];
+    files.extend(Some(bank.to_path_buf()).filter(|bank| bank.is_file()));
+    for file in &files {
+        let signature = key.sign(&fs::read(file)?);
+        fs::write(signature_path(file), hex(&signature.to_bytes()) + "\n")?;
+    }
+    progress.info(format!("Signed {} file(s) with key {}", files.len(), hex(key.verifying_key().as_bytes())));
+    Ok(())
+}
+
+/// Checks the detached signature of `path`.
+fn verify_signature(path: &Path, key: &VerifyingKey) -> Result<(), String> {
+    let signature_path = signature_path(path);
+    let text = fs::read_to_string(&signature_path).map_err(|_| format!("no signature {}", signature_path.display()))?;
+    let signature = from_hex(&text).ok_or_else(|| format!("{} is not a signature", signature_path.display()))?;
+    let contents = fs::read(path).map_err(|e| e.to_string())?;
+    key.verify_strict(&contents, &Signature::from_bytes(&signature))
+        .map_err(|_| "the signature does not match, the file was changed or signed with another key".to_string())
+}
+
+/// Checks the signed manifest of a run, the SHA-256 of every output it lists and the signature of the bank.
+/// Returns the problems found.
+fn verify_run(dir: &Path, key: &VerifyingKey) -> Result<Vec<String>, Box<dyn Error>> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    if let Err(problem) = verify_signature(&manifest_path, key) {
+        return Ok(vec![format!("{}: {}", manifest_path.display(), problem)]);
+    }
+    println!("ok    {}", manifest_path.display());
+    let mut problems = Vec::new();
+    for (recorded, sha256) in manifest::listed_outputs(&manifest_path)? {
+        // the manifest records the paths inside the run directory, wherever the directory is now
+        let relative = Path::new(&recorded);
+        let inside = relative.components().all(|part| matches!(part, Component::Normal(_) | Component::CurDir));
+        if !inside {
+            problems.push(format!("{}: not a path inside the run directory", recorded));
+            continue;
+        }
+        let path = dir.join(relative);
+        let problem = match fs::read(&path) {
+            Err(e) => Some(e.to_string()),
+            Ok(contents) if sha256_hex(&contents) != sha256 => Some("changed since the run".to_string()),
+            Ok(_) if signature_path(&path).exists() => verify_signature(&path, key).err(),
+            Ok(_) => None,
+        };
+        match problem {
+            Some(problem) => problems.push(format!("{}: {}", path.display(), problem)),
+            None => println!("ok    {}", path.display()),
+        }
+    }
+    Ok(problems)
+}
+
+pub(crate) fn run_keygen(args: KeygenArgs) -> Result<(), Box<dyn Error>> {
+    let secret_path = PathBuf::from(&args.secret_key);
+    let public_path = secret_path.with_extension("pub");
+    if !args.force && (secret_path.exists() || public_path.exists()) {
+        return Err(format!("{} already exists, pass --force to replace it", secret_path.display()).into());
+    }
+    let key = SigningKey::generate(&mut rand::rngs::OsRng);
+    // the key goes into a new file, so neither the permissions of a replaced one nor a link in its place carry over
+    match fs::remove_file(&secret_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    create_owner_only(&secret_path)?.write_all((hex(&key.to_bytes()) + "\n").as_bytes())?;
+    fs::write(&public_path, hex(key.verifying_key().as_bytes()) + "\n")?;
+    println!("Secret key written to {}, keep it with the pipeline", secret_path.display());
+    println!("Public key written to {}, hand it to the consumers", public_path.display());
+    Ok(())
+}
+
+/// Creates a file readable by its owner only, from the start rather than after the key is in it.
+#[cfg(unix)]
+fn create_owner_only(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_owner_only(path: &Path) -> io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+pub(crate) fn run_verify(args: VerifyArgs) -> Result<(), Box<dyn Error>> {
+    let key = VerifyingKey::from_bytes(&read_key(&args.key, "public key")?)
+        .map_err(|_| format!("{} is not a valid Ed25519 public key", args.key))?;
+    let path = Path::new(&args.path);
+    let problems = if path.is_dir() {
+        verify_run(path, &key)?
+    } else {
+        match verify_signature(path, &key) {
+            Ok(()) => Vec::new(),
+            Err(problem) => vec![format!("{}: {}", path.display(), problem)],
+        }
+    };
+    for problem in &problems {
+        println!("FAIL  {}", problem);
+    }
+    if !problems.is_empty() {
+        return Err(OutputError {
+            message: format!("Verification failed for {} file(s)", problems.len()),
+        }
+        .into());
+    }
+    println!("Signed with the given key and unchanged");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Console;
+    use crate::timings::Timings;
+    use std::time::SystemTime;
+
+    /// A signed run in `dir`: a bank, a question of the per-question layout and the manifest listing both.
+    fn signed_run(dir: &Path, key: &SigningKey) {
+        fs::create_dir_all(dir.join("questions")).unwrap();
+        let source = dir.with_extension("pdf");
+        fs::write(&source, "%PDF-1.4").unwrap();
+        fs::write(dir.join("questions.json"), r#"{"questions": []}"#).unwrap();
+        fs::write(dir.join("questions").join("1.json"), r#"{"number": 1}"#).unwrap();
+        let outputs = [dir.join("questions.json"), dir.join("questions")];
+        let started = SystemTime::now();
+        let pdf = source.to_str().unwrap();
+        let profile = crate::profile::ENGLISH;
+        manifest::write_manifest(dir, pdf, &profile, started, &Timings::default(), &[], &outputs).unwrap();
+        sign_run(key, dir, &outputs[0], &mut Console::new()).unwrap();
+        fs::remove_file(source).unwrap();
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("s4wm-signing-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn moved_runs_verify() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let (dir, moved) = (temp_dir("run"), temp_dir("published"));
+        signed_run(&dir, &key);
+        let listed = manifest::listed_outputs(&dir.join(MANIFEST_FILE)).unwrap();
+        fs::rename(&dir, &moved).unwrap();
+        let problems = verify_run(&moved, &key.verifying_key());
+        let _ = fs::remove_dir_all(&moved);
+
+        let paths: Vec<&str> = listed.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths, ["questions.json", "questions/1.json"]);
+        assert!(problems.unwrap().is_empty());
+    }
+
+    #[test]
+    fn changed_files_fail() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let dir = temp_dir("tampered");
+        signed_run(&dir, &key);
+        fs::write(dir.join("questions").join("1.json"), r#"{"number": 2}"#).unwrap();
+        let changed = verify_run(&dir, &key.verifying_key()).unwrap();
+        fs::write(dir.join("questions.json"), r#"{"questions": [{}]}"#).unwrap();
+        let both = verify_run(&dir, &key.verifying_key()).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(changed, [format!("{}: changed since the run", dir.join("questions").join("1.json").display())]);
+        assert_eq!(both.len(), 2);
+    }
+
+    #[test]
+    fn other_keys_fail() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let dir = temp_dir("key");
+        signed_run(&dir, &key);
+        let problems = verify_run(&dir, &SigningKey::from_bytes(&[8; 32]).verifying_key()).unwrap();
+        let bank = verify_signature(&dir.join("questions.json"), &SigningKey::from_bytes(&[8; 32]).verifying_key());
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].ends_with("the file was changed or signed with another key"));
+        assert!(bank.is_err());
+    }
+
+    #[test]
+    fn paths_outside_the_run_fail() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let dir = temp_dir("outside");
+        signed_run(&dir, &key);
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest = fs::read_to_string(&manifest_path).unwrap();
+        let manifest = manifest.replace("\"questions/1.json\"", "\"../1.json\"");
+        fs::write(&manifest_path, manifest.replace("\"questions.json\"", "\"/etc/hostname\"")).unwrap();
+        sign_run(&key, &dir, &dir.join("questions.json"), &mut Console::new()).unwrap();
+        let problems = verify_run(&dir, &key.verifying_key()).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let outside = |path: &str| format!("{}: not a path inside the run directory", path);
+        assert_eq!(problems, [outside("/etc/hostname"), outside("../1.json")]);
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::Question;
+
+// Text similarity helpers. Comparisons run on a normalized form (lowercase, alphanumeric words only) so that
+// punctuation and spacing differences introduced by extraction do not hide duplicates.
+
+pub(crate) fn normalize(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub(crate) fn token_set(text: &str) -> HashSet<String> {
+    normalize(text).split(' ').filter(|w| !w.is_empty()).map(str::to_string).collect()
+}
+
+/// Jaccard similarity of two token sets, between 0.0 (disjoint) and 1.0 (identical).
+pub(crate) fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let intersection = a.intersection(b).count();
+    let union = a.len() + b.len() - intersection;
+    intersection as f64 / union as f64
+}
+
+/// Key identifying a question by its normalized stem and choices, independent of numbering and choice order.
+pub(crate) fn question_key(question: &Question) -> (String, BTreeSet<String>) {
+    let choices = question.choices.values().map(|c| normalize(c)).collect();
+    (normalize(&question.text), choices)
+}
+
+/// 64-bit FNV-1a hash, used where hashes are persisted and must not change between builds.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Id of a question that stays the same across extractions as long as its stem and choices do.
+pub(crate) fn stable_id(question: &Question) -> String {
+    let (stem, choices) = question_key(question);
+    let mut key = stem;
+    for choice in choices {
+        key.push('\u{1f}');
+        key.push_str(&choice);
+    }
+    format!("{:016x}", fnv1a(key.as_bytes()))
+}
+
+/// Removes questions whose stem and choices exactly repeat an earlier question, returning how many were dropped.
+pub(crate) fn drop_exact_duplicates(questions: &mut Vec<Question>) -> usize {
+    let before = questions.len();
+    let mut seen = HashSet::new();
+    questions.retain(|q| seen.insert(question_key(q)));
+    before - questions.len()
+}
@@ -0,0 +1,183 @@
+use clap::Args;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::crypt::Crypto;
+use crate::difficulty::{self, Level};
+use crate::keywords;
+use crate::kind::QuestionKind;
+use crate::learner;
+use crate::lifecycle;
+use crate::similarity::stable_id;
+use crate::{load_from_json, Bank, MatchPair, Question, Statement};
+
+// Static quiz site generated from a bank: an HTML page, a script and a stylesheet compiled into the binary, and the
+// questions written as a script assigning a global, so the site works from a plain directory, `file://` included, and
+// can be published on any static host such as GitHub Pages. The page shows one question at a time with navigation,
+// scores each answer on checking and filters the questions by section, difficulty and kind. Sections are consecutive
+// runs of questions, since dumps do not mark topics. Questions can be bookmarked and annotated; the notes are kept in
+// the browser by question id and exported from the page for `notes export`. The page also remembers, by id, every
+// question answered wrong in any session until it has been answered right `--streak` times in a row since; "Missed
+// only" (on from the start with `--missed`) drills just those. With study profiles (`learner`) the page keeps all of
+// that per profile, the questions being shared.
+
+const INDEX: &str = include_str!("assets/site/index.html");
+const SCRIPT: &str = include_str!("assets/site/quiz.js");
+const STYLE: &str = include_str!("assets/site/style.css");
+const DATA_FILE: &str = "questions.js";
+
+#[derive(Args, Debug)]
+pub struct SiteArgs {
+    /// Question bank to publish
+    input: String,
+    /// Directory to write the site into
+    #[arg(short, long, default_value = "site")]
+    output: String,
+    /// Number of consecutive questions per section
+    #[arg(long, default_value_t = 50)]
+    section_size: usize,
+    /// Also publish retired and superseded questions
+    #[arg(long)]
+    include_retired: bool,
+    /// Open in the wrong-answer drill: only the questions answered wrong in earlier sessions
+    #[arg(long)]
+    missed: bool,
+    /// Consecutive correct answers that take a question out of the drill
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    streak: u32,
+    /// Study profile the page opens with, added to the profiles of the site when missing
+    #[arg(long)]
+    profile: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SiteQuestion<'a> {
+    number: &'a str,
+    /// Stable id, the key of the bookmarks and notes
+    id: String,
+    text: String,
+    kind: QuestionKind,
+    /// Choices sorted by letter, as letter and text
+    choices: Vec<(&'a str, &'a str)>,
+    answers: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truth: Option<bool>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    order: &'a [String],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    statements: &'a [Statement],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    premises: &'a [Statement],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pairs: &'a [MatchPair],
+    level: Level,
+    /// Index into the section list
+    section: usize,
+}
+
+#[derive(Serialize)]
+struct SiteData<'a> {
+    title: String,
+    sections: Vec<String>,
+    questions: Vec<SiteQuestion<'a>>,
+    /// Whether the page opens in the drill of missed questions
+    missed: bool,
+    streak: u32,
+    /// Profile picked when the address names none and none was picked before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<&'a str>,
+}
+
+fn site_question(question: &Question, section: usize) -> SiteQuestion<'_> {
+    let mut choices: Vec<(&str, &str)> = question.choices.iter().map(|(l, t)| (l.as_str(), t.as_str())).collect();
+    choices.sort();
+    SiteQuestion {
+        number: &question.number,
+        id: question.id.clone().unwrap_or_else(|| stable_id(question)),
+        // the keywords are the only formatting the stem keeps, set in bold by the page
+        text: keywords::highlight(&question.text, &question.keywords, "**", "**"),
+        kind: question.kind,
+        choices,
+        answers: &question.answers,
+        truth: question.truth,
+        order: &question.order,
+        statements: &question.statements,
+        premises: &question.premises,
+        pairs: &question.pairs,
+        level: difficulty::question_level(question),
+        section,
+    }
+}
+
+fn site_data<'a>(bank: &'a Bank, section_size: usize, drill: Drill, profile: Option<&'a str>) -> SiteData<'a> {
+    let section_size = section_size.max(1);
+    let count = bank.questions.len();
+    let sections = (0..count.div_ceil(section_size))
+        .map(|section| format!("{}–{}", section * section_size + 1, ((section + 1) * section_size).min(count)))
+        .collect();
+    let title = match (&bank.meta.exam_code, &bank.meta.title) {
+        (Some(code), Some(title)) => format!("{} – {}", code, title),
+        (Some(text), None) | (None, Some(text)) => text.clone(),
+        (None, None) => "Practice quiz".to_string(),
+    };
+    SiteData {
+        title,
+        sections,
+        questions: bank
+            .questions
+            .iter()
+            .enumerate()
+            .map(|(index, question)| site_question(question, index / section_size))
+            .collect(),
+        missed: drill.missed,
+        streak: drill.streak,
+        profile,
+    }
+}
+
+/// Settings of the wrong-answer drill of the page
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Drill {
+    pub(crate) missed: bool,
+    pub(crate) streak: u32,
+}
+
+/// Writes the site for `bank` into `dir`, keeping the profiles of an earlier site there.
+pub(crate) fn write_site(
+    bank: &Bank,
+    dir: &Path,
+    section_size: usize,
+    drill: Drill,
+    profile: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let mut profiles = learner::load(dir)?;
+    if let Some(profile) = profile.filter(|profile| !profiles.iter().any(|p| p == profile)) {
+        learner::check_name(profile)?;
+        profiles.push(profile.to_string());
+    }
+    learner::save(dir, &profiles)?;
+    fs::write(dir.join("index.html"), INDEX)?;
+    fs::write(dir.join("quiz.js"), SCRIPT)?;
+    fs::write(dir.join("style.css"), STYLE)?;
+    // "</" would end the script element if the data were ever inlined into the page
+    let data = serde_json::to_string(&site_data(bank, section_size, drill, profile))?.replace("</", "<\\/");
+    fs::write(dir.join(DATA_FILE), format!("window.BANK = {};\n", data))?;
+    Ok(())
+}
+
+pub(crate) fn run(args: SiteArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let mut bank = load_from_json(&args.input, crypto)?;
+    if !args.include_retired {
+        bank = lifecycle::in_use(&bank);
+    }
+    let drill = Drill {
+        missed: args.missed,
+        streak: args.streak,
+    };
+    write_site(&bank, Path::new(&args.output), args.section_size, drill, args.profile.as_deref())?;
+    println!("Wrote a quiz site with {} question(s) to {}", bank.questions.len(), args.output);
+    Ok(())
+}
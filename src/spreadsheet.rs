@@ -0,0 +1,306 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::anki::answer_letters;
+use crate::kind::{true_false_choices, truth_value};
+use crate::{OutputError, Question};
+
+// Question banks kept in spreadsheets, exported as CSV or TSV and imported with `import --format csv --mapping`.
+// Every sheet has its own layout, so a column mapping (TOML) names the columns holding the question, the choices and
+// the answer, by header or by position. The choices are either one column per choice, lettered in column order, or
+// a single column with one choice per line. The cells are normalized on the way in: whitespace trimmed and collapsed,
+// choice labels (`A.`, `(b)`) dropped, and the answer read as letters (`B`, `A;C`) or as the text of the correct
+// choices, so `import` can validate the rows like extracted questions. A row without a question or without choices
+// is skipped, an answer naming no choice is reported by sheet line and the question kept without one.
+
+lazy_static! {
+    static ref CHOICE_LABEL: Regex = Regex::new(r"^\(?[A-Ha-h][.):]\s+").unwrap();
+}
+
+/// Layout of a sheet, e.g.
+///
+/// ```toml
+/// [columns]
+/// number = "No."
+/// question = "Question"
+/// choices = ["Option A", "Option B", "Option C", "Option D"]
+/// answer = "Correct answer"
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Mapping {
+    /// Field separator, a comma for `.csv` and a tab for `.tsv` files by default
+    delimiter: Option<char>,
+    /// Whether the first row names the columns
+    #[serde(default = "default_header")]
+    header: bool,
+    /// Rows above the table (titles, notes) to skip
+    #[serde(default)]
+    skip_rows: usize,
+    columns: Columns,
+}
+
+fn default_header() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Columns {
+    question: Column,
+    choices: Choices,
+    answer: Column,
+    /// Number printed in the sheet, kept as the source number
+    number: Option<Column>,
+    /// Explanation or comments, kept as a note of the question
+    notes: Option<Column>,
+    /// Separator of the choices in a single choices column, a line break by default
+    choice_separator: Option<String>,
+}
+
+/// A column by header or by position, counted from 1
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Column {
+    Position(usize),
+    Header(String),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Choices {
+    /// One column per choice, lettered in order
+    Columns(Vec<Column>),
+    /// One column holding every choice
+    Column(Column),
+}
+
+impl Mapping {
+    pub(crate) fn load(path: &str) -> Result<Mapping, Box<dyn Error>> {
+        let mapping: Mapping = toml::from_str(&fs::read_to_string(path)?).map_err(|e| OutputError {
+            message: format!("Invalid column mapping {}: {}", path, e),
+        })?;
+        if let Some(delimiter) = mapping.delimiter.filter(|delimiter| !delimiter.is_ascii()) {
+            let message = format!("The delimiter {:?} of the column mapping is not an ASCII character", delimiter);
+            return Err(OutputError { message }.into());
+        }
+        Ok(mapping)
+    }
+}
+
+/// Index of a column in the records, checked against the header when there is one.
+fn resolve(column: &Column, headers: Option<&[String]>) -> Result<usize, OutputError> {
+    match (column, headers) {
+        (Column::Position(0), _) => Err(OutputError::from("Column positions in the mapping start at 1")),
+        (Column::Position(position), _) => Ok(position - 1),
+        (Column::Header(name), Some(headers)) => headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| OutputError {
+                message: format!("The sheet has no column {:?}", name),
+            }),
+        (Column::Header(name), None) => Err(OutputError {
+            message: format!("Column {:?} is named but the mapping says the sheet has no header", name),
+        }),
+    }
+}
+
+/// The mapped columns resolved to indexes
+struct Layout {
+    question: usize,
+    choices: Vec<usize>,
+    answer: usize,
+    number: Option<usize>,
+    notes: Option<usize>,
+}
+
+fn normalize_cell(cell: &str) -> String {
+    cell.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The choices of a row, lettered in order, without their own labels.
+fn row_choices(cells: Vec<&str>, separator: Option<&str>) -> HashMap<String, String> {
+    let texts: Vec<&str> = if cells.len() == 1 {
+        cells[0].split(separator.unwrap_or("\n")).collect()
+    } else {
+        cells
+    };
+    let texts = texts.iter().map(|text| CHOICE_LABEL.replace(text.trim(), "")).map(|text| normalize_cell(&text));
+    let letters = (b'A'..=b'Z').map(|letter| char::from(letter).to_string());
+    letters.zip(texts.filter(|text| !text.is_empty())).collect()
+}
+
+/// The questions of a sheet in row order, or why a row is not one.
+pub(crate) fn read_sheet(
+    path: &Path,
+    mapping: &Mapping,
+) -> Result<Vec<Result<Question, &'static str>>, Box<dyn Error>> {
+    let tab_separated = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("tsv"));
+    let delimiter = mapping.delimiter.unwrap_or(if tab_separated { '\t' } else { ',' });
+    let text = fs::read_to_string(path)?;
+    // Spreadsheet programs write a byte order mark in UTF-8 CSV files
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut rows = reader.records().skip(mapping.skip_rows).collect::<Result<Vec<_>, _>>()?.into_iter();
+    let headers: Option<Vec<String>> = if mapping.header {
+        let header = rows.next().ok_or_else(|| OutputError::from("The sheet has no header row"))?;
+        Some(header.iter().map(str::to_string).collect())
+    } else {
+        None
+    };
+
+    let columns = &mapping.columns;
+    let headers = headers.as_deref();
+    let layout = Layout {
+        question: resolve(&columns.question, headers)?,
+        choices: match &columns.choices {
+            Choices::Columns(choices) => {
+                choices.iter().map(|column| resolve(column, headers)).collect::<Result<_, _>>()?
+            }
+            Choices::Column(column) => vec![resolve(column, headers)?],
+        },
+        answer: resolve(&columns.answer, headers)?,
+        number: columns.number.as_ref().map(|column| resolve(column, headers)).transpose()?,
+        notes: columns.notes.as_ref().map(|column| resolve(column, headers)).transpose()?,
+    };
+
+    let mut questions = Vec::new();
+    for record in rows {
+        let line = record.position().map_or(0, |position| position.line());
+        let cell = |index: usize| record.get(index).unwrap_or_default();
+        if record.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let text = normalize_cell(cell(layout.question));
+        if text.is_empty() {
+            questions.push(Err("no question text"));
+            continue;
+        }
+
+        let choices = layout.choices.iter().map(|&index| cell(index)).collect();
+        let mut question = Question {
+            text,
+            choices: row_choices(choices, columns.choice_separator.as_deref()),
+            source_number: layout.number.and_then(|index| cell(index).trim().parse().ok()),
+            ..Question::default()
+        };
+        let answer = normalize_cell(cell(layout.answer));
+        if question.choices.is_empty() {
+            let Some(truth) = truth_value(&answer) else {
+                questions.push(Err("no choices"));
+                continue;
+            };
+            true_false_choices(&mut question, truth);
+        } else {
+            question.answers = answer_letters(&answer, &question.choices);
+            if !answer.is_empty() && question.answers.is_empty() {
+                eprintln!("warning[csv-import] line {}: the answer {:?} names no choice", line, answer);
+            }
+        }
+        if let Some(note) = layout.notes.map(|index| normalize_cell(cell(index))).filter(|note| !note.is_empty()) {
+            question.notes.push(note);
+        }
+        questions.push(Ok(question));
+    }
+    Ok(questions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = "\u{feff}EWM practice questions,,,,,,\n\
+        No.,Question,Option A,Option B,Option C,Correct answer,Explanation\n\
+        1,Which transaction shows the  warehouse monitor?,A. /SCWM/MON,B. /SCWM/PRDI,,A,\n\
+        2,\"Which objects can a wave hold?\",Warehouse requests,Bins,Warehouse request items,\"A; C\",Waves group\n\
+        3,Putaway strategies are set per storage type.,,,,True,\n\
+        ,,,,,,\n\
+        4,,P1,B1,,A,\n\
+        5,Which bin type fits pallets?,,,,P1,\n\
+        6,Which queue is used for picking?,Q1,Q2,,D,\n";
+
+    const MAPPING: &str = "skip_rows = 1\n\n[columns]\nnumber = \"No.\"\nquestion = \"Question\"\n\
+        choices = [\"Option A\", \"Option B\", \"Option C\"]\nanswer = \"Correct answer\"\nnotes = \"Explanation\"\n";
+
+    fn choices(choices: &[(&str, &str)]) -> HashMap<String, String> {
+        choices.iter().map(|(letter, text)| (letter.to_string(), text.to_string())).collect()
+    }
+
+    /// The questions of a sheet read with a mapping, both written to a temp dir first.
+    fn read(name: &str, sheet: &str, mapping: &str) -> Result<Vec<Result<Question, &'static str>>, Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("s4wm-spreadsheet-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (path, mapping_path) = (dir.join(name), dir.join("mapping.toml"));
+        fs::write(&path, sheet).unwrap();
+        fs::write(&mapping_path, mapping).unwrap();
+        let questions = Mapping::load(mapping_path.to_str().unwrap()).and_then(|mapping| read_sheet(&path, &mapping));
+        let _ = fs::remove_dir_all(&dir);
+        questions
+    }
+
+    #[test]
+    fn sheets() {
+        let questions = read("bank.csv", SHEET, MAPPING).unwrap();
+        assert_eq!(questions.len(), 6);
+
+        let monitor = questions[0].as_ref().unwrap();
+        assert_eq!(monitor.text, "Which transaction shows the warehouse monitor?");
+        assert_eq!(monitor.choices, choices(&[("A", "/SCWM/MON"), ("B", "/SCWM/PRDI")]));
+        assert_eq!(monitor.answers, ["A"]);
+        assert_eq!(monitor.source_number, Some(1));
+        assert!(monitor.notes.is_empty());
+
+        let wave = questions[1].as_ref().unwrap();
+        assert_eq!(wave.text, "Which objects can a wave hold?");
+        let expected = [("A", "Warehouse requests"), ("B", "Bins"), ("C", "Warehouse request items")];
+        assert_eq!(wave.choices, choices(&expected));
+        assert_eq!(wave.answers, ["A", "C"]);
+        assert_eq!(wave.notes, ["Waves group"]);
+
+        let putaway = questions[2].as_ref().unwrap();
+        assert_eq!(putaway.choices, choices(&[("A", "True"), ("B", "False")]));
+        assert_eq!(putaway.answers, ["A"]);
+
+        assert_eq!(questions[3].as_ref().err(), Some(&"no question text"));
+        assert_eq!(questions[4].as_ref().err(), Some(&"no choices"));
+        // An answer naming no choice keeps the question, for validation to report
+        let queue = questions[5].as_ref().unwrap();
+        assert_eq!(queue.choices.len(), 2);
+        assert!(queue.answers.is_empty());
+    }
+
+    #[test]
+    fn choice_columns_and_positions() {
+        let sheet = "7\tWhich bin type fits pallets?\t\"(a) P1\n(b) B1\"\tP1\n";
+        let mapping = "header = false\n\n[columns]\nnumber = 1\nquestion = 2\nchoices = 3\nanswer = 4\n";
+        let questions = read("bank.tsv", sheet, mapping).unwrap();
+        let bins = questions[0].as_ref().unwrap();
+        assert_eq!(bins.text, "Which bin type fits pallets?");
+        assert_eq!(bins.choices, choices(&[("A", "P1"), ("B", "B1")]));
+        assert_eq!(bins.answers, ["A"]);
+        assert_eq!(bins.source_number, Some(7));
+    }
+
+    #[test]
+    fn broken_mappings() {
+        let error = |sheet: &str, mapping: &str| read("bank.csv", sheet, mapping).err().unwrap().to_string();
+        let unknown = MAPPING.replace("\"Explanation\"", "\"Comments\"");
+        assert_eq!(error(SHEET, &unknown), "The sheet has no column \"Comments\"");
+        let headless = MAPPING.replace("skip_rows = 1", "header = false");
+        let message = "Column \"Question\" is named but the mapping says the sheet has no header";
+        assert_eq!(error(SHEET, &headless), message);
+        let zero = "header = false\n\n[columns]\nquestion = 0\nchoices = [2, 3]\nanswer = 4\n";
+        assert_eq!(error(SHEET, zero), "Column positions in the mapping start at 1");
+        assert!(error(SHEET, "[columns]\nquestion = \"Question\"\n").starts_with("Invalid column mapping"));
+        assert!(error(SHEET, &format!("delimiter = \"§\"\n{}", MAPPING)).contains("is not an ASCII character"));
+        assert_eq!(error("EWM practice questions\n", MAPPING), "The sheet has no header row");
+    }
+}
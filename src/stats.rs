@@ -0,0 +1,231 @@
+use clap::Args;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::crypt::Crypto;
+use crate::readability::{self, Readability};
+use crate::{load_from_json, Question};
+
+// Bank statistics. Besides plain counts this includes the distribution of correct-answer letters, which should be
+// roughly uniform; a strong skew usually means an answer key was merged with an offset, and the length and
+// readability of the stems, whose outliers are usually junk questions (see `readability`).
+
+/// Fewer answered questions than this make the skew test meaningless
+const MIN_ANSWERED_FOR_BIAS: usize = 20;
+
+/// Stems longer than this are counted apart, they are rarely genuine exam questions
+const VERY_LONG_STEM_WORDS: usize = 100;
+
+/// Longest stems listed
+const LONGEST_LISTED: usize = 5;
+
+/// Standard normal quantile for p = 0.001, used for the chi-square critical value
+const BIAS_Z: f64 = 3.09;
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    /// Question bank to analyse
+    input: String,
+    /// Print the statistics as JSON
+    #[arg(long)]
+    json: bool,
+    /// Also print the length and reading ease of every stem
+    #[arg(long)]
+    per_question: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct PositionBias {
+    /// Correct-answer count per choice letter
+    pub(crate) observed: BTreeMap<String, usize>,
+    /// Count expected per letter if answers were spread uniformly over the available choices
+    pub(crate) expected: BTreeMap<String, f64>,
+    pub(crate) chi_square: f64,
+    pub(crate) critical_value: f64,
+    /// True when the skew is implausible at p < 0.001
+    pub(crate) skewed: bool,
+}
+
+impl PositionBias {
+    pub(crate) fn describe(&self) -> String {
+        let total: usize = self.observed.values().sum();
+        self.observed
+            .iter()
+            .map(|(letter, count)| format!("{} {:.0}%", letter, *count as f64 * 100.0 / total.max(1) as f64))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct StemReadability {
+    number: String,
+    #[serde(flatten)]
+    readability: Readability,
+}
+
+#[derive(Serialize, Debug)]
+struct ReadabilitySummary {
+    average_words: f64,
+    average_sentences: f64,
+    average_reading_ease: f64,
+    /// Stems longer than `VERY_LONG_STEM_WORDS`
+    very_long_stems: usize,
+    /// The longest stems, longest first
+    longest: Vec<StemReadability>,
+}
+
+#[derive(Serialize, Debug)]
+struct Stats {
+    questions: usize,
+    answered: usize,
+    multiple_answer: usize,
+    average_choices: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position_bias: Option<PositionBias>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    readability: Option<ReadabilitySummary>,
+    /// Length and reading ease of every stem, with `--per-question`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stems: Vec<StemReadability>,
+}
+
+/// Wilson-Hilferty approximation of the chi-square quantile for `df` degrees of freedom.
+fn chi_square_critical(df: f64, z: f64) -> f64 {
+    let a = 2.0 / (9.0 * df);
+    df * (1.0 - a + z * a.sqrt()).powi(3)
+}
+
+/// Tests the correct-answer letters of single-answer questions against a uniform distribution over each question's
+/// choices. Returns `None` when there are too few answered questions to judge.
+pub(crate) fn position_bias(questions: &[Question]) -> Option<PositionBias> {
+    let mut observed: BTreeMap<String, usize> = BTreeMap::new();
+    let mut expected: BTreeMap<String, f64> = BTreeMap::new();
+    let mut answered = 0;
+
+    for question in questions {
+        if question.answers.len() != 1 || !question.choices.contains_key(&question.answers[0]) {
+            continue;
+        }
+        answered += 1;
+        let share = 1.0 / question.choices.len() as f64;
+        for letter in question.choices.keys() {
+            *expected.entry(letter.clone()).or_insert(0.0) += share;
+            observed.entry(letter.clone()).or_insert(0);
+        }
+        *observed.entry(question.answers[0].clone()).or_insert(0) += 1;
+    }
+
+    if answered < MIN_ANSWERED_FOR_BIAS || expected.len() < 2 {
+        return None;
+    }
+
+    let chi_square: f64 = observed
+        .iter()
+        .map(|(letter, &count)| {
+            let e = expected[letter];
+            (count as f64 - e).powi(2) / e
+        })
+        .sum();
+    let critical_value = chi_square_critical((expected.len() - 1) as f64, BIAS_Z);
+
+    Some(PositionBias {
+        observed,
+        expected,
+        chi_square,
+        critical_value,
+        skewed: chi_square > critical_value,
+    })
+}
+
+fn stems(questions: &[Question]) -> Vec<StemReadability> {
+    questions
+        .iter()
+        .map(|question| StemReadability {
+            number: question.number.clone(),
+            readability: readability::stem(question),
+        })
+        .collect()
+}
+
+fn readability_summary(questions: &[Question]) -> Option<ReadabilitySummary> {
+    let mut stems = stems(questions);
+    stems.retain(|stem| stem.readability.words > 0);
+    if stems.is_empty() {
+        return None;
+    }
+    let count = stems.len() as f64;
+    let total = |value: fn(&Readability) -> f64| stems.iter().map(|stem| value(&stem.readability)).sum::<f64>();
+    let (words, sentences) = (total(|r| r.words as f64), total(|r| r.sentences as f64));
+    let ease = total(|r| r.reading_ease);
+    let very_long_stems = stems.iter().filter(|stem| stem.readability.words > VERY_LONG_STEM_WORDS).count();
+    stems.sort_by_key(|stem| Reverse(stem.readability.words));
+    stems.truncate(LONGEST_LISTED);
+    Some(ReadabilitySummary {
+        average_words: words / count,
+        average_sentences: sentences / count,
+        average_reading_ease: ease / count,
+        very_long_stems,
+        longest: stems,
+    })
+}
+
+fn compute(questions: &[Question], per_question: bool) -> Stats {
+    let total_choices: usize = questions.iter().map(|q| q.choices.len()).sum();
+    Stats {
+        questions: questions.len(),
+        answered: questions.iter().filter(|q| !q.answers.is_empty()).count(),
+        multiple_answer: questions.iter().filter(|q| q.answers.len() > 1).count(),
+        average_choices: total_choices as f64 / questions.len().max(1) as f64,
+        position_bias: position_bias(questions),
+        readability: readability_summary(questions),
+        stems: if per_question { stems(questions) } else { Vec::new() },
+    }
+}
+
+pub(crate) fn run(args: StatsArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let bank = load_from_json(&args.input, crypto)?;
+    let stats = compute(&bank.questions, args.per_question);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!("Questions:        {}", stats.questions);
+    println!("Answered:         {}", stats.answered);
+    println!("Multiple answer:  {}", stats.multiple_answer);
+    println!("Average choices:  {:.1}", stats.average_choices);
+    match &stats.position_bias {
+        Some(bias) => {
+            println!("Answer letters:   {}", bias.describe());
+            println!(
+                "Position bias:    chi² = {:.1} (critical {:.1}){}",
+                bias.chi_square,
+                bias.critical_value,
+                if bias.skewed { ", implausible skew, check the answer key alignment" } else { "" }
+            );
+        }
+        None => println!("Answer letters:   too few answered questions to analyse"),
+    }
+    if let Some(summary) = &stats.readability {
+        println!(
+            "Stem length:      {:.1} words, {:.1} sentences on average; {} over {} words",
+            summary.average_words, summary.average_sentences, summary.very_long_stems, VERY_LONG_STEM_WORDS
+        );
+        println!("Reading ease:     {:.0} on average (Flesch, higher is easier)", summary.average_reading_ease);
+        let longest: Vec<String> =
+            summary.longest.iter().map(|stem| format!("{} ({} words)", stem.number, stem.readability.words)).collect();
+        println!("Longest stems:    {}", longest.join(", "));
+    }
+    for stem in &stats.stems {
+        let readability = &stem.readability;
+        println!(
+            "{:>8}  {:>4} words  {:>2} sentences  ease {:>4.0}",
+            stem.number, readability.words, readability.sentences, readability.reading_ease
+        );
+    }
+    Ok(())
+}
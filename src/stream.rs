@@ -0,0 +1,221 @@
+use lopdf::Document;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::incremental::page_hash;
+use crate::manifest;
+use crate::meta::{self, BankMeta};
+use crate::pages::{self, page_text, PageTexts};
+use crate::parser::{Parsed, QuestionParser};
+use crate::profile::{self, AnswerStrategy, ParserProfile, Profile};
+use crate::progress::Progress;
+use crate::timings::Timings;
+use crate::tree::Layout;
+use crate::{annotate_questions, bank_path, Bank, ExtractOptions, OutputError, Question};
+
+// Streamed extraction (`--stream`) for documents too large to hold in memory as text: pages are extracted one at a
+// time, fed to the parser and dropped, and every completed question is annotated and appended to
+// `questions.ndjson` (one JSON question per line) right away. Only the parsed PDF, the current page and the stable
+// ids of the questions written so far stay in memory. The bank header goes to `questions.meta.json` at the end;
+// commands reading banks accept the `.ndjson` file.
+//
+// Steps that need the whole document are left out or reduced: the profile is detected and the header read from the
+// first pages, pages are read in text order without column analysis, answers come from markers and asterisks only,
+// and validation is limited to exact duplicates and the stated question count.
+
+pub(crate) const NDJSON_FILE: &str = "questions.ndjson";
+/// Pages read up front for profile detection and the bank header
+const HEAD_PAGES: usize = 5;
+
+/// Options that need the whole document in memory.
+fn check_options(options: &ExtractOptions) -> Result<(), OutputError> {
+    let unsupported = [
+        (options.columns.is_some_and(|columns| columns > 1), "--columns"),
+        (options.previous.is_some(), "--previous"),
+        (options.layout != Layout::Single, "--layout"),
+        (!options.formats.is_empty(), "--format"),
+        (options.page_timeout.is_some(), "--page-timeout"),
+        (options.record.is_some() || options.replay.is_some(), "--record/--replay"),
+        (options.dump_text.is_some() || options.dump_intermediate.is_some(), "--dump-text/--dump-intermediate"),
+        (options.explain, "--explain"),
+        (options.history.is_some(), "--history"),
+        (options.encrypt, "--encrypt"),
+        (options.schema.is_some(), "--schema"),
+        (options.chapter.is_some(), "--chapter"),
+    ];
+    match unsupported.iter().find(|(given, _)| *given) {
+        Some((_, flag)) => Err(OutputError {
+            message: format!("{} cannot be combined with --stream", flag),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Annotates the questions and appends them to the output. Returns the number written.
+fn write_questions(
+    writer: &mut impl Write,
+    parsed: Parsed,
+    seen: &mut HashSet<String>,
+    drop_duplicates: bool,
+    progress: &mut dyn Progress,
+) -> Result<usize, Box<dyn Error>> {
+    for issue in &parsed.issues {
+        progress.diagnostic("warning", "parse", issue.to_string());
+    }
+    let mut questions = parsed.questions;
+    annotate_questions(&mut questions);
+    let mut written = 0;
+    for question in questions {
+        if !seen.insert(question.id.clone().unwrap_or_default()) {
+            if drop_duplicates {
+                continue;
+            }
+            let message = format!("question {}: exact duplicate of an earlier question", question.number);
+            progress.diagnostic("warning", "duplicate-question", message);
+        }
+        serde_json::to_writer(&mut *writer, &question)?;
+        writer.write_all(b"\n")?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Streams the questions of `pdf_path` into `output_dir`, adding the time of the stages to `timings`. As the stages
+/// take turns page after page, each page is timed. Returns the number of saved questions.
+pub(crate) fn extract(
+    options: &ExtractOptions,
+    pdf_path: &str,
+    output_dir: &Path,
+    timings: &mut Timings,
+    progress: &mut dyn Progress,
+) -> Result<usize, Box<dyn Error>> {
+    check_options(options)?;
+    let started = SystemTime::now();
+    timings.start("extract");
+    let document = Document::load(pdf_path)?;
+    let pages: Vec<u32> = document.get_pages().keys().copied().collect();
+    progress.pages(pages.len());
+    let skip = options.skip_pages.as_deref().map(pages::parse_page_list).transpose()?.unwrap_or_default();
+    let text_of = |index: usize, page: u32| {
+        if skip.contains(&(index + 1)) {
+            Ok(String::new())
+        } else {
+            page_text(&document, page)
+        }
+    };
+
+    let mut head: Vec<String> =
+        pages.iter().take(HEAD_PAGES).enumerate().map(|(index, &page)| text_of(index, page)).collect::<Result<_, _>>()?;
+    timings.start("parse");
+    let head_text = head.join("\n");
+    let mut profile = match &options.profile {
+        Some(name) => Profile::by_name(name).ok_or_else(|| OutputError::from("Unknown parsing profile"))?,
+        None => profile::detect(&head_text),
+    };
+    if !options.answer_strategy.is_empty() {
+        profile.answer_strategies = Cow::Owned(options.answer_strategy.clone());
+    }
+    if !options.typography.is_empty() {
+        profile.typography = Cow::Owned(options.typography.clone());
+    }
+    if let Some(element_order) = options.element_order {
+        profile.element_order = element_order;
+    }
+    if profile.uses(AnswerStrategy::Bold) || profile.uses(AnswerStrategy::Color) {
+        progress.info("Bold and colored answers are not detected when streaming".to_string());
+    }
+    let patterns = ParserProfile::cached(&profile)?;
+    let mut meta = meta::extract_meta(pdf_path, &head_text, &patterns.question);
+    meta.lang = Some(profile.lang.to_string());
+    meta.exam_code = options.exam_code.clone().or(meta.exam_code);
+    drop(head_text);
+
+    timings.start("export");
+    fs::create_dir_all(output_dir)?;
+    let ndjson_path = bank_path(options, output_dir);
+    let mut writer = BufWriter::new(File::create(&ndjson_path)?);
+    let mut parser = QuestionParser::new(&patterns);
+    let mut seen = HashSet::new();
+    let mut written = 0;
+    for (index, &page) in pages.iter().enumerate() {
+        timings.start("extract");
+        let text = match head.get_mut(index) {
+            Some(text) => mem::take(text),
+            None => text_of(index, page)?,
+        };
+        timings.start("parse");
+        meta.page_hashes.push(page_hash(&text));
+        parser.set_page(index + 1);
+        for line in text.split('\n') {
+            parser.parse_line(line);
+        }
+        progress.extracted(index + 1);
+        progress.page(index, parser.question_count());
+        let complete = parser.take_complete();
+        timings.start("export");
+        written += write_questions(&mut writer, complete, &mut seen, options.drop_duplicates, progress)?;
+    }
+    written += write_questions(&mut writer, parser.finish(), &mut seen, options.drop_duplicates, progress)?;
+    writer.flush()?;
+    progress.parsed(written);
+
+    timings.start("validate");
+    if let Some(expected) = meta.stated_question_count.filter(|&expected| expected != written) {
+        let message = format!("parsed {} questions but the document states {}", written, expected);
+        if options.strict {
+            return Err(OutputError::from(message.as_str()).into());
+        }
+        progress.diagnostic("error", "count-mismatch", message);
+    }
+
+    timings.start("export");
+    let meta_path = meta_path(&ndjson_path);
+    fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)?;
+    let skipped = PageTexts {
+        skipped: skip.into_iter().filter(|&page| page <= pages.len()).collect(),
+        ..PageTexts::default()
+    };
+    let outputs = [ndjson_path, meta_path];
+    timings.stop();
+    let skipped_pages = manifest::skipped_pages(&skipped, None);
+    manifest::write_manifest(output_dir, pdf_path, &profile, started, timings, &skipped_pages, &outputs)?;
+    Ok(written)
+}
+
+fn meta_path(ndjson_path: &Path) -> PathBuf {
+    ndjson_path.with_extension("meta.json")
+}
+
+/// Number of questions in a streamed bank, without loading it.
+pub(crate) fn count(path: &Path) -> Result<usize, OutputError> {
+    let mut count = 0;
+    for line in BufReader::new(File::open(path)?).lines() {
+        if !line?.trim().is_empty() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Reads a streamed bank, with the header from the `.meta.json` file next to it when present.
+pub(crate) fn load(path: &str) -> Result<Bank, OutputError> {
+    let mut questions = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            questions.push(serde_json::from_str::<Question>(&line)?);
+        }
+    }
+    let meta_path = meta_path(Path::new(path));
+    let meta: BankMeta = match fs::read_to_string(meta_path) {
+        Ok(meta) => serde_json::from_str(&meta)?,
+        Err(_) => BankMeta::default(),
+    };
+    Ok(Bank { meta, questions })
+}
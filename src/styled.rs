@@ -0,0 +1,329 @@
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::layout::MIN_COLUMN_GAP;
+use crate::similarity::normalize;
+use crate::Question;
+
+// Styled text extraction. `pdf_extract` only yields plain text, so this walks the page content streams with lopdf
+// to recover which text runs were set in a bold font or a non-default fill color, grouped into lines. Text is decoded
+// as a single-byte encoding, which covers the WinAnsi/Standard encoded fonts dumps are usually produced with; runs in
+// CID fonts come out garbled and simply never match a choice, nor the page text when reading columns.
+
+pub(crate) type Rgb = [u8; 3];
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct StyledLine {
+    pub(crate) page: usize,
+    pub(crate) text: String,
+    /// Number of non-whitespace characters set in a bold font
+    pub(crate) bold_chars: usize,
+    /// Fill color of the majority of the line's characters
+    pub(crate) color: Option<Rgb>,
+    /// Start of the line in text space (the page transformation set with `cm` is not applied)
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+}
+
+impl StyledLine {
+    pub(crate) fn is_bold(&self) -> bool {
+        let total = self.text.chars().filter(|c| !c.is_whitespace()).count();
+        total > 0 && self.bold_chars * 2 > total
+    }
+}
+
+/// Answers found from styling that disagree with the ones from marker lines or asterisks, which are kept.
+#[derive(Debug, Clone)]
+pub(crate) struct StyleConflict {
+    pub(crate) question: String,
+    pub(crate) marked: Vec<String>,
+    pub(crate) styled: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StyleOutcome {
+    /// Questions that received their answer from styling
+    pub(crate) answered: usize,
+    pub(crate) conflicts: Vec<StyleConflict>,
+}
+
+fn number(object: &Object) -> Option<f64> {
+    match object {
+        Object::Integer(i) => Some(*i as f64),
+        Object::Real(r) => Some(f64::from(*r)),
+        _ => None,
+    }
+}
+
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    match object {
+        Object::Reference(id) => document.get_object(*id).ok(),
+        object => Some(object),
+    }
+}
+
+/// Font resources of a page, following inherited `Resources` up the page tree.
+fn page_fonts(document: &Document, page_id: ObjectId) -> HashMap<Vec<u8>, bool> {
+    let mut fonts = HashMap::new();
+    let mut node = document.get_object(page_id).ok().and_then(|o| o.as_dict().ok());
+    while let Some(dict) = node {
+        let font_dict = dict
+            .get(b"Resources")
+            .ok()
+            .and_then(|r| resolve(document, r))
+            .and_then(|r| r.as_dict().ok())
+            .and_then(|r| r.get(b"Font").ok())
+            .and_then(|f| resolve(document, f))
+            .and_then(|f| f.as_dict().ok());
+        if let Some(font_dict) = font_dict {
+            for (name, font) in font_dict.iter() {
+                fonts.entry(name.clone()).or_insert_with(|| is_bold_font(document, font));
+            }
+        }
+        node = dict.get(b"Parent").ok().and_then(|p| resolve(document, p)).and_then(|p| p.as_dict().ok());
+    }
+    fonts
+}
+
+fn is_bold_font(document: &Document, font: &Object) -> bool {
+    let base_font = resolve(document, font)
+        .and_then(|f| f.as_dict().ok())
+        .and_then(|f: &Dictionary| f.get(b"BaseFont").ok());
+    match base_font {
+        Some(Object::Name(name)) => {
+            let name = String::from_utf8_lossy(name).to_lowercase();
+            ["bold", "black", "heavy", "semibold", "demi"].iter().any(|w| name.contains(w))
+        }
+        _ => false,
+    }
+}
+
+fn channel(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Fill color from the operands of `g`, `rg`, `k`, `sc` or `scn`, told apart by their number of components.
+/// Pattern names passed to `scn` are skipped.
+fn fill_color(operands: &[Object]) -> Option<Rgb> {
+    let values: Vec<f64> = operands.iter().filter_map(number).collect();
+    match values[..] {
+        [gray] => Some([channel(gray); 3]),
+        [r, g, b] => Some([channel(r), channel(g), channel(b)]),
+        [c, m, y, k] => Some([
+            channel((1.0 - c) * (1.0 - k)),
+            channel((1.0 - m) * (1.0 - k)),
+            channel((1.0 - y) * (1.0 - k)),
+        ]),
+        _ => None,
+    }
+}
+
+fn dominant_color(colors: &HashMap<Rgb, usize>) -> Option<Rgb> {
+    colors.iter().max_by_key(|&(color, count)| (*count, *color)).map(|(color, _)| *color)
+}
+
+fn decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Extracts the text lines of every page, or of the given pages only, with their position, fill color and number of
+/// bold characters.
+pub(crate) fn extract_styled_lines(
+    pdf_path: &str,
+    pages: Option<&BTreeSet<usize>>,
+) -> Result<Vec<StyledLine>, lopdf::Error> {
+    let document = Document::load(pdf_path)?;
+    let mut lines = Vec::new();
+
+    for (page_number, page_id) in document.get_pages() {
+        if pages.is_some_and(|pages| !pages.contains(&(page_number as usize))) {
+            continue;
+        }
+        let fonts = page_fonts(&document, page_id);
+        let content = match document.get_page_content(page_id).and_then(|c| Content::decode(&c)) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        let mut current = StyledLine {
+            page: page_number as usize,
+            ..StyledLine::default()
+        };
+        let mut line_colors: HashMap<Rgb, usize> = HashMap::new();
+        let mut bold = false;
+        // the fill color is part of the graphics state saved and restored by q/Q
+        let mut color: Rgb = [0, 0, 0];
+        let mut saved_colors: Vec<Rgb> = Vec::new();
+        // origin of the current text line and the leading used by T*, ' and "
+        let mut origin = (0.0, 0.0);
+        let mut leading = 0.0;
+        let mut shown_at: Option<(f64, f64)> = None;
+
+        for operation in &content.operations {
+            let mut text = String::new();
+            let operand = |i: usize| operation.operands.get(i).and_then(number).unwrap_or(0.0);
+            match operation.operator.as_str() {
+                "q" => saved_colors.push(color),
+                "Q" => color = saved_colors.pop().unwrap_or(color),
+                "g" | "rg" | "k" | "sc" | "scn" => {
+                    if let Some(fill) = fill_color(&operation.operands) {
+                        color = fill;
+                    }
+                }
+                "Tf" => {
+                    if let Some(Object::Name(name)) = operation.operands.first() {
+                        bold = fonts.get(name).copied().unwrap_or(false);
+                    }
+                }
+                "BT" => origin = (0.0, 0.0),
+                "TL" => leading = operand(0),
+                "Td" => origin = (origin.0 + operand(0), origin.1 + operand(1)),
+                "TD" => {
+                    leading = -operand(1);
+                    origin = (origin.0 + operand(0), origin.1 + operand(1));
+                }
+                "Tm" => origin = (operand(4), operand(5)),
+                "T*" => origin.1 -= leading,
+                "Tj" => {
+                    if let Some(Object::String(bytes, _)) = operation.operands.first() {
+                        text = decode(bytes);
+                    }
+                }
+                "'" | "\"" => {
+                    origin.1 -= leading;
+                    if let Some(Object::String(bytes, _)) = operation.operands.last() {
+                        text = decode(bytes);
+                    }
+                }
+                "TJ" => {
+                    if let Some(Object::Array(parts)) = operation.operands.first() {
+                        for part in parts {
+                            match part {
+                                Object::String(bytes, _) => text.push_str(&decode(bytes)),
+                                // large negative kerning is how many producers encode a word space
+                                other => {
+                                    if number(other).is_some_and(|n| n < -200.0) {
+                                        text.push(' ');
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            if text.is_empty() {
+                continue;
+            }
+            // a line ends when the baseline moves, or when the next run starts in another column of the same row
+            let new_line = shown_at.is_some_and(|(x, y): (f64, f64)| {
+                (origin.1 - y).abs() > f64::EPSILON || (origin.0 - x).abs() > MIN_COLUMN_GAP
+            });
+            shown_at = Some(origin);
+            if new_line && !current.text.trim().is_empty() {
+                let page = current.page;
+                current.color = dominant_color(&line_colors);
+                line_colors.clear();
+                lines.push(std::mem::replace(&mut current, StyledLine { page, ..StyledLine::default() }));
+            }
+            if current.text.is_empty() {
+                (current.x, current.y) = origin;
+            }
+            let visible = text.chars().filter(|c| !c.is_whitespace()).count();
+            if bold {
+                current.bold_chars += visible;
+            }
+            if visible > 0 {
+                *line_colors.entry(color).or_default() += visible;
+            }
+            current.text.push_str(&text);
+        }
+        if !current.text.trim().is_empty() {
+            current.color = dominant_color(&line_colors);
+            lines.push(current);
+        }
+    }
+
+    Ok(lines)
+}
+
+fn choice_line<'a>(line: &'a StyledLine, choice_regex: &Regex) -> Option<(String, &'a str)> {
+    let text = line.text.trim().trim_start_matches('*');
+    let caps = choice_regex.captures(text)?;
+    Some((caps[1].to_uppercase(), &text[caps[0].len()..]))
+}
+
+/// Per-document calibration of the answer color. Most choices are printed in the body color; the answer color is the
+/// most frequent other color on choice lines. It is rejected when it covers more than half of the choice lines, since
+/// then the colors follow the layout rather than marking answers.
+pub(crate) fn calibrate_answer_color(lines: &[StyledLine], choice_regex: &Regex) -> Option<Rgb> {
+    let mut counts: HashMap<Rgb, usize> = HashMap::new();
+    let mut choice_lines = 0;
+    for line in lines.iter().filter(|l| choice_line(l, choice_regex).is_some()) {
+        choice_lines += 1;
+        if let Some(color) = line.color {
+            *counts.entry(color).or_default() += 1;
+        }
+    }
+
+    let body = dominant_color(&counts)?;
+    counts.remove(&body);
+    let answer = dominant_color(&counts)?;
+    (counts[&answer] * 2 <= choice_lines).then_some(answer)
+}
+
+/// Answer detection from styling: choice lines for which `is_marked` holds mark the correct answers. Questions without
+/// an answer take the styled one; for questions already answered by a marker line or an asterisk the explicit answer
+/// wins and disagreements are reported as conflicts.
+pub(crate) fn apply_styled_answers(
+    lines: &[StyledLine],
+    questions: &mut [Question],
+    choice_regex: &Regex,
+    is_marked: impl Fn(&StyledLine) -> bool,
+) -> StyleOutcome {
+    // normalized choice text of marked choice lines, per page
+    let mut marked_choices: HashMap<usize, Vec<(String, String)>> = HashMap::new();
+    for line in lines.iter().filter(|l| is_marked(l)) {
+        if let Some((letter, choice)) = choice_line(line, choice_regex) {
+            marked_choices.entry(line.page).or_default().push((letter, normalize(choice)));
+        }
+    }
+
+    let mut outcome = StyleOutcome::default();
+    for question in questions.iter_mut() {
+        let Some(page) = question.page else {
+            continue;
+        };
+        // a question's choices may continue on the next page
+        let candidates = [page, page + 1].into_iter().filter_map(|p| marked_choices.get(&p)).flatten();
+        let mut letters: Vec<String> = candidates
+            .filter(|(letter, text)| question.choices.get(letter).is_some_and(|c| normalize(c) == *text))
+            .map(|(letter, _)| letter.clone())
+            .collect();
+        letters.sort();
+        letters.dedup();
+        if letters.is_empty() {
+            continue;
+        }
+
+        if question.answers.is_empty() {
+            question.correct_answers = Some(letters.len());
+            question.answers = letters;
+            outcome.answered += 1;
+        } else {
+            let mut marked = question.answers.clone();
+            marked.sort();
+            if marked != letters {
+                outcome.conflicts.push(StyleConflict {
+                    question: question.number.clone(),
+                    marked,
+                    styled: letters,
+                });
+            }
+        }
+    }
+    outcome
+}
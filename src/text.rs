@@ -0,0 +1,84 @@
+use std::borrow::Cow;
+
+// Script-aware text helpers shared by the cleaning step and the exporters.
+//
+// CJK scripts do not separate words with spaces, so lines must be joined without one and spaces that PDF extraction
+// inserts between ideographs have to go. RTL scripts (Hebrew, Arabic) need to be isolated when they are embedded in
+// left-to-right Markdown, otherwise neutral characters like choice letters and punctuation get reordered.
+
+const FIRST_STRONG_ISOLATE: char = '\u{2068}';
+const POP_DIRECTIONAL_ISOLATE: char = '\u{2069}';
+
+pub(crate) fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303f}' // CJK symbols and punctuation
+        | '\u{3040}'..='\u{309f}' // Hiragana
+        | '\u{30a0}'..='\u{30ff}' // Katakana
+        | '\u{3400}'..='\u{4dbf}' // CJK unified ideographs extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK unified ideographs
+        | '\u{f900}'..='\u{faff}' // CJK compatibility ideographs
+        | '\u{ff00}'..='\u{ffef}' // Halfwidth and fullwidth forms
+    )
+}
+
+pub(crate) fn is_rtl(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{05ff}' // Hebrew
+        | '\u{0600}'..='\u{06ff}' // Arabic
+        | '\u{0750}'..='\u{077f}' // Arabic supplement
+        | '\u{08a0}'..='\u{08ff}' // Arabic extended-A
+        | '\u{fb1d}'..='\u{fdff}' // Hebrew and Arabic presentation forms
+        | '\u{fe70}'..='\u{feff}' // Arabic presentation forms-B
+    )
+}
+
+pub(crate) fn has_rtl(text: &str) -> bool {
+    text.chars().any(is_rtl)
+}
+
+/// Removes whitespace runs that sit between two CJK characters. Text without CJK characters is returned as is.
+pub(crate) fn collapse_cjk_spaces(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(is_cjk) {
+        return Cow::Borrowed(text);
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            let start = i;
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            let between_cjk = start > 0 && is_cjk(chars[start - 1]) && chars.get(i).is_some_and(|&c| is_cjk(c));
+            if !between_cjk {
+                out.extend(&chars[start..i]);
+            }
+            continue;
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    Cow::Owned(out)
+}
+
+/// Appends a continuation line, separating it with a space unless either side of the join is CJK.
+pub(crate) fn join_line(text: &mut String, line: &str) {
+    let needs_space = match (text.chars().last(), line.chars().next()) {
+        (Some(last), Some(first)) => !last.is_whitespace() && !is_cjk(last) && !is_cjk(first),
+        _ => false,
+    };
+    if needs_space {
+        text.push(' ');
+    }
+    text.push_str(line);
+}
+
+/// Wraps text containing RTL characters in Unicode directional isolates.
+pub(crate) fn bidi_isolate(text: &str) -> Cow<'_, str> {
+    if has_rtl(text) {
+        Cow::Owned(format!("{}{}{}", FIRST_STRONG_ISOLATE, text, POP_DIRECTIONAL_ISOLATE))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
@@ -0,0 +1,116 @@
+use serde::Serialize;
+use std::fs;
+use std::time::{Duration, Instant};
+
+// Where the time of a run goes, to tell which performance work matters on large corpora. An extraction times its
+// stages: downloading the PDF, extracting the text of its pages, parsing it (answer detection and annotation
+// included), validating the questions and exporting them (bank, export formats, encryption). The run summary ends with
+// the time of every stage and the memory high-water mark of the process, and the manifest records them. All of it is
+// measured and kept locally, nothing is sent anywhere. The high-water mark is the peak resident memory reported by the
+// kernel (Linux only); in a batch run in-process it covers the files extracted so far.
+
+/// Time spent in the stages of a run, in the order they were first entered
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Timings {
+    stages: Vec<(&'static str, Duration)>,
+    current: Option<(&'static str, Instant)>,
+}
+
+/// The timings of a run as recorded in its manifest
+#[derive(Serialize, Debug)]
+pub(crate) struct StageTimes {
+    stages: Vec<StageTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peak_memory_mib: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+struct StageTime {
+    stage: &'static str,
+    seconds: f64,
+}
+
+impl Timings {
+    /// Ends the current stage, if any, and starts timing `stage`.
+    pub(crate) fn start(&mut self, stage: &'static str) {
+        self.stop();
+        self.current = Some((stage, Instant::now()));
+    }
+
+    /// Ends the current stage.
+    pub(crate) fn stop(&mut self) {
+        if let Some((stage, started)) = self.current.take() {
+            self.add(stage, started.elapsed());
+        }
+    }
+
+    /// Adds `duration` to the time of `stage`.
+    pub(crate) fn add(&mut self, stage: &'static str, duration: Duration) {
+        match self.stages.iter_mut().find(|(name, _)| *name == stage) {
+            Some((_, total)) => *total += duration,
+            None => self.stages.push((stage, duration)),
+        }
+    }
+
+    /// Adds the stage times of another run, e.g. a file of a batch.
+    pub(crate) fn merge(&mut self, other: &Timings) {
+        for &(stage, duration) in &other.stages {
+            self.add(stage, duration);
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    fn total(&self) -> Duration {
+        self.stages.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// The stage times and the peak memory, e.g. `extract 8.4s (70%), parse 2.1s (18%), ..., total 12.0s, peak memory
+    /// 412 MiB`.
+    pub(crate) fn summary(&self) -> String {
+        let total = self.total();
+        let stages: Vec<String> = self
+            .stages
+            .iter()
+            .map(|&(stage, duration)| {
+                let share = if total.is_zero() { 0.0 } else { duration.as_secs_f64() / total.as_secs_f64() * 100.0 };
+                format!("{} {} ({:.0}%)", stage, format_duration(duration), share)
+            })
+            .collect();
+        let memory = peak_memory_mib().map_or(String::new(), |mib| format!(", peak memory {} MiB", mib));
+        format!("{}, total {}{}", stages.join(", "), format_duration(total), memory)
+    }
+
+    pub(crate) fn stage_times(&self) -> StageTimes {
+        StageTimes {
+            stages: self
+                .stages
+                .iter()
+                .map(|&(stage, duration)| StageTime {
+                    stage,
+                    seconds: duration.as_secs_f64(),
+                })
+                .collect(),
+            peak_memory_mib: peak_memory_mib(),
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds < 60.0 {
+        format!("{:.1}s", seconds)
+    } else {
+        format!("{}m{:02}s", duration.as_secs() / 60, duration.as_secs() % 60)
+    }
+}
+
+/// Peak resident memory of this process in MiB, where the platform exposes it (Linux)
+pub(crate) fn peak_memory_mib() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib / 1024)
+}
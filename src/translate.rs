@@ -0,0 +1,300 @@
+use clap::{Args, ValueEnum};
+use indicatif::ProgressBar;
+use serde::Deserialize;
+use std::error::Error;
+
+use crate::crypt::Crypto;
+use crate::{open_bank, save_to_json, OutputError, Question};
+
+// Translation pipeline: runs every text of a saved question bank (stems, choices and their feedback, statements,
+// premises, emphasis and notes) through a translation backend and writes a parallel bank. Question numbers, choice
+// letters, labels and correct answers are carried over untouched so the translated bank can be used side by side with
+// the original; each translated question is tagged with `lang`.
+
+const DEEPL_ENDPOINT: &str = "https://api-free.deepl.com/v2/translate";
+const LIBRETRANSLATE_ENDPOINT: &str = "https://libretranslate.com/translate";
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Backend {
+    Deepl,
+    Libretranslate,
+}
+
+#[derive(Args, Debug)]
+pub struct TranslateArgs {
+    /// Question bank to translate
+    input: String,
+    /// Where to write the translated bank
+    output: String,
+    /// Target language code (e.g. fr, de)
+    #[arg(long)]
+    to: String,
+    /// Source language code, detected by the backend when omitted
+    #[arg(long)]
+    from: Option<String>,
+    /// Translation backend to use
+    #[arg(long, value_enum, default_value = "deepl")]
+    backend: Backend,
+    /// Override the backend endpoint URL (e.g. a self-hosted LibreTranslate)
+    #[arg(long)]
+    endpoint: Option<String>,
+    /// API key, falls back to DEEPL_AUTH_KEY or LIBRETRANSLATE_API_KEY
+    #[arg(long)]
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeeplResponse {
+    translations: Vec<DeeplTranslation>,
+}
+
+#[derive(Deserialize)]
+struct DeeplTranslation {
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LibreTranslateResponse {
+    translated_text: String,
+}
+
+struct Translator {
+    client: reqwest::Client,
+    backend: Backend,
+    endpoint: String,
+    api_key: Option<String>,
+    source: Option<String>,
+    target: String,
+}
+
+impl Translator {
+    fn new(args: &TranslateArgs) -> Self {
+        let (default_endpoint, key_var) = match args.backend {
+            Backend::Deepl => (DEEPL_ENDPOINT, "DEEPL_AUTH_KEY"),
+            Backend::Libretranslate => (LIBRETRANSLATE_ENDPOINT, "LIBRETRANSLATE_API_KEY"),
+        };
+        Translator {
+            client: reqwest::Client::new(),
+            backend: args.backend,
+            endpoint: args.endpoint.clone().unwrap_or_else(|| default_endpoint.to_string()),
+            api_key: args.api_key.clone().or_else(|| std::env::var(key_var).ok()),
+            source: args.from.clone(),
+            target: args.to.clone(),
+        }
+    }
+
+    async fn translate(&self, text: &str) -> Result<String, OutputError> {
+        if text.trim().is_empty() {
+            return Ok(text.to_string());
+        }
+        match self.backend {
+            Backend::Deepl => self.deepl(text).await,
+            Backend::Libretranslate => self.libretranslate(text).await,
+        }
+    }
+
+    async fn deepl(&self, text: &str) -> Result<String, OutputError> {
+        let key = self
+            .api_key
+            .as_deref()
+            .ok_or_else(|| OutputError::from("DeepL requires an API key (--api-key or DEEPL_AUTH_KEY)"))?;
+        let mut form = vec![("text", text.to_string()), ("target_lang", self.target.to_uppercase())];
+        if let Some(source) = &self.source {
+            form.push(("source_lang", source.to_uppercase()));
+        }
+        let body = self
+            .client
+            .post(self.endpoint.as_str())
+            .header("Authorization", format!("DeepL-Auth-Key {}", key))
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let response: DeeplResponse = serde_json::from_str(&body)?;
+        response
+            .translations
+            .into_iter()
+            .next()
+            .map(|t| t.text)
+            .ok_or_else(|| OutputError::from("DeepL returned no translation"))
+    }
+
+    async fn libretranslate(&self, text: &str) -> Result<String, OutputError> {
+        let mut form = vec![
+            ("q", text.to_string()),
+            ("source", self.source.clone().unwrap_or_else(|| "auto".to_string())),
+            ("target", self.target.clone()),
+            ("format", "text".to_string()),
+        ];
+        if let Some(key) = &self.api_key {
+            form.push(("api_key", key.clone()));
+        }
+        let body = self
+            .client
+            .post(self.endpoint.as_str())
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let response: LibreTranslateResponse = serde_json::from_str(&body)?;
+        Ok(response.translated_text)
+    }
+
+    async fn translate_question(&self, mut question: Question) -> Result<Question, OutputError> {
+        for text in texts(&mut question) {
+            *text = self.translate(text).await?;
+        }
+        question.lang = Some(self.target.to_lowercase());
+        Ok(question)
+    }
+}
+
+/// The texts of a question a reader sees, to translate: the stem, the choices and their feedback, the statements and
+/// premises the choices refer to, the emphasized parts of the stem and the notes.
+fn texts(question: &mut Question) -> Vec<&mut String> {
+    let mut texts = vec![&mut question.text];
+    texts.extend(question.choices.values_mut());
+    texts.extend(question.feedback.values_mut());
+    texts.extend(question.statements.iter_mut().map(|statement| &mut statement.text));
+    texts.extend(question.premises.iter_mut().map(|premise| &mut premise.text));
+    texts.extend(question.emphasis.iter_mut());
+    texts.extend(question.notes.iter_mut());
+    texts
+}
+
+pub(crate) async fn run(args: TranslateArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (mut bank, crypto) = open_bank(&args.input, crypto)?;
+    let translator = Translator::new(&args);
+
+    let progress_bar = ProgressBar::new(bank.questions.len() as u64);
+    let mut translated = Vec::with_capacity(bank.questions.len());
+    for question in bank.questions {
+        translated.push(translator.translate_question(question).await?);
+        progress_bar.inc(1);
+    }
+    progress_bar.finish_with_message("Translation complete");
+
+    bank.questions = translated;
+    bank.meta.lang = Some(args.to.to_lowercase());
+    save_to_json(&bank, &args.output, &crypto)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bank, MatchPair, Statement};
+    use axum::extract::Form;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::fs;
+
+    /// A translation service on a local port answering like LibreTranslate at `/translate` and like DeepL at
+    /// `/v2/translate`, the translation being the text prefixed with the target language.
+    async fn stub_backend() -> String {
+        async fn libretranslate(Form(form): Form<HashMap<String, String>>) -> Json<Value> {
+            Json(json!({ "translatedText": format!("{}: {}", form["target"], form["q"]) }))
+        }
+        async fn deepl(headers: HeaderMap, Form(form): Form<HashMap<String, String>>) -> Json<Value> {
+            assert_eq!(headers["authorization"], "DeepL-Auth-Key secret");
+            Json(json!({ "translations": [{ "text": format!("{}: {}", form["target_lang"], form["text"]) }] }))
+        }
+        let app = Router::new().route("/translate", post(libretranslate)).route("/v2/translate", post(deepl));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", address)
+    }
+
+    fn args(backend: Backend, endpoint: String, input: &str, output: &str) -> TranslateArgs {
+        TranslateArgs {
+            input: input.to_string(),
+            output: output.to_string(),
+            to: "FR".to_string(),
+            from: None,
+            backend,
+            endpoint: Some(endpoint),
+            api_key: Some("secret".to_string()),
+        }
+    }
+
+    fn statement(label: &str, text: &str) -> Statement {
+        Statement {
+            label: label.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    fn question() -> Question {
+        let letters = |pairs: &[(&str, &str)]| pairs.iter().map(|(l, t)| (l.to_string(), t.to_string())).collect();
+        Question {
+            number: "1".to_string(),
+            text: "Match the objects".to_string(),
+            choices: letters(&[("A", "Wave"), ("B", "Bin")]),
+            feedback: letters(&[("B", "Bins hold stock")]),
+            statements: vec![statement("I", "Waves group items")],
+            premises: vec![statement("1", "Groups items")],
+            pairs: vec![MatchPair {
+                premise: "1".to_string(),
+                choice: "A".to_string(),
+            }],
+            answers: vec!["A".to_string()],
+            emphasis: vec!["objects".to_string()],
+            notes: vec!["Checked".to_string()],
+            ..Question::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn every_text_is_translated() {
+        let endpoint = format!("{}/translate", stub_backend().await);
+        let translator = Translator::new(&args(Backend::Libretranslate, endpoint, "", ""));
+        let translated = translator.translate_question(question()).await.unwrap();
+
+        assert_eq!(translated.text, "FR: Match the objects");
+        assert_eq!(translated.choices["A"], "FR: Wave");
+        assert_eq!(translated.choices["B"], "FR: Bin");
+        assert_eq!(translated.feedback["B"], "FR: Bins hold stock");
+        let labelled = |statement: &Statement| (statement.label.clone(), statement.text.clone());
+        assert_eq!(labelled(&translated.statements[0]), ("I".to_string(), "FR: Waves group items".to_string()));
+        assert_eq!(labelled(&translated.premises[0]), ("1".to_string(), "FR: Groups items".to_string()));
+        assert_eq!(translated.emphasis, ["FR: objects"]);
+        assert_eq!(translated.notes, ["FR: Checked"]);
+        // what identifies the question and its answer is kept
+        assert_eq!(translated.number, "1");
+        assert_eq!(translated.answers, ["A"]);
+        assert_eq!(translated.pairs, question().pairs);
+        assert_eq!(translated.lang.as_deref(), Some("fr"));
+    }
+
+    #[tokio::test]
+    async fn banks_are_translated_through_deepl() {
+        let dir = std::env::temp_dir().join(format!("s4wm-translate-bank-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("questions.json"), dir.join("questions.fr.json"));
+        let bank = Bank {
+            questions: vec![question()],
+            ..Bank::default()
+        };
+        save_to_json(&bank, &input, &Crypto::default()).unwrap();
+        let endpoint = format!("{}/v2/translate", stub_backend().await);
+        let (input, output) = (input.to_string_lossy(), output.to_string_lossy());
+        let result = run(args(Backend::Deepl, endpoint, &input, &output), &Crypto::default()).await;
+        let translated = open_bank(&output, &Crypto::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        let (translated, _) = translated.unwrap();
+        assert_eq!(translated.meta.lang.as_deref(), Some("fr"));
+        assert_eq!(translated.questions[0].text, "FR: Match the objects");
+        assert_eq!(translated.questions[0].feedback["B"], "FR: Bins hold stock");
+    }
+}
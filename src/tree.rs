@@ -0,0 +1,110 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::crypt::Crypto;
+use crate::export::write_markdown_question;
+use crate::meta::BankMeta;
+use crate::similarity::stable_id;
+use crate::{Bank, OutputError, Question};
+
+// Per-question bank layout for keeping banks in git: a directory with one JSON and one Markdown file per question,
+// named by the question id, and an `index.json` holding the header and the question order. Small files give
+// readable diffs and let a pull request review questions one by one. Commands reading banks accept such a directory
+// wherever a bank file is expected.
+
+const INDEX_FILE: &str = "index.json";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Layout {
+    /// One JSON file holding the whole bank
+    #[default]
+    Single,
+    /// A directory with a file per question and an index
+    PerQuestion,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct IndexEntry {
+    number: String,
+    /// File name of the question without extension
+    file: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Index {
+    meta: BankMeta,
+    questions: Vec<IndexEntry>,
+}
+
+/// File names by question id. Exact duplicates share an id and get a numeric suffix.
+fn file_names(questions: &[Question]) -> Vec<String> {
+    let mut used = HashSet::new();
+    questions
+        .iter()
+        .map(|question| {
+            let id = question.id.clone().unwrap_or_else(|| stable_id(question));
+            let mut name = id.clone();
+            let mut suffix = 2;
+            while !used.insert(name.clone()) {
+                name = format!("{}-{}", id, suffix);
+                suffix += 1;
+            }
+            name
+        })
+        .collect()
+}
+
+/// Writes the bank into `dir`, removing question files left over from questions that no longer exist.
+pub(crate) fn save_per_question(bank: &Bank, dir: &Path, crypto: &Crypto) -> Result<(), OutputError> {
+    crypto.plaintext_allowed("The per-question layout")?;
+    fs::create_dir_all(dir)?;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_question_file = path.extension().is_some_and(|ext| ext == "json" || ext == "md");
+        if is_question_file && path.file_name().is_some_and(|name| name != INDEX_FILE) {
+            fs::remove_file(path)?;
+        }
+    }
+
+    let names = file_names(&bank.questions);
+    for (question, name) in bank.questions.iter().zip(&names) {
+        fs::write(dir.join(format!("{}.json", name)), serde_json::to_string_pretty(question)?)?;
+        let mut markdown = String::new();
+        write_markdown_question(&mut markdown, question);
+        fs::write(dir.join(format!("{}.md", name)), markdown)?;
+    }
+
+    let index = Index {
+        meta: bank.meta.clone(),
+        questions: bank
+            .questions
+            .iter()
+            .zip(names)
+            .map(|(question, file)| IndexEntry {
+                number: question.number.clone(),
+                file,
+            })
+            .collect(),
+    };
+    fs::write(dir.join(INDEX_FILE), serde_json::to_string_pretty(&index)?)?;
+    Ok(())
+}
+
+/// Reads a bank written by `save_per_question`, in index order.
+pub(crate) fn load_per_question(dir: &str) -> Result<Bank, OutputError> {
+    let dir = Path::new(dir);
+    let index: Index = serde_json::from_str(&fs::read_to_string(dir.join(INDEX_FILE))?)?;
+    let mut questions = Vec::with_capacity(index.questions.len());
+    for entry in index.questions {
+        let question: Question = serde_json::from_str(&fs::read_to_string(dir.join(format!("{}.json", entry.file)))?)?;
+        questions.push(question);
+    }
+    Ok(Bank {
+        meta: index.meta,
+        questions,
+    })
+}
@@ -0,0 +1,269 @@
+use clap::Args;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+
+use crate::kind::QuestionKind;
+use crate::lifecycle::Lifecycle;
+use crate::forum::{CommunityAnswer, Vote};
+use crate::merge::ObservedAnswer;
+use crate::meta::BankMeta;
+use crate::parser::{LineClass, TraceLine};
+use crate::serve::{Edit, Listing};
+use crate::validate::{Finding, Severity, ValidationReport};
+use crate::{Bank, MatchPair, Question, Statement};
+
+// TypeScript declarations of the bank, the validation report and the `serve` API, for frontends reading the JSON
+// (the scaffolded app among them). They are emitted from the Rust types instead of being written by hand: the type of
+// a field follows from its Rust type through `TypeScript`, enum variants are spelled by serializing them, and every
+// declared struct and enum is matched exhaustively, so a field or variant added on the Rust side without being
+// declared here does not compile.
+
+const HEADER: &str = "\
+// Types of the question bank, the validation report and the review API, generated by `S4WM-extract types`.
+// Do not edit: regenerate the file after upgrading S4WM-extract.";
+
+#[derive(Args, Debug)]
+pub struct TypesArgs {
+    /// File to write the declarations to, printed when not given
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+pub(crate) trait TypeScript {
+    /// Type of a value as written in a declaration
+    fn reference() -> String;
+
+    /// Type of a field that is left out when empty or absent, and declared optional
+    fn optional() -> String {
+        Self::reference()
+    }
+}
+
+/// A type declared by name in the generated file
+pub(crate) trait Declared: TypeScript {
+    fn declaration() -> String;
+}
+
+macro_rules! primitive {
+    ($($ty:ty => $ts:literal),* $(,)?) => {
+        $(impl TypeScript for $ty {
+            fn reference() -> String {
+                $ts.to_string()
+            }
+        })*
+    };
+}
+
+primitive!(String => "string", str => "string", bool => "boolean", usize => "number", f64 => "number");
+
+impl<T: TypeScript + ?Sized> TypeScript for &T {
+    fn reference() -> String {
+        T::reference()
+    }
+
+    fn optional() -> String {
+        T::optional()
+    }
+}
+
+impl<T: TypeScript> TypeScript for Option<T> {
+    fn reference() -> String {
+        format!("{} | null", T::reference())
+    }
+
+    fn optional() -> String {
+        T::reference()
+    }
+}
+
+impl<T: TypeScript> TypeScript for Vec<T> {
+    fn reference() -> String {
+        let element = T::reference();
+        if element.contains(' ') {
+            format!("({})[]", element)
+        } else {
+            format!("{}[]", element)
+        }
+    }
+}
+
+impl<T: TypeScript> TypeScript for HashMap<String, T> {
+    fn reference() -> String {
+        format!("Record<string, {}>", T::reference())
+    }
+}
+
+/// Declares a field, its type taken from the field accessor `_field`.
+fn field<S: ?Sized, T: TypeScript + ?Sized>(name: &str, optional: bool, _field: impl Fn(&S) -> &T) -> String {
+    if optional {
+        format!("  {}?: {};\n", name, T::optional())
+    } else {
+        format!("  {}: {};\n", name, T::reference())
+    }
+}
+
+/// String literal a unit variant serializes to.
+fn literal(value: &impl Serialize) -> String {
+    serde_json::to_string(value).expect("unit variants serialize to a string")
+}
+
+/// Declares a struct as an interface. Fields serialized only when set are marked `#[optional]`, fields never
+/// serialized `#[skip]`, and maps flattened into the struct `#[flatten]`.
+macro_rules! interface {
+    (@field $ty:ident, $field:ident) => {
+        field(stringify!($field), false, |value: &$ty| &value.$field)
+    };
+    (@field $ty:ident, $field:ident, optional) => {
+        field(stringify!($field), true, |value: &$ty| &value.$field)
+    };
+    (@field $ty:ident, $field:ident, skip) => {
+        String::new()
+    };
+    (@field $ty:ident, $field:ident, flatten) => {
+        "  [field: string]: unknown;\n".to_string()
+    };
+    ($ty:ident $(<$lt:lifetime>)? { $($(#[$attr:ident])? $field:ident),* $(,)? }) => {
+        impl $(<$lt>)? TypeScript for $ty $(<$lt>)? {
+            fn reference() -> String {
+                stringify!($ty).to_string()
+            }
+        }
+
+        impl $(<$lt>)? Declared for $ty $(<$lt>)? {
+            fn declaration() -> String {
+                let _exhaustive = |value: &$ty| {
+                    let $ty { $($field: _),* } = value;
+                };
+                let fields: Vec<String> = vec![$(interface!(@field $ty, $field $(, $attr)?)),*];
+                format!("export interface {} {{\n{}}}", stringify!($ty), fields.concat())
+            }
+        }
+    };
+}
+
+/// Declares an enum as a union of the strings its unit variants serialize to and of the given types of the other
+/// variants.
+macro_rules! enumeration {
+    ($ty:ident { $($unit:ident),* $(; $($pattern:pat => $ts:literal),*)? $(,)? }) => {
+        impl TypeScript for $ty {
+            fn reference() -> String {
+                stringify!($ty).to_string()
+            }
+        }
+
+        impl Declared for $ty {
+            fn declaration() -> String {
+                let _exhaustive = |value: &$ty| match value {
+                    $($ty::$unit => (),)*
+                    $($($pattern => (),)*)?
+                };
+                let variants: Vec<String> = vec![$(literal(&$ty::$unit)),* $($(, $ts.to_string())*)?];
+                format!("export type {} = {};", stringify!($ty), variants.join(" | "))
+            }
+        }
+    };
+}
+
+enumeration!(QuestionKind { MultipleChoice, MultipleSelect, TrueFalse, Ordering, Matching });
+enumeration!(Lifecycle { Active, NeedsReview, Retired; Lifecycle::SupersededBy(_) => "{ superseded_by: string }" });
+enumeration!(LineClass {
+    Blank, Artifact, Preamble, Question, Premise, Choice, ChoiceText, Answer, IgnoredAnswer, Statement, StatementText,
+    Stem, Feedback, FeedbackText
+});
+enumeration!(Severity { Warning, Error });
+
+interface!(Statement { label, text });
+interface!(MatchPair { premise, choice });
+interface!(ObservedAnswer { answers, count, sources });
+interface!(Vote { answers, percent });
+interface!(CommunityAnswer { answers, votes });
+interface!(TraceLine { #[optional] page, line, text, class, reason, #[skip] question });
+interface!(Question {
+    number,
+    #[optional] id,
+    kind,
+    text,
+    choices,
+    #[optional] feedback,
+    correct_answers,
+    #[optional] statements,
+    #[optional] answers,
+    #[optional] observed,
+    #[optional] community_answer,
+    #[optional] keywords,
+    #[optional] emphasis,
+    #[optional] truth,
+    #[optional] order,
+    #[optional] premises,
+    #[optional] pairs,
+    #[optional] lang,
+    #[optional] page,
+    #[optional] chapter,
+    #[optional] source_number,
+    #[optional] difficulty,
+    #[optional] recovered,
+    #[optional] lifecycle,
+    #[optional] notes,
+    #[optional] variant_of,
+    #[optional] explain,
+    #[flatten] extra,
+});
+interface!(BankMeta {
+    #[optional] exam_code,
+    #[optional] title,
+    #[optional] version,
+    #[optional] date,
+    #[optional] stated_question_count,
+    #[optional] passing_score,
+    #[optional] lang,
+    #[optional] page_hashes,
+});
+interface!(Bank { meta, questions });
+interface!(Finding { rule, severity, #[optional] question, message });
+interface!(ValidationReport { findings });
+interface!(Listing<'a> { review, total, questions });
+interface!(Edit {
+    #[optional] text,
+    #[optional] choices,
+    #[optional] answers,
+    #[optional] verdict,
+    #[optional] note,
+    #[optional] reviewer,
+});
+
+/// The generated file: every declaration, dependencies first.
+pub(crate) fn declarations() -> String {
+    let declarations = [
+        QuestionKind::declaration(),
+        Lifecycle::declaration(),
+        Statement::declaration(),
+        MatchPair::declaration(),
+        ObservedAnswer::declaration(),
+        Vote::declaration(),
+        CommunityAnswer::declaration(),
+        LineClass::declaration(),
+        TraceLine::declaration(),
+        Question::declaration(),
+        BankMeta::declaration(),
+        Bank::declaration(),
+        Severity::declaration(),
+        Finding::declaration(),
+        ValidationReport::declaration(),
+        Listing::declaration(),
+        Edit::declaration(),
+    ];
+    format!("{}\n\n{}\n", HEADER, declarations.join("\n\n"))
+}
+
+pub(crate) fn run(args: TypesArgs) -> Result<(), Box<dyn Error>> {
+    match &args.output {
+        Some(path) => {
+            fs::write(path, declarations())?;
+            println!("Wrote the TypeScript declarations to {}", path);
+        }
+        None => print!("{}", declarations()),
+    }
+    Ok(())
+}
@@ -0,0 +1,397 @@
+use clap::Args;
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::crypt::Crypto;
+use crate::history::{self, Change};
+use crate::manifest::{rfc3339, sha256_hex};
+use crate::progress::Console;
+use crate::timings::Timings;
+use crate::{bank_path, extract_file, load_from_json, ExtractOptions, OutputError, DEFAULT_OUTPUT_DIR};
+
+// Registry of the exam sources a team follows, so dumps are kept up to date instead of being extracted once. Every
+// source of `sources.toml` names a PDF by URL together with what was last seen of it: the SHA-256 of the file, the
+// version printed in it, and the ETag and Last-Modified headers of the server. `update` asks the server whether the
+// file changed with a conditional HEAD request, downloads it when it did (or when the server cannot tell), and
+// compares the hash before going further, since many servers send new validators for the same bytes. A new version is
+// extracted with the extraction options of the command line into `<output dir>/<source name>/`, compared question by
+// question with the bank it replaces as `history` does, and described in `update-report.json` next to the bank. The
+// registry is rewritten with what was seen after every source, so an interrupted update does not lose the others.
+
+const REPORT_FILE: &str = "update-report.json";
+
+#[derive(Args, Debug)]
+pub struct UpdateArgs {
+    /// Sources to update, by name; all of them when none is given
+    names: Vec<String>,
+    /// Registry of the sources
+    #[arg(long, default_value = "sources.toml")]
+    sources: String,
+    /// Only report which sources changed, without downloading or extracting them
+    #[arg(long)]
+    check: bool,
+    /// Download and extract the sources even when they look unchanged
+    #[arg(long, conflicts_with = "check")]
+    force: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Registry {
+    #[serde(default, rename = "source")]
+    sources: Vec<Source>,
+}
+
+/// A PDF followed by `update`, with what was seen of it at the last update
+#[derive(Serialize, Deserialize, Debug)]
+struct Source {
+    name: String,
+    url: String,
+    /// Parsing profile of the source, detected when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    /// SHA-256 of the PDF last extracted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    /// Version printed in the PDF last extracted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checked_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<String>,
+}
+
+/// What the server says about a source
+#[derive(Debug, PartialEq, Eq)]
+enum Freshness {
+    Unchanged(&'static str),
+    Changed(&'static str),
+    /// No validators to compare, only the content tells
+    Unknown,
+}
+
+#[derive(Serialize, Debug)]
+struct QuestionChange {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_id: Option<String>,
+    number: String,
+    #[serde(flatten)]
+    change: Change,
+}
+
+/// Changes of a source between two updates, written next to its bank
+#[derive(Serialize, Debug)]
+struct UpdateReport {
+    source: String,
+    url: String,
+    updated_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_sha256: Option<String>,
+    sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    questions_before: usize,
+    questions_after: usize,
+    added: usize,
+    changed: usize,
+    removed: usize,
+    changes: Vec<QuestionChange>,
+}
+
+impl Registry {
+    fn load(path: &str) -> Result<Self, OutputError> {
+        let text = fs::read_to_string(path).map_err(|e| OutputError {
+            message: format!("Cannot read the source registry {}: {}", path, e),
+        })?;
+        toml::from_str(&text).map_err(|e| OutputError {
+            message: format!("Invalid source registry {}: {}", path, e),
+        })
+    }
+
+    fn save(&self, path: &str) -> Result<(), OutputError> {
+        let text = toml::to_string_pretty(self).map_err(|e| OutputError { message: e.to_string() })?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+}
+
+/// Asks the server whether the source changed since it was last seen, with a conditional HEAD request.
+async fn freshness(client: &Client, source: &Source) -> Result<Freshness, reqwest::Error> {
+    let mut request = client.head(&source.url);
+    if let Some(etag) = &source.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &source.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = request.send().await?;
+    Ok(compare(source, response.status(), response.headers()))
+}
+
+/// Whether the source changed, from the answer of the server to the conditional HEAD request.
+fn compare(source: &Source, status: StatusCode, headers: &HeaderMap) -> Freshness {
+    if status == StatusCode::NOT_MODIFIED {
+        return Freshness::Unchanged("not modified");
+    }
+    // some servers refuse HEAD requests, the download will tell
+    if !status.is_success() {
+        return Freshness::Unknown;
+    }
+    let (etag, last_modified) = (header(headers, ETAG), header(headers, LAST_MODIFIED));
+    if source.sha256.is_none() {
+        return Freshness::Changed("never downloaded");
+    }
+    match (&etag, &source.etag, &last_modified, &source.last_modified) {
+        (Some(etag), Some(seen), _, _) if etag == seen => Freshness::Unchanged("same ETag"),
+        (Some(_), Some(_), _, _) => Freshness::Changed("new ETag"),
+        (_, _, Some(modified), Some(seen)) if modified == seen => Freshness::Unchanged("same Last-Modified"),
+        (_, _, Some(_), Some(_)) => Freshness::Changed("new Last-Modified"),
+        _ => Freshness::Unknown,
+    }
+}
+
+/// Records the validators of a download and returns the SHA-256 of its bytes, or `None` when they are the bytes
+/// last extracted and the update is not forced.
+fn new_content(source: &mut Source, headers: &HeaderMap, bytes: &[u8], force: bool) -> Option<String> {
+    source.etag = header(headers, ETAG);
+    source.last_modified = header(headers, LAST_MODIFIED);
+    let sha256 = sha256_hex(bytes);
+    (force || source.sha256.as_deref() != Some(sha256.as_str())).then_some(sha256)
+}
+
+/// Extracts the new version of a source and describes how its bank changed.
+fn extract_source(
+    options: &ExtractOptions,
+    source: &Source,
+    pdf_path: &Path,
+    sha256: &str,
+    output_dir: &Path,
+    download: Duration,
+) -> Result<UpdateReport, Box<dyn Error>> {
+    let mut options = options.clone();
+    options.profile = source.profile.clone().or(options.profile);
+    let bank_path = bank_path(&options, output_dir);
+    let crypto = Crypto::new(&options);
+    let before = if bank_path.exists() {
+        load_from_json(&bank_path.to_string_lossy(), &crypto)?.questions
+    } else {
+        Vec::new()
+    };
+    let mut timings = Timings::default();
+    timings.add("download", download);
+    extract_file(&options, &pdf_path.to_string_lossy(), output_dir, None, None, timings, &mut Console::new())?;
+    let after = load_from_json(&bank_path.to_string_lossy(), &crypto)?;
+
+    let changes: Vec<QuestionChange> = history::diff(&before, &after.questions)
+        .into_iter()
+        .map(|(id, previous_id, number, change)| QuestionChange {
+            id,
+            previous_id,
+            number,
+            change,
+        })
+        .collect();
+    let count = |kind: fn(&Change) -> bool| changes.iter().filter(|c| kind(&c.change)).count();
+    Ok(UpdateReport {
+        source: source.name.clone(),
+        url: source.url.clone(),
+        updated_at: rfc3339(SystemTime::now()),
+        previous_sha256: source.sha256.clone(),
+        sha256: sha256.to_string(),
+        previous_version: source.version.clone(),
+        version: after.meta.version.clone(),
+        questions_before: before.len(),
+        questions_after: after.questions.len(),
+        added: count(|change| matches!(change, Change::Added { .. })),
+        changed: count(|change| matches!(change, Change::Changed { .. })),
+        removed: count(|change| matches!(change, Change::Removed)),
+        changes,
+    })
+}
+
+/// Checks one source and updates it when it changed. Returns whether a new version was extracted.
+async fn update_source(
+    client: &Client,
+    args: &UpdateArgs,
+    options: &ExtractOptions,
+    source: &mut Source,
+) -> Result<bool, Box<dyn Error>> {
+    let freshness = if args.force {
+        Freshness::Changed("forced")
+    } else {
+        freshness(client, source).await?
+    };
+    source.checked_at = Some(rfc3339(SystemTime::now()));
+    match freshness {
+        Freshness::Unchanged(reason) => {
+            println!("{}: unchanged ({})", source.name, reason);
+            return Ok(false);
+        }
+        Freshness::Changed(reason) if args.check => {
+            println!("{}: changed ({})", source.name, reason);
+            return Ok(false);
+        }
+        Freshness::Unknown if args.check => {
+            println!("{}: unknown, the server sends no ETag or Last-Modified to compare", source.name);
+            return Ok(false);
+        }
+        _ => {}
+    }
+
+    let started = Instant::now();
+    let response = client.get(&source.url).send().await?.error_for_status()?;
+    let headers = response.headers().clone();
+    let bytes = response.bytes().await?;
+    let download = started.elapsed();
+    let Some(sha256) = new_content(source, &headers, &bytes, args.force) else {
+        println!("{}: unchanged (same SHA-256)", source.name);
+        return Ok(false);
+    };
+
+    let root = Path::new(options.output_dir.as_deref().unwrap_or(DEFAULT_OUTPUT_DIR));
+    let output_dir = root.join(&source.name);
+    fs::create_dir_all(&output_dir)?;
+    let pdf_path = output_dir.join(format!("{}.pdf", source.name));
+    fs::write(&pdf_path, &bytes)?;
+    let report = extract_source(options, source, &pdf_path, &sha256, &output_dir, download)?;
+    let report_path = output_dir.join(REPORT_FILE);
+    fs::write(&report_path, serde_json::to_string_pretty(&report)?)?;
+    let version = match (&report.previous_version, &report.version) {
+        (Some(previous), Some(version)) if previous != version => format!(", version {} -> {}", previous, version),
+        _ => String::new(),
+    };
+    println!(
+        "{}: {} added, {} changed, {} removed ({} -> {} questions{}), report in {}",
+        source.name,
+        report.added,
+        report.changed,
+        report.removed,
+        report.questions_before,
+        report.questions_after,
+        version,
+        report_path.display()
+    );
+    source.sha256 = Some(sha256);
+    source.version = report.version;
+    source.updated_at = Some(report.updated_at);
+    Ok(true)
+}
+
+pub(crate) async fn run(args: UpdateArgs, options: &ExtractOptions) -> Result<(), Box<dyn Error>> {
+    let mut registry = Registry::load(&args.sources)?;
+    if let Some(unknown) = args.names.iter().find(|name| !registry.sources.iter().any(|s| &s.name == *name)) {
+        return Err(format!("No source named {} in {}", unknown, args.sources).into());
+    }
+    let client = Client::new();
+    let (mut updated, mut failed) = (0, 0);
+    for index in 0..registry.sources.len() {
+        let source = &mut registry.sources[index];
+        if !args.names.is_empty() && !args.names.contains(&source.name) {
+            continue;
+        }
+        match update_source(&client, &args, options, source).await {
+            Ok(true) => updated += 1,
+            Ok(false) => {}
+            Err(error) => {
+                println!("error[update] {}: {}", source.name, error);
+                failed += 1;
+            }
+        }
+        if !args.check {
+            registry.save(&args.sources)?;
+        }
+    }
+
+    println!("{} source(s) updated, {} failed", updated, failed);
+    if failed > 0 {
+        return Err(format!("{} source(s) could not be updated", failed).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn source(sha256: Option<&str>, etag: Option<&str>, last_modified: Option<&str>) -> Source {
+        Source {
+            name: "ewm".to_string(),
+            url: "https://example.com/c_s4ewm.pdf".to_string(),
+            profile: None,
+            sha256: sha256.map(str::to_string),
+            version: None,
+            etag: etag.map(str::to_string),
+            last_modified: last_modified.map(str::to_string),
+            checked_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn headers(etag: Option<&str>, last_modified: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = etag {
+            headers.insert(ETAG, HeaderValue::from_str(etag).unwrap());
+        }
+        if let Some(last_modified) = last_modified {
+            headers.insert(LAST_MODIFIED, HeaderValue::from_str(last_modified).unwrap());
+        }
+        headers
+    }
+
+    const MONDAY: &str = "Mon, 06 Jan 2025 10:00:00 GMT";
+    const FRIDAY: &str = "Fri, 10 Jan 2025 10:00:00 GMT";
+
+    #[test]
+    fn validators_tell_whether_the_source_changed() {
+        let seen = source(Some("abc"), Some("\"v1\""), Some(MONDAY));
+        let ok = StatusCode::OK;
+        assert_eq!(compare(&seen, StatusCode::NOT_MODIFIED, &HeaderMap::new()), Freshness::Unchanged("not modified"));
+        assert_eq!(compare(&seen, ok, &headers(Some("\"v1\""), Some(FRIDAY))), Freshness::Unchanged("same ETag"));
+        assert_eq!(compare(&seen, ok, &headers(Some("\"v2\""), Some(MONDAY))), Freshness::Changed("new ETag"));
+        assert_eq!(compare(&seen, ok, &headers(None, Some(MONDAY))), Freshness::Unchanged("same Last-Modified"));
+        assert_eq!(compare(&seen, ok, &headers(None, Some(FRIDAY))), Freshness::Changed("new Last-Modified"));
+        assert_eq!(compare(&seen, ok, &HeaderMap::new()), Freshness::Unknown);
+        assert_eq!(compare(&seen, StatusCode::METHOD_NOT_ALLOWED, &HeaderMap::new()), Freshness::Unknown);
+
+        let fresh = source(None, Some("\"v1\""), None);
+        assert_eq!(compare(&fresh, ok, &headers(Some("\"v1\""), None)), Freshness::Changed("never downloaded"));
+        let without_etag = source(Some("abc"), None, Some(MONDAY));
+        assert_eq!(
+            compare(&without_etag, ok, &headers(Some("\"v1\""), Some(FRIDAY))),
+            Freshness::Changed("new Last-Modified")
+        );
+    }
+
+    #[test]
+    fn only_new_bytes_are_extracted_again() {
+        let bytes = b"%PDF-1.5 version 1";
+        let mut seen = source(Some(&sha256_hex(bytes)), Some("\"v1\""), None);
+        assert_eq!(new_content(&mut seen, &headers(Some("\"v2\""), Some(FRIDAY)), bytes, false), None);
+        assert_eq!(seen.etag.as_deref(), Some("\"v2\""));
+        assert_eq!(seen.last_modified.as_deref(), Some(FRIDAY));
+        assert_eq!(new_content(&mut seen, &HeaderMap::new(), bytes, true), Some(sha256_hex(bytes)));
+        assert_eq!(seen.etag, None);
+
+        let updated = b"%PDF-1.5 version 2";
+        assert_eq!(new_content(&mut seen, &HeaderMap::new(), updated, false), Some(sha256_hex(updated)));
+        let mut fresh = source(None, None, None);
+        assert_eq!(new_content(&mut fresh, &HeaderMap::new(), bytes, false), Some(sha256_hex(bytes)));
+    }
+}
@@ -0,0 +1,602 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::kind::QuestionKind;
+use crate::ocr;
+use crate::progress::Progress;
+use crate::schema::Schema;
+use crate::stats;
+use crate::similarity::{jaccard, normalize, question_key, stable_id, token_set};
+use crate::{Bank, OutputError, Question};
+
+const SIMILARITY_THRESHOLD: f64 = 0.9;
+const SYMBOL_RATIO_THRESHOLD: f64 = 0.1;
+const MISS_DENSITY_THRESHOLD: f64 = 0.25;
+const MISS_MIN_COUNT: usize = 3;
+
+// Validation of a parsed bank. Checks push findings into a `ValidationReport` instead of failing on the first
+// problem so a single run shows everything that needs a look; the caller decides whether errors are fatal. Every
+// finding carries the id of its rule, and the rules listed in `allow` (the `--allow` option) are not reported.
+//
+// A baseline file records the findings a legacy bank was accepted with, so that `--strict` fails on new findings
+// only. Findings about a question are matched by rule and question id, which survive renumbering and rewording of
+// the message; findings about the whole bank by rule and message. A rule firing more often on a question than the
+// baseline recorded is new again.
+
+/// Ids of the validation rules
+pub(crate) const RULES: &[&str] = &[
+    "count-mismatch",
+    "count-divergence",
+    "conflicting-answers",
+    "duplicate-question",
+    "similar-question",
+    "ocr-mixed-word",
+    "ocr-symbol-ratio",
+    "ocr-dictionary-miss",
+    "answer-position-bias",
+    "community-disagrees",
+    "duplicate-choice",
+    "choice-repeats-stem",
+    "all-of-the-above",
+    "single-choice",
+    "extra-field",
+];
+
+lazy_static! {
+    /// "All of the above" and "none of the above" choices in the profile languages
+    static ref ABOVE_CHOICE: Regex = Regex::new(concat!(
+        r"(?i)^(?:all|none)\s+of\s+the\s+above|^(?:alle|keine)\s+(?:der\s+)?(?:oben\s+)?genannten",
+        r"|^(?:toutes|aucune)\s+(?:les\s+réponses\s+|des\s+réponses\s+)?ci-dessus"
+    ))
+    .unwrap();
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct Finding {
+    pub(crate) rule: &'static str,
+    pub(crate) severity: Severity,
+    /// Sequential number of the question the finding is about, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) question: Option<String>,
+    pub(crate) message: String,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ValidationOptions {
+    /// Known words for the OCR dictionary-miss check, which is skipped without one
+    pub(crate) dictionary: Option<HashSet<String>>,
+    /// Rules whose findings are not reported
+    pub(crate) allow: HashSet<String>,
+    /// Findings accepted in the bank, reported apart by `validate_questions`
+    pub(crate) baseline: Option<Baseline>,
+    /// Declared extra fields, checked when given
+    pub(crate) schema: Option<Schema>,
+}
+
+/// A finding accepted by the baseline
+#[derive(Serialize, Deserialize, Debug)]
+struct Accepted {
+    rule: String,
+    /// Id of the question, absent for findings about the whole bank
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    /// Number of the question when the baseline was written, for the reader
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    question: Option<String>,
+    message: String,
+}
+
+impl Accepted {
+    fn key(&self) -> (String, String) {
+        (self.rule.clone(), self.id.clone().unwrap_or_else(|| self.message.clone()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct Baseline {
+    findings: Vec<Accepted>,
+}
+
+impl Baseline {
+    pub(crate) fn load(path: &str) -> Result<Baseline, OutputError> {
+        if !Path::new(path).exists() {
+            return Err(OutputError {
+                message: format!("Baseline {} does not exist, write it with --update-baseline", path),
+            });
+        }
+        serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| OutputError {
+            message: format!("Invalid baseline {}: {}", path, e),
+        })
+    }
+
+    pub(crate) fn save(&self, path: &str) -> Result<(), OutputError> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// The findings of a report, to accept them all.
+    pub(crate) fn of(report: &ValidationReport, bank: &Bank) -> Baseline {
+        let ids = question_ids(bank);
+        let findings = report
+            .findings
+            .iter()
+            .map(|finding| Accepted {
+                rule: finding.rule.to_string(),
+                id: finding.question.as_ref().and_then(|number| ids.get(number).cloned()),
+                question: finding.question.clone(),
+                message: finding.message.clone(),
+            })
+            .collect();
+        Baseline { findings }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.findings.len()
+    }
+
+    /// Removes the findings the baseline accepts from the report and returns how many it removed.
+    pub(crate) fn accept(&self, report: &mut ValidationReport, bank: &Bank) -> usize {
+        let mut remaining: HashMap<(String, String), usize> = HashMap::new();
+        for accepted in &self.findings {
+            *remaining.entry(accepted.key()).or_default() += 1;
+        }
+        let ids = question_ids(bank);
+        let before = report.findings.len();
+        report.findings.retain(|finding| {
+            let id = finding.question.as_ref().and_then(|number| ids.get(number));
+            let key = (finding.rule.to_string(), id.cloned().unwrap_or_else(|| finding.message.clone()));
+            match remaining.get_mut(&key) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+        before - report.findings.len()
+    }
+}
+
+/// Ids of the questions by number.
+fn question_ids(bank: &Bank) -> HashMap<String, String> {
+    bank.questions
+        .iter()
+        .map(|question| (question.number.clone(), question.id.clone().unwrap_or_else(|| stable_id(question))))
+        .collect()
+}
+
+/// The rules to leave out of the report, failing on an id that names no rule.
+pub(crate) fn allowed(rules: &[String]) -> Result<HashSet<String>, OutputError> {
+    for rule in rules {
+        if !RULES.contains(&rule.as_str()) {
+            return Err(OutputError {
+                message: format!("Unknown validation rule {:?}, the rules are: {}", rule, RULES.join(", ")),
+            });
+        }
+    }
+    Ok(rules.iter().cloned().collect())
+}
+
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct ValidationReport {
+    pub(crate) findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, rule: &'static str, severity: Severity, question: Option<&Question>, message: String) {
+        self.findings.push(Finding {
+            rule,
+            severity,
+            question: question.map(|q| q.number.clone()),
+            message,
+        });
+    }
+
+    pub(crate) fn error_count(&self) -> usize {
+        self.findings.iter().filter(|f| f.severity == Severity::Error).count()
+    }
+
+    pub(crate) fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub(crate) fn report(&self, progress: &mut dyn Progress) {
+        for finding in &self.findings {
+            let severity = match finding.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            let message = match &finding.question {
+                Some(number) => format!("question {}: {}", number, finding.message),
+                None => finding.message.clone(),
+            };
+            progress.diagnostic(severity, finding.rule, message);
+        }
+    }
+}
+
+fn page_range(from: &Question, to: &Question) -> String {
+    match (from.page, to.page) {
+        (Some(a), Some(b)) if a == b => format!("page {}", a),
+        (Some(a), Some(b)) => format!("pages {}-{}", a, b),
+        _ => "unknown pages".to_string(),
+    }
+}
+
+/// Compares the parsed question count with the count stated in the document and points at the places where the
+/// printed question numbers stop increasing by one, which is where questions were missed or split.
+fn check_expected_count(bank: &Bank, report: &mut ValidationReport) {
+    let Some(expected) = bank.meta.stated_question_count else {
+        return;
+    };
+    let parsed = bank.questions.len();
+    if parsed == expected {
+        return;
+    }
+
+    report.push(
+        "count-mismatch",
+        Severity::Error,
+        None,
+        format!("parsed {} questions but the document states {}", parsed, expected),
+    );
+
+    for pair in bank.questions.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        if let (Some(a), Some(b)) = (prev.source_number, next.source_number) {
+            if b != a + 1 {
+                report.push(
+                    "count-divergence",
+                    Severity::Warning,
+                    Some(next),
+                    format!("numbering jumps from {} to {} on {}", a, b, page_range(prev, next)),
+                );
+            }
+        }
+    }
+}
+
+/// Reports exact duplicates (or conflicting answers when a duplicate disagrees on the answer), identical stems with
+/// different choices and near-identical stems (token Jaccard similarity above the threshold). Dumps often repeat
+/// questions across sections, sometimes with a different answer key.
+fn check_duplicates(bank: &Bank, report: &mut ValidationReport) {
+    let questions = &bank.questions;
+    let keys: Vec<_> = questions.iter().map(question_key).collect();
+    let tokens: Vec<_> = questions.iter().map(|q| token_set(&q.text)).collect();
+
+    for (i, first) in questions.iter().enumerate() {
+        for (j, second) in questions.iter().enumerate().skip(i + 1) {
+            if keys[i] == keys[j] {
+                if !first.answers.is_empty() && !second.answers.is_empty() && first.answers != second.answers {
+                    report.push(
+                        "conflicting-answers",
+                        Severity::Error,
+                        Some(second),
+                        format!(
+                            "answer {} conflicts with {} given for the same question {}",
+                            second.answers.join(", "),
+                            first.answers.join(", "),
+                            first.number
+                        ),
+                    );
+                } else {
+                    report.push(
+                        "duplicate-question",
+                        Severity::Warning,
+                        Some(second),
+                        format!("exact duplicate of question {}", first.number),
+                    );
+                }
+            } else if keys[i].0 == keys[j].0 {
+                report.push(
+                    "similar-question",
+                    Severity::Warning,
+                    Some(second),
+                    format!("same stem as question {} with different choices", first.number),
+                );
+            } else {
+                // Jaccard cannot exceed the ratio of the set sizes, skip pairs that can never reach the threshold
+                let (a, b) = (tokens[i].len(), tokens[j].len());
+                if (a.min(b) as f64) < SIMILARITY_THRESHOLD * a.max(b) as f64 {
+                    continue;
+                }
+                let score = jaccard(&tokens[i], &tokens[j]);
+                if score >= SIMILARITY_THRESHOLD {
+                    report.push(
+                        "similar-question",
+                        Severity::Warning,
+                        Some(second),
+                        format!("stem is {:.0}% similar to question {}", score * 100.0, first.number),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Flags questions that look damaged by OCR: letter/digit confusions inside words, a high share of unusual
+/// symbols, and many words found neither in the dictionary nor elsewhere in the bank.
+fn check_ocr_artifacts(bank: &Bank, options: &ValidationOptions, report: &mut ValidationReport) {
+    let vocabulary = options.dictionary.as_ref().map(|_| ocr::corpus_vocabulary(&bank.questions));
+
+    for question in &bank.questions {
+        let texts = std::iter::once(&question.text).chain(question.choices.values());
+        let mixed: Vec<String> = texts.flat_map(|t| ocr::mixed_words(t)).collect();
+        if !mixed.is_empty() {
+            report.push(
+                "ocr-mixed-word",
+                Severity::Warning,
+                Some(question),
+                format!("letters and digits mixed in {}", mixed.join(", ")),
+            );
+        }
+
+        let ratio = ocr::symbol_ratio(&question.text);
+        if ratio > SYMBOL_RATIO_THRESHOLD {
+            report.push(
+                "ocr-symbol-ratio",
+                Severity::Warning,
+                Some(question),
+                format!("{:.0}% of the stem are unusual symbols", ratio * 100.0),
+            );
+        }
+
+        if let (Some(dictionary), Some(vocabulary)) = (&options.dictionary, &vocabulary) {
+            let (misses, density) = ocr::dictionary_misses(question, dictionary, vocabulary);
+            if misses.len() >= MISS_MIN_COUNT && density > MISS_DENSITY_THRESHOLD {
+                report.push(
+                    "ocr-dictionary-miss",
+                    Severity::Warning,
+                    Some(question),
+                    format!("{:.0}% unknown words: {}", density * 100.0, misses.join(", ")),
+                );
+            }
+        }
+    }
+}
+
+/// Reports an implausibly skewed distribution of correct-answer letters, the typical symptom of a misaligned
+/// answer key merge.
+fn check_position_bias(bank: &Bank, report: &mut ValidationReport) {
+    if let Some(bias) = stats::position_bias(&bank.questions) {
+        if bias.skewed {
+            report.push(
+                "answer-position-bias",
+                Severity::Warning,
+                None,
+                format!("correct answers are implausibly skewed ({})", bias.describe()),
+            );
+        }
+    }
+}
+
+/// Points at imported questions whose official answer is not the one the forum community voted for most.
+fn check_community_answer(bank: &Bank, report: &mut ValidationReport) {
+    for question in &bank.questions {
+        let Some(community) = &question.community_answer else {
+            continue;
+        };
+        if question.answers.is_empty() || community.answers == question.answers {
+            continue;
+        }
+        let share = community.votes.first().map_or(0, |vote| vote.percent);
+        report.push(
+            "community-disagrees",
+            Severity::Warning,
+            Some(question),
+            format!(
+                "official answer {} but the community voted {} ({}%)",
+                question.answers.join(", "),
+                community.answers.join(", "),
+                share
+            ),
+        );
+    }
+}
+
+/// Flags questions that cannot be answered as printed: two choices with the same text, a choice repeating the
+/// stem, an "All of the above" choice with fewer than two choices above it, and a choice question with one choice.
+/// Each is usually a choice lost or duplicated by the extraction.
+fn check_degenerate_choices(bank: &Bank, report: &mut ValidationReport) {
+    for question in &bank.questions {
+        let stem = normalize(&question.text);
+        let mut choices: Vec<(&String, String)> =
+            question.choices.iter().map(|(letter, text)| (letter, normalize(text))).collect();
+        choices.sort();
+        for (i, (letter, text)) in choices.iter().enumerate() {
+            if text.is_empty() {
+                continue;
+            }
+            if let Some((first, _)) = choices[..i].iter().find(|(_, other)| other == text) {
+                report.push(
+                    "duplicate-choice",
+                    Severity::Warning,
+                    Some(question),
+                    format!("choices {} and {} are identical", first, letter),
+                );
+            }
+            if *text == stem {
+                report.push(
+                    "choice-repeats-stem",
+                    Severity::Warning,
+                    Some(question),
+                    format!("choice {} repeats the stem", letter),
+                );
+            }
+            if ABOVE_CHOICE.is_match(&question.choices[*letter]) && i < 2 {
+                report.push(
+                    "all-of-the-above",
+                    Severity::Warning,
+                    Some(question),
+                    format!("choice {} refers to the choices above it but has {} before it", letter, i),
+                );
+            }
+        }
+
+        let choice_kind = matches!(question.kind, QuestionKind::MultipleChoice | QuestionKind::MultipleSelect);
+        if choice_kind && question.choices.len() == 1 {
+            report.push(
+                "single-choice",
+                Severity::Warning,
+                Some(question),
+                "the question has a single choice".to_string(),
+            );
+        }
+    }
+}
+
+/// Checks the extra fields of the questions against the schema extension file.
+fn check_extra_fields(bank: &Bank, options: &ValidationOptions, report: &mut ValidationReport) {
+    let Some(schema) = &options.schema else {
+        return;
+    };
+    for question in &bank.questions {
+        for (severity, message) in schema.check(question) {
+            report.push("extra-field", severity, Some(question), message);
+        }
+    }
+}
+
+pub(crate) fn validate(bank: &Bank, options: &ValidationOptions) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    check_expected_count(bank, &mut report);
+    check_duplicates(bank, &mut report);
+    check_ocr_artifacts(bank, options, &mut report);
+    check_position_bias(bank, &mut report);
+    check_community_answer(bank, &mut report);
+    check_degenerate_choices(bank, &mut report);
+    check_extra_fields(bank, options, &mut report);
+    report.findings.retain(|finding| !options.allow.contains(finding.rule));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::BankMeta;
+
+    fn question(number: usize, source_number: usize, text: &str) -> Question {
+        Question {
+            number: number.to_string(),
+            source_number: Some(source_number),
+            page: Some(number.div_ceil(2)),
+            text: text.to_string(),
+            ..Question::default()
+        }
+    }
+
+    fn bank(stated: Option<usize>, questions: Vec<Question>) -> Bank {
+        Bank {
+            meta: BankMeta {
+                stated_question_count: stated,
+                ..BankMeta::default()
+            },
+            questions,
+        }
+    }
+
+    fn count_findings(bank: &Bank) -> Vec<Finding> {
+        let mut report = ValidationReport::default();
+        check_expected_count(bank, &mut report);
+        report.findings
+    }
+
+    #[test]
+    fn missed_question_is_reported_where_the_numbering_jumps() {
+        let questions = vec![
+            question(1, 1, "Which monitor shows open tasks?"),
+            question(2, 2, "Which transaction defines storage types?"),
+            question(3, 4, "Which object groups warehouse requests?"),
+        ];
+        let findings = count_findings(&bank(Some(4), questions));
+        let rules: Vec<&str> = findings.iter().map(|finding| finding.rule).collect();
+        assert_eq!(rules, ["count-mismatch", "count-divergence"]);
+        assert_eq!(findings[0].message, "parsed 3 questions but the document states 4");
+        assert_eq!(findings[1].question.as_deref(), Some("3"));
+        assert_eq!(findings[1].message, "numbering jumps from 2 to 4 on pages 1-2");
+    }
+
+    #[test]
+    fn matching_or_unstated_count_is_not_reported() {
+        let questions = vec![
+            question(1, 1, "Which monitor shows open tasks?"),
+            question(2, 3, "Which transaction defines storage types?"),
+        ];
+        assert!(count_findings(&bank(Some(2), questions.clone())).is_empty());
+        assert!(count_findings(&bank(None, questions)).is_empty());
+    }
+
+    fn with_choices(mut question: Question, choices: &[&str], answer: &str) -> Question {
+        for (letter, text) in ["A", "B", "C"].iter().zip(choices) {
+            question.choices.insert(letter.to_string(), text.to_string());
+        }
+        question.answers = vec![answer.to_string()];
+        question
+    }
+
+    fn duplicate_findings(questions: Vec<Question>) -> Vec<(&'static str, Option<String>)> {
+        let mut report = ValidationReport::default();
+        check_duplicates(&bank(None, questions), &mut report);
+        report.findings.into_iter().map(|finding| (finding.rule, finding.question)).collect()
+    }
+
+    #[test]
+    fn repeated_questions_are_reported() {
+        let stem = "Which transaction shows the warehouse management monitor?";
+        let choices = ["/SCWM/MON", "/SCWM/PRDI"];
+        let findings = duplicate_findings(vec![
+            with_choices(question(1, 1, stem), &choices, "A"),
+            with_choices(question(2, 2, stem), &choices, "A"),
+            with_choices(question(3, 3, stem), &choices, "B"),
+            with_choices(question(4, 4, stem), &["/SCWM/MON", "/SCWM/WAVE"], "A"),
+        ]);
+        assert!(findings.contains(&("duplicate-question", Some("2".to_string()))));
+        assert!(findings.contains(&("conflicting-answers", Some("3".to_string()))));
+        assert!(findings.contains(&("similar-question", Some("4".to_string()))));
+    }
+
+    #[test]
+    fn distinct_questions_are_not_reported() {
+        let choices = ["/SCWM/MON", "/SCWM/PRDI"];
+        let findings = duplicate_findings(vec![
+            with_choices(question(1, 1, "Which transaction shows the warehouse management monitor?"), &choices, "A"),
+            with_choices(question(2, 2, "Which transaction defines the storage type search sequence?"), &choices, "B"),
+        ]);
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn baseline_accepts_known_findings_only() {
+        let choices = ["/SCWM/MON", "/SCWM/PRDI"];
+        let monitor = with_choices(question(1, 1, "Which transaction shows the warehouse monitor?"), &choices, "A");
+        let search = with_choices(question(3, 3, "Which transaction defines the search sequence?"), &choices, "B");
+        let mut accepted = bank(None, vec![monitor.clone(), monitor.clone(), search.clone()]);
+        accepted.questions[1].number = "2".to_string();
+        let baseline = Baseline::of(&validate(&accepted, &ValidationOptions::default()), &accepted);
+        assert_ne!(baseline.len(), 0);
+
+        // renumbered by a question inserted in front, and a new duplicate of the last question
+        let mut current = accepted.clone();
+        current.questions.insert(0, with_choices(question(1, 1, "Which object groups requests?"), &choices, "A"));
+        current.questions.push(search);
+        for (i, question) in current.questions.iter_mut().enumerate() {
+            question.number = (i + 1).to_string();
+        }
+        let mut report = validate(&current, &ValidationOptions::default());
+        let accepted_count = baseline.accept(&mut report, &current);
+        assert_eq!(accepted_count, baseline.len());
+        let findings: Vec<(&str, Option<&str>)> =
+            report.findings.iter().map(|finding| (finding.rule, finding.question.as_deref())).collect();
+        assert_eq!(findings, [("duplicate-question", Some("5"))]);
+    }
+}
@@ -0,0 +1,148 @@
+use clap::Args;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use crate::crypt::Crypto;
+use crate::difficulty;
+use crate::exam::{seeded_rng, Blueprint};
+use crate::kind::QuestionKind;
+use crate::lifecycle;
+use crate::similarity::{normalize, stable_id};
+use crate::{open_bank, save_to_json, Bank, Question};
+
+// Synthetic variants of questions, for teachers who need practice items that do not look like the ones already
+// seen. A variant keeps the stem and the correct choices of a multiple-choice or multiple-select question and takes
+// its wrong choices from the wrong choices of other questions of the same topic, then shuffles the letters. Topics
+// come from an exam blueprint (`exam --blueprint`), as dumps do not mark them.
+//
+// Variants are synthetic: a distractor from another question may turn out to be right for this one. Each records the
+// id of its original in `variant_of`, carries a note saying where its distractors came from and is numbered after its
+// original (`12-v1`), so exports and reviews can tell it apart and it can be dropped again.
+
+#[derive(Args, Debug)]
+pub struct VariantsArgs {
+    /// Question bank to make variants of
+    input: String,
+    /// Where to write the bank of variants
+    output: String,
+    /// TOML file of topics with their terms, as for `exam --blueprint`
+    #[arg(long)]
+    blueprint: String,
+    /// Variants to make of each question
+    #[arg(short = 'n', long, default_value_t = 1)]
+    per_question: usize,
+    /// Seed for reproducible variants
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Also write the original questions, before their variants
+    #[arg(long)]
+    with_originals: bool,
+}
+
+/// Wrong choices of the questions of one topic, distinct after normalization
+fn distractor_pool(questions: &[&Question]) -> Vec<(String, String)> {
+    let mut seen = HashSet::new();
+    let mut pool = Vec::new();
+    for question in questions {
+        let id = question.id.clone().unwrap_or_else(|| stable_id(question));
+        for (letter, choice) in &question.choices {
+            if !question.answers.contains(letter) && seen.insert(normalize(choice)) {
+                pool.push((id.clone(), choice.clone()));
+            }
+        }
+    }
+    pool
+}
+
+/// A variant of `question` with other wrong choices from `pool`, or none when the pool has too few that the
+/// question does not already offer.
+fn variant(
+    question: &Question,
+    pool: &[(String, String)],
+    index: usize,
+    topic: &str,
+    rng: &mut StdRng,
+) -> Option<Question> {
+    let id = question.id.clone().unwrap_or_else(|| stable_id(question));
+    let own: HashSet<String> = question.choices.values().map(|choice| normalize(choice)).collect();
+    let fresh = |(from, choice): &&(String, String)| *from != id && !own.contains(&normalize(choice));
+    let mut candidates: Vec<&String> = pool.iter().filter(fresh).map(|(_, choice)| choice).collect();
+    let wrong = question.choices.len().saturating_sub(question.answers.len());
+    if candidates.len() < wrong {
+        return None;
+    }
+    candidates.shuffle(rng);
+
+    let correct = question.answers.iter().filter_map(|letter| question.choices.get(letter));
+    let mut texts: Vec<(String, bool)> = correct.map(|text| (text.clone(), true)).collect();
+    texts.extend(candidates.into_iter().take(wrong).map(|text| (text.clone(), false)));
+    texts.shuffle(rng);
+    let mut letters: Vec<&String> = question.choices.keys().collect();
+    letters.sort();
+
+    let mut variant = Question {
+        number: format!("{}-v{}", question.number, index),
+        choices: letters.iter().zip(&texts).map(|(letter, (text, _))| (letter.to_string(), text.clone())).collect(),
+        answers: letters.iter().zip(&texts).filter(|(_, (_, right))| *right).map(|(l, _)| l.to_string()).collect(),
+        variant_of: Some(id),
+        notes: vec![format!(
+            "Synthetic variant of question {}: wrong choices taken from other questions of topic {}, check that none \
+             of them is right",
+            question.number, topic
+        )],
+        // the commentary was on the original distractors
+        feedback: HashMap::new(),
+        observed: Vec::new(),
+        community_answer: None,
+        explain: Vec::new(),
+        recovered: false,
+        ..question.clone()
+    };
+    variant.id = Some(stable_id(&variant));
+    variant.difficulty = Some(difficulty::score(&variant));
+    Some(variant)
+}
+
+pub(crate) fn run(args: VariantsArgs, crypto: &Crypto) -> Result<(), Box<dyn Error>> {
+    let (bank, crypto) = open_bank(&args.input, crypto)?;
+    let bank = lifecycle::in_use(&bank);
+    let blueprint = Blueprint::load(&args.blueprint)?;
+    let mut rng = seeded_rng(args.seed);
+
+    let mut questions = Vec::new();
+    let (mut made, mut short) = (0, 0);
+    for (topic, members) in blueprint.topics.iter().zip(blueprint.classify(&bank.questions)?) {
+        let pool = distractor_pool(&members);
+        for question in members {
+            let choice_kind = matches!(question.kind, QuestionKind::MultipleChoice | QuestionKind::MultipleSelect);
+            if !choice_kind || question.answers.is_empty() || question.variant_of.is_some() {
+                continue;
+            }
+            if args.with_originals {
+                questions.push(question.clone());
+            }
+            for index in 1..=args.per_question {
+                match variant(question, &pool, index, &topic.name, &mut rng) {
+                    Some(variant) => {
+                        questions.push(variant);
+                        made += 1;
+                    }
+                    None => short += 1,
+                }
+            }
+        }
+    }
+    println!("Made {} variants", made);
+    if short > 0 {
+        println!("{} variants could not be made, their topic has too few other wrong choices", short);
+    }
+
+    let variants = Bank {
+        meta: bank.meta.clone(),
+        questions,
+    };
+    save_to_json(&variants, &args.output, &crypto)?;
+    Ok(())
+}
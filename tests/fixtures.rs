@@ -0,0 +1,156 @@
+use std::process::Command;
+
+// Replays the golden-fixture corpus through the binary; see `fixtures record` for adding cases.
+#[test]
+fn parser_output_matches_corpus() {
+    let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+    let output = Command::new(env!("CARGO_BIN_EXE_S4WM-extract"))
+        .args(["fixtures", "check", corpus])
+        .output()
+        .expect("failed to run the fixture check");
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.lines().any(|line| line.starts_with("0 case(s)")), "the corpus has no cases");
+}
+
+/// A page with two questions side by side, written row by row the way that interleaves them in the plain text
+fn two_column_pdf(path: &std::path::Path) {
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    let rows = [
+        ("1. Which tool monitors the warehouse?", "2. Which object groups warehouse tasks?"),
+        ("A. The warehouse monitor", "A. A wave"),
+        ("B. The warehouse cockpit", "B. A queue"),
+        ("Answer: A", "Answer: A"),
+    ];
+    let mut operations = Vec::new();
+    for (row, (left, right)) in rows.iter().enumerate() {
+        let y = 700 - 20 * row as i64;
+        for (x, text) in [(50, left), (320, right)] {
+            operations.push(Operation::new("BT", vec![]));
+            operations.push(Operation::new("Tf", vec!["F1".into(), 10.into()]));
+            operations.push(Operation::new("Td", vec![x.into(), y.into()]));
+            operations.push(Operation::new("Tj", vec![Object::string_literal(*text)]));
+            operations.push(Operation::new("ET", vec![]));
+        }
+    }
+
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let font_id = document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let content = Content { operations };
+    let content_id = document.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+    let page_id = document.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+    });
+    document.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }),
+    );
+    let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    document.trailer.set("Root", catalog_id);
+    document.save(path).unwrap();
+}
+
+// Records a two-column PDF and checks that the columns were read one after the other rather than interleaved.
+#[test]
+fn two_column_pages_are_read_by_column() {
+    let dir = std::env::temp_dir().join(format!("s4wm-two-column-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let pdf = dir.join("two-column.pdf");
+    two_column_pdf(&pdf);
+    let output = Command::new(env!("CARGO_BIN_EXE_S4WM-extract"))
+        .args(["fixtures", "record", pdf.to_str().unwrap(), dir.to_str().unwrap(), "--profile", "en"])
+        .output()
+        .expect("failed to record the two-column PDF");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let expected = std::fs::read_to_string(dir.join("two-column").join("expected.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let bank: serde_json::Value = serde_json::from_str(&expected).unwrap();
+    let questions = bank["questions"].as_array().unwrap();
+    assert_eq!(questions.len(), 2, "{}", expected);
+    assert_eq!(questions[0]["text"], "Which tool monitors the warehouse?");
+    assert_eq!(questions[0]["choices"]["B"], "The warehouse cockpit");
+    assert_eq!(questions[1]["text"], "Which object groups warehouse tasks?");
+    assert_eq!(questions[1]["choices"]["B"], "A queue");
+}
+
+/// A bank encrypted to a new identity, with a stem the whitespace repair of `lint --fix` changes; returns the
+/// identity file and the public key.
+fn encrypted_bank(dir: &std::path::Path) -> (std::path::PathBuf, String) {
+    use age::secrecy::ExposeSecret;
+    use std::io::Write;
+
+    let identity = age::x25519::Identity::generate();
+    let identity_path = dir.join("identity.txt");
+    std::fs::write(&identity_path, identity.to_string().expose_secret()).unwrap();
+    let bank = br#"{"meta":{},"questions":[{"number":"1","text":"Which  monitor shows open tasks?",
+        "choices":{"A":"/SCWM/MON","B":"/SCWM/PRDI"},"answers":["A"]}]}"#;
+    let recipient: Box<dyn age::Recipient + Send> = Box::new(identity.to_public());
+    let mut encrypted = Vec::new();
+    let mut writer = age::Encryptor::with_recipients(vec![recipient]).unwrap().wrap_output(&mut encrypted).unwrap();
+    writer.write_all(bank).unwrap();
+    writer.finish().unwrap();
+    std::fs::write(dir.join("questions.json"), encrypted).unwrap();
+    (identity_path, identity.to_public().to_string())
+}
+
+#[test]
+fn repaired_encrypted_bank_stays_encrypted() {
+    let dir = std::env::temp_dir().join(format!("s4wm-encrypted-lint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (identity, recipient) = encrypted_bank(&dir);
+    let bank = dir.join("questions.json");
+    let lint = |recipient: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_S4WM-extract"))
+            .arg("--identity")
+            .arg(&identity)
+            .args(recipient)
+            .arg("lint")
+            .arg(&bank)
+            .arg("--fix")
+            .env_remove("S4WM_RECIPIENT")
+            .output()
+            .expect("failed to run lint")
+    };
+
+    // without a key to encrypt to, nothing is written
+    let before = std::fs::read(&bank).unwrap();
+    assert!(!lint(&[]).status.success());
+    assert_eq!(std::fs::read(&bank).unwrap(), before);
+
+    let output = lint(&["--recipient", &recipient]);
+    let saved = std::fs::read(&bank).unwrap();
+    let shown = Command::new(env!("CARGO_BIN_EXE_S4WM-extract"))
+        .arg("--identity")
+        .arg(&identity)
+        .arg("lint")
+        .arg(&bank)
+        .output()
+        .expect("failed to run lint");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(saved.starts_with(b"age-encryption.org/"), "the repaired bank was saved in plaintext");
+    assert!(shown.status.success(), "{}", String::from_utf8_lossy(&shown.stderr));
+    assert!(!String::from_utf8_lossy(&shown.stdout).contains("whitespace"));
+}